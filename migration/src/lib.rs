@@ -0,0 +1,66 @@
+pub use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, Statement};
+
+mod m20261018_000001_create_table;
+mod m20261018_000002_fix_legacy_schema;
+mod m20261018_000003_billing_workflow;
+mod m20261018_000004_team_invite;
+mod m20261018_000005_vehicle_profile;
+mod m20261018_000006_expiry_reminder;
+mod m20261018_000007_fuel_log;
+mod m20261018_000008_billing_income;
+mod m20261018_000009_customer_receivable;
+mod m20261018_000010_driver_settlement;
+mod m20261018_000011_driver_payroll;
+
+pub struct Migrator;
+
+#[async_trait::async_trait]
+impl MigratorTrait for Migrator {
+    fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+        vec![
+            Box::new(m20261018_000001_create_table::Migration),
+            Box::new(m20261018_000002_fix_legacy_schema::Migration),
+            Box::new(m20261018_000003_billing_workflow::Migration),
+            Box::new(m20261018_000004_team_invite::Migration),
+            Box::new(m20261018_000005_vehicle_profile::Migration),
+            Box::new(m20261018_000006_expiry_reminder::Migration),
+            Box::new(m20261018_000007_fuel_log::Migration),
+            Box::new(m20261018_000008_billing_income::Migration),
+            Box::new(m20261018_000009_customer_receivable::Migration),
+            Box::new(m20261018_000010_driver_settlement::Migration),
+            Box::new(m20261018_000011_driver_payroll::Migration),
+        ]
+    }
+}
+
+/// Postgres has no `CREATE TYPE IF NOT EXISTS`, so enum types are looked up first.
+pub(crate) async fn has_type(manager: &SchemaManager<'_>, type_name: &str) -> Result<bool, DbErr> {
+    let db = manager.get_connection();
+    let row = db
+        .query_one(Statement::from_sql_and_values(
+            manager.get_database_backend(),
+            "SELECT 1 FROM pg_type WHERE typname = $1",
+            vec![type_name.into()],
+        ))
+        .await?;
+    Ok(row.is_some())
+}
+
+/// A foreign key from `from_table.from_column` to `to_table.to_column`, shared by every
+/// migration that links its tables to the others.
+pub(crate) fn foreign_key<T, F>(
+    from_table: T,
+    from_column: F,
+    to_table: impl Iden + 'static,
+    to_column: impl Iden + 'static,
+) -> ForeignKeyCreateStatement
+where
+    T: Iden + 'static,
+    F: Iden + 'static,
+{
+    ForeignKey::create()
+        .from(from_table, from_column)
+        .to(to_table, to_column)
+        .to_owned()
+}
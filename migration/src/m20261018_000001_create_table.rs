@@ -0,0 +1,365 @@
+use sea_orm_migration::prelude::*;
+
+use crate::foreign_key;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+pub enum RoleType {
+    #[iden = "role_type"]
+    Type,
+    #[iden = "OWNER"]
+    Owner,
+    #[iden = "ADMIN"]
+    Admin,
+    #[iden = "DRIVER"]
+    Driver,
+}
+
+#[derive(Iden)]
+pub enum ItemType {
+    #[iden = "item_type"]
+    Type,
+    #[iden = "BASIC"]
+    Basic,
+    #[iden = "CUSTOM"]
+    Custom,
+    #[iden = "DEFAULT"]
+    Default,
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Iden)]
+pub enum User {
+    Table,
+    Id,
+    UserName,
+    AvatarUrl,
+}
+
+#[derive(Iden)]
+pub enum Item {
+    Table,
+    Id,
+    Type,
+    Name,
+    TeamId,
+    IconUrl,
+}
+
+#[derive(Iden)]
+pub enum TeamCar {
+    Table,
+    Id,
+    TeamId,
+    CarPlateNumber,
+}
+
+#[derive(Iden)]
+pub enum Billing {
+    Table,
+    Id,
+    Name,
+    TeamId,
+    StartTime,
+    EndTime,
+}
+
+#[derive(Iden)]
+enum Role {
+    Table,
+    Id,
+    UserId,
+    Type,
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Iden)]
+pub enum Team {
+    Table,
+    Id,
+    TeamName,
+    UserId,
+}
+
+#[derive(Iden)]
+enum TeamDriver {
+    Table,
+    Id,
+    UserId,
+    TeamId,
+}
+
+#[derive(Iden)]
+pub enum BillingItem {
+    Table,
+    Id,
+    BillingId,
+    Cost,
+    ItemId,
+    Time,
+}
+
+/// Creates the schema as it was first written down in `docs/数据库设计.md`. Every
+/// statement tolerates objects that already exist, so databases set up by hand from that
+/// SQL can adopt the migration history without being rebuilt.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if !crate::has_type(manager, "role_type").await? {
+            manager
+                .create_type(
+                    extension::postgres::Type::create()
+                        .as_enum(RoleType::Type)
+                        .values([RoleType::Owner, RoleType::Admin, RoleType::Driver])
+                        .to_owned(),
+                )
+                .await?;
+        }
+        if !crate::has_type(manager, "item_type").await? {
+            manager
+                .create_type(
+                    extension::postgres::Type::create()
+                        .as_enum(ItemType::Type)
+                        .values([ItemType::Basic, ItemType::Custom, ItemType::Default])
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(User::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(User::Id)
+                            .string_len(128)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(User::UserName).string_len(128).not_null())
+                    .col(ColumnDef::new(User::AvatarUrl).text())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Role::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Role::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Role::UserId).string_len(128).not_null())
+                    .col(ColumnDef::new(Role::Type).custom(RoleType::Type).not_null())
+                    .foreign_key(&mut foreign_key(
+                        Role::Table,
+                        Role::UserId,
+                        User::Table,
+                        User::Id,
+                    ))
+                    .index(Index::create().unique().col(Role::UserId).col(Role::Type))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Team::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Team::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Team::TeamName).string_len(128).not_null())
+                    .col(ColumnDef::new(Team::UserId).string_len(128).not_null())
+                    .foreign_key(&mut foreign_key(
+                        Team::Table,
+                        Team::UserId,
+                        User::Table,
+                        User::Id,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(TeamCar::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(TeamCar::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(TeamCar::TeamId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(TeamCar::CarPlateNumber)
+                            .string_len(128)
+                            .not_null(),
+                    )
+                    .foreign_key(&mut foreign_key(
+                        TeamCar::Table,
+                        TeamCar::TeamId,
+                        Team::Table,
+                        Team::Id,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(TeamDriver::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TeamDriver::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TeamDriver::UserId)
+                            .string_len(128)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TeamDriver::TeamId).uuid().not_null())
+                    .foreign_key(&mut foreign_key(
+                        TeamDriver::Table,
+                        TeamDriver::UserId,
+                        User::Table,
+                        User::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        TeamDriver::Table,
+                        TeamDriver::TeamId,
+                        Team::Table,
+                        Team::Id,
+                    ))
+                    .index(
+                        Index::create()
+                            .unique()
+                            .col(TeamDriver::UserId)
+                            .col(TeamDriver::TeamId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Item::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Item::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Item::Type).custom(ItemType::Type).not_null())
+                    .col(ColumnDef::new(Item::Name).string_len(128).not_null())
+                    .col(ColumnDef::new(Item::TeamId).uuid())
+                    .col(ColumnDef::new(Item::IconUrl).text())
+                    .foreign_key(&mut foreign_key(
+                        Item::Table,
+                        Item::TeamId,
+                        Team::Table,
+                        Team::Id,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Billing::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Billing::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Billing::Name).string_len(128).not_null())
+                    .col(ColumnDef::new(Billing::TeamId).uuid())
+                    .col(ColumnDef::new(Billing::StartTime).timestamp())
+                    .col(ColumnDef::new(Billing::EndTime).timestamp())
+                    .foreign_key(&mut foreign_key(
+                        Billing::Table,
+                        Billing::TeamId,
+                        Team::Table,
+                        Team::Id,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(BillingItem::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(BillingItem::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(BillingItem::BillingId).uuid())
+                    .col(
+                        ColumnDef::new(BillingItem::Cost)
+                            .decimal_len(12, 2)
+                            .not_null()
+                            .extra("CHECK (cost > 0)".to_owned()),
+                    )
+                    .col(ColumnDef::new(BillingItem::ItemId).uuid())
+                    .col(ColumnDef::new(BillingItem::Time).timestamp().not_null())
+                    .foreign_key(&mut foreign_key(
+                        BillingItem::Table,
+                        BillingItem::BillingId,
+                        Billing::Table,
+                        Billing::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        BillingItem::Table,
+                        BillingItem::ItemId,
+                        Item::Table,
+                        Item::Id,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(BillingItem::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Billing::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Item::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(TeamDriver::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(TeamCar::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Team::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Role::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(User::Table).to_owned())
+            .await?;
+        manager
+            .drop_type(
+                extension::postgres::Type::drop()
+                    .name(ItemType::Type)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_type(
+                extension::postgres::Type::drop()
+                    .name(RoleType::Type)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,52 @@
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, Statement},
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Repairs databases created from the hand-run SQL that used to live in the docs. That
+/// script declared the `'COSTOM'` label while `ItemType` maps `"CUSTOM"`, and stored
+/// `billing_item.cost` as `money` while the entity reads a `Decimal`. On a database built
+/// by the first migration both blocks are no-ops.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        for sql in [
+            r#"DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM pg_enum e JOIN pg_type t ON t.oid = e.enumtypid
+        WHERE t.typname = 'item_type' AND e.enumlabel = 'COSTOM'
+    ) THEN
+        ALTER TYPE item_type RENAME VALUE 'COSTOM' TO 'CUSTOM';
+    END IF;
+END $$"#,
+            r#"DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'billing_item' AND column_name = 'cost' AND data_type = 'money'
+    ) THEN
+        ALTER TABLE billing_item DROP CONSTRAINT IF EXISTS billing_item_cost_check;
+        ALTER TABLE billing_item ALTER COLUMN cost TYPE numeric(12, 2) USING cost::numeric;
+        ALTER TABLE billing_item ADD CONSTRAINT billing_item_cost_check CHECK (cost > 0);
+    END IF;
+END $$"#,
+        ] {
+            db.execute(Statement::from_string(
+                manager.get_database_backend(),
+                sql.to_owned(),
+            ))
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        // Going back to the drifted schema would break the entities, so there is nothing to undo.
+        Ok(())
+    }
+}
@@ -0,0 +1,415 @@
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, Statement},
+};
+
+use crate::foreign_key;
+use crate::m20261018_000001_create_table::{Billing, Item, Team, TeamCar, User};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum BillingTemplate {
+    Table,
+    Id,
+    TeamId,
+    Name,
+}
+
+#[derive(Iden)]
+enum BillingTemplateItem {
+    Table,
+    Id,
+    TemplateId,
+    ItemId,
+    SortOrder,
+    DefaultCost,
+    Required,
+}
+
+#[derive(Iden)]
+enum BillingExpectedItem {
+    Table,
+    Id,
+    BillingId,
+    ItemId,
+    SortOrder,
+    DefaultCost,
+    Required,
+}
+
+#[derive(Iden)]
+enum Dispatch {
+    Table,
+    Id,
+    TeamId,
+    TeamCarId,
+    BillingId,
+    StartTime,
+    EndTime,
+}
+
+#[derive(Iden)]
+enum DispatchDriver {
+    Table,
+    Id,
+    DispatchId,
+    UserId,
+}
+
+#[derive(Iden)]
+enum UserSession {
+    Table,
+    Id,
+    UserId,
+    AccessToken,
+    RefreshToken,
+    AccessExpireTime,
+    RefreshExpireTime,
+    CreateTime,
+}
+
+/// Billing items attributed to drivers, billings closed with a total and bound to a truck,
+/// dispatches, the item catalog, billing templates and session tokens.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(BillingTemplate::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(BillingTemplate::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(BillingTemplate::TeamId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(BillingTemplate::Name)
+                            .string_len(128)
+                            .not_null(),
+                    )
+                    .foreign_key(&mut foreign_key(
+                        BillingTemplate::Table,
+                        BillingTemplate::TeamId,
+                        Team::Table,
+                        Team::Id,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(BillingTemplateItem::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(BillingTemplateItem::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(BillingTemplateItem::TemplateId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(BillingTemplateItem::ItemId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(BillingTemplateItem::SortOrder)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(BillingTemplateItem::DefaultCost).decimal_len(12, 2))
+                    .col(
+                        ColumnDef::new(BillingTemplateItem::Required)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .foreign_key(
+                        foreign_key(
+                            BillingTemplateItem::Table,
+                            BillingTemplateItem::TemplateId,
+                            BillingTemplate::Table,
+                            BillingTemplate::Id,
+                        )
+                        .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(&mut foreign_key(
+                        BillingTemplateItem::Table,
+                        BillingTemplateItem::ItemId,
+                        Item::Table,
+                        Item::Id,
+                    ))
+                    .index(
+                        Index::create()
+                            .unique()
+                            .col(BillingTemplateItem::TemplateId)
+                            .col(BillingTemplateItem::ItemId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Columns on the first tables are added in plain SQL, `ADD COLUMN IF NOT EXISTS`
+        // skips the inline foreign key too when a hand-patched database already has it.
+        let db = manager.get_connection();
+        for sql in [
+            "ALTER TABLE item ADD COLUMN IF NOT EXISTS archived boolean NOT NULL DEFAULT false",
+            "ALTER TABLE billing ADD COLUMN IF NOT EXISTS total_cost numeric(12, 2)",
+            "ALTER TABLE billing ADD COLUMN IF NOT EXISTS team_car_id uuid REFERENCES team_car(id)",
+            "ALTER TABLE billing ADD COLUMN IF NOT EXISTS template_id uuid REFERENCES billing_template(id) ON DELETE SET NULL",
+            "ALTER TABLE billing_item ADD COLUMN IF NOT EXISTS user_id VARCHAR(128) REFERENCES \"user\"(id)",
+        ] {
+            db.execute(Statement::from_string(
+                manager.get_database_backend(),
+                sql.to_owned(),
+            ))
+            .await?;
+        }
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(BillingExpectedItem::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(BillingExpectedItem::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(BillingExpectedItem::BillingId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(BillingExpectedItem::ItemId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(BillingExpectedItem::SortOrder)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(BillingExpectedItem::DefaultCost).decimal_len(12, 2))
+                    .col(
+                        ColumnDef::new(BillingExpectedItem::Required)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .foreign_key(&mut foreign_key(
+                        BillingExpectedItem::Table,
+                        BillingExpectedItem::BillingId,
+                        Billing::Table,
+                        Billing::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        BillingExpectedItem::Table,
+                        BillingExpectedItem::ItemId,
+                        Item::Table,
+                        Item::Id,
+                    ))
+                    .index(
+                        Index::create()
+                            .unique()
+                            .col(BillingExpectedItem::BillingId)
+                            .col(BillingExpectedItem::ItemId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Dispatch::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Dispatch::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Dispatch::TeamId).uuid().not_null())
+                    .col(ColumnDef::new(Dispatch::TeamCarId).uuid().not_null())
+                    .col(ColumnDef::new(Dispatch::BillingId).uuid().not_null())
+                    .col(ColumnDef::new(Dispatch::StartTime).timestamp().not_null())
+                    .col(ColumnDef::new(Dispatch::EndTime).timestamp())
+                    .foreign_key(&mut foreign_key(
+                        Dispatch::Table,
+                        Dispatch::TeamId,
+                        Team::Table,
+                        Team::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        Dispatch::Table,
+                        Dispatch::TeamCarId,
+                        TeamCar::Table,
+                        TeamCar::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        Dispatch::Table,
+                        Dispatch::BillingId,
+                        Billing::Table,
+                        Billing::Id,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(DispatchDriver::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(DispatchDriver::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(DispatchDriver::DispatchId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(DispatchDriver::UserId)
+                            .string_len(128)
+                            .not_null(),
+                    )
+                    .foreign_key(&mut foreign_key(
+                        DispatchDriver::Table,
+                        DispatchDriver::DispatchId,
+                        Dispatch::Table,
+                        Dispatch::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        DispatchDriver::Table,
+                        DispatchDriver::UserId,
+                        User::Table,
+                        User::Id,
+                    ))
+                    .index(
+                        Index::create()
+                            .unique()
+                            .col(DispatchDriver::DispatchId)
+                            .col(DispatchDriver::UserId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserSession::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UserSession::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(UserSession::UserId)
+                            .string_len(128)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UserSession::AccessToken)
+                            .string_len(64)
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(UserSession::RefreshToken)
+                            .string_len(64)
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(UserSession::AccessExpireTime)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UserSession::RefreshExpireTime)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UserSession::CreateTime)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("user_session_user_id_idx")
+                    .table(UserSession::Table)
+                    .col(UserSession::UserId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // sea-query can not express partial indexes, these keep one open billing and one
+        // active dispatch per truck.
+        for sql in [
+            "CREATE UNIQUE INDEX IF NOT EXISTS billing_open_team_car_idx ON billing (team_car_id) WHERE end_time IS NULL",
+            "CREATE UNIQUE INDEX IF NOT EXISTS dispatch_active_team_car_idx ON dispatch (team_car_id) WHERE end_time IS NULL",
+        ] {
+            db.execute(Statement::from_string(
+                manager.get_database_backend(),
+                sql.to_owned(),
+            ))
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UserSession::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(DispatchDriver::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Dispatch::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(BillingExpectedItem::Table).to_owned())
+            .await?;
+        let db = manager.get_connection();
+        for sql in [
+            "DROP INDEX IF EXISTS billing_open_team_car_idx",
+            "ALTER TABLE billing_item DROP COLUMN IF EXISTS user_id",
+            "ALTER TABLE billing DROP COLUMN IF EXISTS template_id",
+            "ALTER TABLE billing DROP COLUMN IF EXISTS team_car_id",
+            "ALTER TABLE billing DROP COLUMN IF EXISTS total_cost",
+            "ALTER TABLE item DROP COLUMN IF EXISTS archived",
+        ] {
+            db.execute(Statement::from_string(
+                manager.get_database_backend(),
+                sql.to_owned(),
+            ))
+            .await?;
+        }
+        manager
+            .drop_table(Table::drop().table(BillingTemplateItem::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(BillingTemplate::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use sea_orm_migration::prelude::*;
+
+use crate::foreign_key;
+use crate::m20261018_000001_create_table::{Team, User};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum TeamInvite {
+    Table,
+    Id,
+    TeamId,
+    Code,
+    CreatedBy,
+    MaxUses,
+    UsedCount,
+    ExpireTime,
+    RevokedTime,
+    CreateTime,
+}
+
+/// Invite codes that let drivers join a team from the mini program.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TeamInvite::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TeamInvite::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TeamInvite::TeamId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(TeamInvite::Code)
+                            .string_len(16)
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TeamInvite::CreatedBy)
+                            .string_len(128)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TeamInvite::MaxUses)
+                            .integer()
+                            .extra("CHECK (max_uses > 0)".to_owned()),
+                    )
+                    .col(
+                        ColumnDef::new(TeamInvite::UsedCount)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(TeamInvite::ExpireTime)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TeamInvite::RevokedTime).timestamp())
+                    .col(
+                        ColumnDef::new(TeamInvite::CreateTime)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(&mut foreign_key(
+                        TeamInvite::Table,
+                        TeamInvite::TeamId,
+                        Team::Table,
+                        Team::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        TeamInvite::Table,
+                        TeamInvite::CreatedBy,
+                        User::Table,
+                        User::Id,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("team_invite_team_id_idx")
+                    .table(TeamInvite::Table)
+                    .col(TeamInvite::TeamId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TeamInvite::Table).to_owned())
+            .await
+    }
+}
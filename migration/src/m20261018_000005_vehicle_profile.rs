@@ -0,0 +1,108 @@
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, Statement},
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum FuelType {
+    #[iden = "fuel_type"]
+    Type,
+    #[iden = "DIESEL"]
+    Diesel,
+    #[iden = "GASOLINE"]
+    Gasoline,
+    #[iden = "LNG"]
+    Lng,
+    #[iden = "CNG"]
+    Cng,
+    #[iden = "ELECTRIC"]
+    Electric,
+    #[iden = "HYBRID"]
+    Hybrid,
+}
+
+/// Vehicle profile of a truck on `team_car`, the plate stays the only required field.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if !crate::has_type(manager, "fuel_type").await? {
+            manager
+                .create_type(
+                    extension::postgres::Type::create()
+                        .as_enum(FuelType::Type)
+                        .values([
+                            FuelType::Diesel,
+                            FuelType::Gasoline,
+                            FuelType::Lng,
+                            FuelType::Cng,
+                            FuelType::Electric,
+                            FuelType::Hybrid,
+                        ])
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        // Plates typed before validation existed are brought to the stored form the service
+        // writes, see `normalize_plate`: full width characters folded to ASCII, whitespace
+        // and the separators `·•・.-` dropped, upper case.
+        let full_width: String = ('\u{ff01}'..='\u{ff5e}').collect();
+        let half_width: String = ('\u{21}'..='\u{7e}').collect();
+        let normalize_plates = format!(
+            "UPDATE team_car SET car_plate_number = upper(regexp_replace(translate(car_plate_number, '{}', '{}'), '[[:space:]\u{3000}·•・.-]', '', 'g'))",
+            full_width,
+            half_width.replace('\'', "''")
+        );
+        let db = manager.get_connection();
+        for sql in [
+            "ALTER TABLE team_car ADD COLUMN IF NOT EXISTS vin VARCHAR(17)",
+            "ALTER TABLE team_car ADD COLUMN IF NOT EXISTS vehicle_model VARCHAR(128)",
+            "ALTER TABLE team_car ADD COLUMN IF NOT EXISTS axle_count smallint CHECK (axle_count BETWEEN 2 AND 12)",
+            "ALTER TABLE team_car ADD COLUMN IF NOT EXISTS load_capacity numeric(8, 2) CHECK (load_capacity > 0)",
+            "ALTER TABLE team_car ADD COLUMN IF NOT EXISTS fuel_type fuel_type",
+            "ALTER TABLE team_car ADD COLUMN IF NOT EXISTS purchase_date date",
+            "ALTER TABLE team_car ADD COLUMN IF NOT EXISTS insurance_expire_date date",
+            "ALTER TABLE team_car ADD COLUMN IF NOT EXISTS inspection_expire_date date",
+            "ALTER TABLE team_car ADD COLUMN IF NOT EXISTS odometer_km integer CHECK (odometer_km >= 0)",
+            normalize_plates.as_str(),
+            "CREATE INDEX IF NOT EXISTS team_car_team_id_idx ON team_car (team_id)",
+            // Backs `ensure_plate_free`, two cars added at once can not share a plate.
+            "CREATE UNIQUE INDEX IF NOT EXISTS team_car_team_id_plate_key ON team_car (team_id, car_plate_number)",
+        ] {
+            db.execute(Statement::from_string(
+                manager.get_database_backend(),
+                sql.to_owned(),
+            ))
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        for sql in [
+            "DROP INDEX IF EXISTS team_car_team_id_plate_key",
+            "DROP INDEX IF EXISTS team_car_team_id_idx",
+            "ALTER TABLE team_car DROP COLUMN IF EXISTS odometer_km",
+            "ALTER TABLE team_car DROP COLUMN IF EXISTS inspection_expire_date",
+            "ALTER TABLE team_car DROP COLUMN IF EXISTS insurance_expire_date",
+            "ALTER TABLE team_car DROP COLUMN IF EXISTS purchase_date",
+            "ALTER TABLE team_car DROP COLUMN IF EXISTS fuel_type",
+            "ALTER TABLE team_car DROP COLUMN IF EXISTS load_capacity",
+            "ALTER TABLE team_car DROP COLUMN IF EXISTS axle_count",
+            "ALTER TABLE team_car DROP COLUMN IF EXISTS vehicle_model",
+            "ALTER TABLE team_car DROP COLUMN IF EXISTS vin",
+            "DROP TYPE IF EXISTS fuel_type",
+        ] {
+            db.execute(Statement::from_string(
+                manager.get_database_backend(),
+                sql.to_owned(),
+            ))
+            .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, Statement},
+};
+
+use crate::foreign_key;
+use crate::m20261018_000001_create_table::{Team, TeamCar, User};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum ReminderKind {
+    #[iden = "reminder_kind"]
+    Type,
+    #[iden = "INSURANCE"]
+    Insurance,
+    #[iden = "INSPECTION"]
+    Inspection,
+    #[iden = "DRIVER_LICENCE"]
+    DriverLicence,
+}
+
+#[derive(Iden)]
+enum Reminder {
+    Table,
+    Id,
+    TeamId,
+    Kind,
+    TeamCarId,
+    UserId,
+    ExpireDate,
+    RemindDate,
+    NotifiedTime,
+    CreateTime,
+}
+
+/// Driver licence expiry on `team_driver` and the reminders raised ahead of insurance,
+/// annual inspection and licence expiry.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if !crate::has_type(manager, "reminder_kind").await? {
+            manager
+                .create_type(
+                    extension::postgres::Type::create()
+                        .as_enum(ReminderKind::Type)
+                        .values([
+                            ReminderKind::Insurance,
+                            ReminderKind::Inspection,
+                            ReminderKind::DriverLicence,
+                        ])
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        let db = manager.get_connection();
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            "ALTER TABLE team_driver ADD COLUMN IF NOT EXISTS licence_expire_date date".to_owned(),
+        ))
+        .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Reminder::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Reminder::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Reminder::TeamId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(Reminder::Kind)
+                            .custom(ReminderKind::Type)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Reminder::TeamCarId).uuid())
+                    .col(ColumnDef::new(Reminder::UserId).string_len(128))
+                    .col(ColumnDef::new(Reminder::ExpireDate).date().not_null())
+                    .col(ColumnDef::new(Reminder::RemindDate).date().not_null())
+                    .col(ColumnDef::new(Reminder::NotifiedTime).timestamp())
+                    .col(ColumnDef::new(Reminder::CreateTime).timestamp().not_null())
+                    .foreign_key(&mut foreign_key(
+                        Reminder::Table,
+                        Reminder::TeamId,
+                        Team::Table,
+                        Team::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        Reminder::Table,
+                        Reminder::TeamCarId,
+                        TeamCar::Table,
+                        TeamCar::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        Reminder::Table,
+                        Reminder::UserId,
+                        User::Table,
+                        User::Id,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("reminder_team_id_idx")
+                    .table(Reminder::Table)
+                    .col(Reminder::TeamId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // One reminder per document and expiry date, a renewed document gets a new date and
+        // so a new reminder. sea-query can not index expressions, the subject is either the
+        // truck or the driver.
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            "CREATE UNIQUE INDEX IF NOT EXISTS reminder_subject_idx ON reminder (team_id, kind, COALESCE(team_car_id::text, user_id), expire_date)".to_owned(),
+        ))
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Reminder::Table).to_owned())
+            .await?;
+        let db = manager.get_connection();
+        for sql in [
+            "ALTER TABLE team_driver DROP COLUMN IF EXISTS licence_expire_date",
+            "DROP TYPE IF EXISTS reminder_kind",
+        ] {
+            db.execute(Statement::from_string(
+                manager.get_database_backend(),
+                sql.to_owned(),
+            ))
+            .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, Statement},
+};
+
+use crate::foreign_key;
+use crate::m20261018_000001_create_table::{BillingItem, TeamCar};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum ItemCategory {
+    #[iden = "item_category"]
+    Type,
+    #[iden = "FUEL"]
+    Fuel,
+    #[iden = "TOLL"]
+    Toll,
+    #[iden = "REPAIR"]
+    Repair,
+    #[iden = "MEAL"]
+    Meal,
+    #[iden = "LODGING"]
+    Lodging,
+    #[iden = "OTHER"]
+    Other,
+}
+
+#[derive(Iden)]
+enum FuelEntry {
+    Table,
+    BillingItemId,
+    TeamCarId,
+    Litres,
+    UnitPrice,
+    Station,
+    OdometerKm,
+    FullTank,
+}
+
+/// A category on `item`, and the litres, price and odometer reading recorded with every
+/// billing item of a `FUEL` item.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if !crate::has_type(manager, "item_category").await? {
+            manager
+                .create_type(
+                    extension::postgres::Type::create()
+                        .as_enum(ItemCategory::Type)
+                        .values([
+                            ItemCategory::Fuel,
+                            ItemCategory::Toll,
+                            ItemCategory::Repair,
+                            ItemCategory::Meal,
+                            ItemCategory::Lodging,
+                            ItemCategory::Other,
+                        ])
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        let db = manager.get_connection();
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            "ALTER TABLE item ADD COLUMN IF NOT EXISTS category item_category NOT NULL DEFAULT 'OTHER'"
+                .to_owned(),
+        ))
+        .await?;
+
+        // One fill-up per billing item, the truck is copied from the billing so the
+        // analytics of a truck do not depend on how its billings are split.
+        manager
+            .create_table(
+                Table::create()
+                    .table(FuelEntry::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(FuelEntry::BillingItemId)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(FuelEntry::TeamCarId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(FuelEntry::Litres)
+                            .decimal_len(10, 2)
+                            .not_null()
+                            .extra("CHECK (litres > 0)".to_owned()),
+                    )
+                    .col(
+                        ColumnDef::new(FuelEntry::UnitPrice)
+                            .decimal_len(8, 2)
+                            .not_null()
+                            .extra("CHECK (unit_price > 0)".to_owned()),
+                    )
+                    .col(ColumnDef::new(FuelEntry::Station).string_len(128))
+                    .col(
+                        ColumnDef::new(FuelEntry::OdometerKm)
+                            .integer()
+                            .not_null()
+                            .extra("CHECK (odometer_km >= 0)".to_owned()),
+                    )
+                    .col(
+                        ColumnDef::new(FuelEntry::FullTank)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .foreign_key(&mut foreign_key(
+                        FuelEntry::Table,
+                        FuelEntry::BillingItemId,
+                        BillingItem::Table,
+                        BillingItem::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        FuelEntry::Table,
+                        FuelEntry::TeamCarId,
+                        TeamCar::Table,
+                        TeamCar::Id,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("fuel_entry_team_car_id_idx")
+                    .table(FuelEntry::Table)
+                    .col(FuelEntry::TeamCarId)
+                    .col(FuelEntry::OdometerKm)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(FuelEntry::Table).to_owned())
+            .await?;
+        let db = manager.get_connection();
+        for sql in [
+            "ALTER TABLE item DROP COLUMN IF EXISTS category",
+            "DROP TYPE IF EXISTS item_category",
+        ] {
+            db.execute(Statement::from_string(
+                manager.get_database_backend(),
+                sql.to_owned(),
+            ))
+            .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use sea_orm_migration::prelude::*;
+
+use crate::foreign_key;
+use crate::m20261018_000001_create_table::{Billing, User};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum BillingIncome {
+    Table,
+    Id,
+    BillingId,
+    CustomerName,
+    Cargo,
+    Origin,
+    Destination,
+    WeightTonnes,
+    UnitPrice,
+    Amount,
+    ReceivedAmount,
+    UserId,
+    Time,
+}
+
+/// Freight income recorded on a billing next to its costs.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(BillingIncome::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(BillingIncome::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(BillingIncome::BillingId).uuid().not_null())
+                    .col(ColumnDef::new(BillingIncome::CustomerName).string_len(128))
+                    .col(ColumnDef::new(BillingIncome::Cargo).string_len(128))
+                    .col(ColumnDef::new(BillingIncome::Origin).string_len(128))
+                    .col(ColumnDef::new(BillingIncome::Destination).string_len(128))
+                    .col(
+                        ColumnDef::new(BillingIncome::WeightTonnes)
+                            .decimal_len(10, 3)
+                            .extra("CHECK (weight_tonnes > 0)".to_owned()),
+                    )
+                    .col(
+                        ColumnDef::new(BillingIncome::UnitPrice)
+                            .decimal_len(10, 2)
+                            .extra("CHECK (unit_price > 0)".to_owned()),
+                    )
+                    .col(
+                        ColumnDef::new(BillingIncome::Amount)
+                            .decimal_len(12, 2)
+                            .not_null()
+                            .extra("CHECK (amount > 0)".to_owned()),
+                    )
+                    .col(
+                        ColumnDef::new(BillingIncome::ReceivedAmount)
+                            .decimal_len(12, 2)
+                            .not_null()
+                            .default(0)
+                            .extra("CHECK (received_amount >= 0)".to_owned()),
+                    )
+                    .col(ColumnDef::new(BillingIncome::UserId).string_len(128))
+                    .col(ColumnDef::new(BillingIncome::Time).timestamp().not_null())
+                    .foreign_key(&mut foreign_key(
+                        BillingIncome::Table,
+                        BillingIncome::BillingId,
+                        Billing::Table,
+                        Billing::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        BillingIncome::Table,
+                        BillingIncome::UserId,
+                        User::Table,
+                        User::Id,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("billing_income_billing_id_idx")
+                    .table(BillingIncome::Table)
+                    .col(BillingIncome::BillingId)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(BillingIncome::Table).to_owned())
+            .await
+    }
+}
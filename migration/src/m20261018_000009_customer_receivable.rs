@@ -0,0 +1,226 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, Statement};
+
+use crate::foreign_key;
+use crate::m20261018_000001_create_table::{Team, User};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum Customer {
+    Table,
+    Id,
+    TeamId,
+    Name,
+    ContactName,
+    Phone,
+    Note,
+    CreateTime,
+}
+
+#[derive(Iden)]
+enum CustomerPayment {
+    Table,
+    Id,
+    CustomerId,
+    Amount,
+    PaidDate,
+    Note,
+    UserId,
+    Time,
+}
+
+#[derive(Iden)]
+enum PaymentAllocation {
+    Table,
+    Id,
+    PaymentId,
+    BillingIncomeId,
+    Amount,
+}
+
+#[derive(Iden)]
+enum BillingIncome {
+    Table,
+    Id,
+}
+
+/// Customers of a team, the payments they make and how those payments settle income entries.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Customer::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Customer::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Customer::TeamId).uuid().not_null())
+                    .col(ColumnDef::new(Customer::Name).string_len(128).not_null())
+                    .col(ColumnDef::new(Customer::ContactName).string_len(64))
+                    .col(ColumnDef::new(Customer::Phone).string_len(32))
+                    .col(ColumnDef::new(Customer::Note).string_len(255))
+                    .col(ColumnDef::new(Customer::CreateTime).timestamp().not_null())
+                    .foreign_key(&mut foreign_key(
+                        Customer::Table,
+                        Customer::TeamId,
+                        Team::Table,
+                        Team::Id,
+                    ))
+                    .index(
+                        Index::create()
+                            .unique()
+                            .col(Customer::TeamId)
+                            .col(Customer::Name),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+        for sql in [
+            "ALTER TABLE billing_income ADD COLUMN IF NOT EXISTS customer_id uuid REFERENCES customer(id)",
+            "CREATE INDEX IF NOT EXISTS billing_income_customer_id_idx ON billing_income (customer_id)",
+        ] {
+            db.execute(Statement::from_string(
+                manager.get_database_backend(),
+                sql.to_owned(),
+            ))
+            .await?;
+        }
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(CustomerPayment::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CustomerPayment::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(CustomerPayment::CustomerId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(CustomerPayment::Amount)
+                            .decimal_len(12, 2)
+                            .not_null()
+                            .extra("CHECK (amount > 0)".to_owned()),
+                    )
+                    .col(ColumnDef::new(CustomerPayment::PaidDate).date().not_null())
+                    .col(ColumnDef::new(CustomerPayment::Note).string_len(255))
+                    .col(ColumnDef::new(CustomerPayment::UserId).string_len(128))
+                    .col(ColumnDef::new(CustomerPayment::Time).timestamp().not_null())
+                    .foreign_key(&mut foreign_key(
+                        CustomerPayment::Table,
+                        CustomerPayment::CustomerId,
+                        Customer::Table,
+                        Customer::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        CustomerPayment::Table,
+                        CustomerPayment::UserId,
+                        User::Table,
+                        User::Id,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("customer_payment_customer_id_idx")
+                    .table(CustomerPayment::Table)
+                    .col(CustomerPayment::CustomerId)
+                    .col(CustomerPayment::PaidDate)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(PaymentAllocation::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PaymentAllocation::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PaymentAllocation::PaymentId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PaymentAllocation::BillingIncomeId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PaymentAllocation::Amount)
+                            .decimal_len(12, 2)
+                            .not_null()
+                            .extra("CHECK (amount > 0)".to_owned()),
+                    )
+                    .foreign_key(&mut foreign_key(
+                        PaymentAllocation::Table,
+                        PaymentAllocation::PaymentId,
+                        CustomerPayment::Table,
+                        CustomerPayment::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        PaymentAllocation::Table,
+                        PaymentAllocation::BillingIncomeId,
+                        BillingIncome::Table,
+                        BillingIncome::Id,
+                    ))
+                    .index(
+                        Index::create()
+                            .unique()
+                            .col(PaymentAllocation::PaymentId)
+                            .col(PaymentAllocation::BillingIncomeId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("payment_allocation_billing_income_id_idx")
+                    .table(PaymentAllocation::Table)
+                    .col(PaymentAllocation::BillingIncomeId)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PaymentAllocation::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(CustomerPayment::Table).to_owned())
+            .await?;
+        let db = manager.get_connection();
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            "ALTER TABLE billing_income DROP COLUMN IF EXISTS customer_id".to_owned(),
+        ))
+        .await?;
+        manager
+            .drop_table(Table::drop().table(Customer::Table).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,252 @@
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, Statement},
+};
+
+use crate::foreign_key;
+use crate::m20261018_000001_create_table::{Billing, User};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum ExpensePayer {
+    #[iden = "expense_payer"]
+    Type,
+    #[iden = "COMPANY"]
+    Company,
+    #[iden = "DRIVER"]
+    Driver,
+}
+
+#[derive(Iden)]
+enum DriverAdvance {
+    Table,
+    Id,
+    BillingId,
+    DispatchId,
+    UserId,
+    Amount,
+    Note,
+    IssuedBy,
+    Time,
+}
+
+#[derive(Iden)]
+enum BillingSettlement {
+    Table,
+    Id,
+    BillingId,
+    UserId,
+    AdvanceAmount,
+    DriverPaidAmount,
+    Balance,
+    OwnerConfirmedBy,
+    OwnerConfirmTime,
+    DriverConfirmTime,
+    CreateTime,
+}
+
+#[derive(Iden)]
+enum Dispatch {
+    Table,
+    Id,
+}
+
+/// Who paid each billing item, cash advances handed to drivers and the settlement between
+/// driver and company worked out when a billing ends.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if !crate::has_type(manager, "expense_payer").await? {
+            manager
+                .create_type(
+                    extension::postgres::Type::create()
+                        .as_enum(ExpensePayer::Type)
+                        .values([ExpensePayer::Company, ExpensePayer::Driver])
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        // Costs so far were recorded by drivers out of their own pocket.
+        let db = manager.get_connection();
+        db.execute(Statement::from_string(
+            manager.get_database_backend(),
+            "ALTER TABLE billing_item ADD COLUMN IF NOT EXISTS paid_by expense_payer NOT NULL DEFAULT 'DRIVER'"
+                .to_owned(),
+        ))
+        .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(DriverAdvance::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(DriverAdvance::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(DriverAdvance::BillingId).uuid().not_null())
+                    .col(ColumnDef::new(DriverAdvance::DispatchId).uuid())
+                    .col(
+                        ColumnDef::new(DriverAdvance::UserId)
+                            .string_len(128)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(DriverAdvance::Amount)
+                            .decimal_len(12, 2)
+                            .not_null()
+                            .extra("CHECK (amount > 0)".to_owned()),
+                    )
+                    .col(ColumnDef::new(DriverAdvance::Note).string_len(255))
+                    .col(ColumnDef::new(DriverAdvance::IssuedBy).string_len(128))
+                    .col(ColumnDef::new(DriverAdvance::Time).timestamp().not_null())
+                    .foreign_key(&mut foreign_key(
+                        DriverAdvance::Table,
+                        DriverAdvance::BillingId,
+                        Billing::Table,
+                        Billing::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        DriverAdvance::Table,
+                        DriverAdvance::DispatchId,
+                        Dispatch::Table,
+                        Dispatch::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        DriverAdvance::Table,
+                        DriverAdvance::UserId,
+                        User::Table,
+                        User::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        DriverAdvance::Table,
+                        DriverAdvance::IssuedBy,
+                        User::Table,
+                        User::Id,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("driver_advance_billing_id_idx")
+                    .table(DriverAdvance::Table)
+                    .col(DriverAdvance::BillingId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(BillingSettlement::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(BillingSettlement::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(BillingSettlement::BillingId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(BillingSettlement::UserId)
+                            .string_len(128)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(BillingSettlement::AdvanceAmount)
+                            .decimal_len(12, 2)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(BillingSettlement::DriverPaidAmount)
+                            .decimal_len(12, 2)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(BillingSettlement::Balance)
+                            .decimal_len(12, 2)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(BillingSettlement::OwnerConfirmedBy).string_len(128))
+                    .col(ColumnDef::new(BillingSettlement::OwnerConfirmTime).timestamp())
+                    .col(ColumnDef::new(BillingSettlement::DriverConfirmTime).timestamp())
+                    .col(
+                        ColumnDef::new(BillingSettlement::CreateTime)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(&mut foreign_key(
+                        BillingSettlement::Table,
+                        BillingSettlement::BillingId,
+                        Billing::Table,
+                        Billing::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        BillingSettlement::Table,
+                        BillingSettlement::UserId,
+                        User::Table,
+                        User::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        BillingSettlement::Table,
+                        BillingSettlement::OwnerConfirmedBy,
+                        User::Table,
+                        User::Id,
+                    ))
+                    .index(
+                        Index::create()
+                            .unique()
+                            .col(BillingSettlement::BillingId)
+                            .col(BillingSettlement::UserId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("billing_settlement_user_id_idx")
+                    .table(BillingSettlement::Table)
+                    .col(BillingSettlement::UserId)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(BillingSettlement::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(DriverAdvance::Table).to_owned())
+            .await?;
+        let db = manager.get_connection();
+        for sql in [
+            "ALTER TABLE billing_item DROP COLUMN IF EXISTS paid_by",
+            "DROP TYPE IF EXISTS expense_payer",
+        ] {
+            db.execute(Statement::from_string(
+                manager.get_database_backend(),
+                sql.to_owned(),
+            ))
+            .await?;
+        }
+        Ok(())
+    }
+}
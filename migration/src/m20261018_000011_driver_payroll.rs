@@ -0,0 +1,332 @@
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, Statement},
+};
+
+use crate::foreign_key;
+use crate::m20261018_000001_create_table::{Billing, Team, User};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum WageRuleType {
+    #[iden = "wage_rule_type"]
+    Type,
+    #[iden = "COMMISSION"]
+    Commission,
+    #[iden = "PER_KM"]
+    PerKm,
+    #[iden = "PER_TRIP"]
+    PerTrip,
+}
+
+#[derive(Iden)]
+enum WageRule {
+    Table,
+    Id,
+    TeamDriverId,
+    RuleType,
+    Rate,
+    UpdateTime,
+}
+
+#[derive(Iden)]
+enum WageRuleDeduction {
+    Table,
+    Id,
+    WageRuleId,
+    Category,
+}
+
+#[derive(Iden)]
+enum PayrollRun {
+    Table,
+    Id,
+    TeamId,
+    PeriodStart,
+    CreatedBy,
+    CreateTime,
+    ConfirmedBy,
+    ConfirmTime,
+}
+
+#[derive(Iden)]
+enum PayslipLine {
+    Table,
+    Id,
+    PayrollRunId,
+    UserId,
+    BillingId,
+    RuleType,
+    Quantity,
+    Rate,
+    Amount,
+}
+
+#[derive(Iden)]
+enum TeamDriver {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum ItemCategory {
+    #[iden = "item_category"]
+    Type,
+}
+
+/// Odometer readings on dispatches, the wage rule of each team driver and the monthly payroll
+/// runs with their payslip lines.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        for sql in [
+            "ALTER TABLE dispatch ADD COLUMN IF NOT EXISTS start_odometer_km integer CHECK (start_odometer_km >= 0)",
+            "ALTER TABLE dispatch ADD COLUMN IF NOT EXISTS end_odometer_km integer CHECK (end_odometer_km >= start_odometer_km)",
+        ] {
+            db.execute(Statement::from_string(
+                manager.get_database_backend(),
+                sql.to_owned(),
+            ))
+            .await?;
+        }
+
+        if !crate::has_type(manager, "wage_rule_type").await? {
+            manager
+                .create_type(
+                    extension::postgres::Type::create()
+                        .as_enum(WageRuleType::Type)
+                        .values([
+                            WageRuleType::Commission,
+                            WageRuleType::PerKm,
+                            WageRuleType::PerTrip,
+                        ])
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(WageRule::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(WageRule::Id).uuid().not_null().primary_key())
+                    .col(
+                        ColumnDef::new(WageRule::TeamDriverId)
+                            .uuid()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(WageRule::RuleType)
+                            .custom(WageRuleType::Type)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(WageRule::Rate)
+                            .decimal_len(12, 4)
+                            .not_null()
+                            .extra("CHECK (rate >= 0)".to_owned()),
+                    )
+                    .col(ColumnDef::new(WageRule::UpdateTime).timestamp().not_null())
+                    .foreign_key(&mut foreign_key(
+                        WageRule::Table,
+                        WageRule::TeamDriverId,
+                        TeamDriver::Table,
+                        TeamDriver::Id,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(WageRuleDeduction::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(WageRuleDeduction::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(WageRuleDeduction::WageRuleId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(WageRuleDeduction::Category)
+                            .custom(ItemCategory::Type)
+                            .not_null(),
+                    )
+                    .foreign_key(&mut foreign_key(
+                        WageRuleDeduction::Table,
+                        WageRuleDeduction::WageRuleId,
+                        WageRule::Table,
+                        WageRule::Id,
+                    ))
+                    .index(
+                        Index::create()
+                            .unique()
+                            .col(WageRuleDeduction::WageRuleId)
+                            .col(WageRuleDeduction::Category),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(PayrollRun::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PayrollRun::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PayrollRun::TeamId).uuid().not_null())
+                    .col(ColumnDef::new(PayrollRun::PeriodStart).date().not_null())
+                    .col(ColumnDef::new(PayrollRun::CreatedBy).string_len(128))
+                    .col(
+                        ColumnDef::new(PayrollRun::CreateTime)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(PayrollRun::ConfirmedBy).string_len(128))
+                    .col(ColumnDef::new(PayrollRun::ConfirmTime).timestamp())
+                    .foreign_key(&mut foreign_key(
+                        PayrollRun::Table,
+                        PayrollRun::TeamId,
+                        Team::Table,
+                        Team::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        PayrollRun::Table,
+                        PayrollRun::CreatedBy,
+                        User::Table,
+                        User::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        PayrollRun::Table,
+                        PayrollRun::ConfirmedBy,
+                        User::Table,
+                        User::Id,
+                    ))
+                    .index(
+                        Index::create()
+                            .unique()
+                            .col(PayrollRun::TeamId)
+                            .col(PayrollRun::PeriodStart),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(PayslipLine::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PayslipLine::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PayslipLine::PayrollRunId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(PayslipLine::UserId)
+                            .string_len(128)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(PayslipLine::BillingId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(PayslipLine::RuleType)
+                            .custom(WageRuleType::Type)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PayslipLine::Quantity)
+                            .decimal_len(12, 2)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PayslipLine::Rate)
+                            .decimal_len(12, 4)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PayslipLine::Amount)
+                            .decimal_len(12, 2)
+                            .not_null(),
+                    )
+                    .foreign_key(&mut foreign_key(
+                        PayslipLine::Table,
+                        PayslipLine::PayrollRunId,
+                        PayrollRun::Table,
+                        PayrollRun::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        PayslipLine::Table,
+                        PayslipLine::UserId,
+                        User::Table,
+                        User::Id,
+                    ))
+                    .foreign_key(&mut foreign_key(
+                        PayslipLine::Table,
+                        PayslipLine::BillingId,
+                        Billing::Table,
+                        Billing::Id,
+                    ))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("payslip_line_payroll_run_id_idx")
+                    .table(PayslipLine::Table)
+                    .col(PayslipLine::PayrollRunId)
+                    .col(PayslipLine::UserId)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PayslipLine::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(PayrollRun::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(WageRuleDeduction::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(WageRule::Table).to_owned())
+            .await?;
+        let db = manager.get_connection();
+        for sql in [
+            "DROP TYPE IF EXISTS wage_rule_type",
+            "ALTER TABLE dispatch DROP COLUMN IF EXISTS end_odometer_km",
+            "ALTER TABLE dispatch DROP COLUMN IF EXISTS start_odometer_km",
+        ] {
+            db.execute(Statement::from_string(
+                manager.get_database_backend(),
+                sql.to_owned(),
+            ))
+            .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter};
+use tracing::{instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    entities::{role, sea_orm_active_enums::RoleType, team, team_driver},
+    error::{AppError, ErrorCode},
+    session_service::service::SessionUser,
+};
+
+/// How the caller relates to a team.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TeamRole {
+    Owner,
+    Admin,
+    Driver,
+}
+
+/// What an endpoint asks of the caller.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TeamPermission {
+    /// Team owner or admin. Changes to the team, its cars, drivers, billings and catalog.
+    Manage,
+    /// Team owner, admin or a driver of the team. Reading team data and recording costs.
+    Member,
+}
+
+impl TeamRole {
+    pub fn allows(&self, permission: TeamPermission) -> bool {
+        match permission {
+            TeamPermission::Manage => matches!(self, TeamRole::Owner | TeamRole::Admin),
+            TeamPermission::Member => true,
+        }
+    }
+}
+
+/// Per endpoint authorization against a team, built on `team.user_id`, the ADMIN role and
+/// `team_driver`.
+pub struct TeamGuard;
+
+impl TeamGuard {
+    /// Resolves how the caller relates to the team and rejects them with
+    /// `ErrorCode::Forbidden` unless that relation grants `permission`.
+    #[instrument(skip(db))]
+    pub async fn check<C: ConnectionTrait>(
+        db: &C,
+        session_user: &SessionUser,
+        team_id: Uuid,
+        permission: TeamPermission,
+    ) -> Result<TeamRole, AppError> {
+        let team_model = team::Entity::find_by_id(team_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| {
+                AppError::new(
+                    ErrorCode::TeamNotFound,
+                    format!("team {} is not exist", team_id),
+                )
+            })?;
+        let role = if team_model.user_id == session_user.user_id {
+            Some(TeamRole::Owner)
+        } else if is_admin(db, session_user).await? {
+            Some(TeamRole::Admin)
+        } else if team_driver::Entity::find()
+            .filter(team_driver::Column::TeamId.eq(team_id))
+            .filter(team_driver::Column::UserId.eq(session_user.user_id.clone()))
+            .count(db)
+            .await?
+            > 0
+        {
+            Some(TeamRole::Driver)
+        } else {
+            None
+        };
+        match role {
+            Some(role) if role.allows(permission) => Ok(role),
+            _ => {
+                warn!(
+                    "User ({}) is rejected by team ({}), role is {:?}, permission is {:?}",
+                    session_user.user_id, team_id, role, permission
+                );
+                Err(AppError::forbidden(
+                    "caller is not allowed to access this team",
+                ))
+            }
+        }
+    }
+}
+
+#[instrument(skip(db))]
+pub async fn is_admin<C: ConnectionTrait>(
+    db: &C,
+    session_user: &SessionUser,
+) -> Result<bool, AppError> {
+    let admin_count = role::Entity::find()
+        .filter(role::Column::UserId.eq(session_user.user_id.clone()))
+        .filter(role::Column::Type.eq(RoleType::Admin))
+        .count(db)
+        .await?;
+    Ok(admin_count > 0)
+}
+
+/// Rejects callers without the ADMIN role.
+#[instrument(skip(db))]
+pub async fn require_admin<C: ConnectionTrait>(
+    db: &C,
+    session_user: &SessionUser,
+) -> Result<(), AppError> {
+    if is_admin(db, session_user).await? {
+        Ok(())
+    } else {
+        warn!("User ({}) is not ADMIN.", session_user.user_id);
+        Err(AppError::forbidden("caller is not an admin"))
+    }
+}
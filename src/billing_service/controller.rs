@@ -1,11 +1,30 @@
-use chrono::Local;
-use poem_openapi::{param::Path, payload::Json, ApiResponse, Object, OpenApi, Tags};
-use tracing::log::error;
-use uuid::{uuid, Uuid};
+use chrono::{DateTime, Local, NaiveDate};
+use poem::web::Data;
+use poem_openapi::{
+    param::{Path, Query},
+    payload::Json,
+    ApiResponse, Object, OpenApi, Tags,
+};
+use rust_decimal::Decimal;
 
-use crate::{billing_service::service::Team, team_service::service::TeamError};
+use crate::auth_service::service::{TeamGuard, TeamPermission};
+use crate::state::AppState;
 
-use super::service::TeamBillingService;
+use crate::billing_service::service::Team;
+use crate::dispatch_service::service::Dispatch;
+use crate::error::{parse_uuid, ApiResult, AppError, ErrorCode};
+use crate::fuel_service::controller::{FuelEntryDTO, FuelFillDTO};
+use crate::income_service::controller::BillingIncomeEntityDTO;
+use crate::session_service::service::SessionUser;
+use crate::settlement_service::controller::ExpensePayerDTO;
+
+use super::service::{
+    Billing, BillingItem, BillingItemService, BillingPage, BillingQuery, BillingStatus,
+    BillingSummary, ExpectedItem, ItemSubtotal, TeamBillingService,
+};
+
+pub(crate) const DEFAULT_PAGE_SIZE: usize = 20;
+pub(crate) const MAX_PAGE_SIZE: usize = 100;
 
 #[derive(Tags)]
 enum ApiTags {
@@ -15,26 +34,303 @@ enum ApiTags {
 #[derive(Debug, Object)]
 struct BillingCreateDTO {
     name: Option<String>,
+
+    #[oai(validator(max_length = 128))]
+    car_id: String,
+
+    #[oai(validator(max_length = 128))]
+    template_id: Option<String>,
+}
+
+#[derive(Debug, Object)]
+struct BillingUpdateDTO {
+    #[oai(validator(max_length = 128))]
+    billing_id: String,
+
+    #[oai(validator(max_length = 128))]
+    name: String,
 }
 
 #[derive(ApiResponse)]
-enum BillingResponse {
+enum UpdateBillingResponse {
     #[oai(status = 200)]
-    Ok,
+    Ok(Json<BillingEntityDTO>),
+}
 
+#[derive(ApiResponse)]
+enum CreateBillingResponse {
     #[oai(status = 201)]
-    Created,
+    Created(Json<BillingEntityDTO>),
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct BillingItemCreateDTO {
+    #[oai(validator(max_length = 128))]
+    item_id: String,
+
+    cost: Decimal,
+
+    /// `DRIVER` when omitted, driver paid costs are refunded in the billing settlement.
+    paid_by: Option<ExpensePayerDTO>,
+
+    /// Litres, price and odometer reading, required when the item is a `FUEL` item.
+    fuel: Option<FuelFillDTO>,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct BillingItemDeleteDTO {
+    #[oai(validator(max_length = 128))]
+    billing_item_id: String,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct BillingItemEntityDTO {
+    billing_item_id: String,
+    item_id: String,
+    name: String,
+    item_type: String,
+    cost: Decimal,
+    time: Option<DateTime<Local>>,
+    user_id: Option<String>,
+    paid_by: ExpensePayerDTO,
+    fuel: Option<FuelEntryDTO>,
+}
+
+impl From<BillingItem> for BillingItemEntityDTO {
+    fn from(billing_item: BillingItem) -> Self {
+        BillingItemEntityDTO {
+            billing_item_id: billing_item.id.to_string(),
+            item_id: billing_item.item_id.to_string(),
+            name: billing_item.name,
+            item_type: billing_item.item_type,
+            cost: billing_item.cost,
+            time: billing_item.time,
+            user_id: billing_item.user_id,
+            paid_by: billing_item.paid_by.into(),
+            fuel: billing_item.fuel.map(|fuel| fuel.into()),
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct BillingEndDTO {
+    #[oai(validator(max_length = 128))]
+    billing_id: String,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct BillingEntityDTO {
+    billing_id: String,
+    name: String,
+    car_id: Option<String>,
+    car_plate_number: Option<String>,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+    total_cost: Decimal,
+    /// Income, margin and income entries are left out for drivers.
+    total_income: Option<Decimal>,
+    received_income: Option<Decimal>,
+    gross_margin: Option<Decimal>,
+    gross_margin_percent: Option<Decimal>,
+    item_count: usize,
+    template_id: Option<String>,
+    billing_items: Vec<BillingItemEntityDTO>,
+    incomes: Option<Vec<BillingIncomeEntityDTO>>,
+    item_subtotals: Vec<ItemSubtotalDTO>,
+    expected_items: Vec<ExpectedItemDTO>,
+    missing_required_items: Vec<ExpectedItemDTO>,
+}
+
+impl From<Billing> for BillingEntityDTO {
+    fn from(billing: Billing) -> Self {
+        let summary = billing.summary();
+        let item_subtotals = billing
+            .item_subtotals()
+            .into_iter()
+            .map(|item_subtotal| item_subtotal.into())
+            .collect();
+        let expected_items = billing
+            .expected_items
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .map(|expected_item| {
+                ExpectedItemDTO::new(expected_item, billing.is_item_filled(expected_item.item_id))
+            })
+            .collect();
+        let missing_required_items = billing
+            .missing_required_items()
+            .into_iter()
+            .map(|expected_item| ExpectedItemDTO::new(expected_item, false))
+            .collect();
+        BillingEntityDTO {
+            billing_id: billing.id.to_string(),
+            name: billing.name,
+            car_id: billing
+                .team_car_id
+                .map(|team_car_id| team_car_id.to_string()),
+            car_plate_number: billing.car_plate_number,
+            start_time: billing.start_time,
+            end_time: billing.end_time,
+            total_cost: summary.total_cost,
+            total_income: summary.income.as_ref().map(|income| income.total_income),
+            received_income: summary.income.as_ref().map(|income| income.received_income),
+            gross_margin: summary.gross_margin(),
+            gross_margin_percent: summary.gross_margin_percent(),
+            item_count: summary.item_count,
+            template_id: billing
+                .template_id
+                .map(|template_id| template_id.to_string()),
+            item_subtotals,
+            expected_items,
+            missing_required_items,
+            billing_items: billing
+                .billing_items
+                .unwrap_or_default()
+                .into_iter()
+                .map(|billing_item| billing_item.into())
+                .collect(),
+            incomes: billing
+                .incomes
+                .map(|incomes| incomes.into_iter().map(|income| income.into()).collect()),
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct ExpectedItemDTO {
+    item_id: String,
+    name: String,
+    item_type: String,
+    default_cost: Option<Decimal>,
+    required: bool,
+    filled: bool,
+}
+
+impl ExpectedItemDTO {
+    fn new(expected_item: &ExpectedItem, filled: bool) -> Self {
+        ExpectedItemDTO {
+            item_id: expected_item.item_id.to_string(),
+            name: expected_item.name.clone(),
+            item_type: expected_item.item_type.clone(),
+            default_cost: expected_item.default_cost,
+            required: expected_item.required,
+            filled,
+        }
+    }
+}
 
-    #[oai(status = 500)]
-    Error,
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct ItemSubtotalDTO {
+    item_id: String,
+    name: String,
+    item_type: String,
+    item_count: usize,
+    subtotal: Decimal,
 }
 
-impl From<TeamError> for BillingResponse {
-    fn from(_: TeamError) -> Self {
-        BillingResponse::Error
+impl From<ItemSubtotal> for ItemSubtotalDTO {
+    fn from(item_subtotal: ItemSubtotal) -> Self {
+        ItemSubtotalDTO {
+            item_id: item_subtotal.item_id.to_string(),
+            name: item_subtotal.name,
+            item_type: item_subtotal.item_type,
+            item_count: item_subtotal.item_count,
+            subtotal: item_subtotal.subtotal,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct BillingListEntityDTO {
+    billing_id: String,
+    name: String,
+    car_id: Option<String>,
+    car_plate_number: Option<String>,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+    total_cost: Decimal,
+    total_income: Option<Decimal>,
+    gross_margin: Option<Decimal>,
+    item_count: usize,
+}
+
+impl From<(Billing, BillingSummary)> for BillingListEntityDTO {
+    fn from((billing, summary): (Billing, BillingSummary)) -> Self {
+        BillingListEntityDTO {
+            billing_id: billing.id.to_string(),
+            name: billing.name,
+            car_id: billing
+                .team_car_id
+                .map(|team_car_id| team_car_id.to_string()),
+            car_plate_number: billing.car_plate_number,
+            start_time: billing.start_time,
+            end_time: billing.end_time,
+            total_cost: summary.total_cost,
+            total_income: summary.income.as_ref().map(|income| income.total_income),
+            gross_margin: summary.gross_margin(),
+            item_count: summary.item_count,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct BillingListDTO {
+    billings: Vec<BillingListEntityDTO>,
+    page: usize,
+    page_size: usize,
+    total_count: usize,
+    total_cost: Decimal,
+    total_income: Option<Decimal>,
+}
+
+impl From<BillingPage> for BillingListDTO {
+    fn from(billing_page: BillingPage) -> Self {
+        BillingListDTO {
+            billings: billing_page
+                .billings
+                .into_iter()
+                .map(|billing| billing.into())
+                .collect(),
+            page: billing_page.page,
+            page_size: billing_page.page_size,
+            total_count: billing_page.total_count,
+            total_cost: billing_page.total_cost,
+            total_income: billing_page.total_income,
+        }
     }
 }
 
+#[derive(ApiResponse)]
+enum QueryBillingResponse {
+    #[oai(status = 200)]
+    Ok(Json<BillingListDTO>),
+}
+
+#[derive(ApiResponse)]
+enum GetBillingResponse {
+    #[oai(status = 200)]
+    Ok(Json<BillingEntityDTO>),
+}
+
+#[derive(ApiResponse)]
+enum EndBillingResponse {
+    #[oai(status = 200)]
+    Ok(Json<BillingEntityDTO>),
+}
+
+#[derive(ApiResponse)]
+enum AddBillingItemResponse {
+    #[oai(status = 201)]
+    Ok(Json<BillingItemEntityDTO>),
+}
+
+#[derive(ApiResponse)]
+enum DeleteBillingItemResponse {
+    #[oai(status = 204)]
+    Ok,
+}
+
 pub struct BillingRouter;
 
 #[OpenApi]
@@ -46,28 +342,90 @@ impl BillingRouter {
     )]
     async fn create_billing(
         &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
         team_id: Path<String>,
         team_billing: Json<BillingCreateDTO>,
-    ) -> BillingResponse {
-        let team_uuid_result = Uuid::parse_str(&team_id.0);
-        if team_uuid_result.is_err() {
-            error!("Error uuid string parse! id is {}", team_id.0);
-            return BillingResponse::Error;
-        }
-        let team_uuid = team_uuid_result.unwrap();
+    ) -> ApiResult<CreateBillingResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let car_uuid = parse_uuid("car_id", &team_billing.car_id)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let template_uuid = team_billing
+            .template_id
+            .as_deref()
+            .map(|template_id| parse_uuid("template_id", template_id))
+            .transpose()?;
         let billing_name = team_billing
             .0
             .name
             .unwrap_or(Local::now().format("%Y-%m-%d").to_string());
-        if let Ok(team) = Team::get_by_id(team_uuid).await {
-            if let Ok(_) = team.create_billing(billing_name).await {
-                return BillingResponse::Created;
-            } else {
-                return BillingResponse::Error;
-            }
-        } else {
-            return BillingResponse::Error;
+        let team = Team::get_by_id(&state.db, team_uuid).await?;
+        let billing = team
+            .create_billing(&state.db, billing_name, car_uuid, template_uuid)
+            .await?;
+        Ok(CreateBillingResponse::Created(Json(billing.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/billing",
+        method = "get",
+        tag = "ApiTags::Billing"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    async fn query_billing(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        status: Query<Option<BillingStatus>>,
+        start_date: Query<Option<NaiveDate>>,
+        end_date: Query<Option<NaiveDate>>,
+        car_plate_number: Query<Option<String>>,
+        page: Query<Option<usize>>,
+        page_size: Query<Option<usize>>,
+    ) -> ApiResult<QueryBillingResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let role =
+            TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Member).await?;
+        let team = Team::get_by_id(&state.db, team_uuid).await?;
+        let query = BillingQuery {
+            status: status.0,
+            start_date: start_date.0,
+            end_date: end_date.0,
+            car_plate_number: car_plate_number.0,
+            page: page.0.unwrap_or(1).max(1),
+            page_size: page_size
+                .0
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAX_PAGE_SIZE),
+            with_income: role.allows(TeamPermission::Manage),
+        };
+        let billing_page = team.query_billings(&state.db, query).await?;
+        Ok(QueryBillingResponse::Ok(Json(billing_page.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/billing/:billing_id",
+        method = "get",
+        tag = "ApiTags::Billing"
+    )]
+    async fn get_billing(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        billing_id: Path<String>,
+    ) -> ApiResult<GetBillingResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let billing_uuid = parse_uuid("billing_id", &billing_id.0)?;
+        let role =
+            TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Member).await?;
+        let team = Team::get_by_id(&state.db, team_uuid).await?;
+        let mut billing = team.get_billing_detail(&state.db, billing_uuid).await?;
+        if !role.allows(TeamPermission::Manage) {
+            billing.incomes = None;
         }
+        Ok(GetBillingResponse::Ok(Json(billing.into())))
     }
 
     #[oai(
@@ -77,10 +435,30 @@ impl BillingRouter {
     )]
     async fn end_billing(
         &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
         team_id: Path<String>,
-        team: Json<BillingCreateDTO>,
-    ) -> BillingResponse {
-        BillingResponse::Ok
+        team_billing: Json<BillingEndDTO>,
+    ) -> ApiResult<EndBillingResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let billing_uuid = parse_uuid("billing_id", &team_billing.billing_id)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let team = Team::get_by_id(&state.db, team_uuid).await?;
+        let billing = team.get_billing(&state.db, billing_uuid).await?;
+        // A dispatched billing is closed by finishing its dispatch, which also frees the
+        // drivers and records the odometer.
+        if let Some(dispatch) = Dispatch::active_for_billing(&state.db, billing.id).await? {
+            return Err(AppError::new(
+                ErrorCode::BillingDispatched,
+                format!(
+                    "billing {} belongs to active dispatch {}, finish the dispatch instead",
+                    billing.id, dispatch.id
+                ),
+            )
+            .into());
+        }
+        let billing = billing.end_billing(&state.db).await?;
+        Ok(EndBillingResponse::Ok(Json(billing.into())))
     }
 
     #[oai(
@@ -90,9 +468,79 @@ impl BillingRouter {
     )]
     async fn update_billing(
         &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        team_billing: Json<BillingUpdateDTO>,
+    ) -> ApiResult<UpdateBillingResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let billing_uuid = parse_uuid("billing_id", &team_billing.billing_id)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let team = Team::get_by_id(&state.db, team_uuid).await?;
+        let billing = team.get_billing(&state.db, billing_uuid).await?;
+        billing.rename(&state.db, &team_billing.name).await?;
+        let billing = team.get_billing_detail(&state.db, billing_uuid).await?;
+        Ok(UpdateBillingResponse::Ok(Json(billing.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/billing/:billing_id/item",
+        method = "post",
+        tag = "ApiTags::Billing"
+    )]
+    async fn add_billing_item(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        billing_id: Path<String>,
+        billing_item: Json<BillingItemCreateDTO>,
+    ) -> ApiResult<AddBillingItemResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let billing_uuid = parse_uuid("billing_id", &billing_id.0)?;
+        let item_uuid = parse_uuid("item_id", &billing_item.item_id)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Member).await?;
+        let team = Team::get_by_id(&state.db, team_uuid).await?;
+        let billing = team.get_billing(&state.db, billing_uuid).await?;
+        let billing_item = billing_item.0;
+        let billing_item = billing
+            .add_billing_item(
+                &state.db,
+                session_user.user_id,
+                item_uuid,
+                billing_item.cost,
+                billing_item
+                    .paid_by
+                    .unwrap_or(ExpensePayerDTO::Driver)
+                    .into(),
+                billing_item.fuel.map(|fuel| fuel.into()),
+            )
+            .await?;
+        Ok(AddBillingItemResponse::Ok(Json(billing_item.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/billing/:billing_id/item",
+        method = "delete",
+        tag = "ApiTags::Billing"
+    )]
+    async fn delete_billing_item(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
         team_id: Path<String>,
-        team: Json<BillingCreateDTO>,
-    ) -> BillingResponse {
-        BillingResponse::Ok
+        billing_id: Path<String>,
+        billing_item: Json<BillingItemDeleteDTO>,
+    ) -> ApiResult<DeleteBillingItemResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let billing_uuid = parse_uuid("billing_id", &billing_id.0)?;
+        let billing_item_uuid = parse_uuid("billing_item_id", &billing_item.billing_item_id)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let team = Team::get_by_id(&state.db, team_uuid).await?;
+        let billing = team.get_billing(&state.db, billing_uuid).await?;
+        billing
+            .delete_billing_item(&state.db, billing_item_uuid)
+            .await?;
+        Ok(DeleteBillingItemResponse::Ok)
     }
 }
@@ -1,64 +1,231 @@
-use std::result;
+use std::collections::BTreeMap;
 
 use async_trait::async_trait;
-use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use poem_openapi::Enum;
 use rust_decimal::Decimal;
-use sea_orm::{ActiveModelTrait, ActiveValue::NotSet, DbErr, EntityTrait, Set};
-use tracing::log::error;
+use sea_orm::{
+    sea_query::{Expr, Query},
+    ActiveModelTrait,
+    ActiveValue::NotSet,
+    ColumnTrait, Condition, ConnectionTrait, DatabaseTransaction, EntityTrait, FromQueryResult,
+    ModelTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use tracing::log::warn;
 use uuid::Uuid;
 
 use crate::{
-    entities::{self, billing, billing_item, team},
-    DATABASE,
+    entities::{
+        billing, billing_expected_item, billing_income, billing_item, billing_template,
+        billing_template_item, dispatch, dispatch_driver, fuel_entry, item,
+        sea_orm_active_enums::{ExpensePayer, ItemCategory, ItemType},
+        team, team_car,
+    },
+    error::{AppError, ErrorCode},
+    fuel_service::service::{FuelEntry, FuelFill},
+    income_service::service::{BillingIncome, IncomeTotal},
+    settlement_service::service::Settlement,
 };
 
-pub enum TeamError {
-    DBError(DbErr),
-    EmptyTeamError,
-}
-
-impl From<DbErr> for TeamError {
-    fn from(dbErr: DbErr) -> Self {
-        TeamError::DBError(dbErr)
-    }
+fn billing_not_found(billing_id: Uuid) -> AppError {
+    AppError::new(
+        ErrorCode::BillingNotFound,
+        format!("can not find billing {}", billing_id),
+    )
 }
 
-pub enum TeamBillingError {
-    DBError(DbErr),
-    EmptyBillingError,
+fn billing_ended(billing_id: Uuid) -> AppError {
+    AppError::new(
+        ErrorCode::BillingEnded,
+        format!("billing {} is already ended", billing_id),
+    )
 }
 
-impl From<DbErr> for TeamBillingError {
-    fn from(dbErr: DbErr) -> Self {
-        TeamBillingError::DBError(dbErr)
+impl std::fmt::Display for ItemType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ItemType::Basic => write!(f, "BASIC"),
+            ItemType::Custom => write!(f, "CUSTOM"),
+            ItemType::Default => write!(f, "DEFAULT"),
+        }
     }
 }
 
 pub struct Team {
     id: Uuid,
-    team_name: String,
-    user_id: String,
-    billings: Option<Vec<Billing>>,
 }
 
 #[async_trait]
 pub trait TeamBillingService {
-    async fn create_billing(&self, name: String) -> Result<Billing, TeamError>;
+    async fn create_billing<C: ConnectionTrait + TransactionTrait>(
+        &self,
+        db: &C,
+        name: String,
+        team_car_id: Uuid,
+        template_id: Option<Uuid>,
+    ) -> Result<Billing, AppError>;
 }
 
 #[async_trait]
 pub trait BillingItemService {
-    async fn end_billing(&self) -> Result<(), TeamBillingError>;
-    async fn add_billing_item(&self, item: BillingItem) -> Result<(), TeamBillingError>;
-    async fn delete_billing_item(&self, item_id: Uuid) -> Result<(), TeamBillingError>;
+    async fn end_billing<C: ConnectionTrait + TransactionTrait>(
+        &self,
+        db: &C,
+    ) -> Result<Billing, AppError>;
+    async fn add_billing_item<C: ConnectionTrait + TransactionTrait>(
+        &self,
+        db: &C,
+        user_id: String,
+        item_id: Uuid,
+        cost: Decimal,
+        paid_by: ExpensePayer,
+        fuel: Option<FuelFill>,
+    ) -> Result<BillingItem, AppError>;
+    async fn delete_billing_item<C: ConnectionTrait + TransactionTrait>(
+        &self,
+        db: &C,
+        billing_item_id: Uuid,
+    ) -> Result<(), AppError>;
 }
 
 pub struct Billing {
-    id: Uuid,
-    name: String,
-    start_time: Option<DateTime<Local>>,
-    end_time: Option<DateTime<Local>>,
-    billing_items: Option<Vec<BillingItem>>,
+    pub id: Uuid,
+    pub name: String,
+    pub team_id: Option<Uuid>,
+    pub team_car_id: Option<Uuid>,
+    pub car_plate_number: Option<String>,
+    pub start_time: Option<DateTime<Local>>,
+    pub end_time: Option<DateTime<Local>>,
+    pub total_cost: Option<Decimal>,
+    pub template_id: Option<Uuid>,
+    pub billing_items: Option<Vec<BillingItem>>,
+    pub expected_items: Option<Vec<ExpectedItem>>,
+    /// Freight income, only loaded for callers that manage the team.
+    pub incomes: Option<Vec<BillingIncome>>,
+}
+
+pub struct BillingSummary {
+    pub item_count: usize,
+    pub total_cost: Decimal,
+    /// Left out when the income is not loaded.
+    pub income: Option<IncomeTotal>,
+}
+
+impl BillingSummary {
+    /// Income minus cost.
+    pub fn gross_margin(&self) -> Option<Decimal> {
+        self.income
+            .as_ref()
+            .map(|income| income.total_income - self.total_cost)
+    }
+
+    /// Gross margin as a percentage of income, left out while there is no income.
+    pub fn gross_margin_percent(&self) -> Option<Decimal> {
+        let income = self.income.as_ref()?;
+        if income.total_income.is_zero() {
+            return None;
+        }
+        self.gross_margin()
+            .map(|margin| (margin * Decimal::from(100) / income.total_income).round_dp(2))
+    }
+}
+
+/// An item the billing template expects to be recorded on the billing.
+pub struct ExpectedItem {
+    pub item_id: Uuid,
+    pub name: String,
+    pub item_type: String,
+    pub default_cost: Option<Decimal>,
+    pub required: bool,
+}
+
+pub struct ItemSubtotal {
+    pub item_id: Uuid,
+    pub name: String,
+    pub item_type: String,
+    pub item_count: usize,
+    pub subtotal: Decimal,
+}
+
+#[derive(Debug, Enum, Clone, Copy, Eq, PartialEq)]
+pub enum BillingStatus {
+    Open,
+    Closed,
+}
+
+#[derive(Debug)]
+pub struct BillingQuery {
+    pub status: Option<BillingStatus>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub car_plate_number: Option<String>,
+    pub page: usize,
+    pub page_size: usize,
+    /// Whether the summaries carry income, callers that do not manage the team go without.
+    pub with_income: bool,
+}
+
+impl BillingQuery {
+    fn condition(&self, team_id: Uuid) -> Condition {
+        let mut condition = Condition::all().add(billing::Column::TeamId.eq(team_id));
+        match self.status {
+            Some(BillingStatus::Open) => {
+                condition = condition.add(billing::Column::EndTime.is_null());
+            }
+            Some(BillingStatus::Closed) => {
+                condition = condition.add(billing::Column::EndTime.is_not_null());
+            }
+            None => {}
+        }
+        if let Some(start_date) = self.start_date {
+            condition =
+                condition.add(billing::Column::StartTime.gte(start_date.and_time(NaiveTime::MIN)));
+        }
+        if let Some(end_date) = self.end_date {
+            let next_date = end_date + Duration::days(1);
+            condition =
+                condition.add(billing::Column::StartTime.lt(next_date.and_time(NaiveTime::MIN)));
+        }
+        if let Some(car_plate_number) = &self.car_plate_number {
+            condition = condition.add(
+                billing::Column::TeamCarId.in_subquery(
+                    Query::select()
+                        .column(team_car::Column::Id)
+                        .from(team_car::Entity)
+                        .and_where(team_car::Column::TeamId.eq(team_id))
+                        .and_where(team_car::Column::CarPlateNumber.eq(car_plate_number.clone()))
+                        .to_owned(),
+                ),
+            );
+        }
+        condition
+    }
+}
+
+pub struct BillingPage {
+    pub billings: Vec<(Billing, BillingSummary)>,
+    pub page: usize,
+    pub page_size: usize,
+    pub total_count: usize,
+    pub total_cost: Decimal,
+    pub total_income: Option<Decimal>,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct BillingCostTotal {
+    billing_id: Option<Uuid>,
+    item_count: i64,
+    total_cost: Option<Decimal>,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct CostTotal {
+    total_cost: Option<Decimal>,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct IncomeSum {
+    total_income: Option<Decimal>,
 }
 
 impl From<billing::Model> for Billing {
@@ -66,40 +233,353 @@ impl From<billing::Model> for Billing {
         Billing {
             id: billing_model.id,
             name: billing_model.name,
+            team_id: billing_model.team_id,
+            team_car_id: billing_model.team_car_id,
+            car_plate_number: None,
             start_time: parse_navie_time_to_data_time(billing_model.start_time),
             end_time: parse_navie_time_to_data_time(billing_model.end_time),
+            total_cost: billing_model.total_cost,
+            template_id: billing_model.template_id,
             billing_items: None,
+            expected_items: None,
+            incomes: None,
         }
     }
 }
 
+impl From<(billing::Model, Option<team_car::Model>)> for Billing {
+    fn from((billing_model, team_car_model): (billing::Model, Option<team_car::Model>)) -> Self {
+        let mut billing: Billing = billing_model.into();
+        billing.car_plate_number =
+            team_car_model.map(|team_car_model| team_car_model.car_plate_number);
+        billing
+    }
+}
+
+impl Billing {
+    pub fn is_ended(&self) -> bool {
+        self.end_time.is_some()
+    }
+
+    /// Hold a shared lock on the billing row so it can not be ended while items change.
+    pub(crate) async fn lock_open_billing(
+        &self,
+        txn: &DatabaseTransaction,
+    ) -> Result<(), AppError> {
+        let billing_model = billing::Entity::find_by_id(self.id)
+            .lock_shared()
+            .one(txn)
+            .await?
+            .ok_or_else(|| billing_not_found(self.id))?;
+        if billing_model.end_time.is_some() {
+            warn!("billing {} is ended, can not change items", self.id);
+            return Err(billing_ended(self.id));
+        }
+        Ok(())
+    }
+
+    /// Renames the billing. The name is only a label, ended billings can be renamed too.
+    pub async fn rename<C: ConnectionTrait>(&self, db: &C, name: &str) -> Result<(), AppError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(AppError::new(
+                ErrorCode::InvalidArgument,
+                "billing name can not be empty",
+            ));
+        }
+        let billing_model = billing::Entity::find_by_id(self.id)
+            .one(db)
+            .await?
+            .ok_or_else(|| billing_not_found(self.id))?;
+        let mut billing_active_model: billing::ActiveModel = billing_model.into();
+        billing_active_model.name = Set(name.to_owned());
+        billing_active_model.update(db).await?;
+        Ok(())
+    }
+
+    pub fn summary(&self) -> BillingSummary {
+        let billing_items = self.billing_items.as_deref().unwrap_or_default();
+        BillingSummary {
+            item_count: billing_items.len(),
+            total_cost: self.total_cost.unwrap_or_else(|| {
+                billing_items
+                    .iter()
+                    .map(|billing_item| billing_item.cost)
+                    .sum()
+            }),
+            income: self.incomes.as_ref().map(|incomes| IncomeTotal {
+                total_income: incomes.iter().map(|income| income.fields.amount()).sum(),
+                received_income: incomes
+                    .iter()
+                    .map(|income| income.fields.received_amount)
+                    .sum(),
+            }),
+        }
+    }
+
+    /// Group the loaded billing items by catalog item, ordered by item name.
+    pub fn item_subtotals(&self) -> Vec<ItemSubtotal> {
+        let mut subtotals: BTreeMap<(String, Uuid), ItemSubtotal> = BTreeMap::new();
+        for billing_item in self.billing_items.as_deref().unwrap_or_default() {
+            let subtotal = subtotals
+                .entry((billing_item.name.clone(), billing_item.item_id))
+                .or_insert_with(|| ItemSubtotal {
+                    item_id: billing_item.item_id,
+                    name: billing_item.name.clone(),
+                    item_type: billing_item.item_type.clone(),
+                    item_count: 0,
+                    subtotal: Decimal::ZERO,
+                });
+            subtotal.item_count += 1;
+            subtotal.subtotal += billing_item.cost;
+        }
+        subtotals.into_values().collect()
+    }
+
+    pub fn is_item_filled(&self, item_id: Uuid) -> bool {
+        self.billing_items
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .any(|billing_item| billing_item.item_id == item_id)
+    }
+
+    /// Required template items that have no billing item recorded yet.
+    pub fn missing_required_items(&self) -> Vec<&ExpectedItem> {
+        self.expected_items
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .filter(|expected_item| {
+                expected_item.required && !self.is_item_filled(expected_item.item_id)
+            })
+            .collect()
+    }
+
+    async fn load_expected_items<C: ConnectionTrait>(&mut self, db: &C) -> Result<(), AppError> {
+        let expected_items = billing_expected_item::Entity::find()
+            .filter(billing_expected_item::Column::BillingId.eq(self.id))
+            .find_also_related(item::Entity)
+            .order_by_asc(billing_expected_item::Column::SortOrder)
+            .all(db)
+            .await?;
+        self.expected_items = Some(
+            expected_items
+                .into_iter()
+                .filter_map(|(expected_item_model, item_model)| {
+                    item_model.map(|item_model| ExpectedItem {
+                        item_id: item_model.id,
+                        name: item_model.name,
+                        item_type: item_model.r#type.to_string(),
+                        default_cost: expected_item_model.default_cost,
+                        required: expected_item_model.required,
+                    })
+                })
+                .collect(),
+        );
+        Ok(())
+    }
+
+    async fn load_billing_items<C: ConnectionTrait>(&mut self, db: &C) -> Result<(), AppError> {
+        let billing_model = billing::Entity::find_by_id(self.id)
+            .one(db)
+            .await?
+            .ok_or_else(|| billing_not_found(self.id))?;
+        let billing_items = billing_model
+            .find_related(billing_item::Entity)
+            .find_also_related(item::Entity)
+            .order_by_asc(billing_item::Column::Time)
+            .all(db)
+            .await?;
+        let billing_items = billing_items
+            .into_iter()
+            .filter_map(|(billing_item_model, item_model)| {
+                item_model.map(|item_model| BillingItem::from_model(billing_item_model, item_model))
+            })
+            .collect();
+        self.billing_items = Some(BillingItem::with_fuel(db, billing_items).await?);
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl BillingItemService for Billing {
-    async fn end_billing(&self) -> Result<(), TeamBillingError> {
-        let db = DATABASE.get().unwrap();
-        let billing: Option<billing::Model> = entities::billing::Entity::find_by_id(self.id)
+    async fn end_billing<C: ConnectionTrait + TransactionTrait>(
+        &self,
+        db: &C,
+    ) -> Result<Billing, AppError> {
+        if self.is_ended() {
+            warn!("billing {} is already ended", self.id);
+            return Err(billing_ended(self.id));
+        }
+        let txn = db.begin().await?;
+        let billing_model = billing::Entity::find_by_id(self.id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or_else(|| billing_not_found(self.id))?;
+        if billing_model.end_time.is_some() {
+            warn!("billing {} is already ended", self.id);
+            return Err(billing_ended(self.id));
+        }
+        let billing_items = billing_item::Entity::find()
+            .filter(billing_item::Column::BillingId.eq(self.id))
+            .find_also_related(item::Entity)
+            .all(&txn)
+            .await?;
+        let total_cost: Decimal = billing_items
+            .iter()
+            .map(|(billing_item_model, _)| billing_item_model.cost)
+            .sum();
+        let mut billing_active_model: billing::ActiveModel = billing_model.into();
+        billing_active_model.end_time = Set(Some(Local::now().naive_local()));
+        billing_active_model.total_cost = Set(Some(total_cost));
+        let billing_model = billing_active_model.update(&txn).await?;
+        Settlement::settle_billing(&txn, billing_model.id).await?;
+        txn.commit().await?;
+
+        let mut billing: Billing = billing_model.into();
+        let billing_items = billing_items
+            .into_iter()
+            .filter_map(|(billing_item_model, item_model)| {
+                item_model.map(|item_model| BillingItem::from_model(billing_item_model, item_model))
+            })
+            .collect();
+        billing.billing_items = Some(BillingItem::with_fuel(db, billing_items).await?);
+        billing.incomes = Some(BillingIncome::query(db, billing.id).await?);
+        Ok(billing)
+    }
+
+    async fn add_billing_item<C: ConnectionTrait + TransactionTrait>(
+        &self,
+        db: &C,
+        user_id: String,
+        item_id: Uuid,
+        cost: Decimal,
+        paid_by: ExpensePayer,
+        fuel: Option<FuelFill>,
+    ) -> Result<BillingItem, AppError> {
+        if self.is_ended() {
+            warn!("billing {} is ended, can not add item", self.id);
+            return Err(billing_ended(self.id));
+        }
+        if cost <= Decimal::ZERO {
+            return Err(AppError::new(
+                ErrorCode::InvalidCost,
+                "billing item cost must be positive",
+            ));
+        }
+        let item_model = item::Entity::find_by_id(item_id)
             .one(db)
+            .await?
+            .filter(|item_model| {
+                !item_model.archived
+                    && (item_model.team_id.is_none() || item_model.team_id == self.team_id)
+            })
+            .ok_or_else(|| {
+                AppError::new(
+                    ErrorCode::ItemNotFound,
+                    format!("can not find item {}", item_id),
+                )
+            })?;
+        let fuel = match (item_model.category == ItemCategory::Fuel, fuel) {
+            (true, Some(fill)) => {
+                fill.validate()?;
+                let team_car_id = self.team_car_id.ok_or_else(|| {
+                    AppError::new(
+                        ErrorCode::InvalidArgument,
+                        format!("billing {} has no car to record fuel for", self.id),
+                    )
+                })?;
+                Some((team_car_id, fill))
+            }
+            (true, None) => {
+                return Err(AppError::new(
+                    ErrorCode::InvalidArgument,
+                    "fuel items need litres, unit price and odometer reading",
+                ))
+            }
+            (false, Some(_)) => {
+                return Err(AppError::new(
+                    ErrorCode::InvalidArgument,
+                    format!("item {} is not a fuel item", item_id),
+                ))
+            }
+            (false, None) => None,
+        };
+        let txn = db.begin().await?;
+        self.lock_open_billing(&txn).await?;
+        let dispatch_driver_model = dispatch_driver::Entity::find()
+            .inner_join(dispatch::Entity)
+            .filter(dispatch::Column::BillingId.eq(self.id))
+            .filter(dispatch::Column::EndTime.is_null())
+            .filter(dispatch_driver::Column::UserId.eq(user_id.clone()))
+            .one(&txn)
             .await?;
-        if let Some(bill) = billing {
-            let mut bill_model: entities::billing::ActiveModel = bill.into();
-            bill_model.end_time = Set(Some(Local::now().naive_local()));
-            bill_model.update(db).await?;
-            Ok(())
-        } else {
-            error!("can not find billing info");
-            return Err(TeamBillingError::EmptyBillingError);
+        if dispatch_driver_model.is_none() {
+            warn!(
+                "user {} is not on the active dispatch of billing {}",
+                user_id, self.id
+            );
+            return Err(AppError::new(
+                ErrorCode::DriverNotDispatched,
+                "driver is not on the active dispatch of billing",
+            ));
+        }
+        let billing_item_model = billing_item::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            billing_id: Set(Some(self.id)),
+            cost: Set(cost),
+            item_id: Set(Some(item_model.id)),
+            time: Set(Local::now().naive_local()),
+            user_id: Set(Some(user_id)),
+            paid_by: Set(paid_by),
         }
+        .insert(&txn)
+        .await?;
+        let fuel_entry = match fuel {
+            Some((team_car_id, fill)) => {
+                Some(FuelEntry::record(&txn, billing_item_model.id, team_car_id, fill).await?)
+            }
+            None => None,
+        };
+        txn.commit().await?;
+        let mut billing_item = BillingItem::from_model(billing_item_model, item_model);
+        billing_item.fuel = fuel_entry;
+        Ok(billing_item)
     }
 
-    async fn add_billing_item(&self, item: BillingItem) -> Result<(), TeamBillingError> {
-        todo!()
-    }
-    async fn delete_billing_item(&self, item_id: Uuid) -> Result<(), TeamBillingError> {
-        todo!()
+    async fn delete_billing_item<C: ConnectionTrait + TransactionTrait>(
+        &self,
+        db: &C,
+        billing_item_id: Uuid,
+    ) -> Result<(), AppError> {
+        if self.is_ended() {
+            warn!("billing {} is ended, can not delete item", self.id);
+            return Err(billing_ended(self.id));
+        }
+        let txn = db.begin().await?;
+        self.lock_open_billing(&txn).await?;
+        let billing_item_model = billing_item::Entity::find_by_id(billing_item_id)
+            .filter(billing_item::Column::BillingId.eq(self.id))
+            .one(&txn)
+            .await?
+            .ok_or_else(|| {
+                AppError::new(
+                    ErrorCode::BillingItemNotFound,
+                    format!("can not find billing item {}", billing_item_id),
+                )
+            })?;
+        fuel_entry::Entity::delete_by_id(billing_item_model.id)
+            .exec(&txn)
+            .await?;
+        billing_item_model.delete(&txn).await?;
+        txn.commit().await?;
+        Ok(())
     }
 }
 
-fn parse_navie_time_to_data_time(time: Option<NaiveDateTime>) -> Option<DateTime<Local>> {
+pub fn parse_navie_time_to_data_time(time: Option<NaiveDateTime>) -> Option<DateTime<Local>> {
     if let Some(naive_date_time) = time {
         match Local.from_local_datetime(&naive_date_time) {
             chrono::LocalResult::None => None,
@@ -111,43 +591,339 @@ fn parse_navie_time_to_data_time(time: Option<NaiveDateTime>) -> Option<DateTime
     }
 }
 
-struct BillingItem {
-    id: Uuid,
-    name: String,
-    item_type: String,
-    cost: Decimal,
+pub struct BillingItem {
+    pub id: Uuid,
+    pub item_id: Uuid,
+    pub name: String,
+    pub item_type: String,
+    pub cost: Decimal,
+    pub time: Option<DateTime<Local>>,
+    pub user_id: Option<String>,
+    /// Whether the recording driver paid out of pocket or with company money.
+    pub paid_by: ExpensePayer,
+    /// The fill-up of a fuel item.
+    pub fuel: Option<FuelEntry>,
+}
+
+impl BillingItem {
+    fn from_model(billing_item_model: billing_item::Model, item_model: item::Model) -> Self {
+        BillingItem {
+            id: billing_item_model.id,
+            item_id: item_model.id,
+            name: item_model.name,
+            item_type: item_model.r#type.to_string(),
+            cost: billing_item_model.cost,
+            time: parse_navie_time_to_data_time(Some(billing_item_model.time)),
+            user_id: billing_item_model.user_id,
+            paid_by: billing_item_model.paid_by,
+            fuel: None,
+        }
+    }
+
+    /// Attaches the fill-ups of the fuel items among `billing_items`.
+    async fn with_fuel<C: ConnectionTrait>(
+        db: &C,
+        mut billing_items: Vec<BillingItem>,
+    ) -> Result<Vec<BillingItem>, AppError> {
+        let mut fuel_entries = FuelEntry::of_billing_items(
+            db,
+            billing_items
+                .iter()
+                .map(|billing_item| billing_item.id)
+                .collect(),
+        )
+        .await?;
+        for billing_item in billing_items.iter_mut() {
+            billing_item.fuel = fuel_entries.remove(&billing_item.id);
+        }
+        Ok(billing_items)
+    }
 }
 
 impl Team {
-    pub async fn get_by_id(id: Uuid) -> Result<Self, TeamError> {
-        let db = DATABASE.get().unwrap();
+    pub async fn get_by_id<C: ConnectionTrait>(db: &C, id: Uuid) -> Result<Self, AppError> {
         let team_result = team::Entity::find_by_id(id).one(db).await?;
         if let Some(team_model) = team_result {
-            let team = Team {
-                id: team_model.id,
-                team_name: team_model.team_name,
-                user_id: team_model.user_id,
-                billings: None,
-            };
-            return Ok(team);
+            let team = Team { id: team_model.id };
+            Ok(team)
         } else {
-            return Err(TeamError::EmptyTeamError);
+            Err(AppError::new(
+                ErrorCode::TeamNotFound,
+                format!("can not find team {}", id),
+            ))
         }
     }
+
+    pub async fn get_billing<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        billing_id: Uuid,
+    ) -> Result<Billing, AppError> {
+        let billing_model = billing::Entity::find_by_id(billing_id)
+            .find_also_related(team_car::Entity)
+            .filter(billing::Column::TeamId.eq(self.id))
+            .one(db)
+            .await?
+            .ok_or_else(|| billing_not_found(billing_id))?;
+        Ok(billing_model.into())
+    }
+
+    pub async fn get_billing_detail<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        billing_id: Uuid,
+    ) -> Result<Billing, AppError> {
+        let mut billing = self.get_billing(db, billing_id).await?;
+        billing.load_billing_items(db).await?;
+        billing.load_expected_items(db).await?;
+        billing.incomes = Some(BillingIncome::query(db, billing.id).await?);
+        Ok(billing)
+    }
+
+    pub async fn query_billings<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        query: BillingQuery,
+    ) -> Result<BillingPage, AppError> {
+        let condition = query.condition(self.id);
+        let paginator = billing::Entity::find()
+            .find_also_related(team_car::Entity)
+            .filter(condition.clone())
+            .order_by_desc(billing::Column::StartTime)
+            .paginate(db, query.page_size);
+        let total_count = paginator.num_items().await?;
+        let billing_models = paginator.fetch_page(query.page - 1).await?;
+
+        let billing_ids: Vec<Uuid> = billing_models
+            .iter()
+            .map(|(billing_model, _)| billing_model.id)
+            .collect();
+        let cost_totals = billing_item::Entity::find()
+            .select_only()
+            .column(billing_item::Column::BillingId)
+            .column_as(
+                Expr::tbl(billing_item::Entity, billing_item::Column::Id).count(),
+                "item_count",
+            )
+            .column_as(
+                Expr::tbl(billing_item::Entity, billing_item::Column::Cost).sum(),
+                "total_cost",
+            )
+            .filter(billing_item::Column::BillingId.is_in(billing_ids.clone()))
+            .group_by(billing_item::Column::BillingId)
+            .into_model::<BillingCostTotal>()
+            .all(db)
+            .await?;
+        let team_total = billing_item::Entity::find()
+            .inner_join(billing::Entity)
+            .filter(condition.clone())
+            .select_only()
+            .column_as(
+                Expr::tbl(billing_item::Entity, billing_item::Column::Cost).sum(),
+                "total_cost",
+            )
+            .into_model::<CostTotal>()
+            .one(db)
+            .await?;
+        let (income_totals, team_income) = if query.with_income {
+            let team_income = billing_income::Entity::find()
+                .inner_join(billing::Entity)
+                .filter(condition)
+                .select_only()
+                .column_as(
+                    Expr::tbl(billing_income::Entity, billing_income::Column::Amount).sum(),
+                    "total_income",
+                )
+                .into_model::<IncomeSum>()
+                .one(db)
+                .await?;
+            (
+                Some(BillingIncome::totals(db, billing_ids).await?),
+                Some(
+                    team_income
+                        .and_then(|team_income| team_income.total_income)
+                        .unwrap_or_default(),
+                ),
+            )
+        } else {
+            (None, None)
+        };
+
+        let billings = billing_models
+            .into_iter()
+            .map(|(billing_model, team_car_model)| {
+                let cost_total = cost_totals
+                    .iter()
+                    .find(|cost_total| cost_total.billing_id == Some(billing_model.id));
+                let summary = BillingSummary {
+                    item_count: cost_total
+                        .map(|cost_total| cost_total.item_count as usize)
+                        .unwrap_or_default(),
+                    total_cost: billing_model.total_cost.unwrap_or_else(|| {
+                        cost_total
+                            .and_then(|cost_total| cost_total.total_cost)
+                            .unwrap_or_default()
+                    }),
+                    income: income_totals.as_ref().map(|income_totals| {
+                        income_totals
+                            .get(&billing_model.id)
+                            .cloned()
+                            .unwrap_or_default()
+                    }),
+                };
+                ((billing_model, team_car_model).into(), summary)
+            })
+            .collect();
+        Ok(BillingPage {
+            billings,
+            page: query.page,
+            page_size: query.page_size,
+            total_count,
+            total_cost: team_total
+                .and_then(|team_total| team_total.total_cost)
+                .unwrap_or_default(),
+            total_income: team_income,
+        })
+    }
 }
 
 #[async_trait]
 impl TeamBillingService for Team {
-    async fn create_billing(&self, name: String) -> Result<Billing, TeamError> {
-        let db = DATABASE.get().unwrap();
+    async fn create_billing<C: ConnectionTrait + TransactionTrait>(
+        &self,
+        db: &C,
+        name: String,
+        team_car_id: Uuid,
+        template_id: Option<Uuid>,
+    ) -> Result<Billing, AppError> {
+        let txn = db.begin().await?;
+        let team_car_model = team_car::Entity::find_by_id(team_car_id)
+            .filter(team_car::Column::TeamId.eq(self.id))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or_else(|| {
+                AppError::new(
+                    ErrorCode::CarNotFound,
+                    format!("can not find car {} in team", team_car_id),
+                )
+            })?;
+        let open_billing = billing::Entity::find()
+            .filter(billing::Column::TeamCarId.eq(team_car_model.id))
+            .filter(billing::Column::EndTime.is_null())
+            .one(&txn)
+            .await?;
+        if let Some(open_billing) = open_billing {
+            warn!(
+                "car {} already has open billing {}",
+                team_car_model.id, open_billing.id
+            );
+            return Err(AppError::new(
+                ErrorCode::CarBillingOpen,
+                format!("car {} already has an open billing", team_car_model.id),
+            ));
+        }
+        let template_model = match template_id {
+            Some(template_id) => Some(
+                billing_template::Entity::find_by_id(template_id)
+                    .filter(billing_template::Column::TeamId.eq(self.id))
+                    .one(&txn)
+                    .await?
+                    .ok_or_else(|| {
+                        AppError::new(
+                            ErrorCode::TemplateNotFound,
+                            format!("can not find billing template {} in team", template_id),
+                        )
+                    })?,
+            ),
+            None => None,
+        };
         let billing_model = billing::ActiveModel {
             id: Set(Uuid::new_v4()),
             name: Set(name),
-            team_id: Set(Some(self.id.clone())),
+            team_id: Set(Some(self.id)),
             start_time: Set(Some(Local::now().naive_local())),
             end_time: NotSet,
+            total_cost: NotSet,
+            team_car_id: Set(Some(team_car_model.id)),
+            template_id: Set(template_model
+                .as_ref()
+                .map(|template_model| template_model.id)),
         };
-        let insert_result = billing_model.insert(db).await?;
-        Ok(insert_result.into())
+        let insert_result = billing_model.insert(&txn).await?;
+        if let Some(template_model) = template_model {
+            let template_item_models = template_model
+                .find_related(billing_template_item::Entity)
+                .all(&txn)
+                .await?;
+            for template_item_model in template_item_models {
+                billing_expected_item::ActiveModel {
+                    id: Set(Uuid::new_v4()),
+                    billing_id: Set(insert_result.id),
+                    item_id: Set(template_item_model.item_id),
+                    sort_order: Set(template_item_model.sort_order),
+                    default_cost: Set(template_item_model.default_cost),
+                    required: Set(template_item_model.required),
+                }
+                .insert(&txn)
+                .await?;
+            }
+        }
+        txn.commit().await?;
+        Ok((insert_result, Some(team_car_model)).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    use super::BillingSummary;
+    use crate::income_service::service::IncomeTotal;
+
+    fn summary(total_cost: Decimal, total_income: Option<Decimal>) -> BillingSummary {
+        BillingSummary {
+            item_count: 3,
+            total_cost,
+            income: total_income.map(|total_income| IncomeTotal {
+                total_income,
+                received_income: Decimal::ZERO,
+            }),
+        }
+    }
+
+    #[test]
+    fn margin_is_income_minus_cost() {
+        let summary = summary(dec!(1200), Some(dec!(1500)));
+        assert_eq!(summary.gross_margin(), Some(dec!(300)));
+        assert_eq!(summary.gross_margin_percent(), Some(dec!(20)));
+    }
+
+    #[test]
+    fn margin_percent_is_rounded_to_cents() {
+        let summary = summary(dec!(2000), Some(dec!(3000)));
+        assert_eq!(summary.gross_margin_percent(), Some(dec!(33.33)));
+    }
+
+    #[test]
+    fn loss_is_a_negative_margin() {
+        let summary = summary(dec!(1500), Some(dec!(1000)));
+        assert_eq!(summary.gross_margin(), Some(dec!(-500)));
+        assert_eq!(summary.gross_margin_percent(), Some(dec!(-50)));
+    }
+
+    #[test]
+    fn no_margin_percent_without_revenue() {
+        let summary = summary(dec!(800), Some(Decimal::ZERO));
+        assert_eq!(summary.gross_margin(), Some(dec!(-800)));
+        assert_eq!(summary.gross_margin_percent(), None);
+    }
+
+    #[test]
+    fn no_margin_when_income_is_not_loaded() {
+        let summary = summary(dec!(800), None);
+        assert_eq!(summary.gross_margin(), None);
+        assert_eq!(summary.gross_margin_percent(), None);
     }
 }
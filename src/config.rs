@@ -0,0 +1,305 @@
+use std::{env, fmt, fs, path::Path, str::FromStr};
+
+use serde::Deserialize;
+use tracing::Level;
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Settings read once at startup.
+///
+/// Values are taken from an optional TOML file first (`CONFIG_FILE`, or `config.toml` when it
+/// exists), then overridden by the environment, into which `.env` has already been loaded.
+/// The environment keeps the names the service has always used, `DATABASE_URL`, `SERVER`,
+/// `PORT`, `APP_ID` and `APP_SECRET`, see `AppConfig::apply_env` for the full list.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub log: LogConfig,
+    pub wechat: WechatConfig,
+    pub reminder: ReminderConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            host: "127.0.0.1".to_owned(),
+            port: 3000,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn bind_addr(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// Apply pending migrations before serving.
+    pub run_migrations: bool,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            url: String::new(),
+            max_connections: 10,
+            min_connections: 1,
+            run_migrations: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// One of `trace`, `debug`, `info`, `warn` or `error`.
+    pub level: String,
+    /// Directory of the daily rolled `run.log`.
+    pub dir: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: "info".to_owned(),
+            dir: "./logs".to_owned(),
+        }
+    }
+}
+
+impl LogConfig {
+    /// The parsed level, `AppConfig::load` has already checked that it parses.
+    pub fn max_level(&self) -> Level {
+        Level::from_str(&self.level).unwrap_or(Level::INFO)
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WechatConfig {
+    pub app_id: String,
+    pub app_secret: String,
+    /// Base url of the WeChat API, pointed elsewhere in tests.
+    pub base_url: String,
+    /// Subscribe message template of expiry reminders, see `WxSubscribeNotifier`.
+    pub reminder_template_id: String,
+    /// Mini program page opened from a reminder, the home page when empty.
+    pub reminder_page: String,
+}
+
+impl Default for WechatConfig {
+    fn default() -> Self {
+        WechatConfig {
+            app_id: String::new(),
+            app_secret: String::new(),
+            base_url: "https://api.weixin.qq.com".to_owned(),
+            reminder_template_id: String::new(),
+            reminder_page: String::new(),
+        }
+    }
+}
+
+// The secret must not end up in the log when the config is printed.
+impl fmt::Debug for WechatConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WechatConfig")
+            .field("app_id", &self.app_id)
+            .field("app_secret", &"***")
+            .field("base_url", &self.base_url)
+            .field("reminder_template_id", &self.reminder_template_id)
+            .field("reminder_page", &self.reminder_page)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReminderConfig {
+    /// Run the daily expiry scan.
+    pub enabled: bool,
+    /// How many days ahead of an expiry date the reminder is raised.
+    pub lead_days: u32,
+    /// Local hour of the daily scan, the scan also runs once at startup.
+    pub scan_hour: u32,
+}
+
+impl Default for ReminderConfig {
+    fn default() -> Self {
+        ReminderConfig {
+            enabled: true,
+            lead_days: 30,
+            scan_hour: 8,
+        }
+    }
+}
+
+/// Every problem found while loading, reported together so one run shows all of them.
+#[derive(Debug)]
+pub struct ConfigError {
+    problems: Vec<String>,
+}
+
+impl std::error::Error for ConfigError {}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid configuration:")?;
+        for problem in &self.problems {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl AppConfig {
+    pub fn load() -> Result<AppConfig, ConfigError> {
+        let mut problems = vec![];
+        let mut config = match env::var("CONFIG_FILE") {
+            Ok(path) => Self::from_file(&path, &mut problems),
+            Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::from_file(DEFAULT_CONFIG_FILE, &mut problems)
+            }
+            Err(_) => AppConfig::default(),
+        };
+        config.apply_env(&mut problems);
+        config.validate(&mut problems);
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError { problems })
+        }
+    }
+
+    fn from_file(path: &str, problems: &mut Vec<String>) -> AppConfig {
+        let parsed = fs::read_to_string(path)
+            .map_err(|err| format!("can not read config file {}: {}", path, err))
+            .and_then(|content| {
+                toml::from_str(&content)
+                    .map_err(|err| format!("config file {} is not valid: {}", path, err))
+            });
+        parsed.unwrap_or_else(|problem| {
+            problems.push(problem);
+            AppConfig::default()
+        })
+    }
+
+    fn apply_env(&mut self, problems: &mut Vec<String>) {
+        override_from_env("SERVER", &mut self.server.host, problems);
+        override_from_env("PORT", &mut self.server.port, problems);
+        override_from_env("DATABASE_URL", &mut self.database.url, problems);
+        override_from_env(
+            "DB_MAX_CONNECTIONS",
+            &mut self.database.max_connections,
+            problems,
+        );
+        override_from_env(
+            "DB_MIN_CONNECTIONS",
+            &mut self.database.min_connections,
+            problems,
+        );
+        if let Ok(value) = env::var("RUN_MIGRATIONS") {
+            self.database.run_migrations = value == "true" || value == "1";
+        }
+        override_from_env("LOG_LEVEL", &mut self.log.level, problems);
+        override_from_env("LOG_DIR", &mut self.log.dir, problems);
+        override_from_env("APP_ID", &mut self.wechat.app_id, problems);
+        override_from_env("APP_SECRET", &mut self.wechat.app_secret, problems);
+        override_from_env("WX_BASE_URL", &mut self.wechat.base_url, problems);
+        override_from_env(
+            "WX_REMINDER_TEMPLATE_ID",
+            &mut self.wechat.reminder_template_id,
+            problems,
+        );
+        override_from_env("WX_REMINDER_PAGE", &mut self.wechat.reminder_page, problems);
+        if let Ok(value) = env::var("REMINDER_ENABLED") {
+            self.reminder.enabled = value == "true" || value == "1";
+        }
+        override_from_env("REMINDER_LEAD_DAYS", &mut self.reminder.lead_days, problems);
+        override_from_env("REMINDER_SCAN_HOUR", &mut self.reminder.scan_hour, problems);
+    }
+
+    fn validate(&self, problems: &mut Vec<String>) {
+        for (name, value, required) in [
+            ("database.url (DATABASE_URL)", &self.database.url, true),
+            ("wechat.app_id (APP_ID)", &self.wechat.app_id, true),
+            (
+                "wechat.app_secret (APP_SECRET)",
+                &self.wechat.app_secret,
+                true,
+            ),
+            ("server.host (SERVER)", &self.server.host, true),
+            (
+                "wechat.reminder_template_id (WX_REMINDER_TEMPLATE_ID)",
+                &self.wechat.reminder_template_id,
+                self.reminder.enabled,
+            ),
+        ] {
+            if required && value.trim().is_empty() {
+                problems.push(format!("{} is required", name));
+            }
+        }
+        if self.database.max_connections == 0 {
+            problems.push("database.max_connections must be at least 1".to_owned());
+        }
+        if self.database.min_connections > self.database.max_connections {
+            problems.push(format!(
+                "database.min_connections ({}) is larger than database.max_connections ({})",
+                self.database.min_connections, self.database.max_connections
+            ));
+        }
+        if !(1..=365).contains(&self.reminder.lead_days) {
+            problems.push(format!(
+                "reminder.lead_days (REMINDER_LEAD_DAYS) is {}, expected 1 to 365",
+                self.reminder.lead_days
+            ));
+        }
+        if self.reminder.scan_hour > 23 {
+            problems.push(format!(
+                "reminder.scan_hour (REMINDER_SCAN_HOUR) is {}, expected 0 to 23",
+                self.reminder.scan_hour
+            ));
+        }
+        if Level::from_str(&self.log.level).is_err() {
+            problems.push(format!(
+                "log.level (LOG_LEVEL) is {}, expected one of trace, debug, info, warn, error",
+                self.log.level
+            ));
+        }
+        if !self.wechat.base_url.starts_with("http://")
+            && !self.wechat.base_url.starts_with("https://")
+        {
+            problems.push(format!(
+                "wechat.base_url (WX_BASE_URL) is {}, expected an http(s) url",
+                self.wechat.base_url
+            ));
+        }
+    }
+}
+
+fn override_from_env<T>(name: &str, target: &mut T, problems: &mut Vec<String>)
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    if let Ok(value) = env::var(name) {
+        match value.parse() {
+            Ok(value) => *target = value,
+            Err(err) => problems.push(format!("{} is {}, {}", name, value, err)),
+        }
+    }
+}
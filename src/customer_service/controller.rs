@@ -0,0 +1,540 @@
+use chrono::{DateTime, Local, NaiveDate};
+use poem::web::Data;
+use poem_openapi::{param::Path, payload::Json, ApiResponse, Object, OpenApi, Tags};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::auth_service::service::{TeamGuard, TeamPermission};
+use crate::error::{parse_uuid, ApiResult, AppError};
+use crate::session_service::service::SessionUser;
+use crate::state::AppState;
+
+use super::receivable::{AgeingBuckets, CustomerReceivable, OutstandingIncome, ReceivableReport};
+use super::service::{
+    Allocation, AllocationRequest, Customer, CustomerPayment, CustomerProfile, PaymentAllocation,
+};
+
+#[derive(Tags)]
+enum ApiTags {
+    /// Customers of a team, their payments and what they still owe
+    Customer,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct CustomerProfileDTO {
+    #[oai(validator(max_length = 128))]
+    name: String,
+
+    #[oai(validator(max_length = 64))]
+    contact_name: Option<String>,
+
+    #[oai(validator(max_length = 32))]
+    phone: Option<String>,
+
+    #[oai(validator(max_length = 255))]
+    note: Option<String>,
+}
+
+impl From<CustomerProfileDTO> for CustomerProfile {
+    fn from(dto: CustomerProfileDTO) -> Self {
+        CustomerProfile {
+            name: dto.name,
+            contact_name: dto.contact_name,
+            phone: dto.phone,
+            note: dto.note,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct CustomerEntityDTO {
+    customer_id: String,
+    name: String,
+    contact_name: Option<String>,
+    phone: Option<String>,
+    note: Option<String>,
+    create_time: Option<DateTime<Local>>,
+}
+
+impl From<Customer> for CustomerEntityDTO {
+    fn from(customer: Customer) -> Self {
+        let profile = customer.profile;
+        CustomerEntityDTO {
+            customer_id: customer.id.to_string(),
+            name: profile.name,
+            contact_name: profile.contact_name,
+            phone: profile.phone,
+            note: profile.note,
+            create_time: customer.create_time,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct AllocationDTO {
+    #[oai(validator(max_length = 128))]
+    billing_id: String,
+
+    amount: Decimal,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct PaymentCreateDTO {
+    amount: Decimal,
+
+    /// Day the money came in, today when omitted.
+    paid_date: Option<NaiveDate>,
+
+    #[oai(validator(max_length = 255))]
+    note: Option<String>,
+
+    /// Billings the payment settles. When omitted the oldest unpaid billings are settled
+    /// first, an empty list keeps the whole payment as credit.
+    allocations: Option<Vec<AllocationDTO>>,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct PaymentAllocationDTO {
+    billing_id: String,
+    billing_name: String,
+    income_id: String,
+    amount: Decimal,
+}
+
+impl From<PaymentAllocation> for PaymentAllocationDTO {
+    fn from(allocation: PaymentAllocation) -> Self {
+        PaymentAllocationDTO {
+            billing_id: allocation.billing_id.to_string(),
+            billing_name: allocation.billing_name,
+            income_id: allocation.income_id.to_string(),
+            amount: allocation.amount,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct PaymentEntityDTO {
+    payment_id: String,
+    customer_id: String,
+    amount: Decimal,
+    paid_date: NaiveDate,
+    note: Option<String>,
+    allocations: Vec<PaymentAllocationDTO>,
+    unallocated_amount: Decimal,
+    user_id: Option<String>,
+    time: Option<DateTime<Local>>,
+}
+
+impl From<CustomerPayment> for PaymentEntityDTO {
+    fn from(payment: CustomerPayment) -> Self {
+        PaymentEntityDTO {
+            unallocated_amount: payment.unallocated_amount(),
+            payment_id: payment.id.to_string(),
+            customer_id: payment.customer_id.to_string(),
+            amount: payment.amount,
+            paid_date: payment.paid_date,
+            note: payment.note,
+            allocations: payment
+                .allocations
+                .into_iter()
+                .map(|allocation| allocation.into())
+                .collect(),
+            user_id: payment.user_id,
+            time: payment.time,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct OutstandingIncomeDTO {
+    income_id: String,
+    billing_id: String,
+    billing_name: String,
+    customer_name: Option<String>,
+    owed_since: NaiveDate,
+    age_days: i64,
+    amount: Decimal,
+    received_amount: Decimal,
+    outstanding_amount: Decimal,
+}
+
+impl From<OutstandingIncome> for OutstandingIncomeDTO {
+    fn from(income: OutstandingIncome) -> Self {
+        OutstandingIncomeDTO {
+            outstanding_amount: income.outstanding(),
+            income_id: income.income_id.to_string(),
+            billing_id: income.billing_id.to_string(),
+            billing_name: income.billing_name,
+            customer_name: income.customer_name,
+            owed_since: income.owed_since,
+            age_days: income.age_days,
+            amount: income.amount,
+            received_amount: income.received_amount,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct AgeingBucketsDTO {
+    days_0_30: Decimal,
+    days_31_60: Decimal,
+    days_61_90: Decimal,
+    days_over_90: Decimal,
+    total: Decimal,
+}
+
+impl From<AgeingBuckets> for AgeingBucketsDTO {
+    fn from(buckets: AgeingBuckets) -> Self {
+        AgeingBucketsDTO {
+            total: buckets.total(),
+            days_0_30: buckets.days_0_30,
+            days_31_60: buckets.days_31_60,
+            days_61_90: buckets.days_61_90,
+            days_over_90: buckets.days_over_90,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct CustomerReceivableDTO {
+    /// Missing on the row of income recorded without a customer.
+    customer_id: Option<String>,
+    customer_name: Option<String>,
+    income_count: u64,
+    outstanding: AgeingBucketsDTO,
+    unallocated_credit: Decimal,
+}
+
+impl From<CustomerReceivable> for CustomerReceivableDTO {
+    fn from(receivable: CustomerReceivable) -> Self {
+        CustomerReceivableDTO {
+            customer_id: receivable
+                .customer_id
+                .map(|customer_id| customer_id.to_string()),
+            customer_name: receivable.customer_name,
+            income_count: receivable.income_count as u64,
+            outstanding: receivable.buckets.into(),
+            unallocated_credit: receivable.unallocated_credit,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct ReceivableReportDTO {
+    as_of: NaiveDate,
+    customers: Vec<CustomerReceivableDTO>,
+    total: AgeingBucketsDTO,
+}
+
+impl From<ReceivableReport> for ReceivableReportDTO {
+    fn from(report: ReceivableReport) -> Self {
+        ReceivableReportDTO {
+            as_of: report.as_of,
+            customers: report
+                .customers
+                .into_iter()
+                .map(|receivable| receivable.into())
+                .collect(),
+            total: report.total.into(),
+        }
+    }
+}
+
+#[derive(ApiResponse)]
+enum CustomerResponse {
+    #[oai(status = 200)]
+    Ok(Json<CustomerEntityDTO>),
+
+    #[oai(status = 201)]
+    Created(Json<CustomerEntityDTO>),
+}
+
+#[derive(ApiResponse)]
+enum QueryCustomerResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<CustomerEntityDTO>>),
+}
+
+#[derive(ApiResponse)]
+enum DeleteCustomerResponse {
+    #[oai(status = 204)]
+    Ok,
+}
+
+#[derive(ApiResponse)]
+enum CustomerOutstandingResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<OutstandingIncomeDTO>>),
+}
+
+#[derive(ApiResponse)]
+enum PaymentResponse {
+    #[oai(status = 201)]
+    Created(Json<PaymentEntityDTO>),
+}
+
+#[derive(ApiResponse)]
+enum QueryPaymentResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<PaymentEntityDTO>>),
+}
+
+#[derive(ApiResponse)]
+enum DeletePaymentResponse {
+    #[oai(status = 204)]
+    Ok,
+}
+
+#[derive(ApiResponse)]
+enum ReceivableResponse {
+    #[oai(status = 200)]
+    Ok(Json<ReceivableReportDTO>),
+}
+
+/// Checks that the caller manages the team.
+async fn manage_team(
+    state: &AppState,
+    session_user: &SessionUser,
+    team_id: &str,
+) -> Result<Uuid, AppError> {
+    let team_uuid = parse_uuid("team_id", team_id)?;
+    TeamGuard::check(&state.db, session_user, team_uuid, TeamPermission::Manage).await?;
+    Ok(team_uuid)
+}
+
+/// Checks that the caller manages the team and loads its customer.
+async fn manage_customer(
+    state: &AppState,
+    session_user: &SessionUser,
+    team_id: &str,
+    customer_id: &str,
+) -> Result<Customer, AppError> {
+    let customer_uuid = parse_uuid("customer_id", customer_id)?;
+    let team_uuid = manage_team(state, session_user, team_id).await?;
+    Customer::from_id(&state.db, team_uuid, customer_uuid).await
+}
+
+fn parse_allocation(allocations: Option<Vec<AllocationDTO>>) -> Result<Allocation, AppError> {
+    let allocations = match allocations {
+        Some(allocations) => allocations,
+        None => return Ok(Allocation::Fifo),
+    };
+    let mut requests = vec![];
+    for allocation in allocations {
+        requests.push(AllocationRequest {
+            billing_id: parse_uuid("billing_id", &allocation.billing_id)?,
+            amount: allocation.amount,
+        });
+    }
+    Ok(Allocation::Explicit(requests))
+}
+
+pub struct CustomerRouter;
+
+#[OpenApi]
+impl CustomerRouter {
+    #[oai(
+        path = "/team/:team_id/customer",
+        method = "post",
+        tag = "ApiTags::Customer"
+    )]
+    async fn create_customer(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        customer: Json<CustomerProfileDTO>,
+    ) -> ApiResult<CustomerResponse> {
+        let team_uuid = manage_team(&state, &session_user, &team_id.0).await?;
+        let customer = Customer::create(&state.db, team_uuid, customer.0.into()).await?;
+        Ok(CustomerResponse::Created(Json(customer.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/customer",
+        method = "get",
+        tag = "ApiTags::Customer"
+    )]
+    async fn query_customer(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+    ) -> ApiResult<QueryCustomerResponse> {
+        let team_uuid = manage_team(&state, &session_user, &team_id.0).await?;
+        let customers = Customer::query(&state.db, team_uuid).await?;
+        Ok(QueryCustomerResponse::Ok(Json(
+            customers
+                .into_iter()
+                .map(|customer| customer.into())
+                .collect(),
+        )))
+    }
+
+    #[oai(
+        path = "/team/:team_id/customer/:customer_id",
+        method = "get",
+        tag = "ApiTags::Customer"
+    )]
+    async fn get_customer(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        customer_id: Path<String>,
+    ) -> ApiResult<CustomerResponse> {
+        let customer = manage_customer(&state, &session_user, &team_id.0, &customer_id.0).await?;
+        Ok(CustomerResponse::Ok(Json(customer.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/customer/:customer_id",
+        method = "put",
+        tag = "ApiTags::Customer"
+    )]
+    async fn update_customer(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        customer_id: Path<String>,
+        profile: Json<CustomerProfileDTO>,
+    ) -> ApiResult<CustomerResponse> {
+        let customer = manage_customer(&state, &session_user, &team_id.0, &customer_id.0).await?;
+        let customer = customer.update(&state.db, profile.0.into()).await?;
+        Ok(CustomerResponse::Ok(Json(customer.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/customer/:customer_id",
+        method = "delete",
+        tag = "ApiTags::Customer"
+    )]
+    async fn delete_customer(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        customer_id: Path<String>,
+    ) -> ApiResult<DeleteCustomerResponse> {
+        let customer = manage_customer(&state, &session_user, &team_id.0, &customer_id.0).await?;
+        customer.delete(&state.db).await?;
+        Ok(DeleteCustomerResponse::Ok)
+    }
+
+    /// Unpaid income entries of the customer, oldest billing first.
+    #[oai(
+        path = "/team/:team_id/customer/:customer_id/receivable",
+        method = "get",
+        tag = "ApiTags::Customer"
+    )]
+    async fn customer_outstanding(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        customer_id: Path<String>,
+    ) -> ApiResult<CustomerOutstandingResponse> {
+        let customer = manage_customer(&state, &session_user, &team_id.0, &customer_id.0).await?;
+        let incomes = OutstandingIncome::of_team(
+            &state.db,
+            customer.team_id,
+            Some(customer.id),
+            Local::now().date_naive(),
+        )
+        .await?;
+        Ok(CustomerOutstandingResponse::Ok(Json(
+            incomes.into_iter().map(|income| income.into()).collect(),
+        )))
+    }
+
+    /// Records a payment of the customer and allocates it to billings.
+    #[oai(
+        path = "/team/:team_id/customer/:customer_id/payment",
+        method = "post",
+        tag = "ApiTags::Customer"
+    )]
+    async fn create_payment(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        customer_id: Path<String>,
+        payment: Json<PaymentCreateDTO>,
+    ) -> ApiResult<PaymentResponse> {
+        let payment = payment.0;
+        let allocation = parse_allocation(payment.allocations)?;
+        let customer = manage_customer(&state, &session_user, &team_id.0, &customer_id.0).await?;
+        let payment = CustomerPayment::record(
+            &state.db,
+            &customer,
+            session_user.user_id,
+            payment.amount,
+            payment
+                .paid_date
+                .unwrap_or_else(|| Local::now().date_naive()),
+            payment.note,
+            allocation,
+        )
+        .await?;
+        Ok(PaymentResponse::Created(Json(payment.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/customer/:customer_id/payment",
+        method = "get",
+        tag = "ApiTags::Customer"
+    )]
+    async fn query_payment(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        customer_id: Path<String>,
+    ) -> ApiResult<QueryPaymentResponse> {
+        let customer = manage_customer(&state, &session_user, &team_id.0, &customer_id.0).await?;
+        let payments = CustomerPayment::query(&state.db, customer.id).await?;
+        Ok(QueryPaymentResponse::Ok(Json(
+            payments.into_iter().map(|payment| payment.into()).collect(),
+        )))
+    }
+
+    /// Takes back a payment, the billings it settled are owed again.
+    #[oai(
+        path = "/team/:team_id/customer/:customer_id/payment/:payment_id",
+        method = "delete",
+        tag = "ApiTags::Customer"
+    )]
+    async fn delete_payment(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        customer_id: Path<String>,
+        payment_id: Path<String>,
+    ) -> ApiResult<DeletePaymentResponse> {
+        let payment_uuid = parse_uuid("payment_id", &payment_id.0)?;
+        let customer = manage_customer(&state, &session_user, &team_id.0, &customer_id.0).await?;
+        let payment = CustomerPayment::from_id(&state.db, customer.id, payment_uuid).await?;
+        payment.delete(&state.db).await?;
+        Ok(DeletePaymentResponse::Ok)
+    }
+
+    /// What each customer owes the team, split into 0–30, 31–60, 61–90 and over 90 days.
+    #[oai(
+        path = "/team/:team_id/receivable",
+        method = "get",
+        tag = "ApiTags::Customer"
+    )]
+    async fn team_receivable(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+    ) -> ApiResult<ReceivableResponse> {
+        let team_uuid = manage_team(&state, &session_user, &team_id.0).await?;
+        let report =
+            ReceivableReport::for_team(&state.db, team_uuid, Local::now().date_naive()).await?;
+        Ok(ReceivableResponse::Ok(Json(report.into())))
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, ConnectionTrait, EntityTrait, FromQueryResult, JoinType,
+    QueryFilter, QueryOrder, QuerySelect, RelationTrait,
+};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    entities::{billing, billing_income, customer, customer_payment, payment_allocation},
+    error::AppError,
+};
+
+/// Outstanding amounts split by how many days they have been owed.
+#[derive(Debug, Clone, Default)]
+pub struct AgeingBuckets {
+    pub days_0_30: Decimal,
+    pub days_31_60: Decimal,
+    pub days_61_90: Decimal,
+    pub days_over_90: Decimal,
+}
+
+impl AgeingBuckets {
+    fn add(&mut self, age_days: i64, amount: Decimal) {
+        let bucket = match age_days {
+            i64::MIN..=30 => &mut self.days_0_30,
+            31..=60 => &mut self.days_31_60,
+            61..=90 => &mut self.days_61_90,
+            _ => &mut self.days_over_90,
+        };
+        *bucket += amount;
+    }
+
+    pub fn total(&self) -> Decimal {
+        self.days_0_30 + self.days_31_60 + self.days_61_90 + self.days_over_90
+    }
+}
+
+/// An income entry the customer has not paid in full.
+#[derive(Debug, Clone)]
+pub struct OutstandingIncome {
+    pub income_id: Uuid,
+    pub billing_id: Uuid,
+    pub billing_name: String,
+    pub customer_id: Option<Uuid>,
+    pub customer_name: Option<String>,
+    /// The day the billing ended, or the day the income was recorded while it is open.
+    pub owed_since: NaiveDate,
+    pub age_days: i64,
+    pub amount: Decimal,
+    pub received_amount: Decimal,
+}
+
+impl OutstandingIncome {
+    pub fn outstanding(&self) -> Decimal {
+        self.amount - self.received_amount
+    }
+
+    /// Unpaid income of the team, optionally of one customer, oldest billing first.
+    #[instrument(skip(db))]
+    pub async fn of_team<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        customer_id: Option<Uuid>,
+        today: NaiveDate,
+    ) -> Result<Vec<OutstandingIncome>, AppError> {
+        let mut select = billing_income::Entity::find()
+            .find_also_related(billing::Entity)
+            .filter(billing::Column::TeamId.eq(team_id))
+            .filter(
+                Expr::tbl(billing_income::Entity, billing_income::Column::Amount).greater_than(
+                    Expr::tbl(
+                        billing_income::Entity,
+                        billing_income::Column::ReceivedAmount,
+                    ),
+                ),
+            );
+        if let Some(customer_id) = customer_id {
+            select = select.filter(billing_income::Column::CustomerId.eq(customer_id));
+        }
+        let rows = select
+            .order_by_asc(billing::Column::StartTime)
+            .order_by_asc(billing_income::Column::Time)
+            .all(db)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(income_model, billing_model)| {
+                billing_model.map(|billing_model| {
+                    let owed_since = billing_model.end_time.unwrap_or(income_model.time).date();
+                    OutstandingIncome {
+                        income_id: income_model.id,
+                        billing_id: billing_model.id,
+                        billing_name: billing_model.name,
+                        customer_id: income_model.customer_id,
+                        customer_name: income_model.customer_name,
+                        owed_since,
+                        age_days: (today - owed_since).num_days().max(0),
+                        amount: income_model.amount,
+                        received_amount: income_model.received_amount,
+                    }
+                })
+            })
+            .collect())
+    }
+}
+
+/// What one customer owes the team. Income without a customer is reported on a row of its
+/// own with neither id nor name.
+#[derive(Debug, Clone)]
+pub struct CustomerReceivable {
+    pub customer_id: Option<Uuid>,
+    pub customer_name: Option<String>,
+    pub income_count: usize,
+    pub buckets: AgeingBuckets,
+    /// Payments not allocated to any billing yet.
+    pub unallocated_credit: Decimal,
+}
+
+#[derive(Debug)]
+pub struct ReceivableReport {
+    pub as_of: NaiveDate,
+    /// Largest debtors first.
+    pub customers: Vec<CustomerReceivable>,
+    pub total: AgeingBuckets,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct CustomerAmount {
+    customer_id: Uuid,
+    amount: Option<Decimal>,
+}
+
+impl ReceivableReport {
+    #[instrument(skip(db))]
+    pub async fn for_team<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        today: NaiveDate,
+    ) -> Result<ReceivableReport, AppError> {
+        let customer_names: HashMap<Uuid, String> = customer::Entity::find()
+            .filter(customer::Column::TeamId.eq(team_id))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|customer_model| (customer_model.id, customer_model.name))
+            .collect();
+        let credits = Self::unallocated_credits(db, team_id).await?;
+
+        let mut receivables: HashMap<Option<Uuid>, CustomerReceivable> = HashMap::new();
+        let mut total = AgeingBuckets::default();
+        for income in OutstandingIncome::of_team(db, team_id, None, today).await? {
+            let receivable =
+                receivables
+                    .entry(income.customer_id)
+                    .or_insert_with(|| CustomerReceivable {
+                        customer_id: income.customer_id,
+                        customer_name: income
+                            .customer_id
+                            .and_then(|customer_id| customer_names.get(&customer_id).cloned()),
+                        income_count: 0,
+                        buckets: AgeingBuckets::default(),
+                        unallocated_credit: Decimal::ZERO,
+                    });
+            receivable.income_count += 1;
+            receivable
+                .buckets
+                .add(income.age_days, income.outstanding());
+            total.add(income.age_days, income.outstanding());
+        }
+        for (customer_id, credit) in credits {
+            receivables
+                .entry(Some(customer_id))
+                .or_insert_with(|| CustomerReceivable {
+                    customer_id: Some(customer_id),
+                    customer_name: customer_names.get(&customer_id).cloned(),
+                    income_count: 0,
+                    buckets: AgeingBuckets::default(),
+                    unallocated_credit: Decimal::ZERO,
+                })
+                .unallocated_credit = credit;
+        }
+
+        let mut customers: Vec<CustomerReceivable> = receivables.into_values().collect();
+        customers.sort_by(|a, b| {
+            b.buckets
+                .total()
+                .cmp(&a.buckets.total())
+                .then_with(|| a.customer_name.cmp(&b.customer_name))
+        });
+        Ok(ReceivableReport {
+            as_of: today,
+            customers,
+            total,
+        })
+    }
+
+    /// Paid minus allocated per customer of the team, only customers with credit left.
+    async fn unallocated_credits<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+    ) -> Result<HashMap<Uuid, Decimal>, AppError> {
+        let paid = customer_payment::Entity::find()
+            .select_only()
+            .column(customer_payment::Column::CustomerId)
+            .column_as(
+                Expr::tbl(customer_payment::Entity, customer_payment::Column::Amount).sum(),
+                "amount",
+            )
+            .inner_join(customer::Entity)
+            .filter(customer::Column::TeamId.eq(team_id))
+            .group_by(customer_payment::Column::CustomerId)
+            .into_model::<CustomerAmount>()
+            .all(db)
+            .await?;
+        let allocated: HashMap<Uuid, Decimal> = payment_allocation::Entity::find()
+            .select_only()
+            .column(customer_payment::Column::CustomerId)
+            .column_as(
+                Expr::tbl(
+                    payment_allocation::Entity,
+                    payment_allocation::Column::Amount,
+                )
+                .sum(),
+                "amount",
+            )
+            .inner_join(customer_payment::Entity)
+            .join(
+                JoinType::InnerJoin,
+                customer_payment::Relation::Customer.def(),
+            )
+            .filter(customer::Column::TeamId.eq(team_id))
+            .group_by(customer_payment::Column::CustomerId)
+            .into_model::<CustomerAmount>()
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|row| (row.customer_id, row.amount.unwrap_or_default()))
+            .collect();
+        Ok(paid
+            .into_iter()
+            .filter_map(|row| {
+                let credit = row.amount.unwrap_or_default()
+                    - allocated.get(&row.customer_id).copied().unwrap_or_default();
+                (credit > Decimal::ZERO).then_some((row.customer_id, credit))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+
+    use super::AgeingBuckets;
+
+    #[test]
+    fn buckets_end_on_the_30th_60th_and_90th_day() {
+        let mut buckets = AgeingBuckets::default();
+        for (age_days, amount) in [
+            (0, dec!(1)),
+            (30, dec!(2)),
+            (31, dec!(10)),
+            (60, dec!(20)),
+            (61, dec!(100)),
+            (90, dec!(200)),
+            (91, dec!(1000)),
+            (365, dec!(2000)),
+        ] {
+            buckets.add(age_days, amount);
+        }
+        assert_eq!(buckets.days_0_30, dec!(3));
+        assert_eq!(buckets.days_31_60, dec!(30));
+        assert_eq!(buckets.days_61_90, dec!(300));
+        assert_eq!(buckets.days_over_90, dec!(3000));
+        assert_eq!(buckets.total(), dec!(3333));
+    }
+
+    #[test]
+    fn owed_from_the_future_counts_as_current() {
+        let mut buckets = AgeingBuckets::default();
+        buckets.add(-3, dec!(50));
+        assert_eq!(buckets.days_0_30, dec!(50));
+        assert_eq!(buckets.total(), dec!(50));
+    }
+}
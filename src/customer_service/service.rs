@@ -0,0 +1,860 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, IntoActiveModel,
+    ModelTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use tracing::{instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    billing_service::service::parse_navie_time_to_data_time,
+    entities::{billing, billing_income, customer, customer_payment, payment_allocation},
+    error::{AppError, ErrorCode},
+    income_service::service::trimmed,
+};
+
+fn customer_not_found(customer_id: Uuid) -> AppError {
+    AppError::new(
+        ErrorCode::CustomerNotFound,
+        format!("can not find customer {}", customer_id),
+    )
+}
+
+fn payment_not_found(payment_id: Uuid) -> AppError {
+    AppError::new(
+        ErrorCode::PaymentNotFound,
+        format!("can not find payment {}", payment_id),
+    )
+}
+
+fn invalid_payment(message: impl Into<String>) -> AppError {
+    AppError::new(ErrorCode::InvalidArgument, message)
+}
+
+/// Contact details of a customer, replaced as a whole.
+#[derive(Debug, Clone)]
+pub struct CustomerProfile {
+    pub name: String,
+    pub contact_name: Option<String>,
+    pub phone: Option<String>,
+    pub note: Option<String>,
+}
+
+impl CustomerProfile {
+    pub fn normalize(self) -> Result<CustomerProfile, AppError> {
+        let name = self.name.trim().to_owned();
+        if name.is_empty() {
+            return Err(AppError::new(
+                ErrorCode::InvalidArgument,
+                "customer name can not be blank",
+            ));
+        }
+        Ok(CustomerProfile {
+            name,
+            contact_name: trimmed(self.contact_name),
+            phone: trimmed(self.phone),
+            note: trimmed(self.note),
+        })
+    }
+
+    fn apply(self, active_model: &mut customer::ActiveModel) {
+        active_model.name = Set(self.name);
+        active_model.contact_name = Set(self.contact_name);
+        active_model.phone = Set(self.phone);
+        active_model.note = Set(self.note);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Customer {
+    pub id: Uuid,
+    pub team_id: Uuid,
+    pub profile: CustomerProfile,
+    pub create_time: Option<DateTime<Local>>,
+}
+
+impl From<customer::Model> for Customer {
+    fn from(model: customer::Model) -> Self {
+        Customer {
+            id: model.id,
+            team_id: model.team_id,
+            profile: CustomerProfile {
+                name: model.name,
+                contact_name: model.contact_name,
+                phone: model.phone,
+                note: model.note,
+            },
+            create_time: parse_navie_time_to_data_time(Some(model.create_time)),
+        }
+    }
+}
+
+impl Customer {
+    #[instrument(skip(db))]
+    pub async fn create<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        profile: CustomerProfile,
+    ) -> Result<Customer, AppError> {
+        let profile = profile.normalize()?;
+        Self::check_name_unused(db, team_id, &profile.name, None).await?;
+        let mut active_model = customer::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            team_id: Set(team_id),
+            create_time: Set(Local::now().naive_local()),
+            ..Default::default()
+        };
+        profile.apply(&mut active_model);
+        Ok(active_model.insert(db).await?.into())
+    }
+
+    #[instrument(skip(db))]
+    pub async fn from_id<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        customer_id: Uuid,
+    ) -> Result<Customer, AppError> {
+        let model = customer::Entity::find_by_id(customer_id)
+            .filter(customer::Column::TeamId.eq(team_id))
+            .one(db)
+            .await?
+            .ok_or_else(|| customer_not_found(customer_id))?;
+        Ok(model.into())
+    }
+
+    #[instrument(skip(db))]
+    pub async fn query<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+    ) -> Result<Vec<Customer>, AppError> {
+        let models = customer::Entity::find()
+            .filter(customer::Column::TeamId.eq(team_id))
+            .order_by_asc(customer::Column::Name)
+            .all(db)
+            .await?;
+        Ok(models.into_iter().map(|model| model.into()).collect())
+    }
+
+    /// Replaces the contact details. Income entries keep the shipper name they were
+    /// recorded with.
+    #[instrument(skip(db))]
+    pub async fn update<C: ConnectionTrait + TransactionTrait>(
+        self,
+        db: &C,
+        profile: CustomerProfile,
+    ) -> Result<Customer, AppError> {
+        let profile = profile.normalize()?;
+        let txn = db.begin().await?;
+        let model = customer::Entity::find_by_id(self.id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or_else(|| customer_not_found(self.id))?;
+        Self::check_name_unused(&txn, self.team_id, &profile.name, Some(self.id)).await?;
+        let mut active_model = model.into_active_model();
+        profile.apply(&mut active_model);
+        let model = active_model.update(&txn).await?;
+        txn.commit().await?;
+        Ok(model.into())
+    }
+
+    /// Removes a customer nobody charged or paid yet, others stay for the receivables.
+    #[instrument(skip(db))]
+    pub async fn delete<C: ConnectionTrait + TransactionTrait>(
+        self,
+        db: &C,
+    ) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        // New income and payments of the customer wait for the lock, none slip in before the
+        // delete.
+        let model = customer::Entity::find_by_id(self.id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or_else(|| customer_not_found(self.id))?;
+        let income_count = model
+            .find_related(billing_income::Entity)
+            .count(&txn)
+            .await?;
+        let payment_count = model
+            .find_related(customer_payment::Entity)
+            .count(&txn)
+            .await?;
+        if income_count > 0 || payment_count > 0 {
+            return Err(AppError::new(
+                ErrorCode::CustomerInUse,
+                format!("customer {} has income or payments", self.id),
+            ));
+        }
+        model.delete(&txn).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    async fn check_name_unused<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        name: &str,
+        except_id: Option<Uuid>,
+    ) -> Result<(), AppError> {
+        let mut select = customer::Entity::find()
+            .filter(customer::Column::TeamId.eq(team_id))
+            .filter(customer::Column::Name.eq(name));
+        if let Some(except_id) = except_id {
+            select = select.filter(customer::Column::Id.ne(except_id));
+        }
+        if select.one(db).await?.is_some() {
+            return Err(AppError::new(
+                ErrorCode::DuplicateName,
+                format!("customer named {} already exists", name),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Part of a payment the customer earmarked for one billing.
+#[derive(Debug, Clone)]
+pub struct AllocationRequest {
+    pub billing_id: Uuid,
+    pub amount: Decimal,
+}
+
+/// How a payment is spread over what the customer owes.
+#[derive(Debug, Clone)]
+pub enum Allocation {
+    /// Settles what has been owed longest first, the same order the receivables age in.
+    Fifo,
+    /// Settles the given billings, whatever is left stays on the payment as credit.
+    Explicit(Vec<AllocationRequest>),
+}
+
+/// Amount of a payment settled against one income entry.
+#[derive(Debug, Clone)]
+pub struct PaymentAllocation {
+    pub billing_id: Uuid,
+    pub billing_name: String,
+    pub income_id: Uuid,
+    pub amount: Decimal,
+}
+
+#[derive(Debug, Clone)]
+pub struct CustomerPayment {
+    pub id: Uuid,
+    pub customer_id: Uuid,
+    pub amount: Decimal,
+    pub paid_date: NaiveDate,
+    pub note: Option<String>,
+    pub user_id: Option<String>,
+    pub time: Option<DateTime<Local>>,
+    pub allocations: Vec<PaymentAllocation>,
+}
+
+impl CustomerPayment {
+    fn new(model: customer_payment::Model, allocations: Vec<PaymentAllocation>) -> Self {
+        CustomerPayment {
+            id: model.id,
+            customer_id: model.customer_id,
+            amount: model.amount,
+            paid_date: model.paid_date,
+            note: model.note,
+            user_id: model.user_id,
+            time: parse_navie_time_to_data_time(Some(model.time)),
+            allocations,
+        }
+    }
+
+    /// What is left of the payment after settling billings, credit of the customer.
+    pub fn unallocated_amount(&self) -> Decimal {
+        self.amount
+            - self
+                .allocations
+                .iter()
+                .map(|allocation| allocation.amount)
+                .sum::<Decimal>()
+    }
+
+    /// Records a payment of the customer and settles income entries with it. Each settled
+    /// amount is added to the received amount of its income entry.
+    #[instrument(skip(db, customer))]
+    pub async fn record<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        customer: &Customer,
+        user_id: String,
+        amount: Decimal,
+        paid_date: NaiveDate,
+        note: Option<String>,
+        allocation: Allocation,
+    ) -> Result<CustomerPayment, AppError> {
+        if amount <= Decimal::ZERO {
+            return Err(invalid_payment("payment amount must be positive"));
+        }
+        let txn = db.begin().await?;
+        // Payments of one customer are allocated one at a time.
+        customer::Entity::find_by_id(customer.id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or_else(|| customer_not_found(customer.id))?;
+        let outstanding = Self::outstanding_incomes(&txn, customer.id).await?;
+        let shares = match allocation {
+            Allocation::Fifo => allocate_fifo(amount, &outstanding),
+            Allocation::Explicit(requests) => allocate_explicit(amount, &outstanding, requests)?,
+        };
+
+        let payment_model = customer_payment::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            customer_id: Set(customer.id),
+            amount: Set(amount),
+            paid_date: Set(paid_date),
+            note: Set(trimmed(note)),
+            user_id: Set(Some(user_id)),
+            time: Set(Local::now().naive_local()),
+        }
+        .insert(&txn)
+        .await?;
+        for (income_model, share) in outstanding.into_iter().zip(shares) {
+            if share <= Decimal::ZERO {
+                continue;
+            }
+            payment_allocation::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                payment_id: Set(payment_model.id),
+                billing_income_id: Set(income_model.id),
+                amount: Set(share),
+            }
+            .insert(&txn)
+            .await?;
+            let received_amount = income_model.received_amount + share;
+            let mut active_model = income_model.into_active_model();
+            active_model.received_amount = Set(received_amount);
+            active_model.update(&txn).await?;
+        }
+        txn.commit().await?;
+        Self::from_id(db, customer.id, payment_model.id).await
+    }
+
+    #[instrument(skip(db))]
+    pub async fn from_id<C: ConnectionTrait>(
+        db: &C,
+        customer_id: Uuid,
+        payment_id: Uuid,
+    ) -> Result<CustomerPayment, AppError> {
+        let model = customer_payment::Entity::find_by_id(payment_id)
+            .filter(customer_payment::Column::CustomerId.eq(customer_id))
+            .one(db)
+            .await?
+            .ok_or_else(|| payment_not_found(payment_id))?;
+        let mut allocations = Self::allocations_of(db, vec![model.id]).await?;
+        let allocations = allocations.remove(&model.id).unwrap_or_default();
+        Ok(Self::new(model, allocations))
+    }
+
+    /// Payments of the customer, latest first.
+    #[instrument(skip(db))]
+    pub async fn query<C: ConnectionTrait>(
+        db: &C,
+        customer_id: Uuid,
+    ) -> Result<Vec<CustomerPayment>, AppError> {
+        let models = customer_payment::Entity::find()
+            .filter(customer_payment::Column::CustomerId.eq(customer_id))
+            .order_by_desc(customer_payment::Column::PaidDate)
+            .order_by_desc(customer_payment::Column::Time)
+            .all(db)
+            .await?;
+        let payment_ids = models.iter().map(|model| model.id).collect();
+        let mut allocations = Self::allocations_of(db, payment_ids).await?;
+        Ok(models
+            .into_iter()
+            .map(|model| {
+                let payment_allocations = allocations.remove(&model.id).unwrap_or_default();
+                Self::new(model, payment_allocations)
+            })
+            .collect())
+    }
+
+    /// Takes back a payment recorded by mistake, the income entries it settled are owed
+    /// again.
+    #[instrument(skip(db))]
+    pub async fn delete<C: ConnectionTrait + TransactionTrait>(
+        self,
+        db: &C,
+    ) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        customer::Entity::find_by_id(self.customer_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or_else(|| customer_not_found(self.customer_id))?;
+        let payment_model = customer_payment::Entity::find_by_id(self.id)
+            .one(&txn)
+            .await?
+            .ok_or_else(|| payment_not_found(self.id))?;
+        let allocation_models = payment_model
+            .find_related(payment_allocation::Entity)
+            .all(&txn)
+            .await?;
+        for allocation_model in allocation_models {
+            if let Some(income_model) =
+                billing_income::Entity::find_by_id(allocation_model.billing_income_id)
+                    .lock_exclusive()
+                    .one(&txn)
+                    .await?
+            {
+                let received_amount =
+                    (income_model.received_amount - allocation_model.amount).max(Decimal::ZERO);
+                let mut active_model = income_model.into_active_model();
+                active_model.received_amount = Set(received_amount);
+                active_model.update(&txn).await?;
+            }
+            allocation_model.delete(&txn).await?;
+        }
+        payment_model.delete(&txn).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Unpaid income entries of the customer, longest owed first. The rows stay locked until the
+    /// transaction ends.
+    async fn outstanding_incomes<C: ConnectionTrait>(
+        db: &C,
+        customer_id: Uuid,
+    ) -> Result<Vec<billing_income::Model>, AppError> {
+        let mut income_models = billing_income::Entity::find()
+            .filter(billing_income::Column::CustomerId.eq(customer_id))
+            .filter(
+                Expr::col(billing_income::Column::Amount)
+                    .greater_than(Expr::col(billing_income::Column::ReceivedAmount)),
+            )
+            .lock_exclusive()
+            .all(db)
+            .await?;
+        let billing_ids: Vec<Uuid> = income_models
+            .iter()
+            .map(|income_model| income_model.billing_id)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let billing_ends: HashMap<Uuid, Option<NaiveDateTime>> = billing::Entity::find()
+            .filter(billing::Column::Id.is_in(billing_ids))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|billing_model| (billing_model.id, billing_model.end_time))
+            .collect();
+        income_models.sort_by_key(|income_model| {
+            (
+                billing_ends
+                    .get(&income_model.billing_id)
+                    .copied()
+                    .flatten()
+                    .unwrap_or(income_model.time),
+                income_model.time,
+            )
+        });
+        Ok(income_models)
+    }
+
+    async fn allocations_of<C: ConnectionTrait>(
+        db: &C,
+        payment_ids: Vec<Uuid>,
+    ) -> Result<HashMap<Uuid, Vec<PaymentAllocation>>, AppError> {
+        if payment_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let rows = payment_allocation::Entity::find()
+            .filter(payment_allocation::Column::PaymentId.is_in(payment_ids))
+            .find_also_related(billing_income::Entity)
+            .order_by_asc(billing_income::Column::Time)
+            .all(db)
+            .await?;
+        let billing_ids: Vec<Uuid> = rows
+            .iter()
+            .filter_map(|(_, income_model)| {
+                income_model
+                    .as_ref()
+                    .map(|income_model| income_model.billing_id)
+            })
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let billing_names: HashMap<Uuid, String> = if billing_ids.is_empty() {
+            HashMap::new()
+        } else {
+            billing::Entity::find()
+                .filter(billing::Column::Id.is_in(billing_ids))
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|billing_model| (billing_model.id, billing_model.name))
+                .collect()
+        };
+        let mut allocations: HashMap<Uuid, Vec<PaymentAllocation>> = HashMap::new();
+        for (allocation_model, income_model) in rows {
+            let income_model = match income_model {
+                Some(income_model) => income_model,
+                None => continue,
+            };
+            allocations
+                .entry(allocation_model.payment_id)
+                .or_default()
+                .push(PaymentAllocation {
+                    billing_id: income_model.billing_id,
+                    billing_name: billing_names
+                        .get(&income_model.billing_id)
+                        .cloned()
+                        .unwrap_or_default(),
+                    income_id: income_model.id,
+                    amount: allocation_model.amount,
+                });
+        }
+        Ok(allocations)
+    }
+}
+
+/// Shares of the payment per outstanding income, settling the oldest in full first.
+fn allocate_fifo(amount: Decimal, outstanding: &[billing_income::Model]) -> Vec<Decimal> {
+    let mut left = amount;
+    outstanding
+        .iter()
+        .map(|income_model| {
+            let share = left.min(income_model.amount - income_model.received_amount);
+            left -= share;
+            share
+        })
+        .collect()
+}
+
+/// Shares of the payment per outstanding income for the billings the customer named. The
+/// amount for a billing is spread over its income entries oldest first.
+fn allocate_explicit(
+    amount: Decimal,
+    outstanding: &[billing_income::Model],
+    requests: Vec<AllocationRequest>,
+) -> Result<Vec<Decimal>, AppError> {
+    let mut requested: HashMap<Uuid, Decimal> = HashMap::new();
+    for request in requests {
+        if request.amount <= Decimal::ZERO {
+            return Err(invalid_payment("allocated amount must be positive"));
+        }
+        if requested
+            .insert(request.billing_id, request.amount)
+            .is_some()
+        {
+            return Err(invalid_payment(format!(
+                "billing {} is allocated more than once",
+                request.billing_id
+            )));
+        }
+    }
+    if requested.values().sum::<Decimal>() > amount {
+        return Err(invalid_payment("allocations exceed the payment amount"));
+    }
+    let mut shares = vec![Decimal::ZERO; outstanding.len()];
+    for (billing_id, billing_amount) in requested {
+        let mut left = billing_amount;
+        for (share, income_model) in shares.iter_mut().zip(outstanding) {
+            if income_model.billing_id != billing_id {
+                continue;
+            }
+            *share = left.min(income_model.amount - income_model.received_amount);
+            left -= *share;
+        }
+        if left > Decimal::ZERO {
+            warn!(
+                "billing {} owes less than the allocated {}",
+                billing_id, billing_amount
+            );
+            return Err(invalid_payment(format!(
+                "customer owes less than {} on billing {}",
+                billing_amount, billing_id
+            )));
+        }
+    }
+    Ok(shares)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Local, NaiveDateTime};
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use sea_orm::{ActiveModelTrait, EntityTrait, Set};
+    use uuid::Uuid;
+
+    use super::{Allocation, AllocationRequest, Customer, CustomerPayment, CustomerProfile};
+    use crate::entities::{billing, billing_income, team, user};
+    use crate::error::{AppError, ErrorCode};
+    use crate::test_db::TestDb;
+
+    const OWNER_ID: &str = "owner-openid";
+
+    fn days_ago(days: i64) -> NaiveDateTime {
+        Local::now().naive_local() - Duration::days(days)
+    }
+
+    struct Ledger {
+        customer: Customer,
+        /// Billings with one unpaid income entry each, from the longest owed.
+        billing_ids: Vec<Uuid>,
+        income_ids: Vec<Uuid>,
+    }
+
+    /// A customer owing 1000 on a billing ended 60 days ago, 1000 on one ended 10 days ago and
+    /// 500 on a billing that is still open.
+    async fn ledger(test_db: &TestDb) -> Ledger {
+        let db = &test_db.db;
+        user::ActiveModel {
+            id: Set(OWNER_ID.to_owned()),
+            user_name: Set("车主".to_owned()),
+            avatar_url: Set(None),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        let team_model = team::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            team_name: Set("车队".to_owned()),
+            user_id: Set(OWNER_ID.to_owned()),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        let customer = Customer::create(
+            db,
+            team_model.id,
+            CustomerProfile {
+                name: "煤矿".to_owned(),
+                contact_name: None,
+                phone: None,
+                note: None,
+            },
+        )
+        .await
+        .unwrap();
+        // Inserted newest first so the allocation order can not come from insertion order.
+        let mut billing_ids = vec![];
+        let mut income_ids = vec![];
+        for (name, start, end, amount) in [
+            ("第三趟", days_ago(5), None, dec!(500)),
+            ("第二趟", days_ago(20), Some(days_ago(10)), dec!(1000)),
+            ("第一趟", days_ago(70), Some(days_ago(60)), dec!(1000)),
+        ] {
+            let billing_model = billing::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                name: Set(name.to_owned()),
+                team_id: Set(Some(team_model.id)),
+                start_time: Set(Some(start)),
+                end_time: Set(end),
+                ..Default::default()
+            }
+            .insert(db)
+            .await
+            .unwrap();
+            let income_model = billing_income::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                billing_id: Set(billing_model.id),
+                customer_id: Set(Some(customer.id)),
+                customer_name: Set(Some(customer.profile.name.clone())),
+                amount: Set(amount),
+                received_amount: Set(Decimal::ZERO),
+                user_id: Set(Some(OWNER_ID.to_owned())),
+                time: Set(start),
+                ..Default::default()
+            }
+            .insert(db)
+            .await
+            .unwrap();
+            billing_ids.insert(0, billing_model.id);
+            income_ids.insert(0, income_model.id);
+        }
+        Ledger {
+            customer,
+            billing_ids,
+            income_ids,
+        }
+    }
+
+    async fn received(test_db: &TestDb, income_ids: &[Uuid]) -> Vec<Decimal> {
+        let mut amounts = vec![];
+        for income_id in income_ids {
+            let income_model = billing_income::Entity::find_by_id(*income_id)
+                .one(&test_db.db)
+                .await
+                .unwrap()
+                .unwrap();
+            amounts.push(income_model.received_amount);
+        }
+        amounts
+    }
+
+    async fn pay(
+        test_db: &TestDb,
+        customer: &Customer,
+        amount: Decimal,
+        allocation: Allocation,
+    ) -> Result<CustomerPayment, AppError> {
+        CustomerPayment::record(
+            &test_db.db,
+            customer,
+            OWNER_ID.to_owned(),
+            amount,
+            Local::now().date_naive(),
+            None,
+            allocation,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn fifo_settles_the_longest_owed_first() {
+        let Some(test_db) = TestDb::create().await else {
+            return;
+        };
+        let Ledger {
+            customer,
+            billing_ids,
+            income_ids,
+        } = ledger(&test_db).await;
+
+        let payment = pay(&test_db, &customer, dec!(1500), Allocation::Fifo)
+            .await
+            .unwrap();
+        assert_eq!(
+            received(&test_db, &income_ids).await,
+            vec![dec!(1000), dec!(500), dec!(0)]
+        );
+        let allocated: Vec<(Uuid, Decimal)> = payment
+            .allocations
+            .iter()
+            .map(|allocation| (allocation.billing_id, allocation.amount))
+            .collect();
+        assert_eq!(
+            allocated,
+            vec![(billing_ids[0], dec!(1000)), (billing_ids[1], dec!(500))]
+        );
+        assert_eq!(payment.unallocated_amount(), dec!(0));
+
+        // Paying more than is owed leaves the rest as credit.
+        let payment = pay(&test_db, &customer, dec!(1200), Allocation::Fifo)
+            .await
+            .unwrap();
+        assert_eq!(
+            received(&test_db, &income_ids).await,
+            vec![dec!(1000), dec!(1000), dec!(500)]
+        );
+        assert_eq!(payment.unallocated_amount(), dec!(200));
+        test_db.drop().await;
+    }
+
+    #[tokio::test]
+    async fn explicit_allocation_is_capped_at_what_is_owed() {
+        let Some(test_db) = TestDb::create().await else {
+            return;
+        };
+        let Ledger {
+            customer,
+            billing_ids,
+            income_ids,
+        } = ledger(&test_db).await;
+
+        let err = pay(
+            &test_db,
+            &customer,
+            dec!(2000),
+            Allocation::Explicit(vec![AllocationRequest {
+                billing_id: billing_ids[2],
+                amount: dec!(500.01),
+            }]),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+        let err = pay(
+            &test_db,
+            &customer,
+            dec!(500),
+            Allocation::Explicit(vec![
+                AllocationRequest {
+                    billing_id: billing_ids[1],
+                    amount: dec!(300),
+                },
+                AllocationRequest {
+                    billing_id: billing_ids[2],
+                    amount: dec!(300),
+                },
+            ]),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+        // Nothing is settled by a rejected payment.
+        assert_eq!(
+            received(&test_db, &income_ids).await,
+            vec![dec!(0), dec!(0), dec!(0)]
+        );
+        assert!(CustomerPayment::query(&test_db.db, customer.id)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let payment = pay(
+            &test_db,
+            &customer,
+            dec!(800),
+            Allocation::Explicit(vec![AllocationRequest {
+                billing_id: billing_ids[2],
+                amount: dec!(500),
+            }]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            received(&test_db, &income_ids).await,
+            vec![dec!(0), dec!(0), dec!(500)]
+        );
+        assert_eq!(payment.unallocated_amount(), dec!(300));
+        test_db.drop().await;
+    }
+
+    #[tokio::test]
+    async fn deleting_a_payment_undoes_its_allocations() {
+        let Some(test_db) = TestDb::create().await else {
+            return;
+        };
+        let Ledger {
+            customer,
+            income_ids,
+            ..
+        } = ledger(&test_db).await;
+        let first = pay(&test_db, &customer, dec!(1500), Allocation::Fifo)
+            .await
+            .unwrap();
+        pay(&test_db, &customer, dec!(700), Allocation::Fifo)
+            .await
+            .unwrap();
+        assert_eq!(
+            received(&test_db, &income_ids).await,
+            vec![dec!(1000), dec!(1000), dec!(200)]
+        );
+
+        first.delete(&test_db.db).await.unwrap();
+        assert_eq!(
+            received(&test_db, &income_ids).await,
+            vec![dec!(0), dec!(500), dec!(200)]
+        );
+        let payments = CustomerPayment::query(&test_db.db, customer.id)
+            .await
+            .unwrap();
+        assert_eq!(payments.len(), 1);
+        assert_eq!(payments[0].amount, dec!(700));
+
+        // The customer has payments now and stays for the receivables.
+        let err = customer.delete(&test_db.db).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::CustomerInUse);
+        test_db.drop().await;
+    }
+}
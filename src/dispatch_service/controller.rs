@@ -0,0 +1,190 @@
+use chrono::{DateTime, Local};
+use poem::web::Data;
+use poem_openapi::{
+    param::{Path, Query},
+    payload::Json,
+    ApiResponse, Object, OpenApi, Tags,
+};
+use rust_decimal::Decimal;
+
+use crate::auth_service::service::{TeamGuard, TeamPermission};
+use crate::error::{parse_uuid, ApiResult};
+use crate::session_service::service::SessionUser;
+use crate::state::AppState;
+
+use crate::billing_service::service::Billing;
+
+use super::service::Dispatch;
+
+#[derive(Tags)]
+enum ApiTags {
+    Dispatch,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct DispatchCreateDTO {
+    #[oai(validator(max_length = 128))]
+    car_id: String,
+
+    driver_ids: Vec<String>,
+
+    billing_name: Option<String>,
+
+    #[oai(validator(max_length = 128))]
+    template_id: Option<String>,
+
+    /// Odometer at departure, the car's last known reading when omitted.
+    start_odometer_km: Option<i32>,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct DispatchEndDTO {
+    #[oai(validator(max_length = 128))]
+    dispatch_id: String,
+
+    /// Odometer on return, needed to pay drivers per km.
+    end_odometer_km: Option<i32>,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct DispatchEntityDTO {
+    dispatch_id: String,
+    car_id: String,
+    billing_id: String,
+    driver_ids: Vec<String>,
+    start_time: Option<DateTime<Local>>,
+    end_time: Option<DateTime<Local>>,
+    start_odometer_km: Option<i32>,
+    end_odometer_km: Option<i32>,
+    billing_name: Option<String>,
+    total_cost: Option<Decimal>,
+}
+
+impl From<Dispatch> for DispatchEntityDTO {
+    fn from(dispatch: Dispatch) -> Self {
+        DispatchEntityDTO {
+            dispatch_id: dispatch.id.to_string(),
+            car_id: dispatch.team_car_id.to_string(),
+            billing_id: dispatch.billing_id.to_string(),
+            driver_ids: dispatch.driver_ids,
+            start_time: dispatch.start_time,
+            end_time: dispatch.end_time,
+            start_odometer_km: dispatch.start_odometer_km,
+            end_odometer_km: dispatch.end_odometer_km,
+            billing_name: None,
+            total_cost: None,
+        }
+    }
+}
+
+impl From<(Dispatch, Billing)> for DispatchEntityDTO {
+    fn from((dispatch, billing): (Dispatch, Billing)) -> Self {
+        let summary = billing.summary();
+        DispatchEntityDTO {
+            billing_name: Some(billing.name),
+            total_cost: Some(summary.total_cost),
+            ..dispatch.into()
+        }
+    }
+}
+
+#[derive(ApiResponse)]
+enum DispatchResponse {
+    #[oai(status = 200)]
+    Ok(Json<DispatchEntityDTO>),
+
+    #[oai(status = 201)]
+    Created(Json<DispatchEntityDTO>),
+}
+
+#[derive(ApiResponse)]
+enum QueryDispatchResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<DispatchEntityDTO>>),
+}
+
+pub struct DispatchRouter;
+
+#[OpenApi]
+impl DispatchRouter {
+    #[oai(
+        path = "/team/:team_id/dispatch",
+        method = "post",
+        tag = "ApiTags::Dispatch"
+    )]
+    async fn start_dispatch(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        dispatch: Json<DispatchCreateDTO>,
+    ) -> ApiResult<DispatchResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let car_uuid = parse_uuid("car_id", &dispatch.car_id)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let template_uuid = dispatch
+            .template_id
+            .as_deref()
+            .map(|template_id| parse_uuid("template_id", template_id))
+            .transpose()?;
+        let dispatch = dispatch.0;
+        let billing_name = dispatch
+            .billing_name
+            .unwrap_or(Local::now().format("%Y-%m-%d").to_string());
+        let dispatch = Dispatch::start(
+            &state.db,
+            team_uuid,
+            car_uuid,
+            dispatch.driver_ids,
+            billing_name,
+            template_uuid,
+            dispatch.start_odometer_km,
+        )
+        .await?;
+        Ok(DispatchResponse::Created(Json(dispatch.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/dispatch",
+        method = "put",
+        tag = "ApiTags::Dispatch"
+    )]
+    async fn end_dispatch(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        dispatch: Json<DispatchEndDTO>,
+    ) -> ApiResult<DispatchResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let dispatch_uuid = parse_uuid("dispatch_id", &dispatch.dispatch_id)?;
+        let end_odometer_km = dispatch.end_odometer_km;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let dispatch = Dispatch::from_id(&state.db, team_uuid, dispatch_uuid).await?;
+        let dispatch = dispatch.finish(&state.db, end_odometer_km).await?;
+        Ok(DispatchResponse::Ok(Json(dispatch.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/dispatch",
+        method = "get",
+        tag = "ApiTags::Dispatch"
+    )]
+    async fn query_dispatch(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        active: Query<Option<bool>>,
+    ) -> ApiResult<QueryDispatchResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Member).await?;
+        let dispatches = Dispatch::query(&state.db, team_uuid, active.0).await?;
+        Ok(QueryDispatchResponse::Ok(Json(
+            dispatches
+                .into_iter()
+                .map(|dispatch| dispatch.into())
+                .collect(),
+        )))
+    }
+}
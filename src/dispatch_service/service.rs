@@ -0,0 +1,318 @@
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Local};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, ModelTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use tracing::{instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    billing_service::service::{
+        parse_navie_time_to_data_time, Billing, BillingItemService, Team, TeamBillingService,
+    },
+    entities::{dispatch, dispatch_driver, team_car, team_driver},
+    error::{AppError, ErrorCode},
+};
+
+fn dispatch_not_found(dispatch_id: Uuid) -> AppError {
+    AppError::new(
+        ErrorCode::DispatchNotFound,
+        format!("can not find dispatch {}", dispatch_id),
+    )
+}
+
+fn dispatch_ended() -> AppError {
+    AppError::new(ErrorCode::DispatchEnded, "dispatch is already ended")
+}
+
+#[derive(Debug)]
+pub struct Dispatch {
+    pub id: Uuid,
+    pub team_id: Uuid,
+    pub team_car_id: Uuid,
+    pub billing_id: Uuid,
+    pub driver_ids: Vec<String>,
+    pub start_time: Option<DateTime<Local>>,
+    pub end_time: Option<DateTime<Local>>,
+    pub start_odometer_km: Option<i32>,
+    pub end_odometer_km: Option<i32>,
+}
+
+impl From<(dispatch::Model, Vec<dispatch_driver::Model>)> for Dispatch {
+    fn from(
+        (dispatch_model, dispatch_driver_models): (dispatch::Model, Vec<dispatch_driver::Model>),
+    ) -> Self {
+        Dispatch {
+            id: dispatch_model.id,
+            team_id: dispatch_model.team_id,
+            team_car_id: dispatch_model.team_car_id,
+            billing_id: dispatch_model.billing_id,
+            driver_ids: dispatch_driver_models
+                .into_iter()
+                .map(|dispatch_driver_model| dispatch_driver_model.user_id)
+                .collect(),
+            start_time: parse_navie_time_to_data_time(Some(dispatch_model.start_time)),
+            end_time: parse_navie_time_to_data_time(dispatch_model.end_time),
+            start_odometer_km: dispatch_model.start_odometer_km,
+            end_odometer_km: dispatch_model.end_odometer_km,
+        }
+    }
+}
+
+impl Dispatch {
+    /// Send a car out with its drivers and open the billing of this trip. The odometer reading
+    /// defaults to the last one known for the car.
+    #[instrument(skip(db))]
+    pub async fn start<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        team_id: Uuid,
+        team_car_id: Uuid,
+        driver_ids: Vec<String>,
+        billing_name: String,
+        template_id: Option<Uuid>,
+        start_odometer_km: Option<i32>,
+    ) -> Result<(Dispatch, Billing), AppError> {
+        if start_odometer_km.is_some_and(|km| km < 0) {
+            return Err(AppError::new(
+                ErrorCode::InvalidArgument,
+                "odometer reading can not be negative",
+            ));
+        }
+        let driver_ids: Vec<String> = driver_ids
+            .into_iter()
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect();
+        if driver_ids.is_empty() {
+            return Err(AppError::new(
+                ErrorCode::EmptyDriverList,
+                "dispatch needs at least one driver",
+            ));
+        }
+        let txn = db.begin().await?;
+        let team = Team::get_by_id(&txn, team_id).await?;
+
+        // Locking the drivers' rows makes a concurrent start for any of them wait for this
+        // transaction, so it sees the dispatch written here in the check below. The rows are
+        // taken in id order, two starts sharing drivers can not deadlock each other.
+        let team_driver_models = team_driver::Entity::find()
+            .filter(team_driver::Column::TeamId.eq(team_id))
+            .filter(team_driver::Column::UserId.is_in(driver_ids.clone()))
+            .order_by_asc(team_driver::Column::Id)
+            .lock_exclusive()
+            .all(&txn)
+            .await?;
+        if let Some(user_id) = driver_ids.iter().find(|user_id| {
+            !team_driver_models
+                .iter()
+                .any(|team_driver_model| &team_driver_model.user_id == *user_id)
+        }) {
+            return Err(AppError::new(
+                ErrorCode::DriverNotInTeam,
+                format!("driver {} is not in team", user_id),
+            ));
+        }
+        let dispatched_driver = dispatch_driver::Entity::find()
+            .inner_join(dispatch::Entity)
+            .filter(dispatch::Column::EndTime.is_null())
+            .filter(dispatch_driver::Column::UserId.is_in(driver_ids.clone()))
+            .one(&txn)
+            .await?;
+        if let Some(dispatch_driver_model) = dispatched_driver {
+            return Err(AppError::new(
+                ErrorCode::DriverDispatched,
+                format!(
+                    "driver {} is already on an active dispatch",
+                    dispatch_driver_model.user_id
+                ),
+            ));
+        }
+
+        // The billing and the dispatch are written in one transaction, a failed insert leaves
+        // no open billing behind.
+        let billing = team
+            .create_billing(&txn, billing_name, team_car_id, template_id)
+            .await?;
+        let start_odometer_km = match start_odometer_km {
+            Some(km) => Some(km),
+            None => team_car::Entity::find_by_id(team_car_id)
+                .one(&txn)
+                .await?
+                .and_then(|team_car_model| team_car_model.odometer_km),
+        };
+        let dispatch = Self::insert(
+            &txn,
+            team_id,
+            team_car_id,
+            billing.id,
+            driver_ids,
+            start_odometer_km,
+        )
+        .await?;
+        txn.commit().await?;
+        Ok((dispatch, billing))
+    }
+
+    async fn insert<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        team_car_id: Uuid,
+        billing_id: Uuid,
+        driver_ids: Vec<String>,
+        start_odometer_km: Option<i32>,
+    ) -> Result<Dispatch, AppError> {
+        let dispatch_model = dispatch::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            team_id: Set(team_id),
+            team_car_id: Set(team_car_id),
+            billing_id: Set(billing_id),
+            start_time: Set(Local::now().naive_local()),
+            end_time: Set(None),
+            start_odometer_km: Set(start_odometer_km),
+            end_odometer_km: Set(None),
+        }
+        .insert(db)
+        .await?;
+        let mut dispatch_driver_models = vec![];
+        for user_id in driver_ids {
+            let dispatch_driver_model = dispatch_driver::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                dispatch_id: Set(dispatch_model.id),
+                user_id: Set(user_id),
+            }
+            .insert(db)
+            .await?;
+            dispatch_driver_models.push(dispatch_driver_model);
+        }
+        Ok((dispatch_model, dispatch_driver_models).into())
+    }
+
+    #[instrument(skip(db))]
+    pub async fn from_id<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        dispatch_id: Uuid,
+    ) -> Result<Dispatch, AppError> {
+        let dispatch_model = dispatch::Entity::find_by_id(dispatch_id)
+            .filter(dispatch::Column::TeamId.eq(team_id))
+            .one(db)
+            .await?
+            .ok_or_else(|| dispatch_not_found(dispatch_id))?;
+        let dispatch_driver_models = dispatch_model
+            .find_related(dispatch_driver::Entity)
+            .all(db)
+            .await?;
+        Ok((dispatch_model, dispatch_driver_models).into())
+    }
+
+    /// The dispatch still out with the billing, if any.
+    #[instrument(skip(db))]
+    pub async fn active_for_billing<C: ConnectionTrait>(
+        db: &C,
+        billing_id: Uuid,
+    ) -> Result<Option<Dispatch>, AppError> {
+        let dispatch_model = dispatch::Entity::find()
+            .filter(dispatch::Column::BillingId.eq(billing_id))
+            .filter(dispatch::Column::EndTime.is_null())
+            .find_with_related(dispatch_driver::Entity)
+            .all(db)
+            .await?
+            .into_iter()
+            .next();
+        Ok(dispatch_model.map(|dispatch_model| dispatch_model.into()))
+    }
+
+    #[instrument(skip(db))]
+    pub async fn query<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        active: Option<bool>,
+    ) -> Result<Vec<Dispatch>, AppError> {
+        let mut select = dispatch::Entity::find().filter(dispatch::Column::TeamId.eq(team_id));
+        match active {
+            Some(true) => select = select.filter(dispatch::Column::EndTime.is_null()),
+            Some(false) => select = select.filter(dispatch::Column::EndTime.is_not_null()),
+            None => {}
+        }
+        let dispatch_models = select
+            .order_by_desc(dispatch::Column::StartTime)
+            .find_with_related(dispatch_driver::Entity)
+            .all(db)
+            .await?;
+        Ok(dispatch_models
+            .into_iter()
+            .map(|dispatch_model| dispatch_model.into())
+            .collect())
+    }
+
+    /// Take the car back: close the trip billing and end the dispatch. The odometer reading
+    /// on return also becomes the car's odometer.
+    #[instrument(skip(db))]
+    pub async fn finish<C: ConnectionTrait + TransactionTrait>(
+        self,
+        db: &C,
+        end_odometer_km: Option<i32>,
+    ) -> Result<(Dispatch, Billing), AppError> {
+        if self.end_time.is_some() {
+            return Err(dispatch_ended());
+        }
+        if let Some(end_odometer_km) = end_odometer_km {
+            if end_odometer_km < self.start_odometer_km.unwrap_or(0) {
+                return Err(AppError::new(
+                    ErrorCode::InvalidArgument,
+                    format!(
+                        "odometer reading {} is below the reading at departure",
+                        end_odometer_km
+                    ),
+                ));
+            }
+        }
+        let txn = db.begin().await?;
+        let team = Team::get_by_id(&txn, self.team_id).await?;
+        let billing = team.get_billing(&txn, self.billing_id).await?;
+        let billing = if billing.end_time.is_some() {
+            warn!("billing {} was ended before dispatch returned", billing.id);
+            team.get_billing_detail(&txn, self.billing_id).await?
+        } else {
+            billing.end_billing(&txn).await?
+        };
+
+        let dispatch_model = dispatch::Entity::find_by_id(self.id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or_else(|| dispatch_not_found(self.id))?;
+        if dispatch_model.end_time.is_some() {
+            return Err(dispatch_ended());
+        }
+        let mut dispatch_active_model: dispatch::ActiveModel = dispatch_model.into();
+        dispatch_active_model.end_time = Set(Some(Local::now().naive_local()));
+        dispatch_active_model.end_odometer_km = Set(end_odometer_km);
+        let dispatch_model = dispatch_active_model.update(&txn).await?;
+        if let Some(end_odometer_km) = end_odometer_km {
+            let team_car_model = team_car::Entity::find_by_id(self.team_car_id)
+                .one(&txn)
+                .await?
+                .filter(|team_car_model| {
+                    team_car_model
+                        .odometer_km
+                        .is_none_or(|odometer_km| odometer_km < end_odometer_km)
+                });
+            if let Some(team_car_model) = team_car_model {
+                let mut team_car_active_model: team_car::ActiveModel = team_car_model.into();
+                team_car_active_model.odometer_km = Set(Some(end_odometer_km));
+                team_car_active_model.update(&txn).await?;
+            }
+        }
+        txn.commit().await?;
+
+        let dispatch = Dispatch {
+            end_time: parse_navie_time_to_data_time(dispatch_model.end_time),
+            end_odometer_km: dispatch_model.end_odometer_km,
+            ..self
+        };
+        Ok((dispatch, billing))
+    }
+}
@@ -11,6 +11,10 @@ pub struct Model {
     pub team_id: Option<Uuid>,
     pub start_time: Option<DateTime>,
     pub end_time: Option<DateTime>,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))", nullable)]
+    pub total_cost: Option<Decimal>,
+    pub team_car_id: Option<Uuid>,
+    pub template_id: Option<Uuid>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -23,8 +27,34 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     Team,
+    #[sea_orm(
+        belongs_to = "super::team_car::Entity",
+        from = "Column::TeamCarId",
+        to = "super::team_car::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    TeamCar,
     #[sea_orm(has_many = "super::billing_item::Entity")]
     BillingItem,
+    #[sea_orm(has_many = "super::dispatch::Entity")]
+    Dispatch,
+    #[sea_orm(
+        belongs_to = "super::billing_template::Entity",
+        from = "Column::TemplateId",
+        to = "super::billing_template::Column::Id",
+        on_update = "NoAction",
+        on_delete = "SetNull"
+    )]
+    BillingTemplate,
+    #[sea_orm(has_many = "super::billing_expected_item::Entity")]
+    BillingExpectedItem,
+    #[sea_orm(has_many = "super::billing_income::Entity")]
+    BillingIncome,
+    #[sea_orm(has_many = "super::driver_advance::Entity")]
+    DriverAdvance,
+    #[sea_orm(has_many = "super::billing_settlement::Entity")]
+    BillingSettlement,
 }
 
 impl Related<super::team::Entity> for Entity {
@@ -33,10 +63,52 @@ impl Related<super::team::Entity> for Entity {
     }
 }
 
+impl Related<super::team_car::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TeamCar.def()
+    }
+}
+
 impl Related<super::billing_item::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::BillingItem.def()
     }
 }
 
+impl Related<super::dispatch::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Dispatch.def()
+    }
+}
+
+impl Related<super::billing_template::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BillingTemplate.def()
+    }
+}
+
+impl Related<super::billing_expected_item::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BillingExpectedItem.def()
+    }
+}
+
+impl Related<super::billing_income::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BillingIncome.def()
+    }
+}
+
+impl Related<super::driver_advance::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::DriverAdvance.def()
+    }
+}
+
+impl Related<super::billing_settlement::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BillingSettlement.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
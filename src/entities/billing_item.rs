@@ -1,5 +1,6 @@
 //! SeaORM Entity. Generated by sea-orm-codegen 0.9.2
 
+use super::sea_orm_active_enums::ExpensePayer;
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
@@ -8,9 +9,12 @@ pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: Uuid,
     pub billing_id: Option<Uuid>,
+    #[sea_orm(column_type = "Decimal(Some((12, 2)))")]
     pub cost: Decimal,
     pub item_id: Option<Uuid>,
     pub time: DateTime,
+    pub user_id: Option<String>,
+    pub paid_by: ExpensePayer,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -31,6 +35,16 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     Item,
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    User,
+    #[sea_orm(has_one = "super::fuel_entry::Entity")]
+    FuelEntry,
 }
 
 impl Related<super::billing::Entity> for Entity {
@@ -45,4 +59,16 @@ impl Related<super::item::Entity> for Entity {
     }
 }
 
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl Related<super::fuel_entry::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FuelEntry.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -1,5 +1,6 @@
 //! SeaORM Entity. Generated by sea-orm-codegen 0.9.2
 
+use super::sea_orm_active_enums::ItemCategory;
 use super::sea_orm_active_enums::ItemType;
 use sea_orm::entity::prelude::*;
 
@@ -13,6 +14,8 @@ pub struct Model {
     pub team_id: Option<Uuid>,
     #[sea_orm(column_type = "Text", nullable)]
     pub icon_url: Option<String>,
+    pub archived: bool,
+    pub category: ItemCategory,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -27,6 +30,10 @@ pub enum Relation {
     Team,
     #[sea_orm(has_many = "super::billing_item::Entity")]
     BillingItem,
+    #[sea_orm(has_many = "super::billing_template_item::Entity")]
+    BillingTemplateItem,
+    #[sea_orm(has_many = "super::billing_expected_item::Entity")]
+    BillingExpectedItem,
 }
 
 impl Related<super::team::Entity> for Entity {
@@ -41,4 +48,16 @@ impl Related<super::billing_item::Entity> for Entity {
     }
 }
 
+impl Related<super::billing_template_item::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BillingTemplateItem.def()
+    }
+}
+
+impl Related<super::billing_expected_item::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BillingExpectedItem.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -3,11 +3,30 @@
 pub mod prelude;
 
 pub mod billing;
+pub mod billing_expected_item;
+pub mod billing_income;
 pub mod billing_item;
+pub mod billing_settlement;
+pub mod billing_template;
+pub mod billing_template_item;
+pub mod customer;
+pub mod customer_payment;
+pub mod dispatch;
+pub mod dispatch_driver;
+pub mod driver_advance;
+pub mod fuel_entry;
 pub mod item;
+pub mod payment_allocation;
+pub mod payroll_run;
+pub mod payslip_line;
+pub mod reminder;
 pub mod role;
 pub mod sea_orm_active_enums;
 pub mod team;
 pub mod team_car;
 pub mod team_driver;
+pub mod team_invite;
 pub mod user;
+pub mod user_session;
+pub mod wage_rule;
+pub mod wage_rule_deduction;
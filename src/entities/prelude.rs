@@ -1,10 +1,29 @@
 //! SeaORM Entity. Generated by sea-orm-codegen 0.9.2
 
 pub use super::billing::Entity as Billing;
+pub use super::billing_expected_item::Entity as BillingExpectedItem;
+pub use super::billing_income::Entity as BillingIncome;
 pub use super::billing_item::Entity as BillingItem;
+pub use super::billing_settlement::Entity as BillingSettlement;
+pub use super::billing_template::Entity as BillingTemplate;
+pub use super::billing_template_item::Entity as BillingTemplateItem;
+pub use super::customer::Entity as Customer;
+pub use super::customer_payment::Entity as CustomerPayment;
+pub use super::dispatch::Entity as Dispatch;
+pub use super::dispatch_driver::Entity as DispatchDriver;
+pub use super::driver_advance::Entity as DriverAdvance;
+pub use super::fuel_entry::Entity as FuelEntry;
 pub use super::item::Entity as Item;
+pub use super::payment_allocation::Entity as PaymentAllocation;
+pub use super::payroll_run::Entity as PayrollRun;
+pub use super::payslip_line::Entity as PayslipLine;
+pub use super::reminder::Entity as Reminder;
 pub use super::role::Entity as Role;
 pub use super::team::Entity as Team;
 pub use super::team_car::Entity as TeamCar;
 pub use super::team_driver::Entity as TeamDriver;
+pub use super::team_invite::Entity as TeamInvite;
 pub use super::user::Entity as User;
+pub use super::user_session::Entity as UserSession;
+pub use super::wage_rule::Entity as WageRule;
+pub use super::wage_rule_deduction::Entity as WageRuleDeduction;
@@ -2,6 +2,46 @@
 
 use sea_orm::entity::prelude::*;
 
+#[derive(Debug, Clone, PartialEq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "expense_payer")]
+pub enum ExpensePayer {
+    #[sea_orm(string_value = "COMPANY")]
+    Company,
+    #[sea_orm(string_value = "DRIVER")]
+    Driver,
+}
+#[derive(Debug, Clone, PartialEq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "fuel_type")]
+pub enum FuelType {
+    #[sea_orm(string_value = "CNG")]
+    Cng,
+    #[sea_orm(string_value = "DIESEL")]
+    Diesel,
+    #[sea_orm(string_value = "ELECTRIC")]
+    Electric,
+    #[sea_orm(string_value = "GASOLINE")]
+    Gasoline,
+    #[sea_orm(string_value = "HYBRID")]
+    Hybrid,
+    #[sea_orm(string_value = "LNG")]
+    Lng,
+}
+#[derive(Debug, Clone, PartialEq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "item_category")]
+pub enum ItemCategory {
+    #[sea_orm(string_value = "FUEL")]
+    Fuel,
+    #[sea_orm(string_value = "LODGING")]
+    Lodging,
+    #[sea_orm(string_value = "MEAL")]
+    Meal,
+    #[sea_orm(string_value = "OTHER")]
+    Other,
+    #[sea_orm(string_value = "REPAIR")]
+    Repair,
+    #[sea_orm(string_value = "TOLL")]
+    Toll,
+}
 #[derive(Debug, Clone, PartialEq, EnumIter, DeriveActiveEnum)]
 #[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "item_type")]
 pub enum ItemType {
@@ -13,6 +53,16 @@ pub enum ItemType {
     Default,
 }
 #[derive(Debug, Clone, PartialEq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "reminder_kind")]
+pub enum ReminderKind {
+    #[sea_orm(string_value = "DRIVER_LICENCE")]
+    DriverLicence,
+    #[sea_orm(string_value = "INSPECTION")]
+    Inspection,
+    #[sea_orm(string_value = "INSURANCE")]
+    Insurance,
+}
+#[derive(Debug, Clone, PartialEq, EnumIter, DeriveActiveEnum)]
 #[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "role_type")]
 pub enum RoleType {
     #[sea_orm(string_value = "ADMIN")]
@@ -22,3 +72,13 @@ pub enum RoleType {
     #[sea_orm(string_value = "OWNER")]
     Owner,
 }
+#[derive(Debug, Clone, PartialEq, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "wage_rule_type")]
+pub enum WageRuleType {
+    #[sea_orm(string_value = "COMMISSION")]
+    Commission,
+    #[sea_orm(string_value = "PER_KM")]
+    PerKm,
+    #[sea_orm(string_value = "PER_TRIP")]
+    PerTrip,
+}
@@ -29,6 +29,18 @@ pub enum Relation {
     TeamDriver,
     #[sea_orm(has_many = "super::team_car::Entity")]
     TeamCar,
+    #[sea_orm(has_many = "super::dispatch::Entity")]
+    Dispatch,
+    #[sea_orm(has_many = "super::billing_template::Entity")]
+    BillingTemplate,
+    #[sea_orm(has_many = "super::team_invite::Entity")]
+    TeamInvite,
+    #[sea_orm(has_many = "super::reminder::Entity")]
+    Reminder,
+    #[sea_orm(has_many = "super::customer::Entity")]
+    Customer,
+    #[sea_orm(has_many = "super::payroll_run::Entity")]
+    PayrollRun,
 }
 
 impl Related<super::user::Entity> for Entity {
@@ -61,4 +73,40 @@ impl Related<super::team_car::Entity> for Entity {
     }
 }
 
+impl Related<super::dispatch::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Dispatch.def()
+    }
+}
+
+impl Related<super::billing_template::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BillingTemplate.def()
+    }
+}
+
+impl Related<super::team_invite::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TeamInvite.def()
+    }
+}
+
+impl Related<super::reminder::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Reminder.def()
+    }
+}
+
+impl Related<super::customer::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Customer.def()
+    }
+}
+
+impl Related<super::payroll_run::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PayrollRun.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -1,5 +1,6 @@
 //! SeaORM Entity. Generated by sea-orm-codegen 0.9.2
 
+use super::sea_orm_active_enums::FuelType;
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
@@ -9,6 +10,16 @@ pub struct Model {
     pub id: Uuid,
     pub team_id: Uuid,
     pub car_plate_number: String,
+    pub vin: Option<String>,
+    pub vehicle_model: Option<String>,
+    pub axle_count: Option<i16>,
+    #[sea_orm(column_type = "Decimal(Some((8, 2)))", nullable)]
+    pub load_capacity: Option<Decimal>,
+    pub fuel_type: Option<FuelType>,
+    pub purchase_date: Option<Date>,
+    pub insurance_expire_date: Option<Date>,
+    pub inspection_expire_date: Option<Date>,
+    pub odometer_km: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -21,6 +32,14 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     Team,
+    #[sea_orm(has_many = "super::billing::Entity")]
+    Billing,
+    #[sea_orm(has_many = "super::dispatch::Entity")]
+    Dispatch,
+    #[sea_orm(has_many = "super::fuel_entry::Entity")]
+    FuelEntry,
+    #[sea_orm(has_many = "super::reminder::Entity")]
+    Reminder,
 }
 
 impl Related<super::team::Entity> for Entity {
@@ -29,4 +48,28 @@ impl Related<super::team::Entity> for Entity {
     }
 }
 
+impl Related<super::billing::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Billing.def()
+    }
+}
+
+impl Related<super::dispatch::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Dispatch.def()
+    }
+}
+
+impl Related<super::fuel_entry::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::FuelEntry.def()
+    }
+}
+
+impl Related<super::reminder::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Reminder.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
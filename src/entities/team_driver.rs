@@ -9,6 +9,7 @@ pub struct Model {
     pub id: Uuid,
     pub user_id: String,
     pub team_id: Uuid,
+    pub licence_expire_date: Option<Date>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -29,6 +30,8 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     User,
+    #[sea_orm(has_one = "super::wage_rule::Entity")]
+    WageRule,
 }
 
 impl Related<super::team::Entity> for Entity {
@@ -43,4 +46,10 @@ impl Related<super::user::Entity> for Entity {
     }
 }
 
+impl Related<super::wage_rule::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WageRule.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
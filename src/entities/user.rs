@@ -20,6 +20,10 @@ pub enum Relation {
     Role,
     #[sea_orm(has_many = "super::team_driver::Entity")]
     TeamDriver,
+    #[sea_orm(has_many = "super::dispatch_driver::Entity")]
+    DispatchDriver,
+    #[sea_orm(has_many = "super::billing_item::Entity")]
+    BillingItem,
 }
 
 impl Related<super::team::Entity> for Entity {
@@ -40,4 +44,16 @@ impl Related<super::team_driver::Entity> for Entity {
     }
 }
 
+impl Related<super::dispatch_driver::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::DispatchDriver.def()
+    }
+}
+
+impl Related<super::billing_item::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BillingItem.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
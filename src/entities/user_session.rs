@@ -3,23 +3,21 @@
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
-#[sea_orm(table_name = "car")]
+#[sea_orm(table_name = "user_session")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: Uuid,
-    pub car_plate_number: String,
+    pub user_id: String,
+    #[sea_orm(unique)]
+    pub access_token: String,
+    #[sea_orm(unique)]
+    pub refresh_token: String,
+    pub access_expire_time: DateTime,
+    pub refresh_expire_time: DateTime,
+    pub create_time: DateTime,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {
-    #[sea_orm(has_many = "super::team_car::Entity")]
-    TeamCar,
-}
-
-impl Related<super::team_car::Entity> for Entity {
-    fn to() -> RelationDef {
-        Relation::TeamCar.def()
-    }
-}
+pub enum Relation {}
 
 impl ActiveModelBehavior for ActiveModel {}
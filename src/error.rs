@@ -0,0 +1,236 @@
+use std::error::Error;
+
+use poem_openapi::{payload::Json, ApiResponse, Enum, Object};
+use sea_orm::DbErr;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+/// Stable machine readable error codes. They are part of the API contract, clients match
+/// on them instead of on messages.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Enum)]
+#[oai(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    InvalidUuid,
+    InvalidArgument,
+    InvalidCost,
+    EmptyDriverList,
+    DriverNotInTeam,
+    DuplicateItem,
+    WxCodeInvalid,
+    InvalidPlate,
+    Unauthorized,
+    TokenExpired,
+    Forbidden,
+    DriverNotDispatched,
+    WxUserBlocked,
+    UserNotFound,
+    TeamNotFound,
+    CarNotFound,
+    BillingNotFound,
+    BillingItemNotFound,
+    ItemNotFound,
+    TemplateNotFound,
+    DispatchNotFound,
+    InviteNotFound,
+    IncomeNotFound,
+    CustomerNotFound,
+    PaymentNotFound,
+    AdvanceNotFound,
+    SettlementNotFound,
+    WageRuleNotFound,
+    PayrollNotFound,
+    UniqueViolation,
+    CarBillingOpen,
+    BillingEnded,
+    BillingDispatched,
+    DriverDispatched,
+    DispatchEnded,
+    DuplicateName,
+    InviteExpired,
+    InviteRevoked,
+    InviteUsedUp,
+    AlreadyInTeam,
+    DuplicatePlate,
+    CarInUse,
+    CustomerInUse,
+    IncomeAllocated,
+    PayrollLocked,
+    WxLoginError,
+    WxNotifyError,
+    InternalError,
+    DatabaseError,
+}
+
+impl ErrorCode {
+    pub fn status(&self) -> u16 {
+        match self {
+            ErrorCode::InvalidUuid
+            | ErrorCode::InvalidArgument
+            | ErrorCode::InvalidCost
+            | ErrorCode::EmptyDriverList
+            | ErrorCode::DriverNotInTeam
+            | ErrorCode::DuplicateItem
+            | ErrorCode::WxCodeInvalid
+            | ErrorCode::InvalidPlate => 400,
+            ErrorCode::Unauthorized | ErrorCode::TokenExpired => 401,
+            ErrorCode::Forbidden | ErrorCode::DriverNotDispatched | ErrorCode::WxUserBlocked => 403,
+            ErrorCode::UserNotFound
+            | ErrorCode::TeamNotFound
+            | ErrorCode::CarNotFound
+            | ErrorCode::BillingNotFound
+            | ErrorCode::BillingItemNotFound
+            | ErrorCode::ItemNotFound
+            | ErrorCode::TemplateNotFound
+            | ErrorCode::DispatchNotFound
+            | ErrorCode::InviteNotFound
+            | ErrorCode::IncomeNotFound
+            | ErrorCode::CustomerNotFound
+            | ErrorCode::PaymentNotFound
+            | ErrorCode::AdvanceNotFound
+            | ErrorCode::SettlementNotFound
+            | ErrorCode::WageRuleNotFound
+            | ErrorCode::PayrollNotFound => 404,
+            ErrorCode::UniqueViolation
+            | ErrorCode::CarBillingOpen
+            | ErrorCode::BillingEnded
+            | ErrorCode::BillingDispatched
+            | ErrorCode::DriverDispatched
+            | ErrorCode::DispatchEnded
+            | ErrorCode::DuplicateName
+            | ErrorCode::InviteExpired
+            | ErrorCode::InviteRevoked
+            | ErrorCode::InviteUsedUp
+            | ErrorCode::AlreadyInTeam
+            | ErrorCode::DuplicatePlate
+            | ErrorCode::CarInUse
+            | ErrorCode::CustomerInUse
+            | ErrorCode::IncomeAllocated
+            | ErrorCode::PayrollLocked => 409,
+            ErrorCode::WxLoginError | ErrorCode::WxNotifyError => 502,
+            ErrorCode::InternalError | ErrorCode::DatabaseError => 500,
+        }
+    }
+}
+
+/// The one error type shared by every service.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AppError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl AppError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        AppError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        AppError::new(ErrorCode::Forbidden, message)
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        AppError::new(ErrorCode::Unauthorized, message)
+    }
+}
+
+impl Error for AppError {}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl From<DbErr> for AppError {
+    fn from(db_err: DbErr) -> Self {
+        let message = db_err.to_string();
+        // Postgres reports unique violations as SQLSTATE 23505, sqlx only hands the text on.
+        if message.contains("duplicate key value violates unique constraint") {
+            warn!("unique violation, {}", message);
+            AppError::new(ErrorCode::UniqueViolation, "resource already exists")
+        } else {
+            AppError::new(ErrorCode::DatabaseError, message)
+        }
+    }
+}
+
+/// Parses an id taken from a path or a body, `field` names it in the error.
+pub fn parse_uuid(field: &str, value: &str) -> Result<Uuid, AppError> {
+    Uuid::parse_str(value).map_err(|_| {
+        error!("Error uuid string parse! {} is {}", field, value);
+        AppError::new(
+            ErrorCode::InvalidUuid,
+            format!("{} is not a valid uuid", field),
+        )
+    })
+}
+
+/// Problem body returned with every error response.
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct ProblemDTO {
+    code: ErrorCode,
+    status: u16,
+    message: String,
+}
+
+#[derive(ApiResponse)]
+pub enum ErrorResponse {
+    #[oai(status = 400)]
+    BadRequest(Json<ProblemDTO>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<ProblemDTO>),
+
+    #[oai(status = 403)]
+    Forbidden(Json<ProblemDTO>),
+
+    #[oai(status = 404)]
+    NotFound(Json<ProblemDTO>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ProblemDTO>),
+
+    #[oai(status = 500)]
+    Error(Json<ProblemDTO>),
+
+    #[oai(status = 502)]
+    BadGateway(Json<ProblemDTO>),
+}
+
+impl From<AppError> for ErrorResponse {
+    fn from(err: AppError) -> Self {
+        let status = err.code.status();
+        if status >= 500 {
+            error!("request failed, {}", err);
+        }
+        let problem = Json(ProblemDTO {
+            code: err.code,
+            status,
+            // Database messages can leak schema details, they stay in the log.
+            message: match err.code {
+                ErrorCode::DatabaseError => "database error".to_owned(),
+                _ => err.message,
+            },
+        });
+        match status {
+            400 => ErrorResponse::BadRequest(problem),
+            401 => ErrorResponse::Unauthorized(problem),
+            403 => ErrorResponse::Forbidden(problem),
+            404 => ErrorResponse::NotFound(problem),
+            409 => ErrorResponse::Conflict(problem),
+            502 => ErrorResponse::BadGateway(problem),
+            _ => ErrorResponse::Error(problem),
+        }
+    }
+}
+
+impl From<DbErr> for ErrorResponse {
+    fn from(db_err: DbErr) -> Self {
+        AppError::from(db_err).into()
+    }
+}
+
+pub type ApiResult<T> = Result<T, ErrorResponse>;
@@ -0,0 +1,176 @@
+use chrono::{DateTime, Local, NaiveDate};
+use poem::web::Data;
+use poem_openapi::{
+    param::{Path, Query},
+    payload::Json,
+    ApiResponse, Object, OpenApi, Tags,
+};
+use rust_decimal::Decimal;
+
+use crate::auth_service::service::{TeamGuard, TeamPermission};
+use crate::error::{parse_uuid, ApiResult};
+use crate::session_service::service::SessionUser;
+use crate::state::AppState;
+
+use super::service::{FuelEntry, FuelFill, FuelFillStat, FuelFlag, FuelReport};
+
+#[derive(Tags)]
+enum ApiTags {
+    /// Fill-ups and fuel consumption of trucks
+    Fuel,
+}
+
+/// Fill-up of a billing item, required for `FUEL` items and refused for others.
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct FuelFillDTO {
+    litres: Decimal,
+
+    /// Price per litre shown on the pump.
+    unit_price: Decimal,
+
+    #[oai(validator(max_length = 128))]
+    station: Option<String>,
+
+    /// Odometer reading of the truck at the pump.
+    #[oai(validator(minimum(value = "0")))]
+    odometer_km: i32,
+
+    /// Whether the tank was filled up, true when omitted.
+    full_tank: Option<bool>,
+}
+
+impl From<FuelFillDTO> for FuelFill {
+    fn from(dto: FuelFillDTO) -> Self {
+        FuelFill {
+            litres: dto.litres,
+            unit_price: dto.unit_price,
+            station: dto.station,
+            odometer_km: dto.odometer_km,
+            full_tank: dto.full_tank.unwrap_or(true),
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct FuelEntryDTO {
+    car_id: String,
+    litres: Decimal,
+    unit_price: Decimal,
+    station: Option<String>,
+    odometer_km: i32,
+    full_tank: bool,
+}
+
+impl From<FuelEntry> for FuelEntryDTO {
+    fn from(entry: FuelEntry) -> Self {
+        FuelEntryDTO {
+            car_id: entry.team_car_id.to_string(),
+            litres: entry.litres,
+            unit_price: entry.unit_price,
+            station: entry.station,
+            odometer_km: entry.odometer_km,
+            full_tank: entry.full_tank,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct FuelFillStatDTO {
+    billing_item_id: String,
+    billing_id: Option<String>,
+    time: Option<DateTime<Local>>,
+    cost: Decimal,
+    litres: Decimal,
+    unit_price: Decimal,
+    station: Option<String>,
+    odometer_km: i32,
+    full_tank: bool,
+    /// Kilometres since the previous full tank, only on full tanks.
+    distance_km: Option<i32>,
+    litres_per_100km: Option<Decimal>,
+    cost_per_km: Option<Decimal>,
+    flags: Vec<FuelFlag>,
+}
+
+impl From<FuelFillStat> for FuelFillStatDTO {
+    fn from(fill: FuelFillStat) -> Self {
+        FuelFillStatDTO {
+            billing_item_id: fill.entry.billing_item_id.to_string(),
+            billing_id: fill.billing_id.map(|billing_id| billing_id.to_string()),
+            time: fill.time,
+            cost: fill.cost,
+            litres: fill.entry.litres,
+            unit_price: fill.entry.unit_price,
+            station: fill.entry.station,
+            odometer_km: fill.entry.odometer_km,
+            full_tank: fill.entry.full_tank,
+            distance_km: fill.distance_km,
+            litres_per_100km: fill.litres_per_100km,
+            cost_per_km: fill.cost_per_km,
+            flags: fill.flags,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct FuelReportDTO {
+    car_id: String,
+    car_plate_number: String,
+    fills: Vec<FuelFillStatDTO>,
+    total_litres: Decimal,
+    total_cost: Decimal,
+    /// Kilometres covered by the intervals consumption was measured on.
+    distance_km: i32,
+    litres_per_100km: Option<Decimal>,
+    cost_per_km: Option<Decimal>,
+}
+
+impl From<FuelReport> for FuelReportDTO {
+    fn from(report: FuelReport) -> Self {
+        FuelReportDTO {
+            car_id: report.team_car_id.to_string(),
+            car_plate_number: report.car_plate_number,
+            fills: report.fills.into_iter().map(|fill| fill.into()).collect(),
+            total_litres: report.total_litres,
+            total_cost: report.total_cost,
+            distance_km: report.distance_km,
+            litres_per_100km: report.litres_per_100km,
+            cost_per_km: report.cost_per_km,
+        }
+    }
+}
+
+#[derive(ApiResponse)]
+enum FuelReportResponse {
+    #[oai(status = 200)]
+    Ok(Json<FuelReportDTO>),
+}
+
+pub struct FuelRouter;
+
+#[OpenApi]
+impl FuelRouter {
+    /// Fill-ups of a truck with the consumption between full tanks and flags on suspicious
+    /// fill-ups.
+    #[oai(
+        path = "/team/:team_id/car/:car_id/fuel",
+        method = "get",
+        tag = "ApiTags::Fuel"
+    )]
+    async fn car_fuel_report(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        car_id: Path<String>,
+        start_date: Query<Option<NaiveDate>>,
+        end_date: Query<Option<NaiveDate>>,
+    ) -> ApiResult<FuelReportResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let car_uuid = parse_uuid("car_id", &car_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let report =
+            FuelReport::for_car(&state.db, team_uuid, car_uuid, start_date.0, end_date.0).await?;
+        Ok(FuelReportResponse::Ok(Json(report.into())))
+    }
+}
@@ -0,0 +1,533 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime};
+use poem_openapi::Enum;
+use rust_decimal::Decimal;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, IntoActiveModel, QueryFilter,
+    QueryOrder, Set,
+};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    billing_service::service::parse_navie_time_to_data_time,
+    entities::{billing_item, fuel_entry, team_car},
+    error::{AppError, ErrorCode},
+};
+
+/// A paid amount may differ this much, in percent, from litres times unit price before the
+/// fill-up is flagged. Fuel cards and station discounts make small gaps normal.
+const COST_TOLERANCE_PERCENT: i64 = 5;
+
+/// Consumption further than this, in percent, from the median of the truck is flagged.
+const CONSUMPTION_TOLERANCE_PERCENT: i64 = 25;
+
+/// A median of fewer intervals says too little about a truck to flag consumption.
+const MIN_INTERVALS_FOR_OUTLIERS: usize = 3;
+
+fn invalid_fill(message: impl Into<String>) -> AppError {
+    AppError::new(ErrorCode::InvalidArgument, message)
+}
+
+/// Fill-up details sent with a billing item of a `FUEL` item.
+#[derive(Debug, Clone)]
+pub struct FuelFill {
+    pub litres: Decimal,
+    pub unit_price: Decimal,
+    pub station: Option<String>,
+    pub odometer_km: i32,
+    /// Whether the tank was filled up, consumption is measured from full tank to full tank.
+    pub full_tank: bool,
+}
+
+impl FuelFill {
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.litres <= Decimal::ZERO {
+            return Err(invalid_fill("litres must be positive"));
+        }
+        if self.unit_price <= Decimal::ZERO {
+            return Err(invalid_fill("unit price must be positive"));
+        }
+        if self.odometer_km < 0 {
+            return Err(invalid_fill("odometer reading can not be negative"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FuelEntry {
+    pub billing_item_id: Uuid,
+    pub team_car_id: Uuid,
+    pub litres: Decimal,
+    pub unit_price: Decimal,
+    pub station: Option<String>,
+    pub odometer_km: i32,
+    pub full_tank: bool,
+}
+
+impl From<fuel_entry::Model> for FuelEntry {
+    fn from(model: fuel_entry::Model) -> Self {
+        FuelEntry {
+            billing_item_id: model.billing_item_id,
+            team_car_id: model.team_car_id,
+            litres: model.litres,
+            unit_price: model.unit_price,
+            station: model.station,
+            odometer_km: model.odometer_km,
+            full_tank: model.full_tank,
+        }
+    }
+}
+
+impl FuelEntry {
+    /// Records the fill-up of a billing item. The odometer of the truck only ever moves
+    /// forward, a reading below it is kept on the entry and flagged by the analytics.
+    #[instrument(skip(db))]
+    pub async fn record<C: ConnectionTrait>(
+        db: &C,
+        billing_item_id: Uuid,
+        team_car_id: Uuid,
+        fill: FuelFill,
+    ) -> Result<FuelEntry, AppError> {
+        fill.validate()?;
+        let model = fuel_entry::ActiveModel {
+            billing_item_id: Set(billing_item_id),
+            team_car_id: Set(team_car_id),
+            litres: Set(fill.litres),
+            unit_price: Set(fill.unit_price),
+            station: Set(fill
+                .station
+                .map(|station| station.trim().to_owned())
+                .filter(|station| !station.is_empty())),
+            odometer_km: Set(fill.odometer_km),
+            full_tank: Set(fill.full_tank),
+        }
+        .insert(db)
+        .await?;
+        if let Some(car_model) = team_car::Entity::find_by_id(team_car_id).one(db).await? {
+            if car_model
+                .odometer_km
+                .is_none_or(|odometer_km| odometer_km < model.odometer_km)
+            {
+                let mut active_model = car_model.into_active_model();
+                active_model.odometer_km = Set(Some(model.odometer_km));
+                active_model.update(db).await?;
+            }
+        }
+        Ok(model.into())
+    }
+
+    /// Fill-ups of the given billing items, keyed by billing item.
+    pub async fn of_billing_items<C: ConnectionTrait>(
+        db: &C,
+        billing_item_ids: Vec<Uuid>,
+    ) -> Result<HashMap<Uuid, FuelEntry>, AppError> {
+        if billing_item_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        Ok(fuel_entry::Entity::find()
+            .filter(fuel_entry::Column::BillingItemId.is_in(billing_item_ids))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|model| (model.billing_item_id, model.into()))
+            .collect())
+    }
+}
+
+/// Why a fill-up looks suspicious.
+#[derive(Debug, Enum, Clone, Copy, Eq, PartialEq)]
+#[oai(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FuelFlag {
+    /// The odometer reads less than at the fill-up before.
+    OdometerRollback,
+    /// The truck filled up again without moving.
+    NoDistance,
+    /// Far more litres per 100 km than the truck usually burns.
+    HighConsumption,
+    /// Far fewer litres per 100 km than the truck usually burns, often a missed fill-up.
+    LowConsumption,
+    /// The paid amount does not match litres times unit price.
+    CostMismatch,
+}
+
+/// A fill-up with the consumption since the previous full tank.
+#[derive(Debug, Clone)]
+pub struct FuelFillStat {
+    pub entry: FuelEntry,
+    pub billing_id: Option<Uuid>,
+    pub cost: Decimal,
+    pub time: Option<DateTime<Local>>,
+    /// Kilometres since the previous full tank, only set on full tanks.
+    pub distance_km: Option<i32>,
+    pub litres_per_100km: Option<Decimal>,
+    pub cost_per_km: Option<Decimal>,
+    pub flags: Vec<FuelFlag>,
+}
+
+/// Fill-ups and fuel consumption of one truck.
+#[derive(Debug)]
+pub struct FuelReport {
+    pub team_car_id: Uuid,
+    pub car_plate_number: String,
+    pub fills: Vec<FuelFillStat>,
+    pub total_litres: Decimal,
+    pub total_cost: Decimal,
+    /// Kilometres covered by the measured intervals.
+    pub distance_km: i32,
+    pub litres_per_100km: Option<Decimal>,
+    pub cost_per_km: Option<Decimal>,
+}
+
+impl FuelReport {
+    /// Fill-ups of a truck of the team in time order, optionally limited to the days from
+    /// `start_date` to `end_date`.
+    ///
+    /// Consumption is measured from full tank to full tank: the litres and cost of a full
+    /// tank and of the partial fill-ups before it are spread over the kilometres since the
+    /// previous full tank. The first fill-up of the range only opens an interval, and an
+    /// odometer rollback restarts the measurement.
+    #[instrument(skip(db))]
+    pub async fn for_car<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        team_car_id: Uuid,
+        start_date: Option<NaiveDate>,
+        end_date: Option<NaiveDate>,
+    ) -> Result<FuelReport, AppError> {
+        let car_model = team_car::Entity::find_by_id(team_car_id)
+            .filter(team_car::Column::TeamId.eq(team_id))
+            .one(db)
+            .await?
+            .ok_or_else(|| {
+                AppError::new(
+                    ErrorCode::CarNotFound,
+                    format!("can not find car {}", team_car_id),
+                )
+            })?;
+        let mut select = fuel_entry::Entity::find()
+            .filter(fuel_entry::Column::TeamCarId.eq(car_model.id))
+            .find_also_related(billing_item::Entity);
+        if let Some(start_date) = start_date {
+            select =
+                select.filter(billing_item::Column::Time.gte(start_date.and_time(NaiveTime::MIN)));
+        }
+        if let Some(end_date) = end_date {
+            let next_date = end_date + Duration::days(1);
+            select =
+                select.filter(billing_item::Column::Time.lt(next_date.and_time(NaiveTime::MIN)));
+        }
+        let fills = select
+            .order_by_asc(billing_item::Column::Time)
+            .order_by_asc(fuel_entry::Column::OdometerKm)
+            .all(db)
+            .await?
+            .into_iter()
+            .filter_map(|(fuel_model, billing_item_model)| {
+                billing_item_model.map(|billing_item_model| FuelFillStat {
+                    entry: fuel_model.into(),
+                    billing_id: billing_item_model.billing_id,
+                    cost: billing_item_model.cost,
+                    time: parse_navie_time_to_data_time(Some(billing_item_model.time)),
+                    distance_km: None,
+                    litres_per_100km: None,
+                    cost_per_km: None,
+                    flags: vec![],
+                })
+            })
+            .collect();
+        Ok(Self::analyse(car_model, fills))
+    }
+
+    fn analyse(car_model: team_car::Model, mut fills: Vec<FuelFillStat>) -> FuelReport {
+        let mut previous_odometer: Option<i32> = None;
+        let mut last_full_odometer: Option<i32> = None;
+        let mut open_litres = Decimal::ZERO;
+        let mut open_cost = Decimal::ZERO;
+        let mut measured_litres = Decimal::ZERO;
+        let mut measured_cost = Decimal::ZERO;
+        let mut distance_km = 0;
+        for fill in fills.iter_mut() {
+            let expected_cost = fill.entry.litres * fill.entry.unit_price;
+            if (fill.cost - expected_cost).abs() * Decimal::from(100)
+                > expected_cost * Decimal::from(COST_TOLERANCE_PERCENT)
+            {
+                fill.flags.push(FuelFlag::CostMismatch);
+            }
+
+            let odometer_km = fill.entry.odometer_km;
+            if previous_odometer.is_some_and(|previous| odometer_km < previous) {
+                fill.flags.push(FuelFlag::OdometerRollback);
+                last_full_odometer = None;
+            }
+            previous_odometer = Some(odometer_km);
+            if last_full_odometer.is_none() {
+                open_litres = Decimal::ZERO;
+                open_cost = Decimal::ZERO;
+            }
+            open_litres += fill.entry.litres;
+            open_cost += fill.cost;
+            if !fill.entry.full_tank {
+                continue;
+            }
+
+            if let Some(last_full) = last_full_odometer {
+                let distance = odometer_km - last_full;
+                if distance == 0 {
+                    fill.flags.push(FuelFlag::NoDistance);
+                } else {
+                    let km = Decimal::from(distance);
+                    fill.distance_km = Some(distance);
+                    fill.litres_per_100km =
+                        Some((open_litres * Decimal::from(100) / km).round_dp(2));
+                    fill.cost_per_km = Some((open_cost / km).round_dp(2));
+                    measured_litres += open_litres;
+                    measured_cost += open_cost;
+                    distance_km += distance;
+                }
+            }
+            last_full_odometer = Some(odometer_km);
+            open_litres = Decimal::ZERO;
+            open_cost = Decimal::ZERO;
+        }
+
+        let mut consumptions: Vec<Decimal> = fills
+            .iter()
+            .filter_map(|fill| fill.litres_per_100km)
+            .collect();
+        if consumptions.len() >= MIN_INTERVALS_FOR_OUTLIERS {
+            consumptions.sort();
+            let middle = consumptions.len() / 2;
+            let median = if consumptions.len().is_multiple_of(2) {
+                (consumptions[middle - 1] + consumptions[middle]) / Decimal::from(2)
+            } else {
+                consumptions[middle]
+            };
+            let tolerance =
+                median * Decimal::from(CONSUMPTION_TOLERANCE_PERCENT) / Decimal::from(100);
+            for fill in fills.iter_mut() {
+                match fill.litres_per_100km {
+                    Some(consumption) if consumption > median + tolerance => {
+                        fill.flags.push(FuelFlag::HighConsumption)
+                    }
+                    Some(consumption) if consumption < median - tolerance => {
+                        fill.flags.push(FuelFlag::LowConsumption)
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let km = Decimal::from(distance_km);
+        FuelReport {
+            team_car_id: car_model.id,
+            car_plate_number: car_model.car_plate_number,
+            total_litres: fills.iter().map(|fill| fill.entry.litres).sum(),
+            total_cost: fills.iter().map(|fill| fill.cost).sum(),
+            distance_km,
+            litres_per_100km: (distance_km > 0)
+                .then(|| (measured_litres * Decimal::from(100) / km).round_dp(2)),
+            cost_per_km: (distance_km > 0).then(|| (measured_cost / km).round_dp(2)),
+            fills,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use uuid::Uuid;
+
+    use super::{FuelEntry, FuelFillStat, FuelFlag, FuelReport};
+    use crate::entities::team_car;
+
+    fn car() -> team_car::Model {
+        team_car::Model {
+            id: Uuid::new_v4(),
+            team_id: Uuid::new_v4(),
+            car_plate_number: "京A12345".to_owned(),
+            vin: None,
+            vehicle_model: None,
+            axle_count: None,
+            load_capacity: None,
+            fuel_type: None,
+            purchase_date: None,
+            insurance_expire_date: None,
+            inspection_expire_date: None,
+            odometer_km: None,
+        }
+    }
+
+    /// A fill-up at 8 per litre, paid in full.
+    fn fill(litres: Decimal, odometer_km: i32, full_tank: bool) -> FuelFillStat {
+        paid(litres, odometer_km, full_tank, litres * dec!(8))
+    }
+
+    fn paid(litres: Decimal, odometer_km: i32, full_tank: bool, cost: Decimal) -> FuelFillStat {
+        FuelFillStat {
+            entry: FuelEntry {
+                billing_item_id: Uuid::new_v4(),
+                team_car_id: Uuid::new_v4(),
+                litres,
+                unit_price: dec!(8),
+                station: None,
+                odometer_km,
+                full_tank,
+            },
+            billing_id: None,
+            cost,
+            time: None,
+            distance_km: None,
+            litres_per_100km: None,
+            cost_per_km: None,
+            flags: vec![],
+        }
+    }
+
+    fn analyse(fills: Vec<FuelFillStat>) -> FuelReport {
+        FuelReport::analyse(car(), fills)
+    }
+
+    #[test]
+    fn measures_from_full_tank_to_full_tank() {
+        let report = analyse(vec![
+            fill(dec!(120), 10_000, true),
+            fill(dec!(60), 10_500, false),
+            fill(dec!(40), 11_000, true),
+        ]);
+        let fills = &report.fills;
+        assert_eq!(fills[0].distance_km, None);
+        assert_eq!(fills[0].litres_per_100km, None);
+        assert_eq!(fills[1].distance_km, None);
+        // The partial fill-up counts towards the full tank that closes its interval.
+        assert_eq!(fills[2].distance_km, Some(1_000));
+        assert_eq!(fills[2].litres_per_100km, Some(dec!(10)));
+        assert_eq!(fills[2].cost_per_km, Some(dec!(0.8)));
+        assert!(fills.iter().all(|fill| fill.flags.is_empty()));
+
+        assert_eq!(report.total_litres, dec!(220));
+        assert_eq!(report.total_cost, dec!(1760));
+        assert_eq!(report.distance_km, 1_000);
+        assert_eq!(report.litres_per_100km, Some(dec!(10)));
+        assert_eq!(report.cost_per_km, Some(dec!(0.8)));
+    }
+
+    #[test]
+    fn partial_fills_before_the_first_full_tank_are_not_measured() {
+        let report = analyse(vec![
+            fill(dec!(30), 9_000, false),
+            fill(dec!(50), 9_500, true),
+            fill(dec!(40), 10_000, true),
+        ]);
+        assert_eq!(report.fills[1].litres_per_100km, None);
+        assert_eq!(report.fills[2].litres_per_100km, Some(dec!(8)));
+        assert_eq!(report.distance_km, 500);
+        assert_eq!(report.litres_per_100km, Some(dec!(8)));
+        assert_eq!(report.total_litres, dec!(120));
+    }
+
+    #[test]
+    fn odometer_rollback_restarts_the_measurement() {
+        let report = analyse(vec![
+            fill(dec!(100), 10_000, true),
+            fill(dec!(30), 10_400, false),
+            fill(dec!(80), 9_000, true),
+            fill(dec!(50), 9_500, true),
+        ]);
+        let fills = &report.fills;
+        assert_eq!(fills[2].flags, vec![FuelFlag::OdometerRollback]);
+        assert_eq!(fills[2].litres_per_100km, None);
+        // The partial fill-up before the rollback is dropped with its interval.
+        assert_eq!(fills[3].distance_km, Some(500));
+        assert_eq!(fills[3].litres_per_100km, Some(dec!(10)));
+        assert!(fills[3].flags.is_empty());
+        assert_eq!(report.distance_km, 500);
+        assert_eq!(report.litres_per_100km, Some(dec!(10)));
+    }
+
+    #[test]
+    fn flags_full_tanks_without_distance() {
+        let report = analyse(vec![
+            fill(dec!(100), 10_000, true),
+            fill(dec!(5), 10_000, true),
+            fill(dec!(45), 10_500, true),
+        ]);
+        let fills = &report.fills;
+        assert_eq!(fills[1].flags, vec![FuelFlag::NoDistance]);
+        assert_eq!(fills[1].litres_per_100km, None);
+        assert_eq!(fills[2].distance_km, Some(500));
+        assert_eq!(fills[2].litres_per_100km, Some(dec!(9)));
+    }
+
+    #[test]
+    fn flags_cost_mismatch_beyond_tolerance() {
+        let report = analyse(vec![
+            paid(dec!(100), 10_000, true, dec!(840)),
+            paid(dec!(100), 11_000, true, dec!(841)),
+            paid(dec!(100), 12_000, true, dec!(750)),
+        ]);
+        let flags: Vec<&Vec<FuelFlag>> = report.fills.iter().map(|fill| &fill.flags).collect();
+        assert_eq!(
+            flags,
+            vec![
+                &vec![],
+                &vec![FuelFlag::CostMismatch],
+                &vec![FuelFlag::CostMismatch]
+            ]
+        );
+    }
+
+    #[test]
+    fn flags_consumption_far_from_the_median() {
+        let report = analyse(vec![
+            fill(dec!(100), 10_000, true),
+            fill(dec!(100), 11_000, true),
+            fill(dec!(100), 12_000, true),
+            fill(dec!(100), 13_000, true),
+            fill(dec!(200), 14_000, true),
+            fill(dec!(50), 15_000, true),
+        ]);
+        let flags: Vec<Vec<FuelFlag>> =
+            report.fills.iter().map(|fill| fill.flags.clone()).collect();
+        assert_eq!(
+            flags,
+            vec![
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+                vec![FuelFlag::HighConsumption],
+                vec![FuelFlag::LowConsumption],
+            ]
+        );
+        assert_eq!(report.distance_km, 5_000);
+        assert_eq!(report.litres_per_100km, Some(dec!(11)));
+    }
+
+    #[test]
+    fn too_few_intervals_flag_no_consumption() {
+        let report = analyse(vec![
+            fill(dec!(100), 10_000, true),
+            fill(dec!(100), 11_000, true),
+            fill(dec!(300), 12_000, true),
+        ]);
+        assert!(report.fills.iter().all(|fill| fill.flags.is_empty()));
+        assert_eq!(report.litres_per_100km, Some(dec!(20)));
+    }
+
+    #[test]
+    fn no_interval_leaves_consumption_empty() {
+        let report = analyse(vec![
+            fill(dec!(30), 10_000, false),
+            fill(dec!(100), 10_400, true),
+        ]);
+        assert_eq!(report.distance_km, 0);
+        assert_eq!(report.litres_per_100km, None);
+        assert_eq!(report.cost_per_km, None);
+        assert_eq!(report.total_litres, dec!(130));
+        assert_eq!(report.total_cost, dec!(1040));
+    }
+}
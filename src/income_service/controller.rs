@@ -0,0 +1,238 @@
+use chrono::{DateTime, Local};
+use poem::web::Data;
+use poem_openapi::{param::Path, payload::Json, ApiResponse, Object, OpenApi, Tags};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::auth_service::service::{TeamGuard, TeamPermission};
+use crate::billing_service::service::{Billing, Team};
+use crate::error::{parse_uuid, ApiResult, AppError};
+use crate::session_service::service::SessionUser;
+use crate::state::AppState;
+
+use super::service::{BillingIncome, IncomeFields};
+
+#[derive(Tags)]
+enum ApiTags {
+    /// Freight income recorded on billings
+    Income,
+}
+
+/// Body of recording an income entry and of replacing it.
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct BillingIncomeDTO {
+    /// Customer of the team the freight is charged to, needed for receivables.
+    #[oai(validator(max_length = 128))]
+    customer_id: Option<String>,
+
+    /// Shipper name, the name of the customer when omitted.
+    #[oai(validator(max_length = 128))]
+    customer_name: Option<String>,
+
+    #[oai(validator(max_length = 128))]
+    cargo: Option<String>,
+
+    #[oai(validator(max_length = 128))]
+    origin: Option<String>,
+
+    #[oai(validator(max_length = 128))]
+    destination: Option<String>,
+
+    weight_tonnes: Option<Decimal>,
+
+    /// Agreed price per tonne.
+    unit_price: Option<Decimal>,
+
+    /// Freight charge, weight times unit price when omitted.
+    amount: Option<Decimal>,
+
+    /// What the customer has paid so far, 0 when omitted.
+    received_amount: Option<Decimal>,
+}
+
+fn parse_income(dto: BillingIncomeDTO) -> Result<IncomeFields, AppError> {
+    Ok(IncomeFields {
+        customer_id: dto
+            .customer_id
+            .as_deref()
+            .map(|customer_id| parse_uuid("customer_id", customer_id))
+            .transpose()?,
+        customer_name: dto.customer_name,
+        cargo: dto.cargo,
+        origin: dto.origin,
+        destination: dto.destination,
+        weight_tonnes: dto.weight_tonnes,
+        unit_price: dto.unit_price,
+        amount: dto.amount,
+        received_amount: dto.received_amount.unwrap_or_default(),
+    })
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct BillingIncomeEntityDTO {
+    income_id: String,
+    billing_id: String,
+    customer_id: Option<String>,
+    customer_name: Option<String>,
+    cargo: Option<String>,
+    origin: Option<String>,
+    destination: Option<String>,
+    weight_tonnes: Option<Decimal>,
+    unit_price: Option<Decimal>,
+    amount: Decimal,
+    received_amount: Decimal,
+    outstanding_amount: Decimal,
+    user_id: Option<String>,
+    time: Option<DateTime<Local>>,
+}
+
+impl From<BillingIncome> for BillingIncomeEntityDTO {
+    fn from(income: BillingIncome) -> Self {
+        let fields = income.fields;
+        BillingIncomeEntityDTO {
+            income_id: income.id.to_string(),
+            billing_id: income.billing_id.to_string(),
+            amount: fields.amount(),
+            outstanding_amount: fields.outstanding(),
+            received_amount: fields.received_amount,
+            customer_id: fields
+                .customer_id
+                .map(|customer_id| customer_id.to_string()),
+            customer_name: fields.customer_name,
+            cargo: fields.cargo,
+            origin: fields.origin,
+            destination: fields.destination,
+            weight_tonnes: fields.weight_tonnes,
+            unit_price: fields.unit_price,
+            user_id: income.user_id,
+            time: income.time,
+        }
+    }
+}
+
+#[derive(ApiResponse)]
+enum IncomeResponse {
+    #[oai(status = 200)]
+    Ok(Json<BillingIncomeEntityDTO>),
+
+    #[oai(status = 201)]
+    Created(Json<BillingIncomeEntityDTO>),
+}
+
+#[derive(ApiResponse)]
+enum QueryIncomeResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<BillingIncomeEntityDTO>>),
+}
+
+#[derive(ApiResponse)]
+enum DeleteIncomeResponse {
+    #[oai(status = 204)]
+    Ok,
+}
+
+/// Checks that the caller manages the team and loads the billing.
+async fn manage_billing(
+    state: &AppState,
+    session_user: &SessionUser,
+    team_id: &str,
+    billing_id: &str,
+) -> Result<Billing, AppError> {
+    let team_uuid = parse_uuid("team_id", team_id)?;
+    let billing_uuid = parse_uuid("billing_id", billing_id)?;
+    TeamGuard::check(&state.db, session_user, team_uuid, TeamPermission::Manage).await?;
+    let team = Team::get_by_id(&state.db, team_uuid).await?;
+    team.get_billing(&state.db, billing_uuid).await
+}
+
+fn parse_income_id(income_id: &str) -> Result<Uuid, AppError> {
+    parse_uuid("income_id", income_id)
+}
+
+pub struct IncomeRouter;
+
+#[OpenApi]
+impl IncomeRouter {
+    #[oai(
+        path = "/team/:team_id/billing/:billing_id/income",
+        method = "get",
+        tag = "ApiTags::Income"
+    )]
+    async fn query_income(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        billing_id: Path<String>,
+    ) -> ApiResult<QueryIncomeResponse> {
+        let billing = manage_billing(&state, &session_user, &team_id.0, &billing_id.0).await?;
+        let incomes = BillingIncome::query(&state.db, billing.id).await?;
+        Ok(QueryIncomeResponse::Ok(Json(
+            incomes.into_iter().map(|income| income.into()).collect(),
+        )))
+    }
+
+    #[oai(
+        path = "/team/:team_id/billing/:billing_id/income",
+        method = "post",
+        tag = "ApiTags::Income"
+    )]
+    async fn create_income(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        billing_id: Path<String>,
+        income: Json<BillingIncomeDTO>,
+    ) -> ApiResult<IncomeResponse> {
+        let fields = parse_income(income.0)?;
+        let billing = manage_billing(&state, &session_user, &team_id.0, &billing_id.0).await?;
+        let income =
+            BillingIncome::create(&state.db, &billing, session_user.user_id, fields).await?;
+        Ok(IncomeResponse::Created(Json(income.into())))
+    }
+
+    /// Replaces an income entry. On an ended billing only the customer and the received amount
+    /// can change.
+    #[oai(
+        path = "/team/:team_id/billing/:billing_id/income/:income_id",
+        method = "put",
+        tag = "ApiTags::Income"
+    )]
+    async fn replace_income(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        billing_id: Path<String>,
+        income_id: Path<String>,
+        income: Json<BillingIncomeDTO>,
+    ) -> ApiResult<IncomeResponse> {
+        let income_uuid = parse_income_id(&income_id.0)?;
+        let fields = parse_income(income.0)?;
+        let billing = manage_billing(&state, &session_user, &team_id.0, &billing_id.0).await?;
+        let stored = BillingIncome::from_id(&state.db, billing.id, income_uuid).await?;
+        let income = stored.replace(&state.db, &billing, fields).await?;
+        Ok(IncomeResponse::Ok(Json(income.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/billing/:billing_id/income/:income_id",
+        method = "delete",
+        tag = "ApiTags::Income"
+    )]
+    async fn delete_income(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        billing_id: Path<String>,
+        income_id: Path<String>,
+    ) -> ApiResult<DeleteIncomeResponse> {
+        let income_uuid = parse_income_id(&income_id.0)?;
+        let billing = manage_billing(&state, &session_user, &team_id.0, &billing_id.0).await?;
+        let income = BillingIncome::from_id(&state.db, billing.id, income_uuid).await?;
+        income.delete(&state.db, &billing).await?;
+        Ok(DeleteIncomeResponse::Ok)
+    }
+}
@@ -0,0 +1,723 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local};
+use rust_decimal::Decimal;
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, FromQueryResult,
+    IntoActiveModel, ModelTrait, QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use tracing::{instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    billing_service::service::{parse_navie_time_to_data_time, Billing},
+    entities::{billing_income, customer, payment_allocation},
+    error::{AppError, ErrorCode},
+};
+
+fn income_not_found(income_id: Uuid) -> AppError {
+    AppError::new(
+        ErrorCode::IncomeNotFound,
+        format!("can not find income {}", income_id),
+    )
+}
+
+fn income_allocated(income_id: Uuid) -> AppError {
+    AppError::new(
+        ErrorCode::IncomeAllocated,
+        format!("customer payments are allocated to income {}", income_id),
+    )
+}
+
+fn invalid_income(message: impl Into<String>) -> AppError {
+    AppError::new(ErrorCode::InvalidArgument, message)
+}
+
+/// Drops surrounding spaces and turns blank texts into `None`.
+pub(crate) fn trimmed(text: Option<String>) -> Option<String> {
+    text.map(|text| text.trim().to_owned())
+        .filter(|text| !text.is_empty())
+}
+
+#[derive(Debug, Clone)]
+pub struct BillingIncome {
+    pub id: Uuid,
+    pub billing_id: Uuid,
+    pub fields: IncomeFields,
+    pub user_id: Option<String>,
+    pub time: Option<DateTime<Local>>,
+}
+
+impl From<billing_income::Model> for BillingIncome {
+    fn from(model: billing_income::Model) -> Self {
+        BillingIncome {
+            id: model.id,
+            billing_id: model.billing_id,
+            fields: IncomeFields {
+                customer_id: model.customer_id,
+                customer_name: model.customer_name,
+                cargo: model.cargo,
+                origin: model.origin,
+                destination: model.destination,
+                weight_tonnes: model.weight_tonnes,
+                unit_price: model.unit_price,
+                amount: Some(model.amount),
+                received_amount: model.received_amount,
+            },
+            user_id: model.user_id,
+            time: parse_navie_time_to_data_time(Some(model.time)),
+        }
+    }
+}
+
+/// Freight terms of an income entry and what the customer has paid of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncomeFields {
+    /// Customer of the team the freight is charged to.
+    pub customer_id: Option<Uuid>,
+    /// Free text shipper, taken from the customer when left out.
+    pub customer_name: Option<String>,
+    pub cargo: Option<String>,
+    pub origin: Option<String>,
+    pub destination: Option<String>,
+    pub weight_tonnes: Option<Decimal>,
+    /// Agreed price per tonne.
+    pub unit_price: Option<Decimal>,
+    /// Freight charge, weight times unit price when left out.
+    pub amount: Option<Decimal>,
+    pub received_amount: Decimal,
+}
+
+impl IncomeFields {
+    /// The freight charge, always set once the fields are normalized.
+    pub fn amount(&self) -> Decimal {
+        self.amount.unwrap_or_default()
+    }
+
+    /// What the customer still owes.
+    pub fn outstanding(&self) -> Decimal {
+        (self.amount() - self.received_amount).max(Decimal::ZERO)
+    }
+
+    /// Trims the texts, works out a missing freight charge and checks the amounts.
+    pub fn normalize(self) -> Result<IncomeFields, AppError> {
+        if self
+            .weight_tonnes
+            .is_some_and(|weight| weight <= Decimal::ZERO)
+        {
+            return Err(invalid_income("weight must be positive"));
+        }
+        if self.unit_price.is_some_and(|price| price <= Decimal::ZERO) {
+            return Err(invalid_income("unit price must be positive"));
+        }
+        let amount = match (self.amount, self.weight_tonnes, self.unit_price) {
+            (Some(amount), _, _) => amount,
+            (None, Some(weight), Some(price)) => (weight * price).round_dp(2),
+            (None, _, _) => {
+                return Err(invalid_income(
+                    "freight charge or weight and unit price are required",
+                ))
+            }
+        };
+        if amount <= Decimal::ZERO {
+            return Err(invalid_income("freight charge must be positive"));
+        }
+        if self.received_amount < Decimal::ZERO {
+            return Err(invalid_income("received amount can not be negative"));
+        }
+        if self.received_amount > amount {
+            return Err(invalid_income(
+                "received amount can not exceed the freight charge",
+            ));
+        }
+        Ok(IncomeFields {
+            customer_id: self.customer_id,
+            customer_name: trimmed(self.customer_name),
+            cargo: trimmed(self.cargo),
+            origin: trimmed(self.origin),
+            destination: trimmed(self.destination),
+            weight_tonnes: self.weight_tonnes,
+            unit_price: self.unit_price,
+            amount: Some(amount),
+            received_amount: self.received_amount,
+        })
+    }
+
+    fn apply(self, active_model: &mut billing_income::ActiveModel) {
+        active_model.amount = Set(self.amount());
+        active_model.customer_id = Set(self.customer_id);
+        active_model.customer_name = Set(self.customer_name);
+        active_model.cargo = Set(self.cargo);
+        active_model.origin = Set(self.origin);
+        active_model.destination = Set(self.destination);
+        active_model.weight_tonnes = Set(self.weight_tonnes);
+        active_model.unit_price = Set(self.unit_price);
+        active_model.received_amount = Set(self.received_amount);
+    }
+}
+
+/// Income of one billing, summed in SQL.
+#[derive(Debug, Clone, Default)]
+pub struct IncomeTotal {
+    pub total_income: Decimal,
+    pub received_income: Decimal,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct AllocatedSum {
+    allocated: Option<Decimal>,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct BillingIncomeTotal {
+    billing_id: Uuid,
+    total_income: Option<Decimal>,
+    received_income: Option<Decimal>,
+}
+
+impl BillingIncome {
+    #[instrument(skip(db, billing))]
+    pub async fn create<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        billing: &Billing,
+        user_id: String,
+        fields: IncomeFields,
+    ) -> Result<BillingIncome, AppError> {
+        if billing.is_ended() {
+            warn!("billing {} is ended, can not add income", billing.id);
+            return Err(AppError::new(
+                ErrorCode::BillingEnded,
+                format!("billing {} is already ended", billing.id),
+            ));
+        }
+        let fields = fields.normalize()?;
+        let txn = db.begin().await?;
+        billing.lock_open_billing(&txn).await?;
+        let fields = Self::link_customer(&txn, billing, fields).await?;
+        let mut active_model = billing_income::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            billing_id: Set(billing.id),
+            user_id: Set(Some(user_id)),
+            time: Set(Local::now().naive_local()),
+            ..Default::default()
+        };
+        fields.apply(&mut active_model);
+        let model = active_model.insert(&txn).await?;
+        txn.commit().await?;
+        Ok(model.into())
+    }
+
+    #[instrument(skip(db))]
+    pub async fn from_id<C: ConnectionTrait>(
+        db: &C,
+        billing_id: Uuid,
+        income_id: Uuid,
+    ) -> Result<BillingIncome, AppError> {
+        let model = billing_income::Entity::find_by_id(income_id)
+            .filter(billing_income::Column::BillingId.eq(billing_id))
+            .one(db)
+            .await?
+            .ok_or_else(|| income_not_found(income_id))?;
+        Ok(model.into())
+    }
+
+    #[instrument(skip(db))]
+    pub async fn query<C: ConnectionTrait>(
+        db: &C,
+        billing_id: Uuid,
+    ) -> Result<Vec<BillingIncome>, AppError> {
+        let models = billing_income::Entity::find()
+            .filter(billing_income::Column::BillingId.eq(billing_id))
+            .order_by_asc(billing_income::Column::Time)
+            .all(db)
+            .await?;
+        Ok(models.into_iter().map(|model| model.into()).collect())
+    }
+
+    /// Replaces the income entry. Once the billing is ended its freight terms are final and
+    /// only the customer and the received amount can still change. Amounts allocated from
+    /// customer payments pin the customer and the lowest received amount.
+    #[instrument(skip(db, billing))]
+    pub async fn replace<C: ConnectionTrait + TransactionTrait>(
+        self,
+        db: &C,
+        billing: &Billing,
+        fields: IncomeFields,
+    ) -> Result<BillingIncome, AppError> {
+        let fields = fields.normalize()?;
+        let txn = db.begin().await?;
+        if billing.is_ended() {
+            let terms = IncomeFields {
+                customer_id: self.fields.customer_id,
+                customer_name: self.fields.customer_name.clone(),
+                received_amount: self.fields.received_amount,
+                ..fields.clone()
+            };
+            if terms != self.fields {
+                return Err(AppError::new(
+                    ErrorCode::BillingEnded,
+                    format!(
+                        "billing {} is already ended, only the customer and the received amount can change",
+                        billing.id
+                    ),
+                ));
+            }
+        } else {
+            billing.lock_open_billing(&txn).await?;
+        }
+        let model = billing_income::Entity::find_by_id(self.id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or_else(|| income_not_found(self.id))?;
+        let allocated = Self::allocated_amount(&txn, self.id).await?;
+        if allocated > Decimal::ZERO {
+            if fields.customer_id != model.customer_id {
+                return Err(income_allocated(self.id));
+            }
+            if fields.received_amount < allocated {
+                return Err(invalid_income(format!(
+                    "received amount can not be below the {} allocated from payments",
+                    allocated
+                )));
+            }
+        }
+        let fields = Self::link_customer(&txn, billing, fields).await?;
+        let mut active_model = model.into_active_model();
+        fields.apply(&mut active_model);
+        let model = active_model.update(&txn).await?;
+        txn.commit().await?;
+        Ok(model.into())
+    }
+
+    #[instrument(skip(db, billing))]
+    pub async fn delete<C: ConnectionTrait + TransactionTrait>(
+        self,
+        db: &C,
+        billing: &Billing,
+    ) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        billing.lock_open_billing(&txn).await?;
+        let model = billing_income::Entity::find_by_id(self.id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or_else(|| income_not_found(self.id))?;
+        if Self::allocated_amount(&txn, self.id).await? > Decimal::ZERO {
+            return Err(income_allocated(self.id));
+        }
+        model.delete(&txn).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Checks the customer belongs to the team of the billing and names the shipper after it
+    /// when no other name is given.
+    async fn link_customer<C: ConnectionTrait>(
+        db: &C,
+        billing: &Billing,
+        fields: IncomeFields,
+    ) -> Result<IncomeFields, AppError> {
+        let customer_id = match fields.customer_id {
+            Some(customer_id) => customer_id,
+            None => return Ok(fields),
+        };
+        let customer_model = customer::Entity::find_by_id(customer_id)
+            .filter(customer::Column::TeamId.eq(billing.team_id))
+            .one(db)
+            .await?
+            .ok_or_else(|| {
+                AppError::new(
+                    ErrorCode::CustomerNotFound,
+                    format!("can not find customer {}", customer_id),
+                )
+            })?;
+        Ok(IncomeFields {
+            customer_name: fields.customer_name.or(Some(customer_model.name)),
+            ..fields
+        })
+    }
+
+    /// How much of the income customer payments have settled.
+    async fn allocated_amount<C: ConnectionTrait>(
+        db: &C,
+        income_id: Uuid,
+    ) -> Result<Decimal, AppError> {
+        let sum = payment_allocation::Entity::find()
+            .select_only()
+            .column_as(
+                Expr::tbl(
+                    payment_allocation::Entity,
+                    payment_allocation::Column::Amount,
+                )
+                .sum(),
+                "allocated",
+            )
+            .filter(payment_allocation::Column::BillingIncomeId.eq(income_id))
+            .into_model::<AllocatedSum>()
+            .one(db)
+            .await?;
+        Ok(sum.and_then(|sum| sum.allocated).unwrap_or_default())
+    }
+
+    /// Freight charged and received per billing.
+    pub async fn totals<C: ConnectionTrait>(
+        db: &C,
+        billing_ids: Vec<Uuid>,
+    ) -> Result<HashMap<Uuid, IncomeTotal>, AppError> {
+        if billing_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let totals = billing_income::Entity::find()
+            .select_only()
+            .column(billing_income::Column::BillingId)
+            .column_as(
+                Expr::tbl(billing_income::Entity, billing_income::Column::Amount).sum(),
+                "total_income",
+            )
+            .column_as(
+                Expr::tbl(
+                    billing_income::Entity,
+                    billing_income::Column::ReceivedAmount,
+                )
+                .sum(),
+                "received_income",
+            )
+            .filter(billing_income::Column::BillingId.is_in(billing_ids))
+            .group_by(billing_income::Column::BillingId)
+            .into_model::<BillingIncomeTotal>()
+            .all(db)
+            .await?;
+        Ok(totals
+            .into_iter()
+            .map(|total| {
+                (
+                    total.billing_id,
+                    IncomeTotal {
+                        total_income: total.total_income.unwrap_or_default(),
+                        received_income: total.received_income.unwrap_or_default(),
+                    },
+                )
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Local};
+    use rust_decimal_macros::dec;
+    use sea_orm::{ActiveModelTrait, EntityTrait, IntoActiveModel, Set};
+    use uuid::Uuid;
+
+    use super::{BillingIncome, IncomeFields};
+    use crate::billing_service::service::Billing;
+    use crate::customer_service::service::{
+        Allocation, Customer, CustomerPayment, CustomerProfile,
+    };
+    use crate::entities::{billing, team, user};
+    use crate::error::ErrorCode;
+    use crate::test_db::TestDb;
+
+    const OWNER_ID: &str = "owner-openid";
+
+    /// 30 tonnes of coal at 50 per tonne, nothing received yet.
+    fn coal() -> IncomeFields {
+        IncomeFields {
+            customer_id: None,
+            customer_name: None,
+            cargo: Some("煤".to_owned()),
+            origin: None,
+            destination: None,
+            weight_tonnes: Some(dec!(30)),
+            unit_price: Some(dec!(50)),
+            amount: None,
+            received_amount: dec!(0),
+        }
+    }
+
+    #[test]
+    fn freight_charge_is_weight_times_unit_price() {
+        let fields = IncomeFields {
+            weight_tonnes: Some(dec!(31.257)),
+            unit_price: Some(dec!(48.5)),
+            ..coal()
+        }
+        .normalize()
+        .unwrap();
+        // 1515.9645 rounded to cents.
+        assert_eq!(fields.amount, Some(dec!(1515.96)));
+        assert_eq!(fields.outstanding(), dec!(1515.96));
+    }
+
+    #[test]
+    fn given_freight_charge_is_kept() {
+        let fields = IncomeFields {
+            amount: Some(dec!(1600)),
+            received_amount: dec!(600),
+            ..coal()
+        }
+        .normalize()
+        .unwrap();
+        assert_eq!(fields.amount, Some(dec!(1600)));
+        assert_eq!(fields.outstanding(), dec!(1000));
+    }
+
+    #[test]
+    fn blank_texts_are_dropped() {
+        let fields = IncomeFields {
+            customer_name: Some("  ".to_owned()),
+            cargo: Some(" 煤 ".to_owned()),
+            ..coal()
+        }
+        .normalize()
+        .unwrap();
+        assert_eq!(fields.customer_name, None);
+        assert_eq!(fields.cargo.as_deref(), Some("煤"));
+    }
+
+    #[test]
+    fn rejects_invalid_amounts() {
+        let cases = [
+            IncomeFields {
+                weight_tonnes: Some(dec!(0)),
+                ..coal()
+            },
+            IncomeFields {
+                unit_price: Some(dec!(-1)),
+                ..coal()
+            },
+            IncomeFields {
+                unit_price: None,
+                ..coal()
+            },
+            IncomeFields {
+                amount: Some(dec!(0)),
+                ..coal()
+            },
+            IncomeFields {
+                received_amount: dec!(-1),
+                ..coal()
+            },
+            IncomeFields {
+                received_amount: dec!(1500.01),
+                ..coal()
+            },
+        ];
+        for fields in cases {
+            let err = fields.clone().normalize().unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidArgument, "{:?}", fields);
+        }
+    }
+
+    struct Fixture {
+        billing: Billing,
+        customer: Customer,
+    }
+
+    /// An open billing of a team owned by `OWNER_ID` and one customer of the team.
+    async fn fixture(test_db: &TestDb) -> Fixture {
+        let db = &test_db.db;
+        user::ActiveModel {
+            id: Set(OWNER_ID.to_owned()),
+            user_name: Set("车主".to_owned()),
+            avatar_url: Set(None),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        let team_model = team::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            team_name: Set("车队".to_owned()),
+            user_id: Set(OWNER_ID.to_owned()),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        let billing_model = billing::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            name: Set("第一趟".to_owned()),
+            team_id: Set(Some(team_model.id)),
+            start_time: Set(Some(Local::now().naive_local() - Duration::days(3))),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        let customer = Customer::create(
+            db,
+            team_model.id,
+            CustomerProfile {
+                name: "煤矿".to_owned(),
+                contact_name: None,
+                phone: None,
+                note: None,
+            },
+        )
+        .await
+        .unwrap();
+        Fixture {
+            billing: billing_model.into(),
+            customer,
+        }
+    }
+
+    /// Ends the billing and returns it as the controllers load it.
+    async fn end(test_db: &TestDb, billing: &Billing) -> Billing {
+        let mut active_model = billing::Entity::find_by_id(billing.id)
+            .one(&test_db.db)
+            .await
+            .unwrap()
+            .unwrap()
+            .into_active_model();
+        active_model.end_time = Set(Some(Local::now().naive_local()));
+        active_model.update(&test_db.db).await.unwrap().into()
+    }
+
+    #[tokio::test]
+    async fn ended_billing_only_takes_customer_and_received_amount() {
+        let Some(test_db) = TestDb::create().await else {
+            return;
+        };
+        let db = &test_db.db;
+        let Fixture { billing, customer } = fixture(&test_db).await;
+        let income = BillingIncome::create(db, &billing, OWNER_ID.to_owned(), coal())
+            .await
+            .unwrap();
+        let billing = end(&test_db, &billing).await;
+
+        let err = income
+            .clone()
+            .replace(
+                db,
+                &billing,
+                IncomeFields {
+                    unit_price: Some(dec!(55)),
+                    ..income.fields.clone()
+                },
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::BillingEnded);
+
+        let income = income
+            .clone()
+            .replace(
+                db,
+                &billing,
+                IncomeFields {
+                    customer_id: Some(customer.id),
+                    received_amount: dec!(500),
+                    ..income.fields.clone()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(income.fields.customer_id, Some(customer.id));
+        assert_eq!(income.fields.customer_name.as_deref(), Some("煤矿"));
+        assert_eq!(income.fields.received_amount, dec!(500));
+        assert_eq!(income.fields.amount, Some(dec!(1500)));
+
+        let err = income.delete(db, &billing).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::BillingEnded);
+        test_db.drop().await;
+    }
+
+    #[tokio::test]
+    async fn allocated_income_keeps_its_customer_and_received_amount() {
+        let Some(test_db) = TestDb::create().await else {
+            return;
+        };
+        let db = &test_db.db;
+        let Fixture { billing, customer } = fixture(&test_db).await;
+        let fields = IncomeFields {
+            customer_id: Some(customer.id),
+            ..coal()
+        };
+        let income = BillingIncome::create(db, &billing, OWNER_ID.to_owned(), fields)
+            .await
+            .unwrap();
+        CustomerPayment::record(
+            db,
+            &customer,
+            OWNER_ID.to_owned(),
+            dec!(400),
+            Local::now().date_naive(),
+            None,
+            Allocation::Fifo,
+        )
+        .await
+        .unwrap();
+        let income = BillingIncome::from_id(db, billing.id, income.id)
+            .await
+            .unwrap();
+        assert_eq!(income.fields.received_amount, dec!(400));
+
+        let err = income
+            .clone()
+            .replace(
+                db,
+                &billing,
+                IncomeFields {
+                    customer_id: None,
+                    ..income.fields.clone()
+                },
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::IncomeAllocated);
+
+        let err = income
+            .clone()
+            .replace(
+                db,
+                &billing,
+                IncomeFields {
+                    received_amount: dec!(399.99),
+                    ..income.fields.clone()
+                },
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+
+        // The freight terms of an open billing can still change around the allocation.
+        let replaced = income
+            .clone()
+            .replace(
+                db,
+                &billing,
+                IncomeFields {
+                    amount: Some(dec!(1600)),
+                    received_amount: dec!(600),
+                    ..income.fields.clone()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(replaced.fields.amount, Some(dec!(1600)));
+        assert_eq!(replaced.fields.received_amount, dec!(600));
+
+        let err = income.delete(db, &billing).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::IncomeAllocated);
+        test_db.drop().await;
+    }
+
+    #[tokio::test]
+    async fn deletes_unallocated_income_of_open_billing() {
+        let Some(test_db) = TestDb::create().await else {
+            return;
+        };
+        let db = &test_db.db;
+        let Fixture { billing, .. } = fixture(&test_db).await;
+        let income = BillingIncome::create(db, &billing, OWNER_ID.to_owned(), coal())
+            .await
+            .unwrap();
+        income.delete(db, &billing).await.unwrap();
+        assert!(BillingIncome::query(db, billing.id)
+            .await
+            .unwrap()
+            .is_empty());
+        test_db.drop().await;
+    }
+}
@@ -0,0 +1,217 @@
+use chrono::{DateTime, Duration, Local};
+use poem::web::Data;
+use poem_openapi::{
+    param::Path,
+    payload::{Binary, Json},
+    ApiResponse, Object, OpenApi, Tags,
+};
+
+use crate::auth_service::service::{TeamGuard, TeamPermission};
+use crate::error::{parse_uuid, ApiResult};
+use crate::session_service::service::SessionUser;
+use crate::state::AppState;
+
+use super::service::{JoinedTeam, TeamInvite, QR_PAYLOAD_PREFIX};
+
+const DEFAULT_VALID_HOURS: u32 = 72;
+
+#[derive(Tags)]
+enum ApiTags {
+    /// Invite codes for drivers to join a team
+    Invite,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct InviteCreateDTO {
+    /// Hours the code stays valid, 72 when omitted, at most 30 days.
+    #[oai(validator(minimum(value = "1"), maximum(value = "720")))]
+    valid_hours: Option<u32>,
+
+    /// How many drivers may join with the code, unlimited when omitted.
+    #[oai(validator(minimum(value = "1")))]
+    max_uses: Option<i32>,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct InviteEntityDTO {
+    invite_id: String,
+    team_id: String,
+    code: String,
+    /// Text encoded in the QR code of the invite.
+    qr_payload: String,
+    created_by: String,
+    max_uses: Option<i32>,
+    used_count: i32,
+    active: bool,
+    expire_time: Option<DateTime<Local>>,
+    revoked_time: Option<DateTime<Local>>,
+    create_time: Option<DateTime<Local>>,
+}
+
+impl From<TeamInvite> for InviteEntityDTO {
+    fn from(invite: TeamInvite) -> Self {
+        InviteEntityDTO {
+            invite_id: invite.id.to_string(),
+            team_id: invite.team_id.to_string(),
+            active: invite.is_active(),
+            qr_payload: format!("{}{}", QR_PAYLOAD_PREFIX, invite.code),
+            code: invite.code,
+            created_by: invite.created_by,
+            max_uses: invite.max_uses,
+            used_count: invite.used_count,
+            expire_time: invite.expire_time,
+            revoked_time: invite.revoked_time,
+            create_time: invite.create_time,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct TeamJoinDTO {
+    #[oai(validator(max_length = 64))]
+    code: String,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct JoinedTeamDTO {
+    team_id: String,
+    team_name: String,
+}
+
+impl From<JoinedTeam> for JoinedTeamDTO {
+    fn from(joined: JoinedTeam) -> Self {
+        JoinedTeamDTO {
+            team_id: joined.team_id.to_string(),
+            team_name: joined.team_name,
+        }
+    }
+}
+
+#[derive(ApiResponse)]
+enum InviteResponse {
+    #[oai(status = 200)]
+    Ok(Json<InviteEntityDTO>),
+
+    #[oai(status = 201)]
+    Created(Json<InviteEntityDTO>),
+}
+
+#[derive(ApiResponse)]
+enum QueryInviteResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<InviteEntityDTO>>),
+}
+
+#[derive(ApiResponse)]
+enum InviteQrCodeResponse {
+    #[oai(status = 200, content_type = "image/png")]
+    Ok(Binary<Vec<u8>>),
+}
+
+#[derive(ApiResponse)]
+enum TeamJoinResponse {
+    #[oai(status = 201)]
+    Ok(Json<JoinedTeamDTO>),
+}
+
+pub struct InviteRouter;
+
+#[OpenApi]
+impl InviteRouter {
+    #[oai(
+        path = "/team/:team_id/invite",
+        method = "post",
+        tag = "ApiTags::Invite"
+    )]
+    async fn create_invite(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        invite: Json<InviteCreateDTO>,
+    ) -> ApiResult<InviteResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let valid_hours = invite.valid_hours.unwrap_or(DEFAULT_VALID_HOURS);
+        let invite = TeamInvite::create(
+            &state.db,
+            team_uuid,
+            session_user.user_id,
+            Duration::hours(valid_hours.into()),
+            invite.max_uses,
+        )
+        .await?;
+        Ok(InviteResponse::Created(Json(invite.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/invite",
+        method = "get",
+        tag = "ApiTags::Invite"
+    )]
+    async fn query_invite(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+    ) -> ApiResult<QueryInviteResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let invites = TeamInvite::query(&state.db, team_uuid).await?;
+        Ok(QueryInviteResponse::Ok(Json(
+            invites.into_iter().map(|invite| invite.into()).collect(),
+        )))
+    }
+
+    #[oai(
+        path = "/team/:team_id/invite/:invite_id",
+        method = "delete",
+        tag = "ApiTags::Invite"
+    )]
+    async fn revoke_invite(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        invite_id: Path<String>,
+    ) -> ApiResult<InviteResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let invite_uuid = parse_uuid("invite_id", &invite_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let invite = TeamInvite::from_id(&state.db, team_uuid, invite_uuid).await?;
+        let invite = invite.revoke(&state.db).await?;
+        Ok(InviteResponse::Ok(Json(invite.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/invite/:invite_id/qrcode",
+        method = "get",
+        tag = "ApiTags::Invite"
+    )]
+    async fn invite_qr_code(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        invite_id: Path<String>,
+    ) -> ApiResult<InviteQrCodeResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let invite_uuid = parse_uuid("invite_id", &invite_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let invite = TeamInvite::from_id(&state.db, team_uuid, invite_uuid).await?;
+        Ok(InviteQrCodeResponse::Ok(Binary(invite.qr_png()?)))
+    }
+
+    /// Redeems an invite code, the caller joins the team as a driver.
+    #[oai(path = "/team/join", method = "post", tag = "ApiTags::Invite")]
+    async fn join_team(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        join: Json<TeamJoinDTO>,
+    ) -> ApiResult<TeamJoinResponse> {
+        let code = join.code.trim_start_matches(QR_PAYLOAD_PREFIX);
+        let joined = TeamInvite::redeem(&state.db, code, session_user.user_id).await?;
+        Ok(TeamJoinResponse::Ok(Json(joined.into())))
+    }
+}
@@ -0,0 +1,280 @@
+use chrono::{DateTime, Duration, Local};
+use qrcode::{Color, QrCode};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, IntoActiveModel, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use tracing::{error, info, instrument};
+use uuid::Uuid;
+
+use crate::{
+    billing_service::service::parse_navie_time_to_data_time,
+    entities::{team, team_driver, team_invite},
+    error::{AppError, ErrorCode},
+};
+
+/// Invite codes avoid `0`/`O` and `1`/`I` so they can be read out and typed by hand.
+const CODE_ALPHABET: &[u8; 32] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LENGTH: usize = 8;
+
+/// Prefix of the text encoded in the QR code, the mini program strips it off the scan
+/// result and redeems the rest.
+pub const QR_PAYLOAD_PREFIX: &str = "truck-billing:team-invite:";
+const QR_MODULE_PIXELS: usize = 8;
+const QR_QUIET_ZONE: usize = 4;
+
+fn invite_not_found(invite: impl std::fmt::Display) -> AppError {
+    AppError::new(
+        ErrorCode::InviteNotFound,
+        format!("can not find invite {}", invite),
+    )
+}
+
+/// 8 characters of a 32 letter alphabet carry 40 random bits, the low 5 bits of 8 bytes of a
+/// v4 uuid. Byte 6 holds the version and byte 8 the variant, they are skipped so every
+/// character takes all 32 values.
+fn generate_code() -> String {
+    let bytes = Uuid::new_v4().into_bytes();
+    bytes
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| *index != 6 && *index != 8)
+        .take(CODE_LENGTH)
+        .map(|(_, byte)| CODE_ALPHABET[(byte % 32) as usize] as char)
+        .collect()
+}
+
+#[derive(Debug)]
+pub struct TeamInvite {
+    pub id: Uuid,
+    pub team_id: Uuid,
+    pub code: String,
+    pub created_by: String,
+    pub max_uses: Option<i32>,
+    pub used_count: i32,
+    pub expire_time: Option<DateTime<Local>>,
+    pub revoked_time: Option<DateTime<Local>>,
+    pub create_time: Option<DateTime<Local>>,
+    expired: bool,
+}
+
+impl From<team_invite::Model> for TeamInvite {
+    fn from(model: team_invite::Model) -> Self {
+        TeamInvite {
+            id: model.id,
+            team_id: model.team_id,
+            expired: model.expire_time <= Local::now().naive_local(),
+            code: model.code,
+            created_by: model.created_by,
+            max_uses: model.max_uses,
+            used_count: model.used_count,
+            expire_time: parse_navie_time_to_data_time(Some(model.expire_time)),
+            revoked_time: parse_navie_time_to_data_time(model.revoked_time),
+            create_time: parse_navie_time_to_data_time(Some(model.create_time)),
+        }
+    }
+}
+
+/// The team a user joined by redeeming an invite.
+#[derive(Debug)]
+pub struct JoinedTeam {
+    pub team_id: Uuid,
+    pub team_name: String,
+}
+
+impl TeamInvite {
+    /// Whether the code can still be redeemed.
+    pub fn is_active(&self) -> bool {
+        self.revoked_time.is_none()
+            && !self.expired
+            && self
+                .max_uses
+                .is_none_or(|max_uses| self.used_count < max_uses)
+    }
+
+    #[instrument(skip(db))]
+    pub async fn create<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        created_by: String,
+        valid_for: Duration,
+        max_uses: Option<i32>,
+    ) -> Result<TeamInvite, AppError> {
+        let now = Local::now().naive_local();
+        let mut code = generate_code();
+        // A clash is unlikely with 40 bits, but a retired code could still be drawn again.
+        while team_invite::Entity::find()
+            .filter(team_invite::Column::Code.eq(code.clone()))
+            .one(db)
+            .await?
+            .is_some()
+        {
+            code = generate_code();
+        }
+        let model = team_invite::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            team_id: Set(team_id),
+            code: Set(code),
+            created_by: Set(created_by),
+            max_uses: Set(max_uses),
+            used_count: Set(0),
+            expire_time: Set(now + valid_for),
+            revoked_time: Set(None),
+            create_time: Set(now),
+        }
+        .insert(db)
+        .await?;
+        Ok(model.into())
+    }
+
+    #[instrument(skip(db))]
+    pub async fn from_id<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        invite_id: Uuid,
+    ) -> Result<TeamInvite, AppError> {
+        let model = team_invite::Entity::find_by_id(invite_id)
+            .filter(team_invite::Column::TeamId.eq(team_id))
+            .one(db)
+            .await?
+            .ok_or_else(|| invite_not_found(invite_id))?;
+        Ok(model.into())
+    }
+
+    #[instrument(skip(db))]
+    pub async fn query<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+    ) -> Result<Vec<TeamInvite>, AppError> {
+        let models = team_invite::Entity::find()
+            .filter(team_invite::Column::TeamId.eq(team_id))
+            .order_by_desc(team_invite::Column::CreateTime)
+            .all(db)
+            .await?;
+        Ok(models.into_iter().map(|model| model.into()).collect())
+    }
+
+    /// Revokes the code, revoking it again keeps the first revoke time.
+    #[instrument(skip(db))]
+    pub async fn revoke<C: ConnectionTrait>(self, db: &C) -> Result<TeamInvite, AppError> {
+        if self.revoked_time.is_some() {
+            return Ok(self);
+        }
+        let model = team_invite::Entity::find_by_id(self.id)
+            .one(db)
+            .await?
+            .ok_or_else(|| invite_not_found(self.id))?;
+        let mut active_model = model.into_active_model();
+        active_model.revoked_time = Set(Some(Local::now().naive_local()));
+        Ok(active_model.update(db).await?.into())
+    }
+
+    /// Adds the user to the team of the code as a driver and counts the use.
+    #[instrument(skip(db))]
+    pub async fn redeem<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        code: &str,
+        user_id: String,
+    ) -> Result<JoinedTeam, AppError> {
+        let code = code.trim().to_uppercase();
+        let txn = db.begin().await?;
+        // The row lock serializes redeems of one code, so `max_uses` can not be overrun.
+        let model = team_invite::Entity::find()
+            .filter(team_invite::Column::Code.eq(code.clone()))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or_else(|| invite_not_found(&code))?;
+        let invite: TeamInvite = model.clone().into();
+        if invite.revoked_time.is_some() {
+            return Err(AppError::new(
+                ErrorCode::InviteRevoked,
+                "invite code has been revoked",
+            ));
+        }
+        if invite.expired {
+            return Err(AppError::new(
+                ErrorCode::InviteExpired,
+                "invite code has expired",
+            ));
+        }
+        let team_model = team::Entity::find_by_id(invite.team_id)
+            .one(&txn)
+            .await?
+            .ok_or_else(|| {
+                AppError::new(
+                    ErrorCode::TeamNotFound,
+                    format!("can not find team {}", invite.team_id),
+                )
+            })?;
+        let joined = team_driver::Entity::find()
+            .filter(team_driver::Column::TeamId.eq(team_model.id))
+            .filter(team_driver::Column::UserId.eq(user_id.clone()))
+            .one(&txn)
+            .await?;
+        if joined.is_some() || team_model.user_id == user_id {
+            return Err(AppError::new(
+                ErrorCode::AlreadyInTeam,
+                format!("user is already in team {}", team_model.id),
+            ));
+        }
+        if !invite.is_active() {
+            return Err(AppError::new(
+                ErrorCode::InviteUsedUp,
+                "invite code has no uses left",
+            ));
+        }
+        team_driver::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id.clone()),
+            team_id: Set(team_model.id),
+            licence_expire_date: Set(None),
+        }
+        .insert(&txn)
+        .await?;
+        let used_count = model.used_count + 1;
+        let mut active_model = model.into_active_model();
+        active_model.used_count = Set(used_count);
+        active_model.update(&txn).await?;
+        txn.commit().await?;
+        info!("user {} joined team {} by invite", user_id, team_model.id);
+        Ok(JoinedTeam {
+            team_id: team_model.id,
+            team_name: team_model.team_name,
+        })
+    }
+
+    /// Renders the join payload of the code as a black on white PNG.
+    pub fn qr_png(&self) -> Result<Vec<u8>, AppError> {
+        let render_error = |err: String| {
+            error!("render invite qr code error {}", err);
+            AppError::new(ErrorCode::InternalError, "can not render qr code")
+        };
+        let qr_code = QrCode::new(format!("{}{}", QR_PAYLOAD_PREFIX, self.code))
+            .map_err(|err| render_error(err.to_string()))?;
+        let modules = qr_code.width();
+        let colors = qr_code.to_colors();
+        let size = (modules + 2 * QR_QUIET_ZONE) * QR_MODULE_PIXELS;
+        let mut pixels = vec![255u8; size * size];
+        for (index, color) in colors.iter().enumerate() {
+            if *color != Color::Dark {
+                continue;
+            }
+            let left = (index % modules + QR_QUIET_ZONE) * QR_MODULE_PIXELS;
+            let top = (index / modules + QR_QUIET_ZONE) * QR_MODULE_PIXELS;
+            for row in top..top + QR_MODULE_PIXELS {
+                pixels[row * size + left..row * size + left + QR_MODULE_PIXELS].fill(0);
+            }
+        }
+
+        let mut png_bytes = vec![];
+        let mut encoder = png::Encoder::new(&mut png_bytes, size as u32, size as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&pixels))
+            .map_err(|err| render_error(err.to_string()))?;
+        Ok(png_bytes)
+    }
+}
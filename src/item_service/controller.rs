@@ -0,0 +1,240 @@
+use poem::web::Data;
+use poem_openapi::{
+    param::{Path, Query},
+    payload::Json,
+    ApiResponse, Enum, Object, OpenApi, Tags,
+};
+use uuid::Uuid;
+
+use crate::auth_service::service::{TeamGuard, TeamPermission};
+use crate::entities::sea_orm_active_enums::ItemCategory;
+use crate::error::{parse_uuid, ApiResult, AppError};
+use crate::session_service::service::SessionUser;
+use crate::state::AppState;
+
+use super::service::{Item, ItemRemoval};
+
+#[derive(Tags)]
+enum ApiTags {
+    Item,
+}
+
+#[derive(Debug, Enum, Clone, Copy, Eq, PartialEq)]
+#[oai(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ItemCategoryDTO {
+    Fuel,
+    Toll,
+    Repair,
+    Meal,
+    Lodging,
+    Other,
+}
+
+impl From<ItemCategoryDTO> for ItemCategory {
+    fn from(category: ItemCategoryDTO) -> Self {
+        match category {
+            ItemCategoryDTO::Fuel => ItemCategory::Fuel,
+            ItemCategoryDTO::Toll => ItemCategory::Toll,
+            ItemCategoryDTO::Repair => ItemCategory::Repair,
+            ItemCategoryDTO::Meal => ItemCategory::Meal,
+            ItemCategoryDTO::Lodging => ItemCategory::Lodging,
+            ItemCategoryDTO::Other => ItemCategory::Other,
+        }
+    }
+}
+
+impl From<ItemCategory> for ItemCategoryDTO {
+    fn from(category: ItemCategory) -> Self {
+        match category {
+            ItemCategory::Fuel => ItemCategoryDTO::Fuel,
+            ItemCategory::Toll => ItemCategoryDTO::Toll,
+            ItemCategory::Repair => ItemCategoryDTO::Repair,
+            ItemCategory::Meal => ItemCategoryDTO::Meal,
+            ItemCategory::Lodging => ItemCategoryDTO::Lodging,
+            ItemCategory::Other => ItemCategoryDTO::Other,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct ItemCreateDTO {
+    #[oai(validator(max_length = 128))]
+    name: String,
+
+    icon_url: Option<String>,
+
+    /// `OTHER` when omitted. Billing items of a `FUEL` item carry a fill-up.
+    category: Option<ItemCategoryDTO>,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct ItemUpdateDTO {
+    #[oai(validator(max_length = 128))]
+    name: Option<String>,
+
+    icon_url: Option<String>,
+
+    category: Option<ItemCategoryDTO>,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct ItemEntityDTO {
+    item_id: String,
+    item_type: String,
+    name: String,
+    team_id: Option<String>,
+    icon_url: Option<String>,
+    archived: bool,
+    category: ItemCategoryDTO,
+}
+
+impl From<Item> for ItemEntityDTO {
+    fn from(item: Item) -> Self {
+        ItemEntityDTO {
+            item_id: item.id.to_string(),
+            item_type: item.item_type.to_string(),
+            name: item.name,
+            team_id: item.team_id.map(|team_id| team_id.to_string()),
+            icon_url: item.icon_url,
+            archived: item.archived,
+            category: item.category.into(),
+        }
+    }
+}
+
+#[derive(ApiResponse)]
+enum QueryItemResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<ItemEntityDTO>>),
+}
+
+#[derive(ApiResponse)]
+enum ItemResponse {
+    #[oai(status = 200)]
+    Ok(Json<ItemEntityDTO>),
+
+    #[oai(status = 201)]
+    Created(Json<ItemEntityDTO>),
+}
+
+#[derive(ApiResponse)]
+enum DeleteItemResponse {
+    /// The item was used by billings or templates and has been archived
+    #[oai(status = 200)]
+    Archived(Json<ItemEntityDTO>),
+
+    #[oai(status = 204)]
+    Deleted,
+}
+
+fn parse_team_item_id(team_id: &str, item_id: &str) -> Result<(Uuid, Uuid), AppError> {
+    Ok((
+        parse_uuid("team_id", team_id)?,
+        parse_uuid("item_id", item_id)?,
+    ))
+}
+
+pub struct ItemRouter;
+
+#[OpenApi]
+impl ItemRouter {
+    #[oai(path = "/item", method = "get", tag = "ApiTags::Item")]
+    async fn query_global_item(
+        &self,
+        state: Data<&AppState>,
+        _session_user: SessionUser,
+    ) -> ApiResult<QueryItemResponse> {
+        let items = Item::query_global(&state.db).await?;
+        Ok(QueryItemResponse::Ok(Json(
+            items.into_iter().map(|item| item.into()).collect(),
+        )))
+    }
+
+    #[oai(path = "/team/:team_id/item", method = "get", tag = "ApiTags::Item")]
+    async fn query_team_item(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        include_archived: Query<Option<bool>>,
+    ) -> ApiResult<QueryItemResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Member).await?;
+        let items =
+            Item::query_team(&state.db, team_uuid, include_archived.0.unwrap_or(false)).await?;
+        Ok(QueryItemResponse::Ok(Json(
+            items.into_iter().map(|item| item.into()).collect(),
+        )))
+    }
+
+    #[oai(path = "/team/:team_id/item", method = "post", tag = "ApiTags::Item")]
+    async fn create_item(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        item: Json<ItemCreateDTO>,
+    ) -> ApiResult<ItemResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let item = item.0;
+        let item = Item::create_custom(
+            &state.db,
+            team_uuid,
+            item.name,
+            item.icon_url,
+            item.category.map(|category| category.into()),
+        )
+        .await?;
+        Ok(ItemResponse::Created(Json(item.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/item/:item_id",
+        method = "put",
+        tag = "ApiTags::Item"
+    )]
+    async fn update_item(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        item_id: Path<String>,
+        item: Json<ItemUpdateDTO>,
+    ) -> ApiResult<ItemResponse> {
+        let (team_uuid, item_uuid) = parse_team_item_id(&team_id.0, &item_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let team_item = Item::from_team_id(&state.db, team_uuid, item_uuid).await?;
+        let item = item.0;
+        let item = team_item
+            .update(
+                &state.db,
+                item.name,
+                item.icon_url,
+                item.category.map(|category| category.into()),
+            )
+            .await?;
+        Ok(ItemResponse::Ok(Json(item.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/item/:item_id",
+        method = "delete",
+        tag = "ApiTags::Item"
+    )]
+    async fn delete_item(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        item_id: Path<String>,
+    ) -> ApiResult<DeleteItemResponse> {
+        let (team_uuid, item_uuid) = parse_team_item_id(&team_id.0, &item_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let team_item = Item::from_team_id(&state.db, team_uuid, item_uuid).await?;
+        Ok(match team_item.remove(&state.db).await? {
+            ItemRemoval::Deleted => DeleteItemResponse::Deleted,
+            ItemRemoval::Archived(item) => DeleteItemResponse::Archived(Json(item.into())),
+        })
+    }
+}
@@ -0,0 +1,215 @@
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, IntoActiveModel, ModelTrait,
+    PaginatorTrait, QueryFilter, QueryOrder, Set,
+};
+use tracing::{info, instrument};
+use uuid::Uuid;
+
+use crate::{
+    entities::{
+        billing_expected_item, billing_item, billing_template_item, item,
+        sea_orm_active_enums::{ItemCategory, ItemType},
+    },
+    error::{AppError, ErrorCode},
+};
+
+fn item_not_found(item_id: Uuid) -> AppError {
+    AppError::new(
+        ErrorCode::ItemNotFound,
+        format!("can not find custom item {}", item_id),
+    )
+}
+
+#[derive(Debug)]
+pub struct Item {
+    pub id: Uuid,
+    pub item_type: ItemType,
+    pub name: String,
+    pub team_id: Option<Uuid>,
+    pub icon_url: Option<String>,
+    pub archived: bool,
+    /// `FUEL` items record litres, price and odometer with every billing item.
+    pub category: ItemCategory,
+}
+
+/// Items already used by billing items, template items or expected items are archived instead
+/// of deleted.
+#[derive(Debug)]
+pub enum ItemRemoval {
+    Deleted,
+    Archived(Item),
+}
+
+impl From<item::Model> for Item {
+    fn from(item_model: item::Model) -> Self {
+        Item {
+            id: item_model.id,
+            item_type: item_model.r#type,
+            name: item_model.name,
+            team_id: item_model.team_id,
+            icon_url: item_model.icon_url,
+            archived: item_model.archived,
+            category: item_model.category,
+        }
+    }
+}
+
+impl Item {
+    /// BASIC and DEFAULT items shared by every team.
+    #[instrument(skip(db))]
+    pub async fn query_global<C: ConnectionTrait>(db: &C) -> Result<Vec<Item>, AppError> {
+        let item_models = item::Entity::find()
+            .filter(item::Column::TeamId.is_null())
+            .filter(item::Column::Type.is_in([ItemType::Basic, ItemType::Default]))
+            .filter(item::Column::Archived.eq(false))
+            .order_by_asc(item::Column::Name)
+            .all(db)
+            .await?;
+        Ok(item_models
+            .into_iter()
+            .map(|item_model| item_model.into())
+            .collect())
+    }
+
+    #[instrument(skip(db))]
+    pub async fn query_team<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        include_archived: bool,
+    ) -> Result<Vec<Item>, AppError> {
+        let mut select = item::Entity::find()
+            .filter(item::Column::TeamId.eq(team_id))
+            .filter(item::Column::Type.eq(ItemType::Custom));
+        if !include_archived {
+            select = select.filter(item::Column::Archived.eq(false));
+        }
+        let item_models = select.order_by_asc(item::Column::Name).all(db).await?;
+        Ok(item_models
+            .into_iter()
+            .map(|item_model| item_model.into())
+            .collect())
+    }
+
+    #[instrument(skip(db))]
+    pub async fn from_team_id<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        item_id: Uuid,
+    ) -> Result<Item, AppError> {
+        let item_model = item::Entity::find_by_id(item_id)
+            .filter(item::Column::TeamId.eq(team_id))
+            .filter(item::Column::Type.eq(ItemType::Custom))
+            .one(db)
+            .await?
+            .ok_or_else(|| item_not_found(item_id))?;
+        Ok(item_model.into())
+    }
+
+    #[instrument(skip(db))]
+    pub async fn create_custom<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        name: String,
+        icon_url: Option<String>,
+        category: Option<ItemCategory>,
+    ) -> Result<Item, AppError> {
+        Self::check_name_unused(db, team_id, &name, None).await?;
+        let item_model = item::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            r#type: Set(ItemType::Custom),
+            name: Set(name),
+            team_id: Set(Some(team_id)),
+            icon_url: Set(icon_url),
+            archived: Set(false),
+            category: Set(category.unwrap_or(ItemCategory::Other)),
+        }
+        .insert(db)
+        .await?;
+        Ok(item_model.into())
+    }
+
+    #[instrument(skip(db))]
+    pub async fn update<C: ConnectionTrait>(
+        self,
+        db: &C,
+        name: Option<String>,
+        icon_url: Option<String>,
+        category: Option<ItemCategory>,
+    ) -> Result<Item, AppError> {
+        let item_model = item::Entity::find_by_id(self.id)
+            .one(db)
+            .await?
+            .ok_or_else(|| item_not_found(self.id))?;
+        let mut item_active_model = item_model.into_active_model();
+        if let Some(name) = name {
+            if let Some(team_id) = self.team_id {
+                Self::check_name_unused(db, team_id, &name, Some(self.id)).await?;
+            }
+            item_active_model.name = Set(name);
+        }
+        if let Some(icon_url) = icon_url {
+            item_active_model.icon_url = Set(Some(icon_url));
+        }
+        if let Some(category) = category {
+            item_active_model.category = Set(category);
+        }
+        let item_model = item_active_model.update(db).await?;
+        Ok(item_model.into())
+    }
+
+    #[instrument(skip(db))]
+    pub async fn remove<C: ConnectionTrait>(self, db: &C) -> Result<ItemRemoval, AppError> {
+        let item_model = item::Entity::find_by_id(self.id)
+            .one(db)
+            .await?
+            .ok_or_else(|| item_not_found(self.id))?;
+        // Billing items, template items and expected items all keep a foreign key to the
+        // item, it can only be deleted while none of them refers to it.
+        let billing_item_count = billing_item::Entity::find()
+            .filter(billing_item::Column::ItemId.eq(self.id))
+            .count(db)
+            .await?;
+        let template_item_count = billing_template_item::Entity::find()
+            .filter(billing_template_item::Column::ItemId.eq(self.id))
+            .count(db)
+            .await?;
+        let expected_item_count = billing_expected_item::Entity::find()
+            .filter(billing_expected_item::Column::ItemId.eq(self.id))
+            .count(db)
+            .await?;
+        if billing_item_count + template_item_count + expected_item_count == 0 {
+            item_model.delete(db).await?;
+            return Ok(ItemRemoval::Deleted);
+        }
+        info!(
+            "item {} is used by {} billing items, {} template items and {} expected items, archive it",
+            self.id, billing_item_count, template_item_count, expected_item_count
+        );
+        let mut item_active_model = item_model.into_active_model();
+        item_active_model.archived = Set(true);
+        let item_model = item_active_model.update(db).await?;
+        Ok(ItemRemoval::Archived(item_model.into()))
+    }
+
+    async fn check_name_unused<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        name: &str,
+        except_id: Option<Uuid>,
+    ) -> Result<(), AppError> {
+        let mut select = item::Entity::find()
+            .filter(item::Column::TeamId.eq(team_id))
+            .filter(item::Column::Archived.eq(false))
+            .filter(item::Column::Name.eq(name));
+        if let Some(except_id) = except_id {
+            select = select.filter(item::Column::Id.ne(except_id));
+        }
+        if select.one(db).await?.is_some() {
+            return Err(AppError::new(
+                ErrorCode::DuplicateName,
+                format!("custom item named {} already exists", name),
+            ));
+        }
+        Ok(())
+    }
+}
@@ -1,60 +1,124 @@
-#[macro_use]
-extern crate lazy_static;
 extern crate dotenv;
 
+mod auth_service;
 mod billing_service;
+mod config;
+mod customer_service;
+mod dispatch_service;
 mod entities;
+mod error;
+mod fuel_service;
+mod income_service;
+mod invite_service;
+mod item_service;
+mod payroll_service;
+mod reminder_service;
+mod report_service;
 mod role_service;
+mod session_service;
+mod settlement_service;
+mod state;
 mod team_service;
+mod template_service;
+#[cfg(test)]
+mod test_db;
 mod user_service;
 
 use billing_service::controller::BillingRouter;
+use config::AppConfig;
+use customer_service::controller::CustomerRouter;
+use dispatch_service::controller::DispatchRouter;
 use dotenv::dotenv;
+use fuel_service::controller::FuelRouter;
+use income_service::controller::IncomeRouter;
+use invite_service::controller::InviteRouter;
+use item_service::controller::ItemRouter;
+use migration::{Migrator, MigratorTrait};
+use payroll_service::controller::PayrollRouter;
 use poem::{
     error::NotFoundError, http::StatusCode, listener::TcpListener, EndpointExt, Response, Route,
     Server,
 };
 use poem_openapi::OpenApiService;
+use reminder_service::{controller::ReminderRouter, job::spawn_reminder_job};
+use report_service::controller::ReportRouter;
 use role_service::controller::UserRoleRouter;
 use sea_orm::*;
+use session_service::{controller::SessionRouter, middleware::SessionMiddleware};
+use settlement_service::controller::SettlementRouter;
+use state::AppState;
 use std::env;
 use team_service::controller::TeamRouter;
-use tokio::sync::OnceCell;
-use tracing::log::warn;
+use template_service::controller::TemplateRouter;
+use tracing::log::{error, info};
 
 use user_service::controller::UserRouter;
 
-lazy_static! {
-    static ref DATABASE: OnceCell<DatabaseConnection> = OnceCell::new();
-}
-
 #[tokio::main]
 async fn main() -> Result<(), std::io::Error> {
     dotenv().ok();
+    let config = match AppConfig::load() {
+        Ok(config) => config,
+        Err(err) => {
+            // Logging is configured from these settings, so it is not up yet.
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
 
-    let file_appender = tracing_appender::rolling::daily("./logs", "run.log");
+    let file_appender = tracing_appender::rolling::daily(&config.log.dir, "run.log");
     let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
     tracing_subscriber::fmt()
         .with_writer(non_blocking)
-        .with_max_level(tracing::Level::INFO)
+        .with_max_level(config.log.max_level())
         .with_test_writer()
         .init();
 
-    let db_con = Database::connect(env::var("DATABASE_URL").unwrap())
-        .await
-        .unwrap();
-    if let Err(e) = DATABASE.set(db_con) {
-        warn!("set global db error {}", e);
+    let mut connect_options = ConnectOptions::new(config.database.url.clone());
+    connect_options
+        .max_connections(config.database.max_connections)
+        .min_connections(config.database.min_connections);
+    let db_con = match Database::connect(connect_options).await {
+        Ok(db_con) => db_con,
+        Err(err) => {
+            error!("connect database error {}", err);
+            return Err(std::io::Error::other(err));
+        }
+    };
+    let migrate_only = env::args().nth(1).as_deref() == Some("migrate");
+    if migrate_only || config.database.run_migrations {
+        if let Err(err) = Migrator::up(&db_con, None).await {
+            error!("run migrations error {}", err);
+            return Err(std::io::Error::other(err));
+        }
+        info!("migrations are up to date");
+        if migrate_only {
+            return Ok(());
+        }
     }
-
-    let bind_addr = format!(
-        "{}:{}",
-        env::var("SERVER").unwrap(),
-        env::var("PORT").unwrap()
-    );
+    let state = AppState::new(db_con, config);
+    let bind_addr = state.config.server.bind_addr();
+    spawn_reminder_job(state.clone());
 
     let api_service = OpenApiService::new(
-        (UserRouter, UserRoleRouter, TeamRouter, BillingRouter),
+        (
+            UserRouter,
+            SessionRouter,
+            UserRoleRouter,
+            TeamRouter,
+            InviteRouter,
+            BillingRouter,
+            DispatchRouter,
+            ItemRouter,
+            TemplateRouter,
+            ReminderRouter,
+            FuelRouter,
+            IncomeRouter,
+            CustomerRouter,
+            SettlementRouter,
+            PayrollRouter,
+            ReportRouter,
+        ),
         "Truck Billing Service",
         "1.0",
     )
@@ -65,6 +129,8 @@ async fn main() -> Result<(), std::io::Error> {
     let app = Route::new()
         .nest("/", api_service)
         .nest("/docs", ui)
+        .with(SessionMiddleware::new(state.db.clone()))
+        .data(state)
         .catch_error(|_err: NotFoundError| async move {
             Response::builder()
                 .status(StatusCode::NOT_FOUND)
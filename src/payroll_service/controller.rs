@@ -0,0 +1,486 @@
+use chrono::{DateTime, Local, NaiveDate};
+use poem::web::Data;
+use poem_openapi::{
+    param::Path,
+    payload::{Attachment, Json},
+    ApiResponse, Enum, Object, OpenApi, Tags,
+};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::auth_service::service::{TeamGuard, TeamPermission};
+use crate::entities::sea_orm_active_enums::WageRuleType;
+use crate::error::{parse_uuid, ApiResult, AppError, ErrorCode};
+use crate::item_service::controller::ItemCategoryDTO;
+use crate::session_service::service::SessionUser;
+use crate::state::AppState;
+
+use super::payslip::{DriverPay, PayrollRun, PayslipLine};
+use super::service::{WageRule, WageRuleFields};
+
+#[derive(Tags)]
+enum ApiTags {
+    /// Driver wage rules and the monthly payroll computed from ended billings
+    Payroll,
+}
+
+#[derive(Debug, Enum, Clone, Copy, Eq, PartialEq)]
+#[oai(rename_all = "SCREAMING_SNAKE_CASE")]
+enum WageRuleTypeDTO {
+    PerTrip,
+    PerKm,
+    Commission,
+}
+
+impl From<WageRuleTypeDTO> for WageRuleType {
+    fn from(rule_type: WageRuleTypeDTO) -> Self {
+        match rule_type {
+            WageRuleTypeDTO::PerTrip => WageRuleType::PerTrip,
+            WageRuleTypeDTO::PerKm => WageRuleType::PerKm,
+            WageRuleTypeDTO::Commission => WageRuleType::Commission,
+        }
+    }
+}
+
+impl From<WageRuleType> for WageRuleTypeDTO {
+    fn from(rule_type: WageRuleType) -> Self {
+        match rule_type {
+            WageRuleType::PerTrip => WageRuleTypeDTO::PerTrip,
+            WageRuleType::PerKm => WageRuleTypeDTO::PerKm,
+            WageRuleType::Commission => WageRuleTypeDTO::Commission,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct WageRuleUpdateDTO {
+    rule_type: WageRuleTypeDTO,
+
+    /// Amount per trip, amount per km, or percentage of the billing income for commission.
+    rate: Decimal,
+
+    /// Cost categories taken off the billing income before commission, commission only.
+    deducted_categories: Option<Vec<ItemCategoryDTO>>,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct WageRuleEntityDTO {
+    wage_rule_id: String,
+    user_id: String,
+    rule_type: WageRuleTypeDTO,
+    rate: Decimal,
+    deducted_categories: Vec<ItemCategoryDTO>,
+    update_time: Option<DateTime<Local>>,
+}
+
+impl From<WageRule> for WageRuleEntityDTO {
+    fn from(rule: WageRule) -> Self {
+        WageRuleEntityDTO {
+            wage_rule_id: rule.id.to_string(),
+            user_id: rule.user_id,
+            rule_type: rule.fields.rule_type.into(),
+            rate: rule.fields.rate,
+            deducted_categories: rule
+                .fields
+                .deducted_categories
+                .into_iter()
+                .map(|category| category.into())
+                .collect(),
+            update_time: rule.update_time,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct PayrollCreateDTO {
+    #[oai(validator(minimum(value = "2000"), maximum(value = "9999")))]
+    year: i32,
+
+    #[oai(validator(minimum(value = "1"), maximum(value = "12")))]
+    month: u32,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct PayslipLineDTO {
+    user_id: String,
+    user_name: Option<String>,
+    billing_id: String,
+    billing_name: String,
+    billing_end_time: Option<DateTime<Local>>,
+    rule_type: WageRuleTypeDTO,
+    /// Trips, km driven, or the driver's share of income less deducted costs.
+    quantity: Decimal,
+    rate: Decimal,
+    amount: Decimal,
+}
+
+impl From<PayslipLine> for PayslipLineDTO {
+    fn from(line: PayslipLine) -> Self {
+        PayslipLineDTO {
+            user_id: line.user_id,
+            user_name: line.user_name,
+            billing_id: line.billing_id.to_string(),
+            billing_name: line.billing_name,
+            billing_end_time: line.billing_end_time,
+            rule_type: line.rule_type.into(),
+            quantity: line.quantity,
+            rate: line.rate,
+            amount: line.amount,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct DriverPayDTO {
+    user_id: String,
+    user_name: Option<String>,
+    line_count: u64,
+    total: Decimal,
+}
+
+impl From<DriverPay> for DriverPayDTO {
+    fn from(pay: DriverPay) -> Self {
+        DriverPayDTO {
+            user_id: pay.user_id,
+            user_name: pay.user_name,
+            line_count: pay.line_count as u64,
+            total: pay.total,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct PayrollEntityDTO {
+    payroll_id: String,
+    /// First day of the month paid.
+    period_start: NaiveDate,
+    created_by: Option<String>,
+    create_time: Option<DateTime<Local>>,
+    confirmed_by: Option<String>,
+    confirm_time: Option<DateTime<Local>>,
+    total: Decimal,
+    /// Only given when a single payroll is fetched.
+    drivers: Option<Vec<DriverPayDTO>>,
+    lines: Option<Vec<PayslipLineDTO>>,
+}
+
+impl From<PayrollRun> for PayrollEntityDTO {
+    fn from(run: PayrollRun) -> Self {
+        let drivers = run.lines.as_ref().map(|_| {
+            run.driver_pays()
+                .into_iter()
+                .map(|pay| pay.into())
+                .collect()
+        });
+        PayrollEntityDTO {
+            payroll_id: run.id.to_string(),
+            period_start: run.period_start,
+            created_by: run.created_by,
+            create_time: run.create_time,
+            confirmed_by: run.confirmed_by,
+            confirm_time: run.confirm_time,
+            total: run.total,
+            drivers,
+            lines: run
+                .lines
+                .map(|lines| lines.into_iter().map(|line| line.into()).collect()),
+        }
+    }
+}
+
+#[derive(ApiResponse)]
+enum WageRuleResponse {
+    #[oai(status = 200)]
+    Ok(Json<WageRuleEntityDTO>),
+}
+
+#[derive(ApiResponse)]
+enum QueryWageRuleResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<WageRuleEntityDTO>>),
+}
+
+#[derive(ApiResponse)]
+enum DeleteResponse {
+    #[oai(status = 204)]
+    Ok,
+}
+
+#[derive(ApiResponse)]
+enum PayrollResponse {
+    #[oai(status = 200)]
+    Ok(Json<PayrollEntityDTO>),
+}
+
+#[derive(ApiResponse)]
+enum QueryPayrollResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<PayrollEntityDTO>>),
+}
+
+#[derive(ApiResponse)]
+enum PayrollExportResponse {
+    #[oai(status = 200, content_type = "text/csv")]
+    Ok(Attachment<Vec<u8>>),
+}
+
+pub struct PayrollRouter;
+
+#[OpenApi]
+impl PayrollRouter {
+    #[oai(
+        path = "/team/:team_id/user/:user_id/wage",
+        method = "put",
+        tag = "ApiTags::Payroll"
+    )]
+    async fn set_wage_rule(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        user_id: Path<String>,
+        rule: Json<WageRuleUpdateDTO>,
+    ) -> ApiResult<WageRuleResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let rule = rule.0;
+        let fields = WageRuleFields {
+            rule_type: rule.rule_type.into(),
+            rate: rule.rate,
+            deducted_categories: rule
+                .deducted_categories
+                .unwrap_or_default()
+                .into_iter()
+                .map(|category| category.into())
+                .collect(),
+        };
+        let rule = WageRule::set(&state.db, team_uuid, &user_id.0, fields).await?;
+        Ok(WageRuleResponse::Ok(Json(rule.into())))
+    }
+
+    /// Drivers can read their own rule.
+    #[oai(
+        path = "/team/:team_id/user/:user_id/wage",
+        method = "get",
+        tag = "ApiTags::Payroll"
+    )]
+    async fn get_wage_rule(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        user_id: Path<String>,
+    ) -> ApiResult<WageRuleResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let role =
+            TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Member).await?;
+        if !role.allows(TeamPermission::Manage) && user_id.0 != session_user.user_id {
+            return Err(AppError::forbidden("can only read your own wage rule").into());
+        }
+        let rule = WageRule::of_driver(&state.db, team_uuid, &user_id.0).await?;
+        Ok(WageRuleResponse::Ok(Json(rule.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/user/:user_id/wage",
+        method = "delete",
+        tag = "ApiTags::Payroll"
+    )]
+    async fn delete_wage_rule(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        user_id: Path<String>,
+    ) -> ApiResult<DeleteResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        WageRule::delete(&state.db, team_uuid, &user_id.0).await?;
+        Ok(DeleteResponse::Ok)
+    }
+
+    #[oai(path = "/team/:team_id/wage", method = "get", tag = "ApiTags::Payroll")]
+    async fn query_wage_rule(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+    ) -> ApiResult<QueryWageRuleResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let rules = WageRule::of_team(&state.db, team_uuid).await?;
+        Ok(QueryWageRuleResponse::Ok(Json(
+            rules.into_iter().map(|rule| rule.into()).collect(),
+        )))
+    }
+
+    /// Computes the payroll of the month from the billings that ended in it, again when it
+    /// was computed before and is not confirmed yet.
+    #[oai(
+        path = "/team/:team_id/payroll",
+        method = "post",
+        tag = "ApiTags::Payroll"
+    )]
+    async fn run_payroll(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        period: Json<PayrollCreateDTO>,
+    ) -> ApiResult<PayrollResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let period_start = PayrollRun::period_of(period.year, period.month).ok_or_else(|| {
+            AppError::new(
+                ErrorCode::InvalidArgument,
+                format!("invalid month {}-{}", period.year, period.month),
+            )
+        })?;
+        let run = PayrollRun::run(&state.db, team_uuid, period_start, session_user.user_id).await?;
+        Ok(PayrollResponse::Ok(Json(run.into())))
+    }
+
+    /// Latest month first. Drivers only see confirmed payrolls with the total of their own pay.
+    #[oai(
+        path = "/team/:team_id/payroll",
+        method = "get",
+        tag = "ApiTags::Payroll"
+    )]
+    async fn query_payroll(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+    ) -> ApiResult<QueryPayrollResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let role =
+            TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Member).await?;
+        let user_id = (!role.allows(TeamPermission::Manage)).then_some(session_user.user_id);
+        let runs = PayrollRun::query(&state.db, team_uuid, user_id.is_some(), user_id).await?;
+        Ok(QueryPayrollResponse::Ok(Json(
+            runs.into_iter().map(|run| run.into()).collect(),
+        )))
+    }
+
+    /// Drivers only see their own lines of confirmed payrolls.
+    #[oai(
+        path = "/team/:team_id/payroll/:payroll_id",
+        method = "get",
+        tag = "ApiTags::Payroll"
+    )]
+    async fn get_payroll(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        payroll_id: Path<String>,
+    ) -> ApiResult<PayrollResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let payroll_uuid = parse_uuid("payroll_id", &payroll_id.0)?;
+        let role =
+            TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Member).await?;
+        let run = visible_payroll(
+            &state,
+            team_uuid,
+            payroll_uuid,
+            role.allows(TeamPermission::Manage),
+            session_user.user_id,
+        )
+        .await?;
+        Ok(PayrollResponse::Ok(Json(run.into())))
+    }
+
+    /// Payslip lines as CSV, drivers only get their own lines of confirmed payrolls.
+    #[oai(
+        path = "/team/:team_id/payroll/:payroll_id/export",
+        method = "get",
+        tag = "ApiTags::Payroll"
+    )]
+    async fn export_payroll(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        payroll_id: Path<String>,
+    ) -> ApiResult<PayrollExportResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let payroll_uuid = parse_uuid("payroll_id", &payroll_id.0)?;
+        let role =
+            TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Member).await?;
+        let run = visible_payroll(
+            &state,
+            team_uuid,
+            payroll_uuid,
+            role.allows(TeamPermission::Manage),
+            session_user.user_id,
+        )
+        .await?;
+        let filename = format!("payroll-{}.csv", run.period_start.format("%Y-%m"));
+        Ok(PayrollExportResponse::Ok(
+            Attachment::new(run.to_csv().into_bytes()).filename(filename),
+        ))
+    }
+
+    /// Locks the payroll, it can no longer be computed again or deleted.
+    #[oai(
+        path = "/team/:team_id/payroll/:payroll_id/confirm",
+        method = "post",
+        tag = "ApiTags::Payroll"
+    )]
+    async fn confirm_payroll(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        payroll_id: Path<String>,
+    ) -> ApiResult<PayrollResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let payroll_uuid = parse_uuid("payroll_id", &payroll_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let run = PayrollRun::from_id(&state.db, team_uuid, payroll_uuid, None).await?;
+        let run = run.confirm(&state.db, session_user.user_id).await?;
+        Ok(PayrollResponse::Ok(Json(run.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/payroll/:payroll_id",
+        method = "delete",
+        tag = "ApiTags::Payroll"
+    )]
+    async fn delete_payroll(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        payroll_id: Path<String>,
+    ) -> ApiResult<DeleteResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let payroll_uuid = parse_uuid("payroll_id", &payroll_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let run = PayrollRun::from_id(&state.db, team_uuid, payroll_uuid, None).await?;
+        run.delete(&state.db).await?;
+        Ok(DeleteResponse::Ok)
+    }
+}
+
+/// The payroll with all lines for managers, only the driver's own lines once confirmed for
+/// drivers.
+async fn visible_payroll(
+    state: &AppState,
+    team_id: Uuid,
+    payroll_id: Uuid,
+    is_manager: bool,
+    user_id: String,
+) -> Result<PayrollRun, AppError> {
+    if is_manager {
+        return PayrollRun::from_id(&state.db, team_id, payroll_id, None).await;
+    }
+    let run = PayrollRun::from_id(&state.db, team_id, payroll_id, Some(user_id)).await?;
+    if !run.is_confirmed() {
+        return Err(AppError::new(
+            ErrorCode::PayrollNotFound,
+            format!("can not find payroll {}", payroll_id),
+        ));
+    }
+    Ok(run)
+}
@@ -0,0 +1,926 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime};
+use rust_decimal::Decimal;
+use sea_orm::{
+    sea_query::Expr, ActiveEnum, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait,
+    FromQueryResult, ModelTrait, QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use tracing::{instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    billing_service::service::parse_navie_time_to_data_time,
+    entities::{
+        billing, billing_item, dispatch, dispatch_driver, item, payroll_run, payslip_line,
+        sea_orm_active_enums::{ItemCategory, WageRuleType},
+        user,
+    },
+    error::{AppError, ErrorCode},
+    income_service::service::BillingIncome,
+};
+
+use super::service::{WageRule, WageRuleFields};
+
+fn payroll_not_found(payroll_id: Uuid) -> AppError {
+    AppError::new(
+        ErrorCode::PayrollNotFound,
+        format!("can not find payroll {}", payroll_id),
+    )
+}
+
+fn payroll_locked(period_start: NaiveDate) -> AppError {
+    AppError::new(
+        ErrorCode::PayrollLocked,
+        format!(
+            "payroll of {} is confirmed and can not change",
+            period_start.format("%Y-%m")
+        ),
+    )
+}
+
+/// Pay of one driver for one billing under their wage rule.
+#[derive(Debug, Clone)]
+pub struct PayslipLine {
+    pub user_id: String,
+    pub user_name: Option<String>,
+    pub billing_id: Uuid,
+    pub billing_name: String,
+    pub billing_end_time: Option<DateTime<Local>>,
+    pub rule_type: WageRuleType,
+    /// Trips, km driven, or the driver's share of income less deducted costs.
+    pub quantity: Decimal,
+    pub rate: Decimal,
+    pub amount: Decimal,
+}
+
+/// Total pay of one driver on a payroll run.
+#[derive(Debug, Clone)]
+pub struct DriverPay {
+    pub user_id: String,
+    pub user_name: Option<String>,
+    pub line_count: usize,
+    pub total: Decimal,
+}
+
+#[derive(Debug, Clone)]
+pub struct PayrollRun {
+    pub id: Uuid,
+    /// First day of the month paid.
+    pub period_start: NaiveDate,
+    pub created_by: Option<String>,
+    pub create_time: Option<DateTime<Local>>,
+    pub confirmed_by: Option<String>,
+    pub confirm_time: Option<DateTime<Local>>,
+    /// Ordered by billing end and driver, only loaded for a single run.
+    pub lines: Option<Vec<PayslipLine>>,
+    pub total: Decimal,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct RunTotal {
+    payroll_run_id: Uuid,
+    total: Option<Decimal>,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct CategoryCost {
+    billing_id: Option<Uuid>,
+    category: ItemCategory,
+    cost: Option<Decimal>,
+}
+
+/// Trips and km a driver made for one billing.
+#[derive(Debug, Default)]
+struct DriverTrips {
+    trips: i64,
+    km: i64,
+}
+
+impl From<payroll_run::Model> for PayrollRun {
+    fn from(model: payroll_run::Model) -> Self {
+        PayrollRun {
+            id: model.id,
+            period_start: model.period_start,
+            created_by: model.created_by,
+            create_time: parse_navie_time_to_data_time(Some(model.create_time)),
+            confirmed_by: model.confirmed_by,
+            confirm_time: parse_navie_time_to_data_time(model.confirm_time),
+            lines: None,
+            total: Decimal::ZERO,
+        }
+    }
+}
+
+/// First day of the month after the one `period_start` is in.
+fn next_period(period_start: NaiveDate) -> NaiveDate {
+    if period_start.month() == 12 {
+        NaiveDate::from_ymd_opt(period_start.year() + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(period_start.year(), period_start.month() + 1, 1)
+    }
+    .unwrap_or(period_start)
+}
+
+/// Quotes a CSV field when it holds a separator, a quote or a line break.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_owned()
+    }
+}
+
+/// Quantity and amount of a driver's line on one billing. Commission is a share of the
+/// billing income less the costs, keyed by category, of the categories the rule deducts.
+fn line_pay(
+    fields: &WageRuleFields,
+    driver_trips: &DriverTrips,
+    driver_count: usize,
+    income: Decimal,
+    costs: &HashMap<String, Decimal>,
+) -> (Decimal, Decimal) {
+    match fields.rule_type {
+        WageRuleType::PerTrip => {
+            let quantity = Decimal::from(driver_trips.trips);
+            (quantity, (quantity * fields.rate).round_dp(2))
+        }
+        WageRuleType::PerKm => {
+            let quantity = Decimal::from(driver_trips.km);
+            (quantity, (quantity * fields.rate).round_dp(2))
+        }
+        WageRuleType::Commission => {
+            let deducted: Decimal = fields
+                .deducted_categories
+                .iter()
+                .filter_map(|category| costs.get(&category.to_value()))
+                .sum();
+            let quantity = ((income - deducted) / Decimal::from(driver_count))
+                .max(Decimal::ZERO)
+                .round_dp(2);
+            (
+                quantity,
+                (quantity * fields.rate / Decimal::ONE_HUNDRED).round_dp(2),
+            )
+        }
+    }
+}
+
+impl PayrollRun {
+    pub fn is_confirmed(&self) -> bool {
+        self.confirm_time.is_some()
+    }
+
+    /// First day of the month, `None` when `month` is out of range.
+    pub fn period_of(year: i32, month: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(year, month, 1)
+    }
+
+    /// Pay per driver of the loaded lines, ordered by driver.
+    pub fn driver_pays(&self) -> Vec<DriverPay> {
+        let mut pays: BTreeMap<String, DriverPay> = BTreeMap::new();
+        for line in self.lines.as_deref().unwrap_or_default() {
+            let pay = pays
+                .entry(line.user_id.clone())
+                .or_insert_with(|| DriverPay {
+                    user_id: line.user_id.clone(),
+                    user_name: line.user_name.clone(),
+                    line_count: 0,
+                    total: Decimal::ZERO,
+                });
+            pay.line_count += 1;
+            pay.total += line.amount;
+        }
+        pays.into_values().collect()
+    }
+
+    /// The loaded lines as CSV, one row per line.
+    pub fn to_csv(&self) -> String {
+        let month = self.period_start.format("%Y-%m").to_string();
+        let mut csv = String::from(
+            "month,user_id,user_name,billing_id,billing_name,billing_end_date,rule_type,quantity,rate,amount\n",
+        );
+        for line in self.lines.as_deref().unwrap_or_default() {
+            let row = [
+                month.clone(),
+                csv_field(&line.user_id),
+                csv_field(line.user_name.as_deref().unwrap_or_default()),
+                line.billing_id.to_string(),
+                csv_field(&line.billing_name),
+                line.billing_end_time
+                    .map(|end_time| end_time.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default(),
+                line.rule_type.to_string(),
+                line.quantity.normalize().to_string(),
+                line.rate.normalize().to_string(),
+                line.amount.to_string(),
+            ];
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Computes the pay of the month from the billings that ended in it. A run not confirmed
+    /// yet is computed again, a confirmed one is locked.
+    #[instrument(skip(db))]
+    pub async fn run<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        team_id: Uuid,
+        period_start: NaiveDate,
+        user_id: String,
+    ) -> Result<PayrollRun, AppError> {
+        let txn = db.begin().await?;
+        let existing = payroll_run::Entity::find()
+            .filter(payroll_run::Column::TeamId.eq(team_id))
+            .filter(payroll_run::Column::PeriodStart.eq(period_start))
+            .lock_exclusive()
+            .one(&txn)
+            .await?;
+        let run_model = match existing {
+            Some(run_model) if run_model.confirm_time.is_some() => {
+                warn!("payroll {} is confirmed, can not run again", run_model.id);
+                return Err(payroll_locked(period_start));
+            }
+            Some(run_model) => {
+                payslip_line::Entity::delete_many()
+                    .filter(payslip_line::Column::PayrollRunId.eq(run_model.id))
+                    .exec(&txn)
+                    .await?;
+                let mut active_model: payroll_run::ActiveModel = run_model.into();
+                active_model.created_by = Set(Some(user_id));
+                active_model.create_time = Set(Local::now().naive_local());
+                active_model.update(&txn).await?
+            }
+            None => {
+                payroll_run::ActiveModel {
+                    id: Set(Uuid::new_v4()),
+                    team_id: Set(team_id),
+                    period_start: Set(period_start),
+                    created_by: Set(Some(user_id)),
+                    create_time: Set(Local::now().naive_local()),
+                    confirmed_by: Set(None),
+                    confirm_time: Set(None),
+                }
+                .insert(&txn)
+                .await?
+            }
+        };
+        for line in Self::compute_lines(&txn, team_id, period_start).await? {
+            payslip_line::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                payroll_run_id: Set(run_model.id),
+                user_id: Set(line.user_id),
+                billing_id: Set(line.billing_id),
+                rule_type: Set(line.rule_type),
+                quantity: Set(line.quantity),
+                rate: Set(line.rate),
+                amount: Set(line.amount),
+            }
+            .insert(&txn)
+            .await?;
+        }
+        txn.commit().await?;
+        Self::from_id(db, team_id, run_model.id, None).await
+    }
+
+    /// One line per driver with a wage rule and billing ended in the month. Commission is
+    /// shared equally between the drivers dispatched on the billing.
+    async fn compute_lines<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        period_start: NaiveDate,
+    ) -> Result<Vec<PayslipLine>, AppError> {
+        let billing_models = billing::Entity::find()
+            .filter(billing::Column::TeamId.eq(team_id))
+            .filter(billing::Column::EndTime.gte(period_start.and_time(NaiveTime::MIN)))
+            .filter(billing::Column::EndTime.lt(next_period(period_start).and_time(NaiveTime::MIN)))
+            .order_by_asc(billing::Column::EndTime)
+            .all(db)
+            .await?;
+        if billing_models.is_empty() {
+            return Ok(vec![]);
+        }
+        let billing_ids: Vec<Uuid> = billing_models
+            .iter()
+            .map(|billing_model| billing_model.id)
+            .collect();
+
+        let mut trips: HashMap<Uuid, BTreeMap<String, DriverTrips>> = HashMap::new();
+        let dispatches = dispatch::Entity::find()
+            .filter(dispatch::Column::BillingId.is_in(billing_ids.clone()))
+            .find_with_related(dispatch_driver::Entity)
+            .all(db)
+            .await?;
+        for (dispatch_model, dispatch_driver_models) in dispatches {
+            let km = match (
+                dispatch_model.start_odometer_km,
+                dispatch_model.end_odometer_km,
+            ) {
+                (Some(start_km), Some(end_km)) => i64::from(end_km - start_km),
+                _ => 0,
+            };
+            let billing_trips = trips.entry(dispatch_model.billing_id).or_default();
+            for dispatch_driver_model in dispatch_driver_models {
+                let driver_trips = billing_trips
+                    .entry(dispatch_driver_model.user_id)
+                    .or_default();
+                driver_trips.trips += 1;
+                driver_trips.km += km;
+            }
+        }
+
+        let rules: HashMap<String, WageRule> = WageRule::of_team(db, team_id)
+            .await?
+            .into_iter()
+            .map(|rule| (rule.user_id.clone(), rule))
+            .collect();
+        let incomes = BillingIncome::totals(db, billing_ids.clone()).await?;
+        let mut category_costs: HashMap<Uuid, HashMap<String, Decimal>> = HashMap::new();
+        let rows = billing_item::Entity::find()
+            .select_only()
+            .column(billing_item::Column::BillingId)
+            .column(item::Column::Category)
+            .column_as(
+                Expr::tbl(billing_item::Entity, billing_item::Column::Cost).sum(),
+                "cost",
+            )
+            .inner_join(item::Entity)
+            .filter(billing_item::Column::BillingId.is_in(billing_ids))
+            .group_by(billing_item::Column::BillingId)
+            .group_by(item::Column::Category)
+            .into_model::<CategoryCost>()
+            .all(db)
+            .await?;
+        for row in rows {
+            if let Some(billing_id) = row.billing_id {
+                category_costs
+                    .entry(billing_id)
+                    .or_default()
+                    .insert(row.category.to_value(), row.cost.unwrap_or_default());
+            }
+        }
+
+        let mut lines = vec![];
+        for billing_model in billing_models {
+            let billing_trips = match trips.remove(&billing_model.id) {
+                Some(billing_trips) => billing_trips,
+                None => continue,
+            };
+            let driver_count = billing_trips.len();
+            let income = incomes
+                .get(&billing_model.id)
+                .map(|income| income.total_income)
+                .unwrap_or_default();
+            let costs = category_costs.remove(&billing_model.id).unwrap_or_default();
+            for (user_id, driver_trips) in billing_trips {
+                let rule = match rules.get(&user_id) {
+                    Some(rule) => rule,
+                    None => continue,
+                };
+                let (quantity, amount) =
+                    line_pay(&rule.fields, &driver_trips, driver_count, income, &costs);
+                lines.push(PayslipLine {
+                    user_id,
+                    user_name: None,
+                    billing_id: billing_model.id,
+                    billing_name: billing_model.name.clone(),
+                    billing_end_time: parse_navie_time_to_data_time(billing_model.end_time),
+                    rule_type: rule.fields.rule_type.clone(),
+                    quantity,
+                    rate: rule.fields.rate,
+                    amount,
+                });
+            }
+        }
+        Ok(lines)
+    }
+
+    /// A run with its lines, only those of `user_id` when given.
+    #[instrument(skip(db))]
+    pub async fn from_id<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        payroll_id: Uuid,
+        user_id: Option<String>,
+    ) -> Result<PayrollRun, AppError> {
+        let run_model = payroll_run::Entity::find_by_id(payroll_id)
+            .filter(payroll_run::Column::TeamId.eq(team_id))
+            .one(db)
+            .await?
+            .ok_or_else(|| payroll_not_found(payroll_id))?;
+        let mut select = run_model
+            .find_related(payslip_line::Entity)
+            .find_also_related(billing::Entity);
+        if let Some(user_id) = user_id {
+            select = select.filter(payslip_line::Column::UserId.eq(user_id));
+        }
+        let rows = select
+            .order_by_asc(billing::Column::EndTime)
+            .order_by_asc(payslip_line::Column::UserId)
+            .all(db)
+            .await?;
+        let user_ids: BTreeSet<String> = rows
+            .iter()
+            .map(|(line_model, _)| line_model.user_id.clone())
+            .collect();
+        let user_names: HashMap<String, String> = user::Entity::find()
+            .filter(user::Column::Id.is_in(user_ids))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|user_model| (user_model.id, user_model.user_name))
+            .collect();
+
+        let mut run: PayrollRun = run_model.into();
+        let lines: Vec<PayslipLine> = rows
+            .into_iter()
+            .filter_map(|(line_model, billing_model)| {
+                billing_model.map(|billing_model| PayslipLine {
+                    user_name: user_names.get(&line_model.user_id).cloned(),
+                    user_id: line_model.user_id,
+                    billing_id: billing_model.id,
+                    billing_name: billing_model.name,
+                    billing_end_time: parse_navie_time_to_data_time(billing_model.end_time),
+                    rule_type: line_model.rule_type,
+                    quantity: line_model.quantity,
+                    rate: line_model.rate,
+                    amount: line_model.amount,
+                })
+            })
+            .collect();
+        run.total = lines.iter().map(|line| line.amount).sum();
+        run.lines = Some(lines);
+        Ok(run)
+    }
+
+    /// Runs of the team, latest month first, only confirmed ones when `confirmed_only`. The
+    /// totals only count the lines of `user_id` when given.
+    #[instrument(skip(db))]
+    pub async fn query<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        confirmed_only: bool,
+        user_id: Option<String>,
+    ) -> Result<Vec<PayrollRun>, AppError> {
+        let mut select =
+            payroll_run::Entity::find().filter(payroll_run::Column::TeamId.eq(team_id));
+        if confirmed_only {
+            select = select.filter(payroll_run::Column::ConfirmTime.is_not_null());
+        }
+        let run_models = select
+            .order_by_desc(payroll_run::Column::PeriodStart)
+            .all(db)
+            .await?;
+        let mut totals_select = payslip_line::Entity::find()
+            .select_only()
+            .column(payslip_line::Column::PayrollRunId)
+            .column_as(
+                Expr::tbl(payslip_line::Entity, payslip_line::Column::Amount).sum(),
+                "total",
+            )
+            .inner_join(payroll_run::Entity)
+            .filter(payroll_run::Column::TeamId.eq(team_id));
+        if let Some(user_id) = user_id {
+            totals_select = totals_select.filter(payslip_line::Column::UserId.eq(user_id));
+        }
+        let totals: HashMap<Uuid, Decimal> = totals_select
+            .group_by(payslip_line::Column::PayrollRunId)
+            .into_model::<RunTotal>()
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|row| (row.payroll_run_id, row.total.unwrap_or_default()))
+            .collect();
+        Ok(run_models
+            .into_iter()
+            .map(|run_model| {
+                let total = totals.get(&run_model.id).copied().unwrap_or_default();
+                PayrollRun {
+                    total,
+                    ..run_model.into()
+                }
+            })
+            .collect())
+    }
+
+    /// Locks the run, its lines can no longer be computed again or removed.
+    #[instrument(skip(db))]
+    pub async fn confirm<C: ConnectionTrait + TransactionTrait>(
+        self,
+        db: &C,
+        user_id: String,
+    ) -> Result<PayrollRun, AppError> {
+        let txn = db.begin().await?;
+        let run_model = payroll_run::Entity::find_by_id(self.id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or_else(|| payroll_not_found(self.id))?;
+        if run_model.confirm_time.is_some() {
+            return Err(payroll_locked(run_model.period_start));
+        }
+        let mut active_model: payroll_run::ActiveModel = run_model.into();
+        active_model.confirmed_by = Set(Some(user_id));
+        active_model.confirm_time = Set(Some(Local::now().naive_local()));
+        let run_model = active_model.update(&txn).await?;
+        txn.commit().await?;
+        Ok(PayrollRun {
+            confirmed_by: run_model.confirmed_by,
+            confirm_time: parse_navie_time_to_data_time(run_model.confirm_time),
+            ..self
+        })
+    }
+
+    /// Drops a run that is not confirmed yet.
+    #[instrument(skip(db))]
+    pub async fn delete<C: ConnectionTrait + TransactionTrait>(
+        self,
+        db: &C,
+    ) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        let run_model = payroll_run::Entity::find_by_id(self.id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or_else(|| payroll_not_found(self.id))?;
+        if run_model.confirm_time.is_some() {
+            return Err(payroll_locked(run_model.period_start));
+        }
+        payslip_line::Entity::delete_many()
+            .filter(payslip_line::Column::PayrollRunId.eq(run_model.id))
+            .exec(&txn)
+            .await?;
+        run_model.delete(&txn).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use sea_orm::{ActiveEnum, ActiveModelTrait, Set};
+    use uuid::Uuid;
+
+    use super::{line_pay, DriverTrips, PayrollRun, PayslipLine};
+    use crate::entities::{
+        billing, billing_income, billing_item, dispatch, dispatch_driver, item,
+        sea_orm_active_enums::{ExpensePayer, ItemCategory, ItemType, WageRuleType},
+        team, team_car, team_driver, user,
+    };
+    use crate::error::ErrorCode;
+    use crate::payroll_service::service::{WageRule, WageRuleFields};
+    use crate::test_db::TestDb;
+
+    fn rule(rule_type: WageRuleType, rate: Decimal) -> WageRuleFields {
+        WageRuleFields {
+            rule_type,
+            rate,
+            deducted_categories: vec![],
+        }
+    }
+
+    fn trips(trips: i64, km: i64) -> DriverTrips {
+        DriverTrips { trips, km }
+    }
+
+    /// Fuel 300 and tolls 200 spent on the billing.
+    fn costs() -> HashMap<String, Decimal> {
+        HashMap::from([
+            (ItemCategory::Fuel.to_value(), dec!(300)),
+            (ItemCategory::Toll.to_value(), dec!(200)),
+        ])
+    }
+
+    #[test]
+    fn per_trip_pays_the_rate_per_trip() {
+        let pay = line_pay(
+            &rule(WageRuleType::PerTrip, dec!(150)),
+            &trips(3, 420),
+            2,
+            dec!(5000),
+            &costs(),
+        );
+        assert_eq!(pay, (dec!(3), dec!(450)));
+    }
+
+    #[test]
+    fn per_km_pays_the_rate_per_km_rounded_to_cents() {
+        let pay = line_pay(
+            &rule(WageRuleType::PerKm, dec!(0.3333)),
+            &trips(2, 421),
+            1,
+            dec!(5000),
+            &costs(),
+        );
+        // 140.3193 rounded to cents.
+        assert_eq!(pay, (dec!(421), dec!(140.32)));
+    }
+
+    #[test]
+    fn commission_is_shared_between_the_drivers() {
+        let pay = line_pay(
+            &rule(WageRuleType::Commission, dec!(10)),
+            &trips(1, 0),
+            3,
+            dec!(1000),
+            &costs(),
+        );
+        // A third of 1000 is 333.33, 10 percent of it 33.333.
+        assert_eq!(pay, (dec!(333.33), dec!(33.33)));
+    }
+
+    #[test]
+    fn commission_subtracts_only_the_deducted_categories() {
+        let fields = WageRuleFields {
+            deducted_categories: vec![ItemCategory::Fuel, ItemCategory::Lodging],
+            ..rule(WageRuleType::Commission, dec!(20))
+        };
+        let pay = line_pay(&fields, &trips(1, 0), 2, dec!(5000), &costs());
+        // Tolls are not deducted and there was no lodging.
+        assert_eq!(pay, (dec!(2350), dec!(470)));
+    }
+
+    #[test]
+    fn commission_is_never_negative() {
+        let fields = WageRuleFields {
+            deducted_categories: vec![ItemCategory::Fuel, ItemCategory::Toll],
+            ..rule(WageRuleType::Commission, dec!(20))
+        };
+        let pay = line_pay(&fields, &trips(1, 0), 1, dec!(400), &costs());
+        assert_eq!(pay, (dec!(0), dec!(0)));
+    }
+
+    fn line(user_name: Option<&str>, billing_name: &str) -> PayslipLine {
+        PayslipLine {
+            user_id: "driver-openid".to_owned(),
+            user_name: user_name.map(str::to_owned),
+            billing_id: Uuid::nil(),
+            billing_name: billing_name.to_owned(),
+            billing_end_time: None,
+            rule_type: WageRuleType::PerTrip,
+            quantity: dec!(2.000),
+            rate: dec!(150.0000),
+            amount: dec!(300.00),
+        }
+    }
+
+    fn run(lines: Vec<PayslipLine>) -> PayrollRun {
+        PayrollRun {
+            id: Uuid::nil(),
+            period_start: NaiveDate::from_ymd_opt(2026, 9, 1).unwrap(),
+            created_by: None,
+            create_time: None,
+            confirmed_by: None,
+            confirm_time: None,
+            lines: Some(lines),
+            total: Decimal::ZERO,
+        }
+    }
+
+    #[test]
+    fn csv_quotes_separators_and_quotes() {
+        let csv = run(vec![
+            line(Some("老王"), "北京,天津"),
+            line(None, "\"急\"单"),
+        ])
+        .to_csv();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows[1],
+            format!(
+                "2026-09,driver-openid,老王,{},\"北京,天津\",,PER_TRIP,2,150,300.00",
+                Uuid::nil()
+            )
+        );
+        assert_eq!(
+            rows[2],
+            format!(
+                "2026-09,driver-openid,,{},\"\"\"急\"\"单\",,PER_TRIP,2,150,300.00",
+                Uuid::nil()
+            )
+        );
+    }
+
+    const OWNER_ID: &str = "owner-openid";
+    const DRIVER_ID: &str = "driver-openid";
+
+    fn at(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_time(NaiveTime::MIN)
+    }
+
+    /// A team with `DRIVER_ID` paid 150 per trip, who drove two trips of 200 km for a
+    /// billing ended in September 2026 and one for a billing ended in October.
+    async fn fleet(test_db: &TestDb) -> Uuid {
+        let db = &test_db.db;
+        for (id, name) in [(OWNER_ID, "车主"), (DRIVER_ID, "老王")] {
+            user::ActiveModel {
+                id: Set(id.to_owned()),
+                user_name: Set(name.to_owned()),
+                avatar_url: Set(None),
+            }
+            .insert(db)
+            .await
+            .unwrap();
+        }
+        let team_model = team::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            team_name: Set("车队".to_owned()),
+            user_id: Set(OWNER_ID.to_owned()),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        let car_model = team_car::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            team_id: Set(team_model.id),
+            car_plate_number: Set("京A12345".to_owned()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        team_driver::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(DRIVER_ID.to_owned()),
+            team_id: Set(team_model.id),
+            licence_expire_date: Set(None),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        WageRule::set(
+            db,
+            team_model.id,
+            DRIVER_ID,
+            rule(WageRuleType::PerTrip, dec!(150)),
+        )
+        .await
+        .unwrap();
+        let fuel_model = item::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            r#type: Set(ItemType::Basic),
+            name: Set("油费".to_owned()),
+            team_id: Set(None),
+            icon_url: Set(None),
+            archived: Set(false),
+            category: Set(ItemCategory::Fuel),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+
+        for (name, start, end, trip_count) in [
+            ("九月", at(2026, 9, 10), at(2026, 9, 30), 2),
+            ("十月", at(2026, 9, 30), at(2026, 10, 1), 1),
+        ] {
+            let billing_model = billing::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                name: Set(name.to_owned()),
+                team_id: Set(Some(team_model.id)),
+                team_car_id: Set(Some(car_model.id)),
+                start_time: Set(Some(start)),
+                end_time: Set(Some(end)),
+                ..Default::default()
+            }
+            .insert(db)
+            .await
+            .unwrap();
+            billing_income::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                billing_id: Set(billing_model.id),
+                amount: Set(dec!(3000)),
+                received_amount: Set(Decimal::ZERO),
+                time: Set(start),
+                ..Default::default()
+            }
+            .insert(db)
+            .await
+            .unwrap();
+            billing_item::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                billing_id: Set(Some(billing_model.id)),
+                cost: Set(dec!(1000)),
+                item_id: Set(Some(fuel_model.id)),
+                time: Set(start),
+                user_id: Set(Some(DRIVER_ID.to_owned())),
+                paid_by: Set(ExpensePayer::Company),
+            }
+            .insert(db)
+            .await
+            .unwrap();
+            for _ in 0..trip_count {
+                let dispatch_model = dispatch::ActiveModel {
+                    id: Set(Uuid::new_v4()),
+                    team_id: Set(team_model.id),
+                    team_car_id: Set(car_model.id),
+                    billing_id: Set(billing_model.id),
+                    start_time: Set(start),
+                    end_time: Set(Some(end)),
+                    start_odometer_km: Set(Some(10_000)),
+                    end_odometer_km: Set(Some(10_200)),
+                }
+                .insert(db)
+                .await
+                .unwrap();
+                dispatch_driver::ActiveModel {
+                    id: Set(Uuid::new_v4()),
+                    dispatch_id: Set(dispatch_model.id),
+                    user_id: Set(DRIVER_ID.to_owned()),
+                }
+                .insert(db)
+                .await
+                .unwrap();
+            }
+        }
+        team_model.id
+    }
+
+    #[tokio::test]
+    async fn run_confirm_and_run_again() {
+        let Some(test_db) = TestDb::create().await else {
+            return;
+        };
+        let db = &test_db.db;
+        let team_id = fleet(&test_db).await;
+        let september = PayrollRun::period_of(2026, 9).unwrap();
+
+        let run = PayrollRun::run(db, team_id, september, OWNER_ID.to_owned())
+            .await
+            .unwrap();
+        let lines = run.lines.as_deref().unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].billing_name, "九月");
+        assert_eq!(lines[0].user_name.as_deref(), Some("老王"));
+        assert_eq!((lines[0].quantity, lines[0].amount), (dec!(2), dec!(300)));
+        assert_eq!(run.total, dec!(300));
+
+        // Until it is confirmed the run is computed again under the current rule.
+        WageRule::set(
+            db,
+            team_id,
+            DRIVER_ID,
+            WageRuleFields {
+                deducted_categories: vec![ItemCategory::Fuel],
+                ..rule(WageRuleType::Commission, dec!(10))
+            },
+        )
+        .await
+        .unwrap();
+        let rerun = PayrollRun::run(db, team_id, september, OWNER_ID.to_owned())
+            .await
+            .unwrap();
+        assert_eq!(rerun.id, run.id);
+        let lines = rerun.lines.as_deref().unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].rule_type, WageRuleType::Commission);
+        assert_eq!(
+            (lines[0].quantity, lines[0].amount),
+            (dec!(2000), dec!(200))
+        );
+
+        let confirmed = rerun.confirm(db, OWNER_ID.to_owned()).await.unwrap();
+        assert!(confirmed.is_confirmed());
+        // A confirmed run keeps the pay it was confirmed with.
+        WageRule::set(
+            db,
+            team_id,
+            DRIVER_ID,
+            rule(WageRuleType::PerTrip, dec!(500)),
+        )
+        .await
+        .unwrap();
+        let err = PayrollRun::run(db, team_id, september, OWNER_ID.to_owned())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::PayrollLocked);
+        let err = confirmed
+            .clone()
+            .confirm(db, OWNER_ID.to_owned())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::PayrollLocked);
+        let err = confirmed.delete(db).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::PayrollLocked);
+        let kept = PayrollRun::from_id(db, team_id, run.id, None)
+            .await
+            .unwrap();
+        assert_eq!(kept.total, dec!(200));
+
+        // The next month is paid under the new rule.
+        let october = PayrollRun::period_of(2026, 10).unwrap();
+        let run = PayrollRun::run(db, team_id, october, OWNER_ID.to_owned())
+            .await
+            .unwrap();
+        assert_eq!(run.total, dec!(500));
+        test_db.drop().await;
+    }
+}
@@ -0,0 +1,320 @@
+use chrono::{DateTime, Local};
+use rust_decimal::Decimal;
+use sea_orm::{
+    ActiveEnum, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, ModelTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    billing_service::service::parse_navie_time_to_data_time,
+    entities::{
+        sea_orm_active_enums::{ItemCategory, WageRuleType},
+        team_driver, wage_rule, wage_rule_deduction,
+    },
+    error::{AppError, ErrorCode},
+};
+
+fn wage_rule_not_found(user_id: &str) -> AppError {
+    AppError::new(
+        ErrorCode::WageRuleNotFound,
+        format!("driver {} has no wage rule", user_id),
+    )
+}
+
+impl std::fmt::Display for WageRuleType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WageRuleType::Commission => write!(f, "COMMISSION"),
+            WageRuleType::PerKm => write!(f, "PER_KM"),
+            WageRuleType::PerTrip => write!(f, "PER_TRIP"),
+        }
+    }
+}
+
+/// How a driver is paid. `rate` is an amount per trip, an amount per km or a percentage of
+/// the billing income less the costs of the deducted categories.
+#[derive(Debug, Clone)]
+pub struct WageRuleFields {
+    pub rule_type: WageRuleType,
+    pub rate: Decimal,
+    pub deducted_categories: Vec<ItemCategory>,
+}
+
+impl WageRuleFields {
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.rate < Decimal::ZERO {
+            return Err(AppError::new(
+                ErrorCode::InvalidArgument,
+                "wage rate can not be negative",
+            ));
+        }
+        match self.rule_type {
+            WageRuleType::Commission if self.rate > Decimal::ONE_HUNDRED => Err(AppError::new(
+                ErrorCode::InvalidArgument,
+                "commission can not be above 100 percent",
+            )),
+            WageRuleType::PerKm | WageRuleType::PerTrip if !self.deducted_categories.is_empty() => {
+                Err(AppError::new(
+                    ErrorCode::InvalidArgument,
+                    "only commission rules deduct costs",
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WageRule {
+    pub id: Uuid,
+    pub user_id: String,
+    pub fields: WageRuleFields,
+    pub update_time: Option<DateTime<Local>>,
+}
+
+impl WageRule {
+    fn from_models(
+        rule_model: wage_rule::Model,
+        user_id: String,
+        deduction_models: Vec<wage_rule_deduction::Model>,
+    ) -> Self {
+        WageRule {
+            id: rule_model.id,
+            user_id,
+            fields: WageRuleFields {
+                rule_type: rule_model.rule_type,
+                rate: rule_model.rate,
+                deducted_categories: deduction_models
+                    .into_iter()
+                    .map(|deduction_model| deduction_model.category)
+                    .collect(),
+            },
+            update_time: parse_navie_time_to_data_time(Some(rule_model.update_time)),
+        }
+    }
+
+    async fn team_driver<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        user_id: &str,
+    ) -> Result<team_driver::Model, AppError> {
+        team_driver::Entity::find()
+            .filter(team_driver::Column::TeamId.eq(team_id))
+            .filter(team_driver::Column::UserId.eq(user_id))
+            .one(db)
+            .await?
+            .ok_or_else(|| {
+                AppError::new(
+                    ErrorCode::UserNotFound,
+                    format!("user {} is not a driver of team", user_id),
+                )
+            })
+    }
+
+    #[instrument(skip(db))]
+    pub async fn of_driver<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        user_id: &str,
+    ) -> Result<WageRule, AppError> {
+        let team_driver_model = Self::team_driver(db, team_id, user_id).await?;
+        let rule_model = wage_rule::Entity::find()
+            .filter(wage_rule::Column::TeamDriverId.eq(team_driver_model.id))
+            .one(db)
+            .await?
+            .ok_or_else(|| wage_rule_not_found(user_id))?;
+        let deduction_models = rule_model
+            .find_related(wage_rule_deduction::Entity)
+            .all(db)
+            .await?;
+        Ok(Self::from_models(
+            rule_model,
+            team_driver_model.user_id,
+            deduction_models,
+        ))
+    }
+
+    /// Rules of every driver of the team that has one, ordered by driver.
+    #[instrument(skip(db))]
+    pub async fn of_team<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+    ) -> Result<Vec<WageRule>, AppError> {
+        let rows = team_driver::Entity::find()
+            .find_also_related(wage_rule::Entity)
+            .filter(team_driver::Column::TeamId.eq(team_id))
+            .filter(wage_rule::Column::Id.is_not_null())
+            .order_by_asc(team_driver::Column::UserId)
+            .all(db)
+            .await?;
+        let mut rules = vec![];
+        for (team_driver_model, rule_model) in rows {
+            if let Some(rule_model) = rule_model {
+                let deduction_models = rule_model
+                    .find_related(wage_rule_deduction::Entity)
+                    .all(db)
+                    .await?;
+                rules.push(Self::from_models(
+                    rule_model,
+                    team_driver_model.user_id,
+                    deduction_models,
+                ));
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Sets the rule of a driver, replacing the one they had. Payslip lines already computed
+    /// keep the rate they were computed with.
+    #[instrument(skip(db))]
+    pub async fn set<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        team_id: Uuid,
+        user_id: &str,
+        fields: WageRuleFields,
+    ) -> Result<WageRule, AppError> {
+        fields.validate()?;
+        let mut deducted_categories = fields.deducted_categories;
+        deducted_categories.sort_by_key(|category| category.to_value());
+        deducted_categories.dedup();
+
+        let txn = db.begin().await?;
+        let team_driver_model = Self::team_driver(&txn, team_id, user_id).await?;
+        let now = Local::now().naive_local();
+        let existing = wage_rule::Entity::find()
+            .filter(wage_rule::Column::TeamDriverId.eq(team_driver_model.id))
+            .lock_exclusive()
+            .one(&txn)
+            .await?;
+        let rule_model = match existing {
+            Some(rule_model) => {
+                wage_rule_deduction::Entity::delete_many()
+                    .filter(wage_rule_deduction::Column::WageRuleId.eq(rule_model.id))
+                    .exec(&txn)
+                    .await?;
+                let mut active_model: wage_rule::ActiveModel = rule_model.into();
+                active_model.rule_type = Set(fields.rule_type);
+                active_model.rate = Set(fields.rate);
+                active_model.update_time = Set(now);
+                active_model.update(&txn).await?
+            }
+            None => {
+                wage_rule::ActiveModel {
+                    id: Set(Uuid::new_v4()),
+                    team_driver_id: Set(team_driver_model.id),
+                    rule_type: Set(fields.rule_type),
+                    rate: Set(fields.rate),
+                    update_time: Set(now),
+                }
+                .insert(&txn)
+                .await?
+            }
+        };
+        let mut deduction_models = vec![];
+        for category in deducted_categories {
+            deduction_models.push(
+                wage_rule_deduction::ActiveModel {
+                    id: Set(Uuid::new_v4()),
+                    wage_rule_id: Set(rule_model.id),
+                    category: Set(category),
+                }
+                .insert(&txn)
+                .await?,
+            );
+        }
+        txn.commit().await?;
+        Ok(Self::from_models(
+            rule_model,
+            team_driver_model.user_id,
+            deduction_models,
+        ))
+    }
+
+    #[instrument(skip(db))]
+    pub async fn delete<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        team_id: Uuid,
+        user_id: &str,
+    ) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        let team_driver_model = Self::team_driver(&txn, team_id, user_id).await?;
+        let rule_model = wage_rule::Entity::find()
+            .filter(wage_rule::Column::TeamDriverId.eq(team_driver_model.id))
+            .one(&txn)
+            .await?
+            .ok_or_else(|| wage_rule_not_found(user_id))?;
+        wage_rule_deduction::Entity::delete_many()
+            .filter(wage_rule_deduction::Column::WageRuleId.eq(rule_model.id))
+            .exec(&txn)
+            .await?;
+        rule_model.delete(&txn).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    use super::WageRuleFields;
+    use crate::entities::sea_orm_active_enums::{ItemCategory, WageRuleType};
+    use crate::error::ErrorCode;
+
+    fn fields(rule_type: WageRuleType, rate: Decimal) -> WageRuleFields {
+        WageRuleFields {
+            rule_type,
+            rate,
+            deducted_categories: vec![],
+        }
+    }
+
+    #[test]
+    fn accepts_rates_in_range() {
+        for fields in [
+            fields(WageRuleType::PerTrip, dec!(0)),
+            fields(WageRuleType::PerKm, dec!(1.2)),
+            fields(WageRuleType::Commission, dec!(100)),
+            WageRuleFields {
+                deducted_categories: vec![ItemCategory::Fuel, ItemCategory::Toll],
+                ..fields(WageRuleType::Commission, dec!(15))
+            },
+        ] {
+            assert!(fields.validate().is_ok(), "{:?}", fields);
+        }
+    }
+
+    #[test]
+    fn rejects_negative_rate() {
+        for rule_type in [
+            WageRuleType::PerTrip,
+            WageRuleType::PerKm,
+            WageRuleType::Commission,
+        ] {
+            let err = fields(rule_type, dec!(-0.01)).validate().unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn rejects_commission_above_100_percent() {
+        let err = fields(WageRuleType::Commission, dec!(100.01))
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+    }
+
+    #[test]
+    fn only_commission_deducts_costs() {
+        let err = WageRuleFields {
+            deducted_categories: vec![ItemCategory::Fuel],
+            ..fields(WageRuleType::PerKm, dec!(1.2))
+        }
+        .validate()
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+    }
+}
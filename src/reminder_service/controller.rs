@@ -0,0 +1,126 @@
+use chrono::{DateTime, Local, NaiveDate};
+use poem::web::Data;
+use poem_openapi::{
+    param::{Path, Query},
+    payload::Json,
+    ApiResponse, Enum, Object, OpenApi, Tags,
+};
+
+use crate::auth_service::service::{TeamGuard, TeamPermission};
+use crate::entities::sea_orm_active_enums::ReminderKind;
+use crate::error::{parse_uuid, ApiResult};
+use crate::session_service::service::SessionUser;
+use crate::state::AppState;
+
+use super::service::Reminder;
+
+#[derive(Tags)]
+enum ApiTags {
+    /// Insurance, annual inspection and driver licence expiry reminders
+    Reminder,
+}
+
+#[derive(Debug, Enum, Clone, Copy, Eq, PartialEq)]
+#[oai(rename_all = "SCREAMING_SNAKE_CASE")]
+enum ReminderKindDTO {
+    Insurance,
+    Inspection,
+    DriverLicence,
+}
+
+impl From<ReminderKindDTO> for ReminderKind {
+    fn from(kind: ReminderKindDTO) -> Self {
+        match kind {
+            ReminderKindDTO::Insurance => ReminderKind::Insurance,
+            ReminderKindDTO::Inspection => ReminderKind::Inspection,
+            ReminderKindDTO::DriverLicence => ReminderKind::DriverLicence,
+        }
+    }
+}
+
+impl From<ReminderKind> for ReminderKindDTO {
+    fn from(kind: ReminderKind) -> Self {
+        match kind {
+            ReminderKind::Insurance => ReminderKindDTO::Insurance,
+            ReminderKind::Inspection => ReminderKindDTO::Inspection,
+            ReminderKind::DriverLicence => ReminderKindDTO::DriverLicence,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct ReminderEntityDTO {
+    reminder_id: String,
+    kind: ReminderKindDTO,
+    car_id: Option<String>,
+    car_plate_number: Option<String>,
+    user_id: Option<String>,
+    user_name: Option<String>,
+    expire_date: NaiveDate,
+    /// Days until the expiry date, negative once expired.
+    days_left: i64,
+    remind_date: NaiveDate,
+    notified_time: Option<DateTime<Local>>,
+    create_time: Option<DateTime<Local>>,
+}
+
+impl From<Reminder> for ReminderEntityDTO {
+    fn from(reminder: Reminder) -> Self {
+        ReminderEntityDTO {
+            reminder_id: reminder.id.to_string(),
+            days_left: reminder.days_left(Local::now().date_naive()),
+            kind: reminder.kind.into(),
+            car_id: reminder.team_car_id.map(|car_id| car_id.to_string()),
+            car_plate_number: reminder.car_plate_number,
+            user_id: reminder.user_id,
+            user_name: reminder.user_name,
+            expire_date: reminder.expire_date,
+            remind_date: reminder.remind_date,
+            notified_time: reminder.notified_time,
+            create_time: reminder.create_time,
+        }
+    }
+}
+
+#[derive(ApiResponse)]
+enum QueryReminderResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<ReminderEntityDTO>>),
+}
+
+pub struct ReminderRouter;
+
+#[OpenApi]
+impl ReminderRouter {
+    /// Reminders raised by the daily expiry scan, soonest expiry first.
+    #[oai(
+        path = "/team/:team_id/reminder",
+        method = "get",
+        tag = "ApiTags::Reminder"
+    )]
+    async fn query_reminder(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        kind: Query<Option<ReminderKindDTO>>,
+        /// `true` for reminders not delivered yet, `false` for delivered ones.
+        pending: Query<Option<bool>>,
+    ) -> ApiResult<QueryReminderResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let reminders = Reminder::query(
+            &state.db,
+            team_uuid,
+            kind.0.map(|kind| kind.into()),
+            pending.0,
+        )
+        .await?;
+        Ok(QueryReminderResponse::Ok(Json(
+            reminders
+                .into_iter()
+                .map(|reminder| reminder.into())
+                .collect(),
+        )))
+    }
+}
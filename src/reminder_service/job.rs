@@ -0,0 +1,44 @@
+use chrono::{DateTime, Duration, Local, NaiveTime};
+use tracing::{error, info};
+
+use crate::state::AppState;
+
+use super::service::Reminder;
+
+/// Starts the daily expiry scan in the background when `reminder.enabled` is set.
+///
+/// The scan runs once right away, so a restart after the scan hour does not skip a day,
+/// and then every day at `reminder.scan_hour`. Each run raises new reminders and delivers
+/// the pending ones.
+pub fn spawn_reminder_job(state: AppState) {
+    if !state.config.reminder.enabled {
+        info!("expiry reminders are disabled");
+        return;
+    }
+    tokio::spawn(async move {
+        loop {
+            run_once(&state).await;
+            let wait = until_next_scan(Local::now(), state.config.reminder.scan_hour);
+            tokio::time::sleep(wait.to_std().unwrap_or_default()).await;
+        }
+    });
+}
+
+async fn run_once(state: &AppState) {
+    let today = Local::now().date_naive();
+    if let Err(err) = Reminder::scan(&state.db, today, state.config.reminder.lead_days).await {
+        error!("expiry scan failed, {}", err);
+    }
+    if let Err(err) = Reminder::deliver_pending(&state.db, state.notifier.as_ref(), today).await {
+        error!("reminder delivery failed, {}", err);
+    }
+}
+
+fn until_next_scan(now: DateTime<Local>, scan_hour: u32) -> Duration {
+    let scan_time = NaiveTime::from_hms_opt(scan_hour, 0, 0).unwrap_or(NaiveTime::MIN);
+    let mut next = now.date_naive().and_time(scan_time);
+    if next <= now.naive_local() {
+        next += Duration::days(1);
+    }
+    next - now.naive_local()
+}
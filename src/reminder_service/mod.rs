@@ -0,0 +1,4 @@
+pub mod controller;
+pub mod job;
+pub mod notifier;
+pub mod service;
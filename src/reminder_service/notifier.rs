@@ -0,0 +1,301 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::config::WechatConfig;
+use crate::entities::sea_orm_active_enums::ReminderKind;
+use crate::error::{AppError, ErrorCode};
+
+use super::service::Reminder;
+
+/// Delivers an expiry reminder to one user.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(
+        &self,
+        user_id: &str,
+        reminder: &Reminder,
+        today: NaiveDate,
+    ) -> Result<(), AppError>;
+}
+
+/// Name of the document a reminder is about, as shown to the user.
+fn document_name(kind: &ReminderKind) -> &'static str {
+    match kind {
+        ReminderKind::Insurance => "车辆保险",
+        ReminderKind::Inspection => "车辆年检",
+        ReminderKind::DriverLicence => "驾驶证",
+    }
+}
+
+fn remark(days_left: i64) -> String {
+    match days_left {
+        0 => "今天到期".to_owned(),
+        days if days > 0 => format!("还有{}天到期", days),
+        days => format!("已过期{}天", -days),
+    }
+}
+
+/// Keeps reminders in memory instead of sending them, for tests.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryNotifier {
+    notified: Mutex<Vec<(String, Reminder)>>,
+}
+
+#[cfg(test)]
+impl MemoryNotifier {
+    pub fn new() -> Self {
+        MemoryNotifier::default()
+    }
+
+    /// Every recipient and reminder notified so far, oldest first.
+    pub fn notified(&self) -> Vec<(String, Reminder)> {
+        self.notified
+            .lock()
+            .map(|notified| notified.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl Notifier for MemoryNotifier {
+    async fn notify(
+        &self,
+        user_id: &str,
+        reminder: &Reminder,
+        today: NaiveDate,
+    ) -> Result<(), AppError> {
+        info!(
+            "reminder {} for {}: {} {} {} {}",
+            reminder.id,
+            user_id,
+            reminder.subject(),
+            document_name(&reminder.kind),
+            reminder.expire_date,
+            remark(reminder.days_left(today))
+        );
+        self.notified
+            .lock()
+            .map_err(|_| AppError::new(ErrorCode::InternalError, "notified log is poisoned"))?
+            .push((user_id.to_owned(), reminder.clone()));
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct WxAccessTokenDTO {
+    access_token: Option<String>,
+    expires_in: Option<u64>,
+    errcode: Option<i32>,
+    errmsg: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WxSendDTO {
+    errcode: Option<i32>,
+    errmsg: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct WxSubscribeMessage<'a> {
+    touser: &'a str,
+    template_id: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    page: &'a str,
+    data: ReminderMessageData,
+}
+
+#[derive(Debug, Serialize)]
+struct WxValue {
+    value: String,
+}
+
+/// The keys the reminder template has to define.
+#[derive(Debug, Serialize)]
+struct ReminderMessageData {
+    thing1: WxValue,
+    thing2: WxValue,
+    date3: WxValue,
+    thing4: WxValue,
+}
+
+impl ReminderMessageData {
+    fn new(reminder: &Reminder, today: NaiveDate) -> Self {
+        let value = |value: String| WxValue { value };
+        ReminderMessageData {
+            thing1: value(thing(&reminder.subject())),
+            thing2: value(document_name(&reminder.kind).to_owned()),
+            date3: value(reminder.expire_date.format("%Y年%m月%d日").to_string()),
+            thing4: value(remark(reminder.days_left(today))),
+        }
+    }
+}
+
+fn wx_notify_error(message: impl Into<String>) -> AppError {
+    AppError::new(ErrorCode::WxNotifyError, message)
+}
+
+/// Thing values of subscribe messages are cut off by WeChat after 20 characters.
+fn thing(value: &str) -> String {
+    value.chars().take(20).collect()
+}
+
+/// Sends reminders as mini program subscribe messages.
+///
+/// The configured template needs the keys `thing1` (truck or driver), `thing2` (document),
+/// `date3` (expiry date) and `thing4` (remark). Users that have not accepted the template
+/// are skipped. The access token is cached until shortly before WeChat expires it.
+pub struct WxSubscribeNotifier {
+    client: reqwest::Client,
+    config: WechatConfig,
+    access_token: Mutex<Option<(String, Instant)>>,
+}
+
+impl WxSubscribeNotifier {
+    pub fn new(config: WechatConfig) -> Self {
+        WxSubscribeNotifier {
+            client: reqwest::Client::new(),
+            config,
+            access_token: Mutex::new(None),
+        }
+    }
+
+    fn cached_token(&self) -> Option<String> {
+        let cached = self.access_token.lock().ok()?;
+        cached
+            .as_ref()
+            .filter(|(_, expire_at)| *expire_at > Instant::now())
+            .map(|(token, _)| token.clone())
+    }
+
+    fn cache_token(&self, token: Option<(String, Instant)>) {
+        if let Ok(mut cached) = self.access_token.lock() {
+            *cached = token;
+        }
+    }
+
+    async fn access_token(&self) -> Result<String, AppError> {
+        if let Some(token) = self.cached_token() {
+            return Ok(token);
+        }
+        // `/cgi-bin/token` is the one reminder call that sends the app secret. Its errors are
+        // logged through `without_url`, the secret in the query never reaches the log, and
+        // the token it hands back is cached rather than logged.
+        let resp = self
+            .client
+            .get(format!("{}/cgi-bin/token", self.config.base_url))
+            .query(&[
+                ("grant_type", "client_credential"),
+                ("appid", self.config.app_id.as_str()),
+                ("secret", self.config.app_secret.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(|err| {
+                error!(
+                    "wx access token request failed. error is {}",
+                    err.without_url()
+                );
+                wx_notify_error("wx access token request failed")
+            })?;
+        let token_resp = resp.json::<WxAccessTokenDTO>().await.map_err(|err| {
+            error!(
+                "access token response is not correctly deserialize. error is {}",
+                err.without_url()
+            );
+            wx_notify_error("wx access token response is not correctly deserialize")
+        })?;
+        match (token_resp.access_token, token_resp.errcode.unwrap_or(0)) {
+            (Some(token), 0) => {
+                // Renew five minutes early so a token never expires in flight.
+                let expires_in = token_resp.expires_in.unwrap_or(7200).saturating_sub(300);
+                let expire_at = Instant::now() + Duration::from_secs(expires_in);
+                self.cache_token(Some((token.clone(), expire_at)));
+                Ok(token)
+            }
+            (_, errcode) => {
+                let errmsg = token_resp.errmsg.unwrap_or_else(|| "empty".to_owned());
+                error!("wx access token error code {}, {}", errcode, errmsg);
+                Err(wx_notify_error(format!(
+                    "wx errcode {}, {}",
+                    errcode, errmsg
+                )))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WxSubscribeNotifier {
+    async fn notify(
+        &self,
+        user_id: &str,
+        reminder: &Reminder,
+        today: NaiveDate,
+    ) -> Result<(), AppError> {
+        let access_token = self.access_token().await?;
+        let message = WxSubscribeMessage {
+            touser: user_id,
+            template_id: &self.config.reminder_template_id,
+            page: &self.config.reminder_page,
+            data: ReminderMessageData::new(reminder, today),
+        };
+        let resp = self
+            .client
+            .post(format!(
+                "{}/cgi-bin/message/subscribe/send",
+                self.config.base_url
+            ))
+            .query(&[("access_token", access_token.as_str())])
+            .json(&message)
+            .send()
+            .await
+            .map_err(|err| {
+                error!(
+                    "wx subscribe message request failed. error is {}",
+                    err.without_url()
+                );
+                wx_notify_error("wx subscribe message request failed")
+            })?;
+        let send_resp = resp.json::<WxSendDTO>().await.map_err(|err| {
+            error!(
+                "subscribe message response is not correctly deserialize. error is {}",
+                err.without_url()
+            );
+            wx_notify_error("wx subscribe message response is not correctly deserialize")
+        })?;
+        let errcode = send_resp.errcode.unwrap_or(0);
+        let errmsg = send_resp.errmsg.unwrap_or_else(|| "empty".to_owned());
+        match errcode {
+            0 => Ok(()),
+            43101 => {
+                info!("user {} has not accepted the reminder template", user_id);
+                Ok(())
+            }
+            40001 | 40014 | 42001 => {
+                info!("wx access token is rejected, err msg is {}", errmsg);
+                self.cache_token(None);
+                Err(wx_notify_error(format!(
+                    "wx errcode {}, {}",
+                    errcode, errmsg
+                )))
+            }
+            _ => {
+                error!(
+                    "wx subscribe message error. err code is {}, err msg is {}",
+                    errcode, errmsg
+                );
+                Err(wx_notify_error(format!(
+                    "wx errcode {}, {}",
+                    errcode, errmsg
+                )))
+            }
+        }
+    }
+}
@@ -0,0 +1,512 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Local, NaiveDate};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, EntityTrait, IntoActiveModel,
+    QueryFilter, QueryOrder, Set,
+};
+use tracing::{info, instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    billing_service::service::parse_navie_time_to_data_time,
+    entities::{reminder, sea_orm_active_enums::ReminderKind, team, team_car, team_driver, user},
+    error::{AppError, ErrorCode},
+};
+
+use super::notifier::Notifier;
+
+#[derive(Debug, Clone)]
+pub struct Reminder {
+    pub id: Uuid,
+    pub team_id: Uuid,
+    pub kind: ReminderKind,
+    /// The truck of an insurance or inspection reminder.
+    pub team_car_id: Option<Uuid>,
+    pub car_plate_number: Option<String>,
+    /// The driver of a licence reminder.
+    pub user_id: Option<String>,
+    pub user_name: Option<String>,
+    pub expire_date: NaiveDate,
+    pub remind_date: NaiveDate,
+    pub notified_time: Option<DateTime<Local>>,
+    pub create_time: Option<DateTime<Local>>,
+}
+
+impl From<reminder::Model> for Reminder {
+    fn from(model: reminder::Model) -> Self {
+        Reminder {
+            id: model.id,
+            team_id: model.team_id,
+            kind: model.kind,
+            team_car_id: model.team_car_id,
+            car_plate_number: None,
+            user_id: model.user_id,
+            user_name: None,
+            expire_date: model.expire_date,
+            remind_date: model.remind_date,
+            notified_time: parse_navie_time_to_data_time(model.notified_time),
+            create_time: parse_navie_time_to_data_time(Some(model.create_time)),
+        }
+    }
+}
+
+/// A document found by the scan whose expiry date is inside the lead window.
+struct ExpiringDocument {
+    team_id: Uuid,
+    kind: ReminderKind,
+    team_car_id: Option<Uuid>,
+    user_id: Option<String>,
+    expire_date: NaiveDate,
+}
+
+impl Reminder {
+    /// Days from `today` to the expiry date, negative once the document has expired.
+    pub fn days_left(&self, today: NaiveDate) -> i64 {
+        (self.expire_date - today).num_days()
+    }
+
+    /// Name of the truck or driver the reminder is about.
+    pub fn subject(&self) -> String {
+        self.car_plate_number
+            .clone()
+            .or_else(|| self.user_name.clone())
+            .or_else(|| self.user_id.clone())
+            .unwrap_or_default()
+    }
+
+    /// Raises a reminder for every insurance, inspection and driver licence expiring within
+    /// `lead_days` of `today`, already expired ones included. A document gets one reminder
+    /// per expiry date, so rescanning is harmless. Returns how many were raised.
+    #[instrument(skip(db))]
+    pub async fn scan<C: ConnectionTrait>(
+        db: &C,
+        today: NaiveDate,
+        lead_days: u32,
+    ) -> Result<usize, AppError> {
+        let horizon = today + Duration::days(lead_days.into());
+        let mut documents = vec![];
+        let car_models = team_car::Entity::find()
+            .filter(
+                Condition::any()
+                    .add(team_car::Column::InsuranceExpireDate.lte(horizon))
+                    .add(team_car::Column::InspectionExpireDate.lte(horizon)),
+            )
+            .all(db)
+            .await?;
+        for car_model in car_models {
+            for (kind, expire_date) in [
+                (ReminderKind::Insurance, car_model.insurance_expire_date),
+                (ReminderKind::Inspection, car_model.inspection_expire_date),
+            ] {
+                if let Some(expire_date) = expire_date.filter(|date| *date <= horizon) {
+                    documents.push(ExpiringDocument {
+                        team_id: car_model.team_id,
+                        kind,
+                        team_car_id: Some(car_model.id),
+                        user_id: None,
+                        expire_date,
+                    });
+                }
+            }
+        }
+        let driver_models = team_driver::Entity::find()
+            .filter(team_driver::Column::LicenceExpireDate.lte(horizon))
+            .all(db)
+            .await?;
+        for driver_model in driver_models {
+            if let Some(expire_date) = driver_model.licence_expire_date {
+                documents.push(ExpiringDocument {
+                    team_id: driver_model.team_id,
+                    kind: ReminderKind::DriverLicence,
+                    team_car_id: None,
+                    user_id: Some(driver_model.user_id),
+                    expire_date,
+                });
+            }
+        }
+
+        let mut raised = 0;
+        for document in documents {
+            if Self::raise(db, document, today).await? {
+                raised += 1;
+            }
+        }
+        info!("expiry scan raised {} reminders", raised);
+        Ok(raised)
+    }
+
+    async fn raise<C: ConnectionTrait>(
+        db: &C,
+        document: ExpiringDocument,
+        today: NaiveDate,
+    ) -> Result<bool, AppError> {
+        let mut select = reminder::Entity::find()
+            .filter(reminder::Column::TeamId.eq(document.team_id))
+            .filter(reminder::Column::Kind.eq(document.kind.clone()))
+            .filter(reminder::Column::ExpireDate.eq(document.expire_date));
+        select = match (&document.team_car_id, &document.user_id) {
+            (Some(team_car_id), _) => select.filter(reminder::Column::TeamCarId.eq(*team_car_id)),
+            (None, Some(user_id)) => select.filter(reminder::Column::UserId.eq(user_id.clone())),
+            (None, None) => return Ok(false),
+        };
+        if select.one(db).await?.is_some() {
+            return Ok(false);
+        }
+        let inserted = reminder::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            team_id: Set(document.team_id),
+            kind: Set(document.kind),
+            team_car_id: Set(document.team_car_id),
+            user_id: Set(document.user_id),
+            expire_date: Set(document.expire_date),
+            remind_date: Set(today),
+            notified_time: Set(None),
+            create_time: Set(Local::now().naive_local()),
+        }
+        .insert(db)
+        .await
+        .map_err(AppError::from);
+        match inserted {
+            Ok(_) => Ok(true),
+            // Another instance raised the same reminder in between.
+            Err(err) if err.code == ErrorCode::UniqueViolation => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Sends every reminder not delivered yet to the team owner, and licence reminders to the
+    /// driver as well. A reminder is marked notified once all of its recipients got it, the
+    /// others are tried again on the next run. Returns how many were delivered.
+    #[instrument(skip(db, notifier))]
+    pub async fn deliver_pending<C: ConnectionTrait>(
+        db: &C,
+        notifier: &dyn Notifier,
+        today: NaiveDate,
+    ) -> Result<usize, AppError> {
+        let reminder_models = reminder::Entity::find()
+            .filter(reminder::Column::NotifiedTime.is_null())
+            .order_by_asc(reminder::Column::ExpireDate)
+            .all(db)
+            .await?;
+        let reminders = Self::with_subjects(db, reminder_models).await?;
+        let team_ids: Vec<Uuid> = reminders.iter().map(|reminder| reminder.team_id).collect();
+        let owners: HashMap<Uuid, String> = team::Entity::find()
+            .filter(team::Column::Id.is_in(team_ids))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|team_model| (team_model.id, team_model.user_id))
+            .collect();
+
+        let mut delivered = 0;
+        for reminder in reminders {
+            let mut recipients: Vec<String> =
+                owners.get(&reminder.team_id).cloned().into_iter().collect();
+            if let Some(user_id) = &reminder.user_id {
+                if !recipients.contains(user_id) {
+                    recipients.push(user_id.clone());
+                }
+            }
+            let mut all_sent = true;
+            for recipient in &recipients {
+                if let Err(err) = notifier.notify(recipient, &reminder, today).await {
+                    warn!(
+                        "notify {} of reminder {} failed, {}",
+                        recipient, reminder.id, err
+                    );
+                    all_sent = false;
+                }
+            }
+            if !all_sent {
+                continue;
+            }
+            if let Some(model) = reminder::Entity::find_by_id(reminder.id).one(db).await? {
+                let mut active_model = model.into_active_model();
+                active_model.notified_time = Set(Some(Local::now().naive_local()));
+                active_model.update(db).await?;
+                delivered += 1;
+            }
+        }
+        info!("delivered {} reminders", delivered);
+        Ok(delivered)
+    }
+
+    /// Reminders of the team by expiry date, `pending` picks the undelivered or the
+    /// delivered ones.
+    #[instrument(skip(db))]
+    pub async fn query<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        kind: Option<ReminderKind>,
+        pending: Option<bool>,
+    ) -> Result<Vec<Reminder>, AppError> {
+        let mut select = reminder::Entity::find().filter(reminder::Column::TeamId.eq(team_id));
+        if let Some(kind) = kind {
+            select = select.filter(reminder::Column::Kind.eq(kind));
+        }
+        match pending {
+            Some(true) => select = select.filter(reminder::Column::NotifiedTime.is_null()),
+            Some(false) => select = select.filter(reminder::Column::NotifiedTime.is_not_null()),
+            None => {}
+        }
+        let reminder_models = select
+            .order_by_asc(reminder::Column::ExpireDate)
+            .all(db)
+            .await?;
+        Self::with_subjects(db, reminder_models).await
+    }
+
+    /// Fills in the plate or the driver name of each reminder.
+    async fn with_subjects<C: ConnectionTrait>(
+        db: &C,
+        reminder_models: Vec<reminder::Model>,
+    ) -> Result<Vec<Reminder>, AppError> {
+        let car_ids: Vec<Uuid> = reminder_models
+            .iter()
+            .filter_map(|model| model.team_car_id)
+            .collect();
+        let user_ids: Vec<String> = reminder_models
+            .iter()
+            .filter_map(|model| model.user_id.clone())
+            .collect();
+        let plates: HashMap<Uuid, String> = team_car::Entity::find()
+            .filter(team_car::Column::Id.is_in(car_ids))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|car_model| (car_model.id, car_model.car_plate_number))
+            .collect();
+        let names: HashMap<String, String> = user::Entity::find()
+            .filter(user::Column::Id.is_in(user_ids))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|user_model| (user_model.id, user_model.user_name))
+            .collect();
+        Ok(reminder_models
+            .into_iter()
+            .map(|model| {
+                let mut reminder: Reminder = model.into();
+                reminder.car_plate_number = reminder
+                    .team_car_id
+                    .and_then(|car_id| plates.get(&car_id).cloned());
+                reminder.user_name = reminder
+                    .user_id
+                    .as_ref()
+                    .and_then(|user_id| names.get(user_id).cloned());
+                reminder
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Local, NaiveDate};
+    use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+    use uuid::Uuid;
+
+    use super::Reminder;
+    use crate::entities::{
+        reminder, sea_orm_active_enums::ReminderKind, team, team_car, team_driver, user,
+    };
+    use crate::reminder_service::notifier::MemoryNotifier;
+    use crate::test_db::TestDb;
+
+    const OWNER_ID: &str = "owner-openid";
+    const DRIVER_ID: &str = "driver-openid";
+    const LEAD_DAYS: u32 = 30;
+
+    fn today() -> NaiveDate {
+        Local::now().date_naive()
+    }
+
+    fn days(days: i64) -> NaiveDate {
+        today() + Duration::days(days)
+    }
+
+    struct Fleet {
+        team_id: Uuid,
+        car_id: Uuid,
+    }
+
+    /// A team owned by `OWNER_ID` with one truck and `DRIVER_ID` as its driver.
+    async fn fleet(
+        test_db: &TestDb,
+        insurance: NaiveDate,
+        inspection: NaiveDate,
+        licence: NaiveDate,
+    ) -> Fleet {
+        let db = &test_db.db;
+        for (id, name) in [(OWNER_ID, "车主"), (DRIVER_ID, "老王")] {
+            user::ActiveModel {
+                id: Set(id.to_owned()),
+                user_name: Set(name.to_owned()),
+                avatar_url: Set(None),
+            }
+            .insert(db)
+            .await
+            .unwrap();
+        }
+        let team_model = team::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            team_name: Set("车队".to_owned()),
+            user_id: Set(OWNER_ID.to_owned()),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        let car_model = team_car::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            team_id: Set(team_model.id),
+            car_plate_number: Set("京A12345".to_owned()),
+            insurance_expire_date: Set(Some(insurance)),
+            inspection_expire_date: Set(Some(inspection)),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        team_driver::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(DRIVER_ID.to_owned()),
+            team_id: Set(team_model.id),
+            licence_expire_date: Set(Some(licence)),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        Fleet {
+            team_id: team_model.id,
+            car_id: car_model.id,
+        }
+    }
+
+    async fn reminders(test_db: &TestDb) -> Vec<reminder::Model> {
+        reminder::Entity::find()
+            .order_by_asc(reminder::Column::ExpireDate)
+            .all(&test_db.db)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn scan_raises_reminders_within_lead_days() {
+        let Some(test_db) = TestDb::create().await else {
+            return;
+        };
+        // Insurance is due on the last day of the lead window, inspection the day after it,
+        // the licence has expired already.
+        let fleet = fleet(&test_db, days(30), days(31), days(-8)).await;
+
+        let raised = Reminder::scan(&test_db.db, today(), LEAD_DAYS)
+            .await
+            .unwrap();
+        assert_eq!(raised, 2);
+        let reminders = reminders(&test_db).await;
+        assert_eq!(reminders.len(), 2);
+        assert_eq!(reminders[0].kind, ReminderKind::DriverLicence);
+        assert_eq!(reminders[0].user_id.as_deref(), Some(DRIVER_ID));
+        assert_eq!(reminders[0].expire_date, days(-8));
+        assert_eq!(reminders[1].kind, ReminderKind::Insurance);
+        assert_eq!(reminders[1].team_car_id, Some(fleet.car_id));
+        assert_eq!(reminders[1].expire_date, days(30));
+        for reminder in &reminders {
+            assert_eq!(reminder.team_id, fleet.team_id);
+            assert_eq!(reminder.remind_date, today());
+            assert_eq!(reminder.notified_time, None);
+        }
+        test_db.drop().await;
+    }
+
+    #[tokio::test]
+    async fn rescan_raises_no_duplicates() {
+        let Some(test_db) = TestDb::create().await else {
+            return;
+        };
+        fleet(&test_db, days(3), days(10), days(20)).await;
+
+        let raised = Reminder::scan(&test_db.db, today(), LEAD_DAYS)
+            .await
+            .unwrap();
+        assert_eq!(raised, 3);
+        // The next day's scan finds the same documents with the same expiry dates.
+        let raised = Reminder::scan(&test_db.db, days(1), LEAD_DAYS)
+            .await
+            .unwrap();
+        assert_eq!(raised, 0);
+        assert_eq!(reminders(&test_db).await.len(), 3);
+
+        // A renewed document expires on a new date and is reminded of again.
+        let car_model = team_car::Entity::find()
+            .one(&test_db.db)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut car_active_model: team_car::ActiveModel = car_model.into();
+        car_active_model.insurance_expire_date = Set(Some(days(25)));
+        car_active_model.update(&test_db.db).await.unwrap();
+        let raised = Reminder::scan(&test_db.db, days(1), LEAD_DAYS)
+            .await
+            .unwrap();
+        assert_eq!(raised, 1);
+        test_db.drop().await;
+    }
+
+    #[tokio::test]
+    async fn deliver_pending_notifies_every_recipient_once() {
+        let Some(test_db) = TestDb::create().await else {
+            return;
+        };
+        // Only the insurance and the licence are inside the lead window.
+        fleet(&test_db, days(5), days(200), days(12)).await;
+        Reminder::scan(&test_db.db, today(), LEAD_DAYS)
+            .await
+            .unwrap();
+        let notifier = MemoryNotifier::new();
+
+        let delivered = Reminder::deliver_pending(&test_db.db, &notifier, today())
+            .await
+            .unwrap();
+        assert_eq!(delivered, 2);
+        let notified: Vec<(String, ReminderKind, String)> = notifier
+            .notified()
+            .into_iter()
+            .map(|(user_id, reminder)| (user_id, reminder.kind.clone(), reminder.subject()))
+            .collect();
+        assert_eq!(
+            notified,
+            vec![
+                (
+                    OWNER_ID.to_owned(),
+                    ReminderKind::Insurance,
+                    "京A12345".to_owned()
+                ),
+                (
+                    OWNER_ID.to_owned(),
+                    ReminderKind::DriverLicence,
+                    "老王".to_owned()
+                ),
+                (
+                    DRIVER_ID.to_owned(),
+                    ReminderKind::DriverLicence,
+                    "老王".to_owned()
+                ),
+            ]
+        );
+        let pending = reminder::Entity::find()
+            .filter(reminder::Column::NotifiedTime.is_null())
+            .all(&test_db.db)
+            .await
+            .unwrap();
+        assert!(pending.is_empty());
+
+        let delivered = Reminder::deliver_pending(&test_db.db, &notifier, days(1))
+            .await
+            .unwrap();
+        assert_eq!(delivered, 0);
+        assert_eq!(notifier.notified().len(), 3);
+        test_db.drop().await;
+    }
+}
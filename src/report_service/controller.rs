@@ -0,0 +1,316 @@
+use chrono::NaiveDate;
+use poem::web::Data;
+use poem_openapi::{
+    param::{Path, Query},
+    payload::Json,
+    ApiResponse, Enum, Object, OpenApi, Tags,
+};
+use rust_decimal::Decimal;
+
+use crate::auth_service::service::{TeamGuard, TeamPermission};
+use crate::error::{parse_uuid, ApiResult, AppError};
+use crate::item_service::controller::ItemCategoryDTO;
+use crate::session_service::service::SessionUser;
+use crate::state::AppState;
+
+use super::service::{
+    CarCost, CategoryCost, PeriodTrend, Report, ReportFilter, ReportPeriod, TopItem,
+};
+
+#[derive(Tags)]
+enum ApiTags {
+    /// Cost and income of ended billings per period, truck and item, dates are matched against
+    /// the day the billing ended
+    Report,
+}
+
+#[derive(Debug, Enum, Clone, Copy, Eq, PartialEq)]
+#[oai(rename_all = "SCREAMING_SNAKE_CASE")]
+enum ReportPeriodDTO {
+    Month,
+    Year,
+}
+
+impl From<ReportPeriodDTO> for ReportPeriod {
+    fn from(period: ReportPeriodDTO) -> Self {
+        match period {
+            ReportPeriodDTO::Month => ReportPeriod::Month,
+            ReportPeriodDTO::Year => ReportPeriod::Year,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct CategoryCostDTO {
+    /// First day of the month or year.
+    period_start: NaiveDate,
+    category: ItemCategoryDTO,
+    cost: Decimal,
+    item_count: i64,
+}
+
+impl From<CategoryCost> for CategoryCostDTO {
+    fn from(row: CategoryCost) -> Self {
+        CategoryCostDTO {
+            period_start: row.period_start,
+            category: row.category.into(),
+            cost: row.cost,
+            item_count: row.item_count,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct CarCostDTO {
+    /// Missing for billings without a truck.
+    car_id: Option<String>,
+    car_plate_number: Option<String>,
+    billing_count: i64,
+    cost: Decimal,
+    income: Decimal,
+    gross_margin: Decimal,
+}
+
+impl From<CarCost> for CarCostDTO {
+    fn from(row: CarCost) -> Self {
+        CarCostDTO {
+            gross_margin: row.gross_margin(),
+            car_id: row.team_car_id.map(|team_car_id| team_car_id.to_string()),
+            car_plate_number: row.car_plate_number,
+            billing_count: row.billing_count,
+            cost: row.cost,
+            income: row.income,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct PeriodTrendDTO {
+    /// First day of the month or year.
+    period_start: NaiveDate,
+    income: Decimal,
+    cost: Decimal,
+    gross_margin: Decimal,
+}
+
+impl From<PeriodTrend> for PeriodTrendDTO {
+    fn from(row: PeriodTrend) -> Self {
+        PeriodTrendDTO {
+            gross_margin: row.gross_margin(),
+            period_start: row.period_start,
+            income: row.income,
+            cost: row.cost,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct TopItemDTO {
+    item_id: String,
+    item_name: String,
+    category: ItemCategoryDTO,
+    cost: Decimal,
+    item_count: i64,
+}
+
+impl From<TopItem> for TopItemDTO {
+    fn from(row: TopItem) -> Self {
+        TopItemDTO {
+            item_id: row.item_id.to_string(),
+            item_name: row.item_name,
+            category: row.category.into(),
+            cost: row.cost,
+            item_count: row.item_count,
+        }
+    }
+}
+
+#[derive(ApiResponse)]
+enum CategoryCostResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<CategoryCostDTO>>),
+}
+
+#[derive(ApiResponse)]
+enum CarCostResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<CarCostDTO>>),
+}
+
+#[derive(ApiResponse)]
+enum TrendResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<PeriodTrendDTO>>),
+}
+
+#[derive(ApiResponse)]
+enum TopItemResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<TopItemDTO>>),
+}
+
+/// Checks the caller manages the team and builds the filter shared by the reports.
+async fn report_filter(
+    state: &AppState,
+    session_user: &SessionUser,
+    team_id: &str,
+    car_id: Option<&str>,
+    start_date: Option<NaiveDate>,
+    end_date: Option<NaiveDate>,
+) -> Result<ReportFilter, AppError> {
+    let team_uuid = parse_uuid("team_id", team_id)?;
+    TeamGuard::check(&state.db, session_user, team_uuid, TeamPermission::Manage).await?;
+    let filter = ReportFilter {
+        team_id: team_uuid,
+        start_date,
+        end_date,
+        team_car_id: car_id
+            .map(|car_id| parse_uuid("car_id", car_id))
+            .transpose()?,
+    };
+    filter.validate()?;
+    Ok(filter)
+}
+
+pub struct ReportRouter;
+
+#[OpenApi]
+impl ReportRouter {
+    /// Cost per item category and month or year.
+    #[oai(
+        path = "/team/:team_id/report/category",
+        method = "get",
+        tag = "ApiTags::Report"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    async fn category_report(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        start_date: Query<Option<NaiveDate>>,
+        end_date: Query<Option<NaiveDate>>,
+        car_id: Query<Option<String>>,
+        /// MONTH when omitted.
+        period: Query<Option<ReportPeriodDTO>>,
+    ) -> ApiResult<CategoryCostResponse> {
+        let filter = report_filter(
+            &state,
+            &session_user,
+            &team_id.0,
+            car_id.0.as_deref(),
+            start_date.0,
+            end_date.0,
+        )
+        .await?;
+        let period = period.0.unwrap_or(ReportPeriodDTO::Month).into();
+        let rows = Report::category_costs(&state.db, &filter, period).await?;
+        Ok(CategoryCostResponse::Ok(Json(
+            rows.into_iter().map(|row| row.into()).collect(),
+        )))
+    }
+
+    /// Cost, income and gross margin per truck.
+    #[oai(
+        path = "/team/:team_id/report/car",
+        method = "get",
+        tag = "ApiTags::Report"
+    )]
+    async fn car_report(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        start_date: Query<Option<NaiveDate>>,
+        end_date: Query<Option<NaiveDate>>,
+    ) -> ApiResult<CarCostResponse> {
+        let filter = report_filter(
+            &state,
+            &session_user,
+            &team_id.0,
+            None,
+            start_date.0,
+            end_date.0,
+        )
+        .await?;
+        let rows = Report::car_costs(&state.db, &filter).await?;
+        Ok(CarCostResponse::Ok(Json(
+            rows.into_iter().map(|row| row.into()).collect(),
+        )))
+    }
+
+    /// Income against cost per month or year.
+    #[oai(
+        path = "/team/:team_id/report/trend",
+        method = "get",
+        tag = "ApiTags::Report"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    async fn trend_report(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        start_date: Query<Option<NaiveDate>>,
+        end_date: Query<Option<NaiveDate>>,
+        car_id: Query<Option<String>>,
+        /// MONTH when omitted.
+        period: Query<Option<ReportPeriodDTO>>,
+    ) -> ApiResult<TrendResponse> {
+        let filter = report_filter(
+            &state,
+            &session_user,
+            &team_id.0,
+            car_id.0.as_deref(),
+            start_date.0,
+            end_date.0,
+        )
+        .await?;
+        let period = period.0.unwrap_or(ReportPeriodDTO::Month).into();
+        let rows = Report::trend(&state.db, &filter, period).await?;
+        Ok(TrendResponse::Ok(Json(
+            rows.into_iter().map(|row| row.into()).collect(),
+        )))
+    }
+
+    /// The items the most was spent on.
+    #[oai(
+        path = "/team/:team_id/report/top-item",
+        method = "get",
+        tag = "ApiTags::Report"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    async fn top_item_report(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        start_date: Query<Option<NaiveDate>>,
+        end_date: Query<Option<NaiveDate>>,
+        car_id: Query<Option<String>>,
+        category: Query<Option<ItemCategoryDTO>>,
+        /// 10 when omitted.
+        #[oai(validator(minimum(value = "1"), maximum(value = "100")))]
+        limit: Query<Option<u64>>,
+    ) -> ApiResult<TopItemResponse> {
+        let filter = report_filter(
+            &state,
+            &session_user,
+            &team_id.0,
+            car_id.0.as_deref(),
+            start_date.0,
+            end_date.0,
+        )
+        .await?;
+        let rows = Report::top_items(
+            &state.db,
+            &filter,
+            category.0.map(|category| category.into()),
+            limit.0,
+        )
+        .await?;
+        Ok(TopItemResponse::Ok(Json(
+            rows.into_iter().map(|row| row.into()).collect(),
+        )))
+    }
+}
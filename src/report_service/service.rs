@@ -0,0 +1,578 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use rust_decimal::Decimal;
+use sea_orm::{
+    sea_query::{Alias, Expr, Func, SimpleExpr},
+    ColumnTrait, Condition, ConnectionTrait, EntityTrait, FromQueryResult, JoinType, Order,
+    QueryFilter, QueryOrder, QuerySelect, RelationTrait,
+};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    entities::{
+        billing, billing_income, billing_item, item, sea_orm_active_enums::ItemCategory, team_car,
+    },
+    error::{AppError, ErrorCode},
+};
+
+/// How report rows are bucketed over time, by the day the billing ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportPeriod {
+    Month,
+    Year,
+}
+
+impl ReportPeriod {
+    fn start_of_period(&self) -> SimpleExpr {
+        let unit = match self {
+            ReportPeriod::Month => "month",
+            ReportPeriod::Year => "year",
+        };
+        // The unit is inlined, a bound value would differ between the selected and the
+        // grouped expression and Postgres would not match them.
+        Func::cust(Alias::new("date_trunc")).args([
+            SimpleExpr::Constant(unit.into()),
+            Expr::col((billing::Entity, billing::Column::EndTime)).into(),
+        ])
+    }
+}
+
+/// Which billings a report covers. Only ended billings count, `start_date` and `end_date` are
+/// matched against the day the billing ended.
+#[derive(Debug, Clone)]
+pub struct ReportFilter {
+    pub team_id: Uuid,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub team_car_id: Option<Uuid>,
+}
+
+impl ReportFilter {
+    pub fn validate(&self) -> Result<(), AppError> {
+        match (self.start_date, self.end_date) {
+            (Some(start_date), Some(end_date)) if start_date > end_date => Err(AppError::new(
+                ErrorCode::InvalidArgument,
+                format!("start date {} is after end date {}", start_date, end_date),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn condition(&self) -> Condition {
+        let mut condition = Condition::all()
+            .add(billing::Column::TeamId.eq(self.team_id))
+            .add(billing::Column::EndTime.is_not_null());
+        if let Some(start_date) = self.start_date {
+            condition =
+                condition.add(billing::Column::EndTime.gte(start_date.and_time(NaiveTime::MIN)));
+        }
+        if let Some(end_date) = self.end_date {
+            let next_date = end_date + Duration::days(1);
+            condition =
+                condition.add(billing::Column::EndTime.lt(next_date.and_time(NaiveTime::MIN)));
+        }
+        if let Some(team_car_id) = self.team_car_id {
+            condition = condition.add(billing::Column::TeamCarId.eq(team_car_id));
+        }
+        condition
+    }
+}
+
+/// Cost of one item category in one period.
+#[derive(Debug, Clone)]
+pub struct CategoryCost {
+    pub period_start: NaiveDate,
+    pub category: ItemCategory,
+    pub cost: Decimal,
+    pub item_count: i64,
+}
+
+/// Cost and income of the billings of one truck, `team_car_id` is `None` for billings without
+/// a truck.
+#[derive(Debug, Clone)]
+pub struct CarCost {
+    pub team_car_id: Option<Uuid>,
+    pub car_plate_number: Option<String>,
+    pub billing_count: i64,
+    pub cost: Decimal,
+    pub income: Decimal,
+}
+
+impl CarCost {
+    pub fn gross_margin(&self) -> Decimal {
+        self.income - self.cost
+    }
+}
+
+/// Income and cost of the billings ended in one period.
+#[derive(Debug, Clone)]
+pub struct PeriodTrend {
+    pub period_start: NaiveDate,
+    pub income: Decimal,
+    pub cost: Decimal,
+}
+
+impl PeriodTrend {
+    pub fn gross_margin(&self) -> Decimal {
+        self.income - self.cost
+    }
+}
+
+/// What was spent on one item over the covered billings.
+#[derive(Debug, Clone)]
+pub struct TopItem {
+    pub item_id: Uuid,
+    pub item_name: String,
+    pub category: ItemCategory,
+    pub cost: Decimal,
+    pub item_count: i64,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct CategoryCostRow {
+    period_start: NaiveDateTime,
+    category: ItemCategory,
+    cost: Option<Decimal>,
+    item_count: i64,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct CarBillingRow {
+    team_car_id: Option<Uuid>,
+    car_plate_number: Option<String>,
+    billing_count: i64,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct CarAmountRow {
+    team_car_id: Option<Uuid>,
+    amount: Option<Decimal>,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct PeriodAmountRow {
+    period_start: NaiveDateTime,
+    amount: Option<Decimal>,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct TopItemRow {
+    item_id: Uuid,
+    item_name: String,
+    category: ItemCategory,
+    cost: Option<Decimal>,
+    item_count: i64,
+}
+
+/// Number of top items listed, 10 when not given and never more than 100.
+fn top_item_limit(limit: Option<u64>) -> u64 {
+    limit.unwrap_or(10).clamp(1, 100)
+}
+
+fn cost_sum() -> SimpleExpr {
+    Expr::tbl(billing_item::Entity, billing_item::Column::Cost).sum()
+}
+
+pub struct Report;
+
+impl Report {
+    /// Cost per item category and period, oldest period first and the largest cost first
+    /// within a period.
+    #[instrument(skip(db))]
+    pub async fn category_costs<C: ConnectionTrait>(
+        db: &C,
+        filter: &ReportFilter,
+        period: ReportPeriod,
+    ) -> Result<Vec<CategoryCost>, AppError> {
+        let rows = billing_item::Entity::find()
+            .select_only()
+            .column_as(period.start_of_period(), "period_start")
+            .column(item::Column::Category)
+            .column_as(cost_sum(), "cost")
+            .column_as(
+                Expr::tbl(billing_item::Entity, billing_item::Column::Id).count(),
+                "item_count",
+            )
+            .inner_join(billing::Entity)
+            .inner_join(item::Entity)
+            .filter(filter.condition())
+            .group_by(period.start_of_period())
+            .group_by(item::Column::Category)
+            .order_by(period.start_of_period(), Order::Asc)
+            .order_by(cost_sum(), Order::Desc)
+            .into_model::<CategoryCostRow>()
+            .all(db)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| CategoryCost {
+                period_start: row.period_start.date(),
+                category: row.category,
+                cost: row.cost.unwrap_or_default(),
+                item_count: row.item_count,
+            })
+            .collect())
+    }
+
+    /// Cost and income per truck, the most expensive truck first.
+    #[instrument(skip(db))]
+    pub async fn car_costs<C: ConnectionTrait>(
+        db: &C,
+        filter: &ReportFilter,
+    ) -> Result<Vec<CarCost>, AppError> {
+        let billing_rows = billing::Entity::find()
+            .select_only()
+            .column(billing::Column::TeamCarId)
+            .column(team_car::Column::CarPlateNumber)
+            .column_as(
+                Expr::tbl(billing::Entity, billing::Column::Id).count(),
+                "billing_count",
+            )
+            .join(JoinType::LeftJoin, billing::Relation::TeamCar.def())
+            .filter(filter.condition())
+            .group_by(billing::Column::TeamCarId)
+            .group_by(team_car::Column::CarPlateNumber)
+            .into_model::<CarBillingRow>()
+            .all(db)
+            .await?;
+        let costs: HashMap<Option<Uuid>, Decimal> = billing_item::Entity::find()
+            .select_only()
+            .column(billing::Column::TeamCarId)
+            .column_as(cost_sum(), "amount")
+            .inner_join(billing::Entity)
+            .filter(filter.condition())
+            .group_by(billing::Column::TeamCarId)
+            .into_model::<CarAmountRow>()
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|row| (row.team_car_id, row.amount.unwrap_or_default()))
+            .collect();
+        let incomes: HashMap<Option<Uuid>, Decimal> = billing_income::Entity::find()
+            .select_only()
+            .column(billing::Column::TeamCarId)
+            .column_as(
+                Expr::tbl(billing_income::Entity, billing_income::Column::Amount).sum(),
+                "amount",
+            )
+            .inner_join(billing::Entity)
+            .filter(filter.condition())
+            .group_by(billing::Column::TeamCarId)
+            .into_model::<CarAmountRow>()
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|row| (row.team_car_id, row.amount.unwrap_or_default()))
+            .collect();
+
+        let mut car_costs: Vec<CarCost> = billing_rows
+            .into_iter()
+            .map(|row| CarCost {
+                cost: costs.get(&row.team_car_id).copied().unwrap_or_default(),
+                income: incomes.get(&row.team_car_id).copied().unwrap_or_default(),
+                team_car_id: row.team_car_id,
+                car_plate_number: row.car_plate_number,
+                billing_count: row.billing_count,
+            })
+            .collect();
+        car_costs.sort_by(|a, b| {
+            b.cost
+                .cmp(&a.cost)
+                .then_with(|| a.car_plate_number.cmp(&b.car_plate_number))
+        });
+        Ok(car_costs)
+    }
+
+    /// Income against cost per period, oldest period first. Periods without any cost or
+    /// income are left out.
+    #[instrument(skip(db))]
+    pub async fn trend<C: ConnectionTrait>(
+        db: &C,
+        filter: &ReportFilter,
+        period: ReportPeriod,
+    ) -> Result<Vec<PeriodTrend>, AppError> {
+        let costs = billing_item::Entity::find()
+            .select_only()
+            .column_as(period.start_of_period(), "period_start")
+            .column_as(cost_sum(), "amount")
+            .inner_join(billing::Entity)
+            .filter(filter.condition())
+            .group_by(period.start_of_period())
+            .into_model::<PeriodAmountRow>()
+            .all(db)
+            .await?;
+        let incomes = billing_income::Entity::find()
+            .select_only()
+            .column_as(period.start_of_period(), "period_start")
+            .column_as(
+                Expr::tbl(billing_income::Entity, billing_income::Column::Amount).sum(),
+                "amount",
+            )
+            .inner_join(billing::Entity)
+            .filter(filter.condition())
+            .group_by(period.start_of_period())
+            .into_model::<PeriodAmountRow>()
+            .all(db)
+            .await?;
+
+        let mut trends: BTreeMap<NaiveDate, PeriodTrend> = BTreeMap::new();
+        for (row, is_income) in costs
+            .into_iter()
+            .map(|row| (row, false))
+            .chain(incomes.into_iter().map(|row| (row, true)))
+        {
+            let period_start = row.period_start.date();
+            let trend = trends.entry(period_start).or_insert_with(|| PeriodTrend {
+                period_start,
+                income: Decimal::ZERO,
+                cost: Decimal::ZERO,
+            });
+            let amount = row.amount.unwrap_or_default();
+            if is_income {
+                trend.income += amount;
+            } else {
+                trend.cost += amount;
+            }
+        }
+        Ok(trends.into_values().collect())
+    }
+
+    /// The items the most was spent on, optionally only those of one category.
+    #[instrument(skip(db))]
+    pub async fn top_items<C: ConnectionTrait>(
+        db: &C,
+        filter: &ReportFilter,
+        category: Option<ItemCategory>,
+        limit: Option<u64>,
+    ) -> Result<Vec<TopItem>, AppError> {
+        let mut select = billing_item::Entity::find()
+            .select_only()
+            .column_as(item::Column::Id, "item_id")
+            .column_as(item::Column::Name, "item_name")
+            .column(item::Column::Category)
+            .column_as(cost_sum(), "cost")
+            .column_as(
+                Expr::tbl(billing_item::Entity, billing_item::Column::Id).count(),
+                "item_count",
+            )
+            .inner_join(billing::Entity)
+            .inner_join(item::Entity)
+            .filter(filter.condition());
+        if let Some(category) = category {
+            select = select.filter(item::Column::Category.eq(category));
+        }
+        let rows = select
+            .group_by(item::Column::Id)
+            .group_by(item::Column::Name)
+            .group_by(item::Column::Category)
+            .order_by(cost_sum(), Order::Desc)
+            .order_by_asc(item::Column::Name)
+            .limit(top_item_limit(limit))
+            .into_model::<TopItemRow>()
+            .all(db)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| TopItem {
+                item_id: row.item_id,
+                item_name: row.item_name,
+                category: row.category,
+                cost: row.cost.unwrap_or_default(),
+                item_count: row.item_count,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use sea_orm::{ActiveModelTrait, Set};
+    use uuid::Uuid;
+
+    use super::{top_item_limit, Report, ReportFilter, ReportPeriod};
+    use crate::entities::{
+        billing, billing_income, billing_item, item,
+        sea_orm_active_enums::{ExpensePayer, ItemCategory, ItemType},
+        team, user,
+    };
+    use crate::error::ErrorCode;
+    use crate::test_db::TestDb;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn filter(
+        team_id: Uuid,
+        start_date: Option<NaiveDate>,
+        end_date: Option<NaiveDate>,
+    ) -> ReportFilter {
+        ReportFilter {
+            team_id,
+            start_date,
+            end_date,
+            team_car_id: None,
+        }
+    }
+
+    #[test]
+    fn start_date_can_not_be_after_end_date() {
+        let team_id = Uuid::new_v4();
+        for (start_date, end_date) in [
+            (None, None),
+            (Some(date(2026, 9, 1)), None),
+            (None, Some(date(2026, 9, 1))),
+            (Some(date(2026, 9, 1)), Some(date(2026, 9, 1))),
+            (Some(date(2026, 9, 1)), Some(date(2026, 9, 30))),
+        ] {
+            assert!(filter(team_id, start_date, end_date).validate().is_ok());
+        }
+        let err = filter(team_id, Some(date(2026, 9, 2)), Some(date(2026, 9, 1)))
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+    }
+
+    #[test]
+    fn top_item_limit_stays_between_1_and_100() {
+        assert_eq!(top_item_limit(None), 10);
+        assert_eq!(top_item_limit(Some(0)), 1);
+        assert_eq!(top_item_limit(Some(1)), 1);
+        assert_eq!(top_item_limit(Some(25)), 25);
+        assert_eq!(top_item_limit(Some(100)), 100);
+        assert_eq!(top_item_limit(Some(101)), 100);
+        assert_eq!(top_item_limit(Some(u64::MAX)), 100);
+    }
+
+    fn at(date: NaiveDate) -> NaiveDateTime {
+        date.and_time(NaiveTime::MIN)
+    }
+
+    /// Billings ended on 31 August and 1 September 2026, each with 500 income, 100 of fuel and
+    /// 50 of tolls, and one still open.
+    async fn team(test_db: &TestDb) -> Uuid {
+        let db = &test_db.db;
+        user::ActiveModel {
+            id: Set("owner-openid".to_owned()),
+            user_name: Set("车主".to_owned()),
+            avatar_url: Set(None),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        let team_model = team::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            team_name: Set("车队".to_owned()),
+            user_id: Set("owner-openid".to_owned()),
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        let mut item_ids = vec![];
+        for (name, category) in [("油费", ItemCategory::Fuel), ("路桥费", ItemCategory::Toll)]
+        {
+            let item_model = item::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                r#type: Set(ItemType::Basic),
+                name: Set(name.to_owned()),
+                team_id: Set(None),
+                icon_url: Set(None),
+                archived: Set(false),
+                category: Set(category),
+            }
+            .insert(db)
+            .await
+            .unwrap();
+            item_ids.push(item_model.id);
+        }
+        for end_date in [Some(date(2026, 8, 31)), Some(date(2026, 9, 1)), None] {
+            let start_time = at(date(2026, 8, 20));
+            let billing_model = billing::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                name: Set("账单".to_owned()),
+                team_id: Set(Some(team_model.id)),
+                start_time: Set(Some(start_time)),
+                end_time: Set(end_date.map(|end_date| end_date.and_hms_opt(18, 0, 0).unwrap())),
+                ..Default::default()
+            }
+            .insert(db)
+            .await
+            .unwrap();
+            billing_income::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                billing_id: Set(billing_model.id),
+                amount: Set(dec!(500)),
+                received_amount: Set(Decimal::ZERO),
+                time: Set(start_time),
+                ..Default::default()
+            }
+            .insert(db)
+            .await
+            .unwrap();
+            for (item_id, cost) in item_ids.iter().zip([dec!(100), dec!(50)]) {
+                billing_item::ActiveModel {
+                    id: Set(Uuid::new_v4()),
+                    billing_id: Set(Some(billing_model.id)),
+                    cost: Set(cost),
+                    item_id: Set(Some(*item_id)),
+                    time: Set(start_time),
+                    user_id: Set(None),
+                    paid_by: Set(ExpensePayer::Company),
+                }
+                .insert(db)
+                .await
+                .unwrap();
+            }
+        }
+        team_model.id
+    }
+
+    #[tokio::test]
+    async fn buckets_ended_billings_by_period() {
+        let Some(test_db) = TestDb::create().await else {
+            return;
+        };
+        let db = &test_db.db;
+        let team_id = team(&test_db).await;
+        let filter = filter(team_id, None, None);
+
+        let months = Report::trend(db, &filter, ReportPeriod::Month)
+            .await
+            .unwrap();
+        let months: Vec<(NaiveDate, Decimal, Decimal)> = months
+            .into_iter()
+            .map(|trend| (trend.period_start, trend.income, trend.cost))
+            .collect();
+        assert_eq!(
+            months,
+            vec![
+                (date(2026, 8, 1), dec!(500), dec!(150)),
+                (date(2026, 9, 1), dec!(500), dec!(150)),
+            ]
+        );
+        let years = Report::trend(db, &filter, ReportPeriod::Year)
+            .await
+            .unwrap();
+        assert_eq!(years.len(), 1);
+        assert_eq!(years[0].period_start, date(2026, 1, 1));
+        assert_eq!(years[0].gross_margin(), dec!(700));
+
+        let costs = Report::category_costs(db, &filter, ReportPeriod::Month)
+            .await
+            .unwrap();
+        assert_eq!(costs.len(), 4);
+        assert_eq!(costs[0].period_start, date(2026, 8, 1));
+        assert_eq!(costs[0].category, ItemCategory::Fuel);
+
+        let top_items = Report::top_items(db, &filter, None, Some(1)).await.unwrap();
+        assert_eq!(top_items.len(), 1);
+        assert_eq!(top_items[0].item_name, "油费");
+        assert_eq!(top_items[0].cost, dec!(200));
+        test_db.drop().await;
+    }
+}
@@ -1,13 +1,20 @@
 use std::str::FromStr;
 
 use chrono::Utc;
+use poem::web::Data;
 use poem_openapi::{param::Path, payload::Json, ApiResponse, Object, OpenApi, Tags};
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
 
 use tracing::info;
 use uuid::Uuid;
 
-use crate::{entities::role, DATABASE};
+use crate::state::AppState;
+use crate::{
+    auth_service::service::require_admin,
+    entities::role,
+    error::{parse_uuid, ApiResult, AppError},
+    session_service::service::SessionUser,
+};
 
 use super::service::{UserRoleAggregate, UserRoleType};
 
@@ -27,18 +34,12 @@ pub struct UserRoleDTO {
 enum AddUserRoleResponse {
     #[oai(status = 200)]
     Ok(Json<String>),
-
-    #[oai(status = 500)]
-    Error,
 }
 
 #[derive(ApiResponse)]
 enum GetUserRoleResponse {
     #[oai(status = 200)]
     Ok(Json<Vec<UserRoleResponseEntity>>),
-
-    #[oai(status = 500)]
-    Error,
 }
 
 #[derive(Debug, Object, Clone, Eq, PartialEq)]
@@ -60,9 +61,6 @@ impl From<role::Model> for UserRoleResponseEntity {
 enum DeleteUserRoleResponse {
     #[oai(status = 201)]
     Ok,
-
-    #[oai(status = 500)]
-    Error,
 }
 
 #[derive(Debug, Object, Clone, Eq, PartialEq)]
@@ -71,6 +69,18 @@ pub struct DeleteUserRoleDTO {
     pub role_id: String,
 }
 
+/// Role management is reserved to admins, except that a user may read their own roles.
+async fn can_manage_roles(
+    db: &DatabaseConnection,
+    session_user: &SessionUser,
+    user_id: Option<&str>,
+) -> Result<(), AppError> {
+    if user_id == Some(session_user.user_id.as_str()) {
+        return Ok(());
+    }
+    require_admin(db, session_user).await
+}
+
 pub struct UserRoleRouter;
 
 #[OpenApi]
@@ -82,19 +92,19 @@ impl UserRoleRouter {
     )]
     async fn create(
         &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
         user_id: Path<String>,
         user_role: Json<UserRoleDTO>,
-    ) -> AddUserRoleResponse {
+    ) -> ApiResult<AddUserRoleResponse> {
+        can_manage_roles(&state.db, &session_user, None).await?;
         let user_aggregate: UserRoleAggregate = UserRoleAggregate::new(
             Uuid::new_v4(),
             user_id.0,
             UserRoleType::from_str(&user_role.role_type).unwrap(),
         );
-        let add_result = user_aggregate.save().await;
-        if let Ok(id) = add_result {
-            return AddUserRoleResponse::Ok(Json(id.to_string()));
-        }
-        AddUserRoleResponse::Error
+        let id = user_aggregate.save(&state.db).await?;
+        Ok(AddUserRoleResponse::Ok(Json(id.to_string())))
     }
 
     #[oai(
@@ -102,34 +112,36 @@ impl UserRoleRouter {
         method = "get",
         tag = "ApiTags::UserRole"
     )]
-    async fn get(&self, user_id: Path<String>) -> GetUserRoleResponse {
-        let db = DATABASE.get().unwrap();
+    async fn get(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        user_id: Path<String>,
+    ) -> ApiResult<GetUserRoleResponse> {
+        let db = &state.db;
         let user_id = user_id.0;
+        can_manage_roles(&state.db, &session_user, Some(&user_id)).await?;
         let start_time = Utc::now();
         info!("start query orm");
-        let user_entity_result = role::Entity::find()
+        let models = role::Entity::find()
             .filter(role::Column::UserId.eq(user_id))
             .all(db)
-            .await;
+            .await?;
         let end_time = Utc::now();
         info!(
             "end query orm, cost time: {}ms",
             (end_time - start_time).num_milliseconds()
         );
-        if let Ok(models) = user_entity_result {
-            let mut response: Vec<UserRoleResponseEntity> = vec![];
-            for model in models {
-                response.push(model.into())
-            }
-            let end_time = Utc::now();
-            info!(
-                "end api response, cost time: {}ms",
-                (end_time - start_time).num_milliseconds()
-            );
-            GetUserRoleResponse::Ok(Json(response))
-        } else {
-            GetUserRoleResponse::Error
+        let mut response: Vec<UserRoleResponseEntity> = vec![];
+        for model in models {
+            response.push(model.into())
         }
+        let end_time = Utc::now();
+        info!(
+            "end api response, cost time: {}ms",
+            (end_time - start_time).num_milliseconds()
+        );
+        Ok(GetUserRoleResponse::Ok(Json(response)))
     }
 
     #[oai(
@@ -139,22 +151,20 @@ impl UserRoleRouter {
     )]
     async fn delete(
         &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
         user_id: Path<String>,
         body: Json<DeleteUserRoleDTO>,
-    ) -> DeleteUserRoleResponse {
-        let db = DATABASE.get().unwrap();
+    ) -> ApiResult<DeleteUserRoleResponse> {
+        can_manage_roles(&state.db, &session_user, None).await?;
+        let db = &state.db;
         let user_id = user_id.0;
         let delete_user_role_dto = body.0;
-        if let Ok(role_id) = Uuid::parse_str(&delete_user_role_dto.role_id) {
-            if (role::Entity::delete_by_id(role_id)
-                .filter(role::Column::UserId.eq(user_id))
-                .exec(db)
-                .await)
-                .is_ok()
-            {
-                return DeleteUserRoleResponse::Ok;
-            }
-        };
-        DeleteUserRoleResponse::Error
+        let role_id = parse_uuid("role_id", &delete_user_role_dto.role_id)?;
+        role::Entity::delete_by_id(role_id)
+            .filter(role::Column::UserId.eq(user_id))
+            .exec(db)
+            .await?;
+        Ok(DeleteUserRoleResponse::Ok)
     }
 }
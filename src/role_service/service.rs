@@ -1,15 +1,12 @@
 use std::str::FromStr;
 
 use poem_openapi::Enum;
-use sea_orm::{ActiveModelTrait, ActiveValue, DbErr, Set};
+use sea_orm::{ActiveModelTrait, ActiveValue, ConnectionTrait, DbErr, Set};
 
 use tracing::instrument;
 use uuid::Uuid;
 
-use crate::{
-    entities::{role, sea_orm_active_enums::RoleType},
-    DATABASE,
-};
+use crate::entities::{role, sea_orm_active_enums::RoleType};
 
 #[derive(Debug)]
 pub struct UserRoleAggregate {
@@ -88,9 +85,8 @@ impl UserRoleAggregate {
         }
     }
 
-    #[instrument]
-    pub async fn save(self) -> Result<Uuid, DbErr> {
-        let db = DATABASE.get().unwrap();
+    #[instrument(skip(db))]
+    pub async fn save<C: ConnectionTrait>(self, db: &C) -> Result<Uuid, DbErr> {
         let _insert_result = role::ActiveModel {
             id: Set(self.id),
             user_id: Set(self.user_id.clone()),
@@ -0,0 +1,65 @@
+use crate::state::AppState;
+use crate::{billing_service::service::parse_navie_time_to_data_time, error::ApiResult};
+use chrono::{DateTime, Local};
+use poem::web::Data;
+use poem_openapi::{payload::Json, ApiResponse, Object, OpenApi, Tags};
+
+use super::service::Session;
+
+#[derive(Tags)]
+enum ApiTags {
+    /// Session tokens issued on login
+    Session,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct SessionDTO {
+    pub user_id: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub access_expire_time: Option<DateTime<Local>>,
+    pub refresh_expire_time: Option<DateTime<Local>>,
+}
+
+impl From<Session> for SessionDTO {
+    fn from(session: Session) -> Self {
+        SessionDTO {
+            user_id: session.user_id,
+            access_token: session.access_token,
+            refresh_token: session.refresh_token,
+            access_expire_time: parse_navie_time_to_data_time(Some(session.access_expire_time)),
+            refresh_expire_time: parse_navie_time_to_data_time(Some(session.refresh_expire_time)),
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct SessionRefreshDTO {
+    #[oai(validator(max_length = 64))]
+    refresh_token: String,
+}
+
+#[derive(ApiResponse)]
+enum RefreshSessionResponse {
+    #[oai(status = 200)]
+    Ok(Json<SessionDTO>),
+}
+
+pub struct SessionRouter;
+
+#[OpenApi]
+impl SessionRouter {
+    #[oai(
+        path = "/user/token/refresh",
+        method = "post",
+        tag = "ApiTags::Session"
+    )]
+    async fn refresh(
+        &self,
+        state: Data<&AppState>,
+        body: Json<SessionRefreshDTO>,
+    ) -> ApiResult<RefreshSessionResponse> {
+        let session = Session::refresh(&state.db, &body.refresh_token).await?;
+        Ok(RefreshSessionResponse::Ok(Json(session.into())))
+    }
+}
@@ -0,0 +1,78 @@
+use poem::{
+    web::headers::{authorization::Bearer, Authorization, HeaderMapExt},
+    Endpoint, Error, FromRequest, Middleware, Request, RequestBody, Result,
+};
+use sea_orm::DatabaseConnection;
+use tracing::warn;
+
+use crate::error::{AppError, ErrorCode, ErrorResponse};
+
+use super::service::{Session, SessionUser};
+
+/// Resolves the `Authorization: Bearer <access token>` header into a `SessionUser` and
+/// stores it in the request extensions.
+///
+/// Requests without the header pass through untouched, endpoints that need a user ask
+/// for `SessionUser` and get a 401 when it is missing. A header carrying an unknown or
+/// expired token is rejected with a 401 right here. Both carry the usual problem body.
+pub struct SessionMiddleware {
+    db: DatabaseConnection,
+}
+
+impl SessionMiddleware {
+    pub fn new(db: DatabaseConnection) -> Self {
+        SessionMiddleware { db }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for SessionMiddleware {
+    type Output = SessionEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        SessionEndpoint {
+            ep,
+            db: self.db.clone(),
+        }
+    }
+}
+
+pub struct SessionEndpoint<E> {
+    ep: E,
+    db: DatabaseConnection,
+}
+
+#[poem::async_trait]
+impl<E: Endpoint> Endpoint for SessionEndpoint<E> {
+    type Output = E::Output;
+
+    async fn call(&self, mut req: Request) -> Result<Self::Output> {
+        if let Some(Authorization(bearer)) = req.headers().typed_get::<Authorization<Bearer>>() {
+            match Session::resolve(&self.db, bearer.token()).await {
+                Ok(session_user) => {
+                    req.extensions_mut().insert(session_user);
+                }
+                Err(err) => {
+                    if err.code != ErrorCode::DatabaseError {
+                        warn!("reject request with bad session token, {}", err);
+                    }
+                    return Err(ErrorResponse::from(err).into());
+                }
+            }
+        }
+        self.ep.call(req).await
+    }
+}
+
+#[poem::async_trait]
+impl<'a> FromRequest<'a> for SessionUser {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        req.extensions()
+            .get::<SessionUser>()
+            .cloned()
+            .ok_or_else(|| {
+                Error::from(ErrorResponse::from(AppError::unauthorized(
+                    "missing session token",
+                )))
+            })
+    }
+}
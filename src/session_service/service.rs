@@ -0,0 +1,128 @@
+use chrono::{Duration, Local, NaiveDateTime};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QuerySelect, Set,
+    TransactionTrait,
+};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    entities::user_session,
+    error::{AppError, ErrorCode},
+};
+
+/// Lifetime of an access token. Clients refresh before it runs out.
+const ACCESS_TOKEN_TTL_MINUTES: i64 = 120;
+/// Lifetime of a refresh token. Past this the user has to log in through WeChat again.
+const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+
+fn invalid_token() -> AppError {
+    AppError::unauthorized("session token is invalid")
+}
+
+fn expired_token() -> AppError {
+    AppError::new(ErrorCode::TokenExpired, "session token is expired")
+}
+
+/// The user behind a request, resolved from its access token by `SessionMiddleware`.
+///
+/// `user_id` is the WeChat openid the session was issued for.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SessionUser {
+    pub user_id: String,
+}
+
+#[derive(Debug)]
+pub struct Session {
+    pub user_id: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub access_expire_time: NaiveDateTime,
+    pub refresh_expire_time: NaiveDateTime,
+}
+
+impl From<user_session::Model> for Session {
+    fn from(model: user_session::Model) -> Self {
+        Session {
+            user_id: model.user_id,
+            access_token: model.access_token,
+            refresh_token: model.refresh_token,
+            access_expire_time: model.access_expire_time,
+            refresh_expire_time: model.refresh_expire_time,
+        }
+    }
+}
+
+/// Opaque random token, 244 bits of entropy from two v4 uuids.
+fn generate_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+impl Session {
+    fn new_active_model(user_id: String) -> user_session::ActiveModel {
+        let now = Local::now().naive_local();
+        user_session::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            user_id: Set(user_id),
+            access_token: Set(generate_token()),
+            refresh_token: Set(generate_token()),
+            access_expire_time: Set(now + Duration::minutes(ACCESS_TOKEN_TTL_MINUTES)),
+            refresh_expire_time: Set(now + Duration::days(REFRESH_TOKEN_TTL_DAYS)),
+            create_time: Set(now),
+        }
+    }
+
+    /// Issues a new session for the user, called after a successful WeChat login.
+    #[instrument(skip(db))]
+    pub async fn issue<C: ConnectionTrait>(db: &C, user_id: String) -> Result<Session, AppError> {
+        let model = Session::new_active_model(user_id).insert(db).await?;
+        Ok(model.into())
+    }
+
+    /// Exchanges a refresh token for a new session. The old session is removed so a
+    /// refresh token can only be used once.
+    #[instrument(skip(db, refresh_token))]
+    pub async fn refresh<C: TransactionTrait>(
+        db: &C,
+        refresh_token: &str,
+    ) -> Result<Session, AppError> {
+        let txn = db.begin().await?;
+        let model = user_session::Entity::find()
+            .filter(user_session::Column::RefreshToken.eq(refresh_token))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or_else(invalid_token)?;
+        user_session::Entity::delete_by_id(model.id)
+            .exec(&txn)
+            .await?;
+        if model.refresh_expire_time <= Local::now().naive_local() {
+            txn.commit().await?;
+            return Err(expired_token());
+        }
+        let model = Session::new_active_model(model.user_id)
+            .insert(&txn)
+            .await?;
+        txn.commit().await?;
+        Ok(model.into())
+    }
+
+    /// Resolves the user an access token was issued for.
+    #[instrument(skip(db, access_token))]
+    pub async fn resolve<C: ConnectionTrait>(
+        db: &C,
+        access_token: &str,
+    ) -> Result<SessionUser, AppError> {
+        let model = user_session::Entity::find()
+            .filter(user_session::Column::AccessToken.eq(access_token))
+            .one(db)
+            .await?
+            .ok_or_else(invalid_token)?;
+        if model.access_expire_time <= Local::now().naive_local() {
+            return Err(expired_token());
+        }
+        Ok(SessionUser {
+            user_id: model.user_id,
+        })
+    }
+}
@@ -0,0 +1,395 @@
+use chrono::{DateTime, Local};
+use poem::web::Data;
+use poem_openapi::{
+    param::{Path, Query},
+    payload::Json,
+    ApiResponse, Enum, Object, OpenApi, Tags,
+};
+use rust_decimal::Decimal;
+
+use crate::auth_service::service::{TeamGuard, TeamPermission};
+use crate::billing_service::service::Team;
+use crate::dispatch_service::service::Dispatch;
+use crate::entities::sea_orm_active_enums::ExpensePayer;
+use crate::error::{parse_uuid, ApiResult, AppError, ErrorCode};
+use crate::session_service::service::SessionUser;
+use crate::state::AppState;
+
+use super::service::{DriverAdvance, Settlement, SettlementStatus};
+
+#[derive(Tags)]
+enum ApiTags {
+    /// Cash advances to drivers and what drivers and company owe each other per billing
+    Settlement,
+}
+
+#[derive(Debug, Enum, Clone, Copy, Eq, PartialEq)]
+#[oai(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ExpensePayerDTO {
+    Driver,
+    Company,
+}
+
+impl From<ExpensePayerDTO> for ExpensePayer {
+    fn from(paid_by: ExpensePayerDTO) -> Self {
+        match paid_by {
+            ExpensePayerDTO::Driver => ExpensePayer::Driver,
+            ExpensePayerDTO::Company => ExpensePayer::Company,
+        }
+    }
+}
+
+impl From<ExpensePayer> for ExpensePayerDTO {
+    fn from(paid_by: ExpensePayer) -> Self {
+        match paid_by {
+            ExpensePayer::Driver => ExpensePayerDTO::Driver,
+            ExpensePayer::Company => ExpensePayerDTO::Company,
+        }
+    }
+}
+
+#[derive(Debug, Enum, Clone, Copy, Eq, PartialEq)]
+#[oai(rename_all = "SCREAMING_SNAKE_CASE")]
+enum SettlementStatusDTO {
+    Open,
+    Pending,
+    OwnerConfirmed,
+    DriverConfirmed,
+    Settled,
+}
+
+impl From<SettlementStatus> for SettlementStatusDTO {
+    fn from(status: SettlementStatus) -> Self {
+        match status {
+            SettlementStatus::Open => SettlementStatusDTO::Open,
+            SettlementStatus::Pending => SettlementStatusDTO::Pending,
+            SettlementStatus::OwnerConfirmed => SettlementStatusDTO::OwnerConfirmed,
+            SettlementStatus::DriverConfirmed => SettlementStatusDTO::DriverConfirmed,
+            SettlementStatus::Settled => SettlementStatusDTO::Settled,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct AdvanceCreateDTO {
+    #[oai(validator(max_length = 128))]
+    user_id: String,
+
+    amount: Decimal,
+
+    /// The billing the advance is paid back on, can be left out when a dispatch is given.
+    #[oai(validator(max_length = 128))]
+    billing_id: Option<String>,
+
+    /// The trip the advance is for, the driver has to be on it.
+    #[oai(validator(max_length = 128))]
+    dispatch_id: Option<String>,
+
+    #[oai(validator(max_length = 255))]
+    note: Option<String>,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct AdvanceEntityDTO {
+    advance_id: String,
+    billing_id: String,
+    dispatch_id: Option<String>,
+    user_id: String,
+    amount: Decimal,
+    note: Option<String>,
+    issued_by: Option<String>,
+    time: Option<DateTime<Local>>,
+}
+
+impl From<DriverAdvance> for AdvanceEntityDTO {
+    fn from(advance: DriverAdvance) -> Self {
+        AdvanceEntityDTO {
+            advance_id: advance.id.to_string(),
+            billing_id: advance.billing_id.to_string(),
+            dispatch_id: advance
+                .dispatch_id
+                .map(|dispatch_id| dispatch_id.to_string()),
+            user_id: advance.user_id,
+            amount: advance.amount,
+            note: advance.note,
+            issued_by: advance.issued_by,
+            time: advance.time,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct SettlementEntityDTO {
+    /// Missing while the billing is open and the settlement is only a preview.
+    settlement_id: Option<String>,
+    billing_id: String,
+    billing_name: String,
+    user_id: String,
+    advance_amount: Decimal,
+    /// Costs the driver recorded as paid out of pocket.
+    driver_paid_amount: Decimal,
+    /// Positive when the company owes the driver, negative when the driver owes the company.
+    balance: Decimal,
+    status: SettlementStatusDTO,
+    owner_confirmed_by: Option<String>,
+    owner_confirm_time: Option<DateTime<Local>>,
+    driver_confirm_time: Option<DateTime<Local>>,
+    create_time: Option<DateTime<Local>>,
+}
+
+impl From<Settlement> for SettlementEntityDTO {
+    fn from(settlement: Settlement) -> Self {
+        SettlementEntityDTO {
+            balance: settlement.balance(),
+            status: settlement.status().into(),
+            settlement_id: settlement.id.map(|settlement_id| settlement_id.to_string()),
+            billing_id: settlement.billing_id.to_string(),
+            billing_name: settlement.billing_name,
+            user_id: settlement.user_id,
+            advance_amount: settlement.advance_amount,
+            driver_paid_amount: settlement.driver_paid_amount,
+            owner_confirmed_by: settlement.owner_confirmed_by,
+            owner_confirm_time: settlement.owner_confirm_time,
+            driver_confirm_time: settlement.driver_confirm_time,
+            create_time: settlement.create_time,
+        }
+    }
+}
+
+#[derive(ApiResponse)]
+enum AdvanceResponse {
+    #[oai(status = 201)]
+    Created(Json<AdvanceEntityDTO>),
+}
+
+#[derive(ApiResponse)]
+enum QueryAdvanceResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<AdvanceEntityDTO>>),
+}
+
+#[derive(ApiResponse)]
+enum DeleteAdvanceResponse {
+    #[oai(status = 204)]
+    Ok,
+}
+
+#[derive(ApiResponse)]
+enum SettlementResponse {
+    #[oai(status = 200)]
+    Ok(Json<SettlementEntityDTO>),
+}
+
+#[derive(ApiResponse)]
+enum QuerySettlementResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<SettlementEntityDTO>>),
+}
+
+pub struct SettlementRouter;
+
+#[OpenApi]
+impl SettlementRouter {
+    #[oai(
+        path = "/team/:team_id/advance",
+        method = "post",
+        tag = "ApiTags::Settlement"
+    )]
+    async fn issue_advance(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        advance: Json<AdvanceCreateDTO>,
+    ) -> ApiResult<AdvanceResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let advance = advance.0;
+        let billing_uuid = match &advance.billing_id {
+            Some(billing_id) => Some(parse_uuid("billing_id", billing_id)?),
+            None => None,
+        };
+        let dispatch = match &advance.dispatch_id {
+            Some(dispatch_id) => Some(
+                Dispatch::from_id(
+                    &state.db,
+                    team_uuid,
+                    parse_uuid("dispatch_id", dispatch_id)?,
+                )
+                .await?,
+            ),
+            None => None,
+        };
+        let billing_uuid = match (billing_uuid, &dispatch) {
+            (Some(billing_uuid), Some(dispatch)) if billing_uuid != dispatch.billing_id => {
+                return Err(AppError::new(
+                    ErrorCode::InvalidArgument,
+                    format!(
+                        "dispatch {} is not on billing {}",
+                        dispatch.id, billing_uuid
+                    ),
+                )
+                .into())
+            }
+            (_, Some(dispatch)) => dispatch.billing_id,
+            (Some(billing_uuid), None) => billing_uuid,
+            (None, None) => {
+                return Err(AppError::new(
+                    ErrorCode::InvalidArgument,
+                    "an advance needs a billing or a dispatch",
+                )
+                .into())
+            }
+        };
+        let team = Team::get_by_id(&state.db, team_uuid).await?;
+        let billing = team.get_billing(&state.db, billing_uuid).await?;
+        let advance = DriverAdvance::issue(
+            &state.db,
+            &billing,
+            dispatch.map(|dispatch| dispatch.id),
+            advance.user_id,
+            advance.amount,
+            advance.note,
+            session_user.user_id,
+        )
+        .await?;
+        Ok(AdvanceResponse::Created(Json(advance.into())))
+    }
+
+    /// Drivers only see their own advances.
+    #[oai(
+        path = "/team/:team_id/billing/:billing_id/advance",
+        method = "get",
+        tag = "ApiTags::Settlement"
+    )]
+    async fn query_advance(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        billing_id: Path<String>,
+    ) -> ApiResult<QueryAdvanceResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let billing_uuid = parse_uuid("billing_id", &billing_id.0)?;
+        let role =
+            TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Member).await?;
+        let team = Team::get_by_id(&state.db, team_uuid).await?;
+        let billing = team.get_billing(&state.db, billing_uuid).await?;
+        let user_id = (!role.allows(TeamPermission::Manage)).then_some(session_user.user_id);
+        let advances = DriverAdvance::query(&state.db, billing.id, user_id).await?;
+        Ok(QueryAdvanceResponse::Ok(Json(
+            advances.into_iter().map(|advance| advance.into()).collect(),
+        )))
+    }
+
+    #[oai(
+        path = "/team/:team_id/advance/:advance_id",
+        method = "delete",
+        tag = "ApiTags::Settlement"
+    )]
+    async fn delete_advance(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        advance_id: Path<String>,
+    ) -> ApiResult<DeleteAdvanceResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let advance_uuid = parse_uuid("advance_id", &advance_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let advance = DriverAdvance::from_id(&state.db, advance_uuid).await?;
+        let team = Team::get_by_id(&state.db, team_uuid).await?;
+        let billing = team.get_billing(&state.db, advance.billing_id).await?;
+        advance.delete(&state.db, &billing).await?;
+        Ok(DeleteAdvanceResponse::Ok)
+    }
+
+    /// Settlements recorded when the billing ended, a preview while it is open. Drivers only
+    /// see their own.
+    #[oai(
+        path = "/team/:team_id/billing/:billing_id/settlement",
+        method = "get",
+        tag = "ApiTags::Settlement"
+    )]
+    async fn get_billing_settlement(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        billing_id: Path<String>,
+    ) -> ApiResult<QuerySettlementResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let billing_uuid = parse_uuid("billing_id", &billing_id.0)?;
+        let role =
+            TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Member).await?;
+        let team = Team::get_by_id(&state.db, team_uuid).await?;
+        let billing = team.get_billing(&state.db, billing_uuid).await?;
+        let user_id = (!role.allows(TeamPermission::Manage)).then_some(session_user.user_id);
+        let settlements = Settlement::of_billing(&state.db, &billing, user_id).await?;
+        Ok(QuerySettlementResponse::Ok(Json(
+            settlements
+                .into_iter()
+                .map(|settlement| settlement.into())
+                .collect(),
+        )))
+    }
+
+    /// Owners and admins confirm for the company, the driver confirms their own settlement.
+    #[oai(
+        path = "/team/:team_id/billing/:billing_id/settlement/:user_id/confirm",
+        method = "post",
+        tag = "ApiTags::Settlement"
+    )]
+    async fn confirm_settlement(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        billing_id: Path<String>,
+        user_id: Path<String>,
+    ) -> ApiResult<SettlementResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let billing_uuid = parse_uuid("billing_id", &billing_id.0)?;
+        let role =
+            TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Member).await?;
+        let team = Team::get_by_id(&state.db, team_uuid).await?;
+        let billing = team.get_billing(&state.db, billing_uuid).await?;
+        let settlement = Settlement::confirm(
+            &state.db,
+            &billing,
+            &user_id.0,
+            &session_user.user_id,
+            role.allows(TeamPermission::Manage),
+        )
+        .await?;
+        Ok(SettlementResponse::Ok(Json(settlement.into())))
+    }
+
+    /// Settlements of ended billings, newest first. Drivers only see their own.
+    #[oai(
+        path = "/team/:team_id/settlement",
+        method = "get",
+        tag = "ApiTags::Settlement"
+    )]
+    async fn query_settlement(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        /// Only settlements still waiting for the owner or the driver.
+        unsettled: Query<Option<bool>>,
+    ) -> ApiResult<QuerySettlementResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let role =
+            TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Member).await?;
+        let user_id = (!role.allows(TeamPermission::Manage)).then_some(session_user.user_id);
+        let settlements =
+            Settlement::query(&state.db, team_uuid, user_id, unsettled.0.unwrap_or(false)).await?;
+        Ok(QuerySettlementResponse::Ok(Json(
+            settlements
+                .into_iter()
+                .map(|settlement| settlement.into())
+                .collect(),
+        )))
+    }
+}
@@ -0,0 +1,455 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Local};
+use rust_decimal::Decimal;
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, FromQueryResult,
+    ModelTrait, QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use tracing::{instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    billing_service::service::{parse_navie_time_to_data_time, Billing},
+    entities::{
+        billing, billing_item, billing_settlement, dispatch, dispatch_driver, driver_advance,
+        sea_orm_active_enums::ExpensePayer, team_driver,
+    },
+    error::{AppError, ErrorCode},
+    income_service::service::trimmed,
+};
+
+fn advance_not_found(advance_id: Uuid) -> AppError {
+    AppError::new(
+        ErrorCode::AdvanceNotFound,
+        format!("can not find advance {}", advance_id),
+    )
+}
+
+fn settlement_not_found(billing_id: Uuid, user_id: &str) -> AppError {
+    AppError::new(
+        ErrorCode::SettlementNotFound,
+        format!(
+            "can not find settlement of driver {} on billing {}",
+            user_id, billing_id
+        ),
+    )
+}
+
+/// Cash handed to a driver before a trip, paid back through the billing settlement.
+#[derive(Debug, Clone)]
+pub struct DriverAdvance {
+    pub id: Uuid,
+    pub billing_id: Uuid,
+    pub dispatch_id: Option<Uuid>,
+    pub user_id: String,
+    pub amount: Decimal,
+    pub note: Option<String>,
+    pub issued_by: Option<String>,
+    pub time: Option<DateTime<Local>>,
+}
+
+impl From<driver_advance::Model> for DriverAdvance {
+    fn from(model: driver_advance::Model) -> Self {
+        DriverAdvance {
+            id: model.id,
+            billing_id: model.billing_id,
+            dispatch_id: model.dispatch_id,
+            user_id: model.user_id,
+            amount: model.amount,
+            note: model.note,
+            issued_by: model.issued_by,
+            time: parse_navie_time_to_data_time(Some(model.time)),
+        }
+    }
+}
+
+impl DriverAdvance {
+    /// Hands `amount` to a driver of the team on an open billing. With a dispatch the driver
+    /// has to be on it and the dispatch has to belong to the billing.
+    #[instrument(skip(db, billing))]
+    pub async fn issue<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        billing: &Billing,
+        dispatch_id: Option<Uuid>,
+        user_id: String,
+        amount: Decimal,
+        note: Option<String>,
+        issued_by: String,
+    ) -> Result<DriverAdvance, AppError> {
+        if amount <= Decimal::ZERO {
+            return Err(AppError::new(
+                ErrorCode::InvalidArgument,
+                "advance amount must be positive",
+            ));
+        }
+        if billing.is_ended() {
+            warn!("billing {} is ended, can not issue advance", billing.id);
+            return Err(AppError::new(
+                ErrorCode::BillingEnded,
+                format!("billing {} is already ended", billing.id),
+            ));
+        }
+        let team_driver_model = team_driver::Entity::find()
+            .filter(team_driver::Column::TeamId.eq(billing.team_id))
+            .filter(team_driver::Column::UserId.eq(user_id.clone()))
+            .one(db)
+            .await?;
+        if team_driver_model.is_none() {
+            return Err(AppError::new(
+                ErrorCode::DriverNotInTeam,
+                format!("driver {} is not in team", user_id),
+            ));
+        }
+        if let Some(dispatch_id) = dispatch_id {
+            let dispatch_driver_model = dispatch_driver::Entity::find()
+                .inner_join(dispatch::Entity)
+                .filter(dispatch::Column::Id.eq(dispatch_id))
+                .filter(dispatch::Column::BillingId.eq(billing.id))
+                .filter(dispatch_driver::Column::UserId.eq(user_id.clone()))
+                .one(db)
+                .await?;
+            if dispatch_driver_model.is_none() {
+                return Err(AppError::new(
+                    ErrorCode::DriverNotDispatched,
+                    format!("driver {} is not on dispatch {}", user_id, dispatch_id),
+                ));
+            }
+        }
+
+        let txn = db.begin().await?;
+        billing.lock_open_billing(&txn).await?;
+        let model = driver_advance::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            billing_id: Set(billing.id),
+            dispatch_id: Set(dispatch_id),
+            user_id: Set(user_id),
+            amount: Set(amount),
+            note: Set(trimmed(note)),
+            issued_by: Set(Some(issued_by)),
+            time: Set(Local::now().naive_local()),
+        }
+        .insert(&txn)
+        .await?;
+        txn.commit().await?;
+        Ok(model.into())
+    }
+
+    #[instrument(skip(db))]
+    pub async fn from_id<C: ConnectionTrait>(
+        db: &C,
+        advance_id: Uuid,
+    ) -> Result<DriverAdvance, AppError> {
+        let model = driver_advance::Entity::find_by_id(advance_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| advance_not_found(advance_id))?;
+        Ok(model.into())
+    }
+
+    /// Advances on a billing, only those of `user_id` when given, in the order issued.
+    #[instrument(skip(db))]
+    pub async fn query<C: ConnectionTrait>(
+        db: &C,
+        billing_id: Uuid,
+        user_id: Option<String>,
+    ) -> Result<Vec<DriverAdvance>, AppError> {
+        let mut select =
+            driver_advance::Entity::find().filter(driver_advance::Column::BillingId.eq(billing_id));
+        if let Some(user_id) = user_id {
+            select = select.filter(driver_advance::Column::UserId.eq(user_id));
+        }
+        Ok(select
+            .order_by_asc(driver_advance::Column::Time)
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|model| model.into())
+            .collect())
+    }
+
+    /// Takes back an advance issued by mistake, only while its billing is open.
+    #[instrument(skip(db, billing))]
+    pub async fn delete<C: ConnectionTrait + TransactionTrait>(
+        self,
+        db: &C,
+        billing: &Billing,
+    ) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        billing.lock_open_billing(&txn).await?;
+        let model = driver_advance::Entity::find_by_id(self.id)
+            .filter(driver_advance::Column::BillingId.eq(billing.id))
+            .one(&txn)
+            .await?
+            .ok_or_else(|| advance_not_found(self.id))?;
+        model.delete(&txn).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SettlementStatus {
+    /// The billing is still open, the figures are a preview.
+    Open,
+    Pending,
+    OwnerConfirmed,
+    DriverConfirmed,
+    Settled,
+}
+
+/// What one driver and the company owe each other for a billing.
+#[derive(Debug, Clone)]
+pub struct Settlement {
+    /// Missing on previews of open billings.
+    pub id: Option<Uuid>,
+    pub billing_id: Uuid,
+    pub billing_name: String,
+    pub user_id: String,
+    pub advance_amount: Decimal,
+    pub driver_paid_amount: Decimal,
+    pub owner_confirmed_by: Option<String>,
+    pub owner_confirm_time: Option<DateTime<Local>>,
+    pub driver_confirm_time: Option<DateTime<Local>>,
+    pub create_time: Option<DateTime<Local>>,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct UserAmount {
+    user_id: Option<String>,
+    amount: Option<Decimal>,
+}
+
+impl From<(billing_settlement::Model, String)> for Settlement {
+    fn from((model, billing_name): (billing_settlement::Model, String)) -> Self {
+        Settlement {
+            id: Some(model.id),
+            billing_id: model.billing_id,
+            billing_name,
+            user_id: model.user_id,
+            advance_amount: model.advance_amount,
+            driver_paid_amount: model.driver_paid_amount,
+            owner_confirmed_by: model.owner_confirmed_by,
+            owner_confirm_time: parse_navie_time_to_data_time(model.owner_confirm_time),
+            driver_confirm_time: parse_navie_time_to_data_time(model.driver_confirm_time),
+            create_time: parse_navie_time_to_data_time(Some(model.create_time)),
+        }
+    }
+}
+
+impl Settlement {
+    /// Positive when the company owes the driver, negative when the driver owes the company.
+    pub fn balance(&self) -> Decimal {
+        self.driver_paid_amount - self.advance_amount
+    }
+
+    pub fn status(&self) -> SettlementStatus {
+        match (
+            self.id,
+            self.owner_confirm_time.is_some(),
+            self.driver_confirm_time.is_some(),
+        ) {
+            (None, _, _) => SettlementStatus::Open,
+            (Some(_), false, false) => SettlementStatus::Pending,
+            (Some(_), true, false) => SettlementStatus::OwnerConfirmed,
+            (Some(_), false, true) => SettlementStatus::DriverConfirmed,
+            (Some(_), true, true) => SettlementStatus::Settled,
+        }
+    }
+
+    /// Advances and out of pocket costs per driver of the billing, ordered by driver.
+    async fn driver_amounts<C: ConnectionTrait>(
+        db: &C,
+        billing_id: Uuid,
+    ) -> Result<BTreeMap<String, (Decimal, Decimal)>, AppError> {
+        let advances = driver_advance::Entity::find()
+            .select_only()
+            .column(driver_advance::Column::UserId)
+            .column_as(
+                Expr::tbl(driver_advance::Entity, driver_advance::Column::Amount).sum(),
+                "amount",
+            )
+            .filter(driver_advance::Column::BillingId.eq(billing_id))
+            .group_by(driver_advance::Column::UserId)
+            .into_model::<UserAmount>()
+            .all(db)
+            .await?;
+        let driver_paid = billing_item::Entity::find()
+            .select_only()
+            .column(billing_item::Column::UserId)
+            .column_as(
+                Expr::tbl(billing_item::Entity, billing_item::Column::Cost).sum(),
+                "amount",
+            )
+            .filter(billing_item::Column::BillingId.eq(billing_id))
+            .filter(billing_item::Column::PaidBy.eq(ExpensePayer::Driver))
+            .filter(billing_item::Column::UserId.is_not_null())
+            .group_by(billing_item::Column::UserId)
+            .into_model::<UserAmount>()
+            .all(db)
+            .await?;
+
+        let mut amounts: BTreeMap<String, (Decimal, Decimal)> = BTreeMap::new();
+        for row in advances {
+            if let Some(user_id) = row.user_id {
+                amounts.entry(user_id).or_default().0 += row.amount.unwrap_or_default();
+            }
+        }
+        for row in driver_paid {
+            if let Some(user_id) = row.user_id {
+                amounts.entry(user_id).or_default().1 += row.amount.unwrap_or_default();
+            }
+        }
+        Ok(amounts)
+    }
+
+    /// Records the settlements of a billing being ended, inside the transaction that ends it.
+    #[instrument(skip(txn))]
+    pub(crate) async fn settle_billing<C: ConnectionTrait>(
+        txn: &C,
+        billing_id: Uuid,
+    ) -> Result<(), AppError> {
+        let now = Local::now().naive_local();
+        for (user_id, (advance_amount, driver_paid_amount)) in
+            Self::driver_amounts(txn, billing_id).await?
+        {
+            billing_settlement::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                billing_id: Set(billing_id),
+                user_id: Set(user_id),
+                advance_amount: Set(advance_amount),
+                driver_paid_amount: Set(driver_paid_amount),
+                balance: Set(driver_paid_amount - advance_amount),
+                owner_confirmed_by: Set(None),
+                owner_confirm_time: Set(None),
+                driver_confirm_time: Set(None),
+                create_time: Set(now),
+            }
+            .insert(txn)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Settlements of a billing, a live preview while it is still open. Only those of
+    /// `user_id` when given.
+    #[instrument(skip(db, billing))]
+    pub async fn of_billing<C: ConnectionTrait>(
+        db: &C,
+        billing: &Billing,
+        user_id: Option<String>,
+    ) -> Result<Vec<Settlement>, AppError> {
+        if !billing.is_ended() {
+            return Ok(Self::driver_amounts(db, billing.id)
+                .await?
+                .into_iter()
+                .filter(|(driver_id, _)| {
+                    user_id.as_ref().is_none_or(|user_id| user_id == driver_id)
+                })
+                .map(
+                    |(user_id, (advance_amount, driver_paid_amount))| Settlement {
+                        id: None,
+                        billing_id: billing.id,
+                        billing_name: billing.name.clone(),
+                        user_id,
+                        advance_amount,
+                        driver_paid_amount,
+                        owner_confirmed_by: None,
+                        owner_confirm_time: None,
+                        driver_confirm_time: None,
+                        create_time: None,
+                    },
+                )
+                .collect());
+        }
+        let mut select = billing_settlement::Entity::find()
+            .filter(billing_settlement::Column::BillingId.eq(billing.id));
+        if let Some(user_id) = user_id {
+            select = select.filter(billing_settlement::Column::UserId.eq(user_id));
+        }
+        Ok(select
+            .order_by_asc(billing_settlement::Column::UserId)
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|model| (model, billing.name.clone()).into())
+            .collect())
+    }
+
+    /// Settlements of ended billings of the team, newest first. Only those of `user_id` when
+    /// given, only those still waiting for a confirmation when `unsettled`.
+    #[instrument(skip(db))]
+    pub async fn query<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        user_id: Option<String>,
+        unsettled: bool,
+    ) -> Result<Vec<Settlement>, AppError> {
+        let mut select = billing_settlement::Entity::find()
+            .find_also_related(billing::Entity)
+            .filter(billing::Column::TeamId.eq(team_id));
+        if let Some(user_id) = user_id {
+            select = select.filter(billing_settlement::Column::UserId.eq(user_id));
+        }
+        if unsettled {
+            select = select.filter(
+                billing_settlement::Column::OwnerConfirmTime
+                    .is_null()
+                    .or(billing_settlement::Column::DriverConfirmTime.is_null()),
+            );
+        }
+        Ok(select
+            .order_by_desc(billing_settlement::Column::CreateTime)
+            .order_by_asc(billing_settlement::Column::UserId)
+            .all(db)
+            .await?
+            .into_iter()
+            .filter_map(|(model, billing_model)| {
+                billing_model.map(|billing_model| (model, billing_model.name).into())
+            })
+            .collect())
+    }
+
+    /// Confirms the settlement of `user_id` on an ended billing. Managers confirm for the
+    /// company, the driver for themselves; confirming twice keeps the first confirmation.
+    #[instrument(skip(db, billing))]
+    pub async fn confirm<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        billing: &Billing,
+        user_id: &str,
+        confirmed_by: &str,
+        as_owner: bool,
+    ) -> Result<Settlement, AppError> {
+        let as_driver = confirmed_by == user_id;
+        if !as_owner && !as_driver {
+            return Err(AppError::forbidden(
+                "only team managers and the driver can confirm a settlement",
+            ));
+        }
+        let txn = db.begin().await?;
+        let model = billing_settlement::Entity::find()
+            .filter(billing_settlement::Column::BillingId.eq(billing.id))
+            .filter(billing_settlement::Column::UserId.eq(user_id))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or_else(|| settlement_not_found(billing.id, user_id))?;
+        let confirm_owner = as_owner && model.owner_confirm_time.is_none();
+        let confirm_driver = as_driver && model.driver_confirm_time.is_none();
+        if !confirm_owner && !confirm_driver {
+            return Ok((model, billing.name.clone()).into());
+        }
+        let now = Local::now().naive_local();
+        let mut active_model: billing_settlement::ActiveModel = model.into();
+        if confirm_owner {
+            active_model.owner_confirmed_by = Set(Some(confirmed_by.to_owned()));
+            active_model.owner_confirm_time = Set(Some(now));
+        }
+        if confirm_driver {
+            active_model.driver_confirm_time = Set(Some(now));
+        }
+        let model = active_model.update(&txn).await?;
+        txn.commit().await?;
+        Ok((model, billing.name.clone()).into())
+    }
+}
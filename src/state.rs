@@ -0,0 +1,32 @@
+use std::sync::Arc;
+
+use sea_orm::DatabaseConnection;
+
+use crate::config::AppConfig;
+use crate::reminder_service::notifier::{Notifier, WxSubscribeNotifier};
+use crate::user_service::wx_client::{HttpWxAuthClient, WxAuthClient};
+
+/// Shared application state, handed to endpoints through poem's `Data` extractor.
+///
+/// Services never reach for a global connection, they take the `db` they are given so the
+/// same code runs on the pool, inside a transaction or against a test database.
+#[derive(Clone)]
+pub struct AppState {
+    pub db: DatabaseConnection,
+    pub config: Arc<AppConfig>,
+    /// Exchanges login codes with WeChat, tests put `FakeWxAuthClient` here.
+    pub wx_auth: Arc<dyn WxAuthClient>,
+    /// Delivers expiry reminders, tests put `MemoryNotifier` here.
+    pub notifier: Arc<dyn Notifier>,
+}
+
+impl AppState {
+    pub fn new(db: DatabaseConnection, config: AppConfig) -> Self {
+        AppState {
+            db,
+            wx_auth: Arc::new(HttpWxAuthClient::new(config.wechat.clone())),
+            notifier: Arc::new(WxSubscribeNotifier::new(config.wechat.clone())),
+            config: Arc::new(config),
+        }
+    }
+}
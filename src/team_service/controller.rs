@@ -1,10 +1,24 @@
-use poem_openapi::{param::Path, payload::Json, ApiResponse, Object, OpenApi, Tags};
-use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set, IntoActiveModel};
-use tracing::error;
+use chrono::{DateTime, Local, NaiveDate};
+use poem::web::Data;
+use poem_openapi::{
+    param::{Path, Query},
+    payload::Json,
+    ApiResponse, Enum, Object, OpenApi, Tags,
+};
+use rust_decimal::Decimal;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter,
+    Set,
+};
 use uuid::Uuid;
 
-use crate::team_service::service::{TeamCar, TeamUser};
-use crate::{entities::team, DATABASE};
+use crate::auth_service::service::{TeamGuard, TeamPermission, TeamRole};
+use crate::billing_service::controller::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::entities::{sea_orm_active_enums::FuelType, team};
+use crate::error::{parse_uuid, ApiResult, AppError, ErrorCode};
+use crate::session_service::service::SessionUser;
+use crate::state::AppState;
+use crate::team_service::service::{CarProfile, TeamCar, TeamCarPage, TeamUser, TeamUserPage};
 
 use super::service::Team;
 
@@ -29,9 +43,6 @@ pub struct TeamDeleteDTO {
 enum CreateTeamResponse {
     #[oai(status = 200)]
     Ok,
-
-    #[oai(status = 500)]
-    Error,
 }
 
 #[derive(Debug, Object, Clone, Eq, PartialEq)]
@@ -55,27 +66,18 @@ impl From<team::Model> for TeamEntityDTO {
 enum QueryTeamResponse {
     #[oai(status = 200)]
     Ok(Json<Vec<TeamEntityDTO>>),
-
-    #[oai(status = 500)]
-    Error,
 }
 
 #[derive(ApiResponse)]
 enum UpdateTeamResponse {
     #[oai(status = 200)]
     Ok,
-
-    #[oai(status = 500)]
-    Error,
 }
 
 #[derive(ApiResponse)]
 enum DeleteTeamResponse {
     #[oai(status = 204)]
     Ok,
-
-    #[oai(status = 500)]
-    Error,
 }
 
 #[derive(Debug, Object, Clone, Eq, PartialEq)]
@@ -88,46 +90,160 @@ pub struct TeamUserDTO {
 enum TeamAddUserResponse {
     #[oai(status = 201)]
     Ok,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct TeamDriverUpdateDTO {
+    /// Expiry of the driver licence, watched by the expiry reminders.
+    pub licence_expire_date: Option<NaiveDate>,
+}
 
-    #[oai(status = 500)]
-    Error,
+#[derive(ApiResponse)]
+enum TeamUpdateUserResponse {
+    #[oai(status = 200)]
+    Ok,
 }
 
 #[derive(ApiResponse)]
 enum TeamDeleteUserResponse {
     #[oai(status = 204)]
     Ok,
-
-    #[oai(status = 500)]
-    Error,
 }
 
 #[derive(ApiResponse)]
 enum TeamGetUserResponse {
     #[oai(status = 200)]
-    Ok(Json<Vec<TeamUserResponseEntity>>),
-
-    #[oai(status = 500)]
-    Error,
+    Ok(Json<TeamUserListDTO>),
 }
 
 #[derive(Debug, Object, Clone, Eq, PartialEq)]
 struct TeamUserResponseEntity {
     user_id: String,
+    user_name: Option<String>,
+    avatar_url: Option<String>,
+    licence_expire_date: Option<NaiveDate>,
+    /// Set while the driver is out on a dispatch.
+    dispatch_id: Option<String>,
+    car_id: Option<String>,
+    car_plate_number: Option<String>,
 }
 
 impl From<TeamUser> for TeamUserResponseEntity {
     fn from(team_user: TeamUser) -> Self {
+        let dispatch = team_user.dispatch;
         TeamUserResponseEntity {
             user_id: team_user.user_id,
+            user_name: team_user.user_name,
+            avatar_url: team_user.avatar_url,
+            licence_expire_date: team_user.licence_expire_date,
+            dispatch_id: dispatch
+                .as_ref()
+                .map(|dispatch| dispatch.dispatch_id.to_string()),
+            car_id: dispatch
+                .as_ref()
+                .map(|dispatch| dispatch.car_id.to_string()),
+            car_plate_number: dispatch.and_then(|dispatch| dispatch.car_plate_number),
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct TeamUserListDTO {
+    users: Vec<TeamUserResponseEntity>,
+    page: usize,
+    page_size: usize,
+    total_count: usize,
+}
+
+impl From<TeamUserPage> for TeamUserListDTO {
+    fn from(user_page: TeamUserPage) -> Self {
+        TeamUserListDTO {
+            users: user_page
+                .users
+                .into_iter()
+                .map(|user| user.into())
+                .collect(),
+            page: user_page.page,
+            page_size: user_page.page_size,
+            total_count: user_page.total_count,
+        }
+    }
+}
+
+#[derive(Debug, Enum, Clone, Copy, Eq, PartialEq)]
+#[oai(rename_all = "SCREAMING_SNAKE_CASE")]
+enum FuelTypeDTO {
+    Diesel,
+    Gasoline,
+    Lng,
+    Cng,
+    Electric,
+    Hybrid,
+}
+
+impl From<FuelTypeDTO> for FuelType {
+    fn from(fuel_type: FuelTypeDTO) -> Self {
+        match fuel_type {
+            FuelTypeDTO::Diesel => FuelType::Diesel,
+            FuelTypeDTO::Gasoline => FuelType::Gasoline,
+            FuelTypeDTO::Lng => FuelType::Lng,
+            FuelTypeDTO::Cng => FuelType::Cng,
+            FuelTypeDTO::Electric => FuelType::Electric,
+            FuelTypeDTO::Hybrid => FuelType::Hybrid,
+        }
+    }
+}
+
+impl From<FuelType> for FuelTypeDTO {
+    fn from(fuel_type: FuelType) -> Self {
+        match fuel_type {
+            FuelType::Diesel => FuelTypeDTO::Diesel,
+            FuelType::Gasoline => FuelTypeDTO::Gasoline,
+            FuelType::Lng => FuelTypeDTO::Lng,
+            FuelType::Cng => FuelTypeDTO::Cng,
+            FuelType::Electric => FuelTypeDTO::Electric,
+            FuelType::Hybrid => FuelTypeDTO::Hybrid,
         }
     }
 }
 
+/// Body of creating a truck and of replacing its profile.
 #[derive(Debug, Object, Clone, Eq, PartialEq)]
-pub struct TeamCarCreateDTO {
+pub struct TeamCarProfileDTO {
+    /// Mainland plate such as `京A12345`, spaces and `·` are dropped and letters upper cased.
+    #[oai(validator(max_length = 32))]
+    car_plate_number: String,
+    #[oai(validator(max_length = 32))]
+    vin: Option<String>,
     #[oai(validator(max_length = 128))]
-    pub car_plate_number: String,
+    vehicle_model: Option<String>,
+    #[oai(validator(minimum(value = "2"), maximum(value = "12")))]
+    axle_count: Option<i16>,
+    /// Rated load in tonnes.
+    load_capacity: Option<Decimal>,
+    fuel_type: Option<FuelTypeDTO>,
+    purchase_date: Option<NaiveDate>,
+    insurance_expire_date: Option<NaiveDate>,
+    inspection_expire_date: Option<NaiveDate>,
+    #[oai(validator(minimum(value = "0")))]
+    odometer_km: Option<i32>,
+}
+
+impl From<TeamCarProfileDTO> for CarProfile {
+    fn from(dto: TeamCarProfileDTO) -> Self {
+        CarProfile {
+            car_plate_number: dto.car_plate_number,
+            vin: dto.vin,
+            vehicle_model: dto.vehicle_model,
+            axle_count: dto.axle_count,
+            load_capacity: dto.load_capacity,
+            fuel_type: dto.fuel_type.map(|fuel_type| fuel_type.into()),
+            purchase_date: dto.purchase_date,
+            insurance_expire_date: dto.insurance_expire_date,
+            inspection_expire_date: dto.inspection_expire_date,
+            odometer_km: dto.odometer_km,
+        }
+    }
 }
 
 #[derive(Debug, Object, Clone, Eq, PartialEq)]
@@ -139,260 +255,382 @@ pub struct TeamCarDeleteDTO {
 #[derive(ApiResponse)]
 enum TeamAddCarResponse {
     #[oai(status = 201)]
-    Ok,
+    Ok(Json<TeamCarResponseEntity>),
+}
 
-    #[oai(status = 500)]
-    Error,
+#[derive(ApiResponse)]
+enum TeamCarResponse {
+    #[oai(status = 200)]
+    Ok(Json<TeamCarResponseEntity>),
 }
 
 #[derive(ApiResponse)]
 enum TeamDeleteCarResponse {
     #[oai(status = 204)]
     Ok,
-
-    #[oai(status = 500)]
-    Error,
 }
 
 #[derive(ApiResponse)]
 enum TeamGetCarResponse {
     #[oai(status = 200)]
-    Ok(Json<Vec<TeamCarResponseEntity>>),
-
-    #[oai(status = 500)]
-    Error,
+    Ok(Json<TeamCarListDTO>),
 }
 
 #[derive(Debug, Object, Clone, Eq, PartialEq)]
 struct TeamCarResponseEntity {
     car_id: String,
     car_plate_number: String,
+    vin: Option<String>,
+    vehicle_model: Option<String>,
+    axle_count: Option<i16>,
+    load_capacity: Option<Decimal>,
+    fuel_type: Option<FuelTypeDTO>,
+    purchase_date: Option<NaiveDate>,
+    insurance_expire_date: Option<NaiveDate>,
+    inspection_expire_date: Option<NaiveDate>,
+    odometer_km: Option<i32>,
+    /// Set while a billing is open on the car.
+    billing_id: Option<String>,
+    billing_name: Option<String>,
+    billing_start_time: Option<DateTime<Local>>,
 }
 
 impl From<TeamCar> for TeamCarResponseEntity {
     fn from(team_car: TeamCar) -> Self {
+        let open_billing = team_car.open_billing;
+        let profile = team_car.profile;
         TeamCarResponseEntity {
             car_id: team_car.car_id.to_string(),
-            car_plate_number: team_car.car_plate_number,
+            car_plate_number: profile.car_plate_number,
+            vin: profile.vin,
+            vehicle_model: profile.vehicle_model,
+            axle_count: profile.axle_count,
+            load_capacity: profile.load_capacity,
+            fuel_type: profile.fuel_type.map(|fuel_type| fuel_type.into()),
+            purchase_date: profile.purchase_date,
+            insurance_expire_date: profile.insurance_expire_date,
+            inspection_expire_date: profile.inspection_expire_date,
+            odometer_km: profile.odometer_km,
+            billing_id: open_billing
+                .as_ref()
+                .map(|billing| billing.billing_id.to_string()),
+            billing_start_time: open_billing.as_ref().and_then(|billing| billing.start_time),
+            billing_name: open_billing.map(|billing| billing.name),
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct TeamCarListDTO {
+    cars: Vec<TeamCarResponseEntity>,
+    page: usize,
+    page_size: usize,
+    total_count: usize,
+}
+
+impl From<TeamCarPage> for TeamCarListDTO {
+    fn from(car_page: TeamCarPage) -> Self {
+        TeamCarListDTO {
+            cars: car_page.cars.into_iter().map(|car| car.into()).collect(),
+            page: car_page.page,
+            page_size: car_page.page_size,
+            total_count: car_page.total_count,
         }
     }
 }
 
+/// Authorizes the caller against a team addressed by its id string.
+async fn guard_team(
+    db: &DatabaseConnection,
+    session_user: &SessionUser,
+    team_id: &str,
+    permission: TeamPermission,
+) -> Result<TeamRole, AppError> {
+    let team_uuid = parse_uuid("team_id", team_id)?;
+    TeamGuard::check(db, session_user, team_uuid, permission).await
+}
+
+/// Page numbers start at 1, the page size is kept within the limits of the billing list.
+fn page_params(page: Option<usize>, page_size: Option<usize>) -> (usize, usize) {
+    (
+        page.unwrap_or(1).max(1),
+        page_size
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE),
+    )
+}
+
 pub struct TeamRouter;
 
 #[OpenApi]
 impl TeamRouter {
-    #[oai(path = "/user/:user_id/team", method = "post", tag = "ApiTags::Team")]
+    #[oai(path = "/user/team", method = "post", tag = "ApiTags::Team")]
     async fn create_team(
         &self,
-        user_id: Path<String>,
+        state: Data<&AppState>,
+        session_user: SessionUser,
         team: Json<TeamCreateDTO>,
-    ) -> CreateTeamResponse {
-        let db = DATABASE.get().unwrap();
-        let user_id = user_id.0;
+    ) -> ApiResult<CreateTeamResponse> {
+        let db = &state.db;
+        let user_id = session_user.user_id;
         let team_name = team.0.name;
-        let result = team::ActiveModel {
+        team::ActiveModel {
             id: Set(Uuid::new_v4()),
             team_name: Set(team_name),
             user_id: Set(user_id),
         }
         .insert(db)
-        .await;
-        if let Err(_err) = result {
-            return CreateTeamResponse::Error;
-        }
-        CreateTeamResponse::Ok
+        .await?;
+        Ok(CreateTeamResponse::Ok)
     }
 
-    #[oai(path = "/user/:user_id/team", method = "put", tag = "ApiTags::Team")]
+    #[oai(path = "/user/team", method = "put", tag = "ApiTags::Team")]
     async fn update_team(
         &self,
-        user_id: Path<String>,
+        state: Data<&AppState>,
+        session_user: SessionUser,
         team: Json<TeamEntityDTO>,
-    ) -> UpdateTeamResponse {
-        let db = DATABASE.get().unwrap();
-        let team_id = team.team_id.clone();
-        let team_id_uuid = Uuid::parse_str(&team_id);
-        if let Err(err) = team_id_uuid {
-            error!("parse id to uuid error, err is {}", err);
-            return UpdateTeamResponse::Error;
-        }
-        let team_id_uuid = team_id_uuid.unwrap();
-        let model_result = team::Entity::find_by_id(team_id_uuid).one(db).await;
-        if let Err(err) = model_result {
-            error!("Query Team error, error is {}, team id is {}", err, team_id.clone());
-            return UpdateTeamResponse::Error;
-        }
-        let model = model_result.unwrap();
-        if let Some(team_model) = model {
-            let mut team_active_model = team_model.into_active_model();
-            team_active_model.team_name = Set(team.team_name.clone());
-            let update_result = team_active_model.update(db).await;
-            if update_result.is_err() {
-                error!("update team name error");
-                return UpdateTeamResponse::Error;
-            }
-            return UpdateTeamResponse::Ok;
-        }
-        UpdateTeamResponse::Error
+    ) -> ApiResult<UpdateTeamResponse> {
+        let db = &state.db;
+        let team_id_uuid = parse_uuid("team_id", &team.team_id)?;
+        TeamGuard::check(
+            &state.db,
+            &session_user,
+            team_id_uuid,
+            TeamPermission::Manage,
+        )
+        .await?;
+        let team_model = team::Entity::find_by_id(team_id_uuid)
+            .one(db)
+            .await?
+            .ok_or_else(|| {
+                AppError::new(
+                    ErrorCode::TeamNotFound,
+                    format!("can not find team {}", team_id_uuid),
+                )
+            })?;
+        let mut team_active_model = team_model.into_active_model();
+        team_active_model.team_name = Set(team.team_name.clone());
+        team_active_model.update(db).await?;
+        Ok(UpdateTeamResponse::Ok)
     }
 
-    #[oai(path = "/user/:user_id/team", method = "delete", tag = "ApiTags::Team")]
+    #[oai(path = "/user/team", method = "delete", tag = "ApiTags::Team")]
     async fn delete_team(
         &self,
-        user_id: Path<String>,
+        state: Data<&AppState>,
+        session_user: SessionUser,
         team: Json<TeamDeleteDTO>,
-    ) -> DeleteTeamResponse {
-        let db = DATABASE.get().unwrap();
+    ) -> ApiResult<DeleteTeamResponse> {
         let team_id = team.0.team_id;
-        let team_aggreagte = Team::from_id(team_id).await;
-        if let Err(err) = team_aggreagte {
-            error!("Get team form db error, err is {}", err);
-            return DeleteTeamResponse::Error;
-        }
-        let team_aggreagte = team_aggreagte.unwrap();
-        let team_delte_result = team_aggreagte.delete().await;
-        if let Err(err) = team_delte_result {
-            error!("Team delete error. Error is {}", err);
-            return  DeleteTeamResponse::Error;
-        }
-        DeleteTeamResponse::Ok
+        guard_team(&state.db, &session_user, &team_id, TeamPermission::Manage).await?;
+        let team_aggreagte = Team::from_id(&state.db, team_id).await?;
+        team_aggreagte.delete(&state.db).await?;
+        Ok(DeleteTeamResponse::Ok)
     }
 
-
-    #[oai(path = "/user/:user_id/team", method = "get", tag = "ApiTags::Team")]
-    async fn query_team(&self, user_id: Path<String>) -> QueryTeamResponse {
-        let db = DATABASE.get().unwrap();
-        let user_id = user_id.0;
-        let query_result = team::Entity::find()
-            .filter(team::Column::UserId.eq(user_id.clone()))
+    #[oai(path = "/user/team", method = "get", tag = "ApiTags::Team")]
+    async fn query_team(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+    ) -> ApiResult<QueryTeamResponse> {
+        let db = &state.db;
+        let user_id = session_user.user_id;
+        let query_models = team::Entity::find()
+            .filter(team::Column::UserId.eq(user_id))
             .all(db)
-            .await;
-        if let Err(err) = query_result {
-            error!(
-                "query team db error, err is {}, user id is {}",
-                err,
-                user_id.clone()
-            );
-            return QueryTeamResponse::Error;
-        }
-        let query_models = query_result.unwrap();
+            .await?;
         let mut response = vec![];
         for model in query_models {
             response.push(model.into());
         }
-        return QueryTeamResponse::Ok(Json(response));
+        Ok(QueryTeamResponse::Ok(Json(response)))
     }
 
     #[oai(path = "/team/:team_id/user", method = "delete", tag = "ApiTags::Team")]
     async fn team_delete_user(
         &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
         team_id: Path<String>,
         team_dto: Json<TeamUserDTO>,
-    ) -> TeamDeleteUserResponse {
+    ) -> ApiResult<TeamDeleteUserResponse> {
         let team_id = team_id.0;
-        if let Ok(team) = Team::from_id(team_id).await {
-            if let Ok(_res) = team.delete_driver(team_dto.user_id.clone()).await {
-                TeamDeleteUserResponse::Ok
-            } else {
-                TeamDeleteUserResponse::Error
-            }
-        } else {
-            TeamDeleteUserResponse::Error
-        }
+        guard_team(&state.db, &session_user, &team_id, TeamPermission::Manage).await?;
+        let team = Team::from_id(&state.db, team_id).await?;
+        team.delete_driver(&state.db, team_dto.user_id.clone())
+            .await?;
+        Ok(TeamDeleteUserResponse::Ok)
+    }
+
+    #[oai(
+        path = "/team/:team_id/user/:user_id",
+        method = "put",
+        tag = "ApiTags::Team"
+    )]
+    async fn team_update_user(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        user_id: Path<String>,
+        team_dto: Json<TeamDriverUpdateDTO>,
+    ) -> ApiResult<TeamUpdateUserResponse> {
+        let team_id = team_id.0;
+        guard_team(&state.db, &session_user, &team_id, TeamPermission::Manage).await?;
+        let team = Team::from_id(&state.db, team_id).await?;
+        team.update_driver(&state.db, user_id.0, team_dto.licence_expire_date)
+            .await?;
+        Ok(TeamUpdateUserResponse::Ok)
     }
 
     #[oai(path = "/team/:team_id/user", method = "post", tag = "ApiTags::Team")]
     async fn team_add_user(
         &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
         team_id: Path<String>,
         team_dto: Json<TeamUserDTO>,
-    ) -> TeamAddUserResponse {
+    ) -> ApiResult<TeamAddUserResponse> {
         let team_id = team_id.0;
-        if let Ok(team) = Team::from_id(team_id).await {
-            if let Ok(_res) = team.add_driver(team_dto.user_id.clone()).await {
-                TeamAddUserResponse::Ok
-            } else {
-                TeamAddUserResponse::Error
-            }
-        } else {
-            TeamAddUserResponse::Error
-        }
+        guard_team(&state.db, &session_user, &team_id, TeamPermission::Manage).await?;
+        let team = Team::from_id(&state.db, team_id).await?;
+        team.add_driver(&state.db, team_dto.user_id.clone()).await?;
+        Ok(TeamAddUserResponse::Ok)
     }
 
     #[oai(path = "/team/:team_id/user", method = "get", tag = "ApiTags::Team")]
-    async fn team_get_user(&self, team_id: Path<String>) -> TeamGetUserResponse {
+    async fn team_get_user(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        page: Query<Option<usize>>,
+        page_size: Query<Option<usize>>,
+    ) -> ApiResult<TeamGetUserResponse> {
         let team_id = team_id.0;
-        if let Ok(team) = Team::from_id(team_id).await {
-            if let Ok(res) = team.get_drivers().await {
-                let mut response: Vec<TeamUserResponseEntity> = vec![];
-                for team_user in res {
-                    response.push(team_user.into());
-                }
-                TeamGetUserResponse::Ok(Json(response))
-            } else {
-                TeamGetUserResponse::Error
-            }
-        } else {
-            TeamGetUserResponse::Error
-        }
+        guard_team(&state.db, &session_user, &team_id, TeamPermission::Member).await?;
+        let team = Team::from_id(&state.db, team_id).await?;
+        let (page, page_size) = page_params(page.0, page_size.0);
+        let user_page = team.get_drivers(&state.db, page, page_size).await?;
+        Ok(TeamGetUserResponse::Ok(Json(user_page.into())))
     }
 
     #[oai(path = "/team/:team_id/car", method = "post", tag = "ApiTags::Team")]
     async fn team_add_car(
         &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
         team_id: Path<String>,
-        team_dto: Json<TeamCarCreateDTO>,
-    ) -> TeamAddCarResponse {
+        team_dto: Json<TeamCarProfileDTO>,
+    ) -> ApiResult<TeamAddCarResponse> {
         let team_id = team_id.0;
-        let team_result = Team::from_id(team_id).await;
-        if let Ok(team) = team_result {
-            let add_car_result = team.add_car(team_dto.car_plate_number.clone()).await;
-            if let Ok(_res) = add_car_result {
-                TeamAddCarResponse::Ok
-            } else {
-                error!("add car error");
-                TeamAddCarResponse::Error
-            }
-        } else {
-            error!("get team error, error is {}", team_result.unwrap_err());
-            TeamAddCarResponse::Error
-        }
+        guard_team(&state.db, &session_user, &team_id, TeamPermission::Manage).await?;
+        let team = Team::from_id(&state.db, team_id).await?;
+        let team_car = team.add_car(&state.db, team_dto.0.into()).await?;
+        Ok(TeamAddCarResponse::Ok(Json(team_car.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/car/:car_id",
+        method = "get",
+        tag = "ApiTags::Team"
+    )]
+    async fn team_get_car_profile(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        car_id: Path<String>,
+    ) -> ApiResult<TeamCarResponse> {
+        let team_id = team_id.0;
+        let car_uuid = parse_uuid("car_id", &car_id.0)?;
+        guard_team(&state.db, &session_user, &team_id, TeamPermission::Member).await?;
+        let team = Team::from_id(&state.db, team_id).await?;
+        let team_car = team.get_car(&state.db, car_uuid).await?;
+        Ok(TeamCarResponse::Ok(Json(team_car.into())))
     }
 
-    #[oai(path = "/team/:team_id/car", method = "delete", tag = "ApiTags::Team")]
+    #[oai(
+        path = "/team/:team_id/car/:car_id",
+        method = "put",
+        tag = "ApiTags::Team"
+    )]
+    async fn team_update_car(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        car_id: Path<String>,
+        team_dto: Json<TeamCarProfileDTO>,
+    ) -> ApiResult<TeamCarResponse> {
+        let team_id = team_id.0;
+        let car_uuid = parse_uuid("car_id", &car_id.0)?;
+        guard_team(&state.db, &session_user, &team_id, TeamPermission::Manage).await?;
+        let team = Team::from_id(&state.db, team_id).await?;
+        let team_car = team
+            .update_car(&state.db, car_uuid, team_dto.0.into())
+            .await?;
+        Ok(TeamCarResponse::Ok(Json(team_car.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/car/:car_id",
+        method = "delete",
+        tag = "ApiTags::Team"
+    )]
+    async fn team_delete_car_by_id(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        car_id: Path<String>,
+    ) -> ApiResult<TeamDeleteCarResponse> {
+        let team_id = team_id.0;
+        let car_uuid = parse_uuid("car_id", &car_id.0)?;
+        guard_team(&state.db, &session_user, &team_id, TeamPermission::Manage).await?;
+        let team = Team::from_id(&state.db, team_id).await?;
+        team.delete_car(&state.db, car_uuid).await?;
+        Ok(TeamDeleteCarResponse::Ok)
+    }
+
+    /// Use `DELETE /team/:team_id/car/:car_id` instead.
+    #[oai(
+        path = "/team/:team_id/car",
+        method = "delete",
+        tag = "ApiTags::Team",
+        deprecated
+    )]
     async fn team_delete_car(
         &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
         team_id: Path<String>,
         team_car_dto: Json<TeamCarDeleteDTO>,
-    ) -> TeamDeleteCarResponse {
+    ) -> ApiResult<TeamDeleteCarResponse> {
         let team_id = team_id.0;
-        if let Ok(team) = Team::from_id(team_id).await {
-            if let Ok(_res) = team.delete_car(team_car_dto.car_id.clone()).await {
-                TeamDeleteCarResponse::Ok
-            } else {
-                TeamDeleteCarResponse::Error
-            }
-        } else {
-            TeamDeleteCarResponse::Error
-        }
+        guard_team(&state.db, &session_user, &team_id, TeamPermission::Manage).await?;
+        let team = Team::from_id(&state.db, team_id).await?;
+        let car_uuid = parse_uuid("car_id", &team_car_dto.car_id)?;
+        team.delete_car(&state.db, car_uuid).await?;
+        Ok(TeamDeleteCarResponse::Ok)
     }
 
     #[oai(path = "/team/:team_id/car", method = "get", tag = "ApiTags::Team")]
-    async fn team_get_car(&self, team_id: Path<String>) -> TeamGetCarResponse {
+    async fn team_get_car(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        page: Query<Option<usize>>,
+        page_size: Query<Option<usize>>,
+    ) -> ApiResult<TeamGetCarResponse> {
         let team_id = team_id.0;
-        if let Ok(team) = Team::from_id(team_id).await {
-            if let Ok(res) = team.get_cars().await {
-                let mut response: Vec<TeamCarResponseEntity> = vec![];
-                for team_car in res {
-                    response.push(team_car.into());
-                }
-                TeamGetCarResponse::Ok(Json(response))
-            } else {
-                TeamGetCarResponse::Error
-            }
-        } else {
-            TeamGetCarResponse::Error
-        }
+        guard_team(&state.db, &session_user, &team_id, TeamPermission::Member).await?;
+        let team = Team::from_id(&state.db, team_id).await?;
+        let (page, page_size) = page_params(page.0, page_size.0);
+        let car_page = team.get_cars(&state.db, page, page_size).await?;
+        Ok(TeamGetCarResponse::Ok(Json(car_page.into())))
     }
 }
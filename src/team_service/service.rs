@@ -1,56 +1,49 @@
-use std::error::Error;
+use std::collections::HashMap;
 
+use chrono::{DateTime, Local, NaiveDate};
+use rust_decimal::Decimal;
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, ModelTrait, QueryFilter, Set,
+    sea_query::Query, ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait,
+    IntoActiveModel, ModelTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Set,
     TransactionTrait,
 };
-use tracing::{info, instrument, warn};
+use tracing::{info, instrument};
 use uuid::Uuid;
 
-use crate::entities::{billing, team_car};
+use crate::billing_service::service::parse_navie_time_to_data_time;
+use crate::entities::{
+    billing, billing_expected_item, billing_income, billing_item, billing_settlement,
+    billing_template, billing_template_item, customer, customer_payment, dispatch, dispatch_driver,
+    driver_advance, fuel_entry, item, payment_allocation, payroll_run, payslip_line, reminder,
+    sea_orm_active_enums::FuelType, team_car, team_invite, user, wage_rule, wage_rule_deduction,
+};
 use crate::{
     entities::{team, team_driver},
-    DATABASE,
+    error::{parse_uuid, AppError, ErrorCode},
 };
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub enum TeamError {
-    QueryTeamError(String),
-    DbError(DbErr),
-    UuidError(uuid::Error),
-}
+use super::vehicle::{normalize_plate, normalize_vin};
 
-impl From<DbErr> for TeamError {
-    fn from(db_err: DbErr) -> Self {
-        TeamError::DbError(db_err)
-    }
+fn car_not_found(car_id: Uuid) -> AppError {
+    AppError::new(
+        ErrorCode::CarNotFound,
+        format!("can not find car {} in team", car_id),
+    )
 }
 
-impl From<uuid::Error> for TeamError {
-    fn from(err: uuid::Error) -> Self {
-        TeamError::UuidError(err)
-    }
+fn duplicate_plate(car_plate_number: &str) -> AppError {
+    AppError::new(
+        ErrorCode::DuplicatePlate,
+        format!("car {} is already in team", car_plate_number),
+    )
 }
 
-impl Error for TeamError {}
-
-impl std::fmt::Display for TeamError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            TeamError::QueryTeamError(team_id) => {
-                write!(
-                    f,
-                    "Database get info error, empty team info. team id is {}",
-                    team_id
-                )
-            }
-            TeamError::DbError(db_err) => {
-                write!(f, "Database query error, db error is {}", db_err)
-            }
-            TeamError::UuidError(err) => {
-                write!(f, "parse string to uuid error, uuid error is {}", err)
-            }
-        }
+/// A car written with the same plate between `ensure_plate_free` and the insert or update
+/// trips the unique index on the team's plates, it is reported like the checked case.
+fn plate_write_error(err: DbErr, car_plate_number: &str) -> AppError {
+    match AppError::from(err) {
+        err if err.code == ErrorCode::UniqueViolation => duplicate_plate(car_plate_number),
+        err => err,
     }
 }
 
@@ -64,158 +57,650 @@ pub struct Team {
 #[derive(Debug)]
 pub struct TeamUser {
     pub user_id: String,
+    pub user_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub licence_expire_date: Option<NaiveDate>,
+    /// The active dispatch the driver is out on, if any.
+    pub dispatch: Option<TeamUserDispatch>,
+}
+
+#[derive(Debug)]
+pub struct TeamUserDispatch {
+    pub dispatch_id: Uuid,
+    pub car_id: Uuid,
+    pub car_plate_number: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct TeamUserPage {
+    pub users: Vec<TeamUser>,
+    pub page: usize,
+    pub page_size: usize,
+    pub total_count: usize,
 }
 
 #[derive(Debug)]
 pub struct TeamCar {
     pub car_id: Uuid,
+    pub profile: CarProfile,
+    /// The billing still open on the car, at most one per car.
+    pub open_billing: Option<TeamCarBilling>,
+}
+
+/// What is known about a truck besides its id, only the plate is required.
+#[derive(Debug, Clone)]
+pub struct CarProfile {
     pub car_plate_number: String,
+    pub vin: Option<String>,
+    pub vehicle_model: Option<String>,
+    pub axle_count: Option<i16>,
+    /// Rated load in tonnes.
+    pub load_capacity: Option<Decimal>,
+    pub fuel_type: Option<FuelType>,
+    pub purchase_date: Option<NaiveDate>,
+    pub insurance_expire_date: Option<NaiveDate>,
+    pub inspection_expire_date: Option<NaiveDate>,
+    pub odometer_km: Option<i32>,
 }
 
-impl Team {
-    #[instrument]
-    pub async fn from_id(id: String) -> Result<Self, TeamError> {
-        let db = DATABASE.get().unwrap();
-        if let Ok(team_id) = Uuid::parse_str(&id) {
-            match team::Entity::find_by_id(team_id).one(db).await {
-                Ok(query_result) => {
-                    if let Some(team_model) = query_result {
-                        Ok(Team {
-                            id: team_model.id,
-                            name: team_model.team_name,
-                            user_id: team_model.user_id,
-                        })
-                    } else {
-                        Err(TeamError::QueryTeamError(id))
-                    }
-                }
-                _ => Err(TeamError::QueryTeamError(id)),
-            }
-        } else {
-            warn!("uuid parse error, id is {}", id);
-            Err(TeamError::QueryTeamError(id))
+impl From<team_car::Model> for CarProfile {
+    fn from(model: team_car::Model) -> Self {
+        CarProfile {
+            car_plate_number: model.car_plate_number,
+            vin: model.vin,
+            vehicle_model: model.vehicle_model,
+            axle_count: model.axle_count,
+            load_capacity: model.load_capacity,
+            fuel_type: model.fuel_type,
+            purchase_date: model.purchase_date,
+            insurance_expire_date: model.insurance_expire_date,
+            inspection_expire_date: model.inspection_expire_date,
+            odometer_km: model.odometer_km,
+        }
+    }
+}
+
+impl CarProfile {
+    /// Brings plate and vin to their stored form and rejects values no truck can have.
+    fn normalize(self) -> Result<CarProfile, AppError> {
+        if self.load_capacity.is_some_and(|load| load <= Decimal::ZERO) {
+            return Err(AppError::new(
+                ErrorCode::InvalidArgument,
+                "load_capacity must be positive",
+            ));
+        }
+        if self
+            .purchase_date
+            .is_some_and(|date| date > Local::now().date_naive())
+        {
+            return Err(AppError::new(
+                ErrorCode::InvalidArgument,
+                "purchase_date can not be in the future",
+            ));
         }
+        Ok(CarProfile {
+            car_plate_number: normalize_plate(&self.car_plate_number)?,
+            vin: self.vin.as_deref().map(normalize_vin).transpose()?,
+            vehicle_model: self
+                .vehicle_model
+                .map(|model| model.trim().to_owned())
+                .filter(|model| !model.is_empty()),
+            ..self
+        })
     }
 
-    #[instrument]
-    pub async fn add_driver(&self, user_id: String) -> Result<(), TeamError> {
+    fn apply(self, active_model: &mut team_car::ActiveModel) {
+        active_model.car_plate_number = Set(self.car_plate_number);
+        active_model.vin = Set(self.vin);
+        active_model.vehicle_model = Set(self.vehicle_model);
+        active_model.axle_count = Set(self.axle_count);
+        active_model.load_capacity = Set(self.load_capacity);
+        active_model.fuel_type = Set(self.fuel_type);
+        active_model.purchase_date = Set(self.purchase_date);
+        active_model.insurance_expire_date = Set(self.insurance_expire_date);
+        active_model.inspection_expire_date = Set(self.inspection_expire_date);
+        active_model.odometer_km = Set(self.odometer_km);
+    }
+}
+
+#[derive(Debug)]
+pub struct TeamCarBilling {
+    pub billing_id: Uuid,
+    pub name: String,
+    pub start_time: Option<DateTime<Local>>,
+}
+
+#[derive(Debug)]
+pub struct TeamCarPage {
+    pub cars: Vec<TeamCar>,
+    pub page: usize,
+    pub page_size: usize,
+    pub total_count: usize,
+}
+
+impl Team {
+    #[instrument(skip(db))]
+    pub async fn from_id<C: ConnectionTrait>(db: &C, id: String) -> Result<Self, AppError> {
+        let team_id = parse_uuid("team_id", &id)?;
+        let team_model = team::Entity::find_by_id(team_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| {
+                AppError::new(
+                    ErrorCode::TeamNotFound,
+                    format!("can not find team {}", team_id),
+                )
+            })?;
+        Ok(Team {
+            id: team_model.id,
+            name: team_model.team_name,
+            user_id: team_model.user_id,
+        })
+    }
+
+    #[instrument(skip(db))]
+    pub async fn add_driver<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        user_id: String,
+    ) -> Result<(), AppError> {
         let team_id = self.id;
-        let db = DATABASE.get().unwrap();
         let _insert_result = team_driver::ActiveModel {
             id: Set(Uuid::new_v4()),
             user_id: Set(user_id),
             team_id: Set(team_id),
+            licence_expire_date: Set(None),
         }
         .insert(db)
         .await?;
         Ok(())
     }
 
-    #[instrument]
-    pub async fn add_car(&self, car_plate_number: String) -> Result<(), TeamError> {
-        let team_id = self.id;
-        let db = DATABASE.get().unwrap();
-        let _insert_result = team_car::ActiveModel {
+    #[instrument(skip(db))]
+    pub async fn add_car<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        profile: CarProfile,
+    ) -> Result<TeamCar, AppError> {
+        let profile = profile.normalize()?;
+        self.ensure_plate_free(db, &profile.car_plate_number, None)
+            .await?;
+        let car_plate_number = profile.car_plate_number.clone();
+        let mut active_model = team_car::ActiveModel {
             id: Set(Uuid::new_v4()),
-            car_plate_number: Set(car_plate_number),
-            team_id: Set(team_id),
+            team_id: Set(self.id),
+            ..Default::default()
+        };
+        profile.apply(&mut active_model);
+        let car_model = active_model
+            .insert(db)
+            .await
+            .map_err(|err| plate_write_error(err, &car_plate_number))?;
+        Ok(TeamCar {
+            car_id: car_model.id,
+            profile: car_model.into(),
+            open_billing: None,
+        })
+    }
+
+    #[instrument(skip(db))]
+    pub async fn get_car<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        car_id: Uuid,
+    ) -> Result<TeamCar, AppError> {
+        let car_model = team_car::Entity::find_by_id(car_id)
+            .filter(team_car::Column::TeamId.eq(self.id))
+            .one(db)
+            .await?
+            .ok_or_else(|| car_not_found(car_id))?;
+        let mut open_billings = self.open_billings(db, vec![car_id]).await?;
+        Ok(TeamCar {
+            car_id,
+            profile: car_model.into(),
+            open_billing: open_billings.remove(&car_id),
+        })
+    }
+
+    /// Replaces the whole profile of the truck.
+    #[instrument(skip(db))]
+    pub async fn update_car<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        car_id: Uuid,
+        profile: CarProfile,
+    ) -> Result<TeamCar, AppError> {
+        let profile = profile.normalize()?;
+        let car_model = team_car::Entity::find_by_id(car_id)
+            .filter(team_car::Column::TeamId.eq(self.id))
+            .one(db)
+            .await?
+            .ok_or_else(|| car_not_found(car_id))?;
+        self.ensure_plate_free(db, &profile.car_plate_number, Some(car_id))
+            .await?;
+        let car_plate_number = profile.car_plate_number.clone();
+        let mut active_model = car_model.into_active_model();
+        profile.apply(&mut active_model);
+        active_model
+            .update(db)
+            .await
+            .map_err(|err| plate_write_error(err, &car_plate_number))?;
+        self.get_car(db, car_id).await
+    }
+
+    async fn ensure_plate_free<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        car_plate_number: &str,
+        except_car_id: Option<Uuid>,
+    ) -> Result<(), AppError> {
+        let mut select = team_car::Entity::find()
+            .filter(team_car::Column::TeamId.eq(self.id))
+            .filter(team_car::Column::CarPlateNumber.eq(car_plate_number));
+        if let Some(car_id) = except_car_id {
+            select = select.filter(team_car::Column::Id.ne(car_id));
+        }
+        if select.one(db).await?.is_some() {
+            return Err(duplicate_plate(car_plate_number));
         }
-        .insert(db)
-        .await?;
         Ok(())
     }
 
-    #[instrument]
-    pub async fn delete_driver(&self, user_id: String) -> Result<(), TeamError> {
-        let _team_id = self.id;
-        let db = DATABASE.get().unwrap();
+    /// Removes the driver from the team together with its licence reminders and wage rules,
+    /// all or nothing. A driver out on a dispatch stays until the dispatch is finished.
+    #[instrument(skip(db))]
+    pub async fn delete_driver<C: ConnectionTrait + TransactionTrait>(
+        &self,
+        db: &C,
+        user_id: String,
+    ) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        // The same row lock `Dispatch::start` takes, a dispatch can not pick the driver up
+        // while it is being removed.
         let query_result = team_driver::Entity::find()
             .filter(team_driver::Column::TeamId.eq(self.id))
             .filter(team_driver::Column::UserId.eq(user_id))
-            .one(db)
+            .lock_exclusive()
+            .one(&txn)
             .await?;
         if let Some(query_model) = query_result {
-            let delete_result = query_model.delete(db).await?;
+            let dispatched = dispatch_driver::Entity::find()
+                .inner_join(dispatch::Entity)
+                .filter(dispatch::Column::TeamId.eq(self.id))
+                .filter(dispatch::Column::EndTime.is_null())
+                .filter(dispatch_driver::Column::UserId.eq(query_model.user_id.clone()))
+                .one(&txn)
+                .await?;
+            if dispatched.is_some() {
+                return Err(AppError::new(
+                    ErrorCode::DriverDispatched,
+                    format!(
+                        "driver {} is on an active dispatch, finish it first",
+                        query_model.user_id
+                    ),
+                ));
+            }
+            reminder::Entity::delete_many()
+                .filter(reminder::Column::TeamId.eq(self.id))
+                .filter(reminder::Column::UserId.eq(query_model.user_id.clone()))
+                .exec(&txn)
+                .await?;
+            let driver_rule_ids = Query::select()
+                .column(wage_rule::Column::Id)
+                .from(wage_rule::Entity)
+                .and_where(wage_rule::Column::TeamDriverId.eq(query_model.id))
+                .to_owned();
+            wage_rule_deduction::Entity::delete_many()
+                .filter(wage_rule_deduction::Column::WageRuleId.in_subquery(driver_rule_ids))
+                .exec(&txn)
+                .await?;
+            wage_rule::Entity::delete_many()
+                .filter(wage_rule::Column::TeamDriverId.eq(query_model.id))
+                .exec(&txn)
+                .await?;
+            let delete_result = query_model.delete(&txn).await?;
             let affect_row = delete_result.rows_affected;
             info!("Delete affected row is {}", affect_row);
         }
+        txn.commit().await?;
         Ok(())
     }
 
-    #[instrument]
-    pub async fn delete_car(&self, car_id: String) -> Result<(), TeamError> {
-        let _team_id = self.id;
-        let car_id = Uuid::parse_str(&car_id)?;
-        let db = DATABASE.get().unwrap();
-        let query_result = team_car::Entity::find()
+    /// Records when the driver's licence expires, `None` clears it.
+    #[instrument(skip(db))]
+    pub async fn update_driver<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        user_id: String,
+        licence_expire_date: Option<NaiveDate>,
+    ) -> Result<(), AppError> {
+        let team_driver_model = team_driver::Entity::find()
+            .filter(team_driver::Column::TeamId.eq(self.id))
+            .filter(team_driver::Column::UserId.eq(user_id.clone()))
+            .one(db)
+            .await?
+            .ok_or_else(|| {
+                AppError::new(
+                    ErrorCode::UserNotFound,
+                    format!("user {} is not a driver of team", user_id),
+                )
+            })?;
+        let mut active_model = team_driver_model.into_active_model();
+        active_model.licence_expire_date = Set(licence_expire_date);
+        active_model.update(db).await?;
+        Ok(())
+    }
+
+    /// Removes a truck that never went out, trucks with billings or dispatches stay for the
+    /// history.
+    #[instrument(skip(db))]
+    pub async fn delete_car<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        car_id: Uuid,
+    ) -> Result<(), AppError> {
+        let car_model = team_car::Entity::find_by_id(car_id)
             .filter(team_car::Column::TeamId.eq(self.id))
-            .filter(team_car::Column::Id.eq(car_id))
             .one(db)
-            .await?;
-        if let Some(query_model) = query_result {
-            let delete_result = query_model.delete(db).await?;
-            let affect_row = delete_result.rows_affected;
-            info!("Delete car affected row is {}", affect_row);
+            .await?
+            .ok_or_else(|| car_not_found(car_id))?;
+        let billing_count = car_model.find_related(billing::Entity).count(db).await?;
+        let dispatch_count = car_model.find_related(dispatch::Entity).count(db).await?;
+        if billing_count > 0 || dispatch_count > 0 {
+            return Err(AppError::new(
+                ErrorCode::CarInUse,
+                format!("car {} has billings or dispatches", car_id),
+            ));
         }
+        reminder::Entity::delete_many()
+            .filter(reminder::Column::TeamCarId.eq(car_id))
+            .exec(db)
+            .await?;
+        let delete_result = car_model.delete(db).await?;
+        info!("Delete car affected row is {}", delete_result.rows_affected);
         Ok(())
     }
 
-    #[instrument]
-    pub async fn get_drivers(&self) -> Result<Vec<TeamUser>, TeamError> {
-        let team_id = self.id;
-        let db = DATABASE.get().unwrap();
-        let query_result = team_driver::Entity::find()
-            .filter(team_driver::Column::TeamId.eq(team_id))
+    /// One page of the drivers of the team with their profile and current truck.
+    #[instrument(skip(db))]
+    pub async fn get_drivers<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        page: usize,
+        page_size: usize,
+    ) -> Result<TeamUserPage, AppError> {
+        let paginator = team_driver::Entity::find()
+            .find_also_related(user::Entity)
+            .filter(team_driver::Column::TeamId.eq(self.id))
+            .order_by_asc(user::Column::UserName)
+            .order_by_asc(team_driver::Column::UserId)
+            .paginate(db, page_size);
+        let total_count = paginator.num_items().await?;
+        let driver_models = paginator.fetch_page(page - 1).await?;
+
+        let user_ids: Vec<String> = driver_models
+            .iter()
+            .map(|(team_driver_model, _)| team_driver_model.user_id.clone())
+            .collect();
+        let dispatch_models = dispatch_driver::Entity::find()
+            .find_also_related(dispatch::Entity)
+            .filter(dispatch::Column::TeamId.eq(self.id))
+            .filter(dispatch::Column::EndTime.is_null())
+            .filter(dispatch_driver::Column::UserId.is_in(user_ids))
             .all(db)
             .await?;
+        let car_ids: Vec<Uuid> = dispatch_models
+            .iter()
+            .filter_map(|(_, dispatch_model)| dispatch_model.as_ref())
+            .map(|dispatch_model| dispatch_model.team_car_id)
+            .collect();
+        let plates: HashMap<Uuid, String> = team_car::Entity::find()
+            .filter(team_car::Column::Id.is_in(car_ids))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|team_car_model| (team_car_model.id, team_car_model.car_plate_number))
+            .collect();
+        let mut dispatches: HashMap<String, TeamUserDispatch> = dispatch_models
+            .into_iter()
+            .filter_map(|(dispatch_driver_model, dispatch_model)| {
+                dispatch_model.map(|dispatch_model| {
+                    (
+                        dispatch_driver_model.user_id,
+                        TeamUserDispatch {
+                            dispatch_id: dispatch_model.id,
+                            car_id: dispatch_model.team_car_id,
+                            car_plate_number: plates.get(&dispatch_model.team_car_id).cloned(),
+                        },
+                    )
+                })
+            })
+            .collect();
 
-        let mut res: Vec<TeamUser> = vec![];
-        for query in query_result {
-            let team_user = TeamUser {
-                user_id: query.user_id,
-            };
-            res.push(team_user);
-        }
-        Ok(res)
+        let users = driver_models
+            .into_iter()
+            .map(|(team_driver_model, user_model)| TeamUser {
+                dispatch: dispatches.remove(&team_driver_model.user_id),
+                licence_expire_date: team_driver_model.licence_expire_date,
+                user_name: user_model.as_ref().map(|model| model.user_name.clone()),
+                avatar_url: user_model.and_then(|model| model.avatar_url),
+                user_id: team_driver_model.user_id,
+            })
+            .collect();
+        Ok(TeamUserPage {
+            users,
+            page,
+            page_size,
+            total_count,
+        })
     }
 
-    #[instrument]
-    pub async fn get_cars(&self) -> Result<Vec<TeamCar>, TeamError> {
-        let team_id = self.id;
-        let db = DATABASE.get().unwrap();
-        let query_result = team_car::Entity::find()
-            .filter(team_car::Column::TeamId.eq(team_id))
-            .all(db)
-            .await?;
+    /// One page of the trucks of the team with the billing open on each.
+    #[instrument(skip(db))]
+    pub async fn get_cars<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        page: usize,
+        page_size: usize,
+    ) -> Result<TeamCarPage, AppError> {
+        let paginator = team_car::Entity::find()
+            .filter(team_car::Column::TeamId.eq(self.id))
+            .order_by_asc(team_car::Column::CarPlateNumber)
+            .paginate(db, page_size);
+        let total_count = paginator.num_items().await?;
+        let car_models = paginator.fetch_page(page - 1).await?;
 
-        let mut res: Vec<TeamCar> = vec![];
-        for query in query_result {
-            let team_car = TeamCar {
-                car_id: query.id,
-                car_plate_number: query.car_plate_number,
-            };
-            res.push(team_car);
-        }
-        Ok(res)
+        let car_ids: Vec<Uuid> = car_models.iter().map(|car_model| car_model.id).collect();
+        let mut open_billings = self.open_billings(db, car_ids).await?;
+
+        let cars = car_models
+            .into_iter()
+            .map(|car_model| TeamCar {
+                open_billing: open_billings.remove(&car_model.id),
+                car_id: car_model.id,
+                profile: car_model.into(),
+            })
+            .collect();
+        Ok(TeamCarPage {
+            cars,
+            page,
+            page_size,
+            total_count,
+        })
     }
 
-    pub async fn delete(self) -> Result<(), TeamError> {
-        let db = DATABASE.get().unwrap();
+    async fn open_billings<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        car_ids: Vec<Uuid>,
+    ) -> Result<HashMap<Uuid, TeamCarBilling>, AppError> {
+        let open_billings = billing::Entity::find()
+            .filter(billing::Column::TeamId.eq(self.id))
+            .filter(billing::Column::TeamCarId.is_in(car_ids))
+            .filter(billing::Column::EndTime.is_null())
+            .all(db)
+            .await?
+            .into_iter()
+            .filter_map(|billing_model| {
+                billing_model.team_car_id.map(|car_id| {
+                    (
+                        car_id,
+                        TeamCarBilling {
+                            billing_id: billing_model.id,
+                            name: billing_model.name,
+                            start_time: parse_navie_time_to_data_time(billing_model.start_time),
+                        },
+                    )
+                })
+            })
+            .collect();
+        Ok(open_billings)
+    }
+
+    pub async fn delete<C: ConnectionTrait + TransactionTrait>(
+        self,
+        db: &C,
+    ) -> Result<(), AppError> {
         let txn = db.begin().await?;
+        let team_billing_ids = Query::select()
+            .column(billing::Column::Id)
+            .from(billing::Entity)
+            .and_where(billing::Column::TeamId.eq(self.id))
+            .to_owned();
+        let team_payroll_ids = Query::select()
+            .column(payroll_run::Column::Id)
+            .from(payroll_run::Entity)
+            .and_where(payroll_run::Column::TeamId.eq(self.id))
+            .to_owned();
+        let _del_payslip_line_result = payslip_line::Entity::delete_many()
+            .filter(payslip_line::Column::PayrollRunId.in_subquery(team_payroll_ids))
+            .exec(&txn)
+            .await?;
+        let _del_payroll_run_result = payroll_run::Entity::delete_many()
+            .filter(payroll_run::Column::TeamId.eq(self.id))
+            .exec(&txn)
+            .await?;
+        let _del_driver_advance_result = driver_advance::Entity::delete_many()
+            .filter(driver_advance::Column::BillingId.in_subquery(team_billing_ids.clone()))
+            .exec(&txn)
+            .await?;
+        let _del_billing_settlement_result = billing_settlement::Entity::delete_many()
+            .filter(billing_settlement::Column::BillingId.in_subquery(team_billing_ids.clone()))
+            .exec(&txn)
+            .await?;
+        let team_dispatch_ids = Query::select()
+            .column(dispatch::Column::Id)
+            .from(dispatch::Entity)
+            .and_where(dispatch::Column::TeamId.eq(self.id))
+            .to_owned();
+        let _del_dispatch_driver_result = dispatch_driver::Entity::delete_many()
+            .filter(dispatch_driver::Column::DispatchId.in_subquery(team_dispatch_ids))
+            .exec(&txn)
+            .await?;
+        let _del_dispatch_result = dispatch::Entity::delete_many()
+            .filter(dispatch::Column::TeamId.eq(self.id))
+            .exec(&txn)
+            .await?;
+        let team_billing_item_ids = Query::select()
+            .column(billing_item::Column::Id)
+            .from(billing_item::Entity)
+            .and_where(billing_item::Column::BillingId.in_subquery(team_billing_ids.clone()))
+            .to_owned();
+        let _del_fuel_entry_result = fuel_entry::Entity::delete_many()
+            .filter(fuel_entry::Column::BillingItemId.in_subquery(team_billing_item_ids))
+            .exec(&txn)
+            .await?;
+        let _del_billing_item_result = billing_item::Entity::delete_many()
+            .filter(billing_item::Column::BillingId.in_subquery(team_billing_ids.clone()))
+            .exec(&txn)
+            .await?;
+        let team_customer_ids = Query::select()
+            .column(customer::Column::Id)
+            .from(customer::Entity)
+            .and_where(customer::Column::TeamId.eq(self.id))
+            .to_owned();
+        let team_payment_ids = Query::select()
+            .column(customer_payment::Column::Id)
+            .from(customer_payment::Entity)
+            .and_where(customer_payment::Column::CustomerId.in_subquery(team_customer_ids.clone()))
+            .to_owned();
+        let _del_payment_allocation_result = payment_allocation::Entity::delete_many()
+            .filter(payment_allocation::Column::PaymentId.in_subquery(team_payment_ids))
+            .exec(&txn)
+            .await?;
+        let _del_customer_payment_result = customer_payment::Entity::delete_many()
+            .filter(customer_payment::Column::CustomerId.in_subquery(team_customer_ids))
+            .exec(&txn)
+            .await?;
+        let _del_billing_income_result = billing_income::Entity::delete_many()
+            .filter(billing_income::Column::BillingId.in_subquery(team_billing_ids.clone()))
+            .exec(&txn)
+            .await?;
+        let _del_billing_expected_item_result = billing_expected_item::Entity::delete_many()
+            .filter(billing_expected_item::Column::BillingId.in_subquery(team_billing_ids))
+            .exec(&txn)
+            .await?;
+        let _del_team_billing_result = billing::Entity::delete_many()
+            .filter(billing::Column::TeamId.eq(Some(self.id)))
+            .exec(&txn)
+            .await?;
+        let team_template_ids = Query::select()
+            .column(billing_template::Column::Id)
+            .from(billing_template::Entity)
+            .and_where(billing_template::Column::TeamId.eq(self.id))
+            .to_owned();
+        let _del_billing_template_item_result = billing_template_item::Entity::delete_many()
+            .filter(billing_template_item::Column::TemplateId.in_subquery(team_template_ids))
+            .exec(&txn)
+            .await?;
+        let _del_billing_template_result = billing_template::Entity::delete_many()
+            .filter(billing_template::Column::TeamId.eq(self.id))
+            .exec(&txn)
+            .await?;
+        let _del_customer_result = customer::Entity::delete_many()
+            .filter(customer::Column::TeamId.eq(self.id))
+            .exec(&txn)
+            .await?;
+        let _del_item_result = item::Entity::delete_many()
+            .filter(item::Column::TeamId.eq(self.id))
+            .exec(&txn)
+            .await?;
+        let _del_reminder_result = reminder::Entity::delete_many()
+            .filter(reminder::Column::TeamId.eq(self.id))
+            .exec(&txn)
+            .await?;
         let _del_team_car_result = team_car::Entity::delete_many()
             .filter(team_car::Column::TeamId.eq(self.id))
-            .exec(db)
+            .exec(&txn)
+            .await?;
+        let team_driver_ids = Query::select()
+            .column(team_driver::Column::Id)
+            .from(team_driver::Entity)
+            .and_where(team_driver::Column::TeamId.eq(self.id))
+            .to_owned();
+        let team_rule_ids = Query::select()
+            .column(wage_rule::Column::Id)
+            .from(wage_rule::Entity)
+            .and_where(wage_rule::Column::TeamDriverId.in_subquery(team_driver_ids.clone()))
+            .to_owned();
+        let _del_wage_rule_deduction_result = wage_rule_deduction::Entity::delete_many()
+            .filter(wage_rule_deduction::Column::WageRuleId.in_subquery(team_rule_ids))
+            .exec(&txn)
+            .await?;
+        let _del_wage_rule_result = wage_rule::Entity::delete_many()
+            .filter(wage_rule::Column::TeamDriverId.in_subquery(team_driver_ids))
+            .exec(&txn)
             .await?;
         let _del_team_driver_result = team_driver::Entity::delete_many()
             .filter(team_driver::Column::TeamId.eq(self.id))
-            .exec(db)
+            .exec(&txn)
             .await?;
-        let _del_team_billing_result = billing::Entity::delete_many()
-            .filter(billing::Column::TeamId.eq(Some(self.id)))
-            .exec(db)
+        let _del_team_invite_result = team_invite::Entity::delete_many()
+            .filter(team_invite::Column::TeamId.eq(self.id))
+            .exec(&txn)
             .await?;
-        let _del_team_result = team::Entity::delete_by_id(self.id).exec(db).await?;
-                txn.commit().await?;
+        let _del_team_result = team::Entity::delete_by_id(self.id).exec(&txn).await?;
+        txn.commit().await?;
         Ok(())
     }
 }
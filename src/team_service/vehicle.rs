@@ -0,0 +1,178 @@
+use crate::error::{AppError, ErrorCode};
+
+/// Province abbreviations that open a civil plate.
+const PROVINCES: &str = "京津沪渝冀豫云辽黑湘皖鲁新苏浙赣鄂桂甘晋蒙陕吉闽贵粤青藏川宁琼";
+
+/// Characters closing a plate with a four character serial, `挂` marks a trailer.
+const SUFFIXES: &str = "挂学警港澳";
+
+/// Separators people type inside plates, `京A·12345` or `京A 12345`.
+const PLATE_SEPARATORS: &str = "·•・.-";
+
+fn invalid_plate(plate: &str) -> AppError {
+    AppError::new(
+        ErrorCode::InvalidPlate,
+        format!("{} is not a valid plate number", plate),
+    )
+}
+
+/// Plate serials never use `I` and `O`, they read as `1` and `0`.
+fn is_serial_char(c: char) -> bool {
+    (c.is_ascii_uppercase() || c.is_ascii_digit()) && c != 'I' && c != 'O'
+}
+
+/// Full width letters and digits typed with a Chinese input method become ASCII.
+fn to_half_width(c: char) -> char {
+    match c {
+        '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - 0xfee0).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// Normalises a mainland plate number to its stored form and checks its format.
+///
+/// Separators and spaces are dropped and letters upper cased, so `京a·12345` is stored as
+/// `京A12345`. Accepted are the five character serials of ordinary plates, four character
+/// serials with a suffix such as the trailer plate `豫A1234挂`, and six character new energy
+/// serials that start or end with `D` or `F`.
+pub fn normalize_plate(plate: &str) -> Result<String, AppError> {
+    let normalized: String = plate
+        .chars()
+        .map(to_half_width)
+        .filter(|c| !c.is_whitespace() && !PLATE_SEPARATORS.contains(*c))
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    let chars: Vec<char> = normalized.chars().collect();
+    if chars.len() < 7 || !PROVINCES.contains(chars[0]) || !chars[1].is_ascii_uppercase() {
+        return Err(invalid_plate(plate));
+    }
+    let serial = &chars[2..];
+    let valid = match serial.len() {
+        5 if SUFFIXES.contains(serial[4]) => serial[..4].iter().all(|c| is_serial_char(*c)),
+        5 => serial.iter().all(|c| is_serial_char(*c)),
+        6 => {
+            serial.iter().all(|c| is_serial_char(*c))
+                && (matches!(serial[0], 'D' | 'F') || matches!(serial[5], 'D' | 'F'))
+        }
+        _ => false,
+    };
+    if !valid {
+        return Err(invalid_plate(plate));
+    }
+    Ok(normalized)
+}
+
+/// Upper cases a vehicle identification number and checks its 17 characters, which never
+/// include `I`, `O` or `Q`.
+pub fn normalize_vin(vin: &str) -> Result<String, AppError> {
+    let normalized = vin.trim().to_ascii_uppercase();
+    let valid = normalized.len() == 17
+        && normalized
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() && !matches!(c, 'I' | 'O' | 'Q'));
+    if !valid {
+        return Err(AppError::new(
+            ErrorCode::InvalidArgument,
+            format!("{} is not a valid vin", vin),
+        ));
+    }
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_plate, normalize_vin};
+    use crate::error::ErrorCode;
+
+    #[test]
+    fn normalizes_ordinary_plates() {
+        for (input, stored) in [
+            ("京A12345", "京A12345"),
+            ("京a12345", "京A12345"),
+            ("京A·12345", "京A12345"),
+            ("京A•12345", "京A12345"),
+            ("京A・12345", "京A12345"),
+            ("京A.123-45", "京A12345"),
+            (" 京A 12345 ", "京A12345"),
+            ("粤B\u{3000}8Z9X7", "粤B8Z9X7"),
+        ] {
+            assert_eq!(normalize_plate(input).unwrap(), stored, "{}", input);
+        }
+    }
+
+    #[test]
+    fn folds_full_width_characters() {
+        assert_eq!(normalize_plate("沪ＡＢ１２３４").unwrap(), "沪AB1234");
+        assert_eq!(normalize_plate("沪ａ１２３４５").unwrap(), "沪A12345");
+        assert_eq!(normalize_plate("沪A－12345").unwrap(), "沪A12345");
+        assert_eq!(normalize_plate("沪A．12345").unwrap(), "沪A12345");
+    }
+
+    #[test]
+    fn accepts_suffixed_plates() {
+        assert_eq!(normalize_plate("豫A1234挂").unwrap(), "豫A1234挂");
+        assert_eq!(normalize_plate("豫a·1234挂").unwrap(), "豫A1234挂");
+        assert_eq!(normalize_plate("粤Z1234港").unwrap(), "粤Z1234港");
+        assert_eq!(normalize_plate("京A1234警").unwrap(), "京A1234警");
+    }
+
+    #[test]
+    fn accepts_new_energy_plates() {
+        assert_eq!(normalize_plate("京AD12345").unwrap(), "京AD12345");
+        assert_eq!(normalize_plate("京AF12345").unwrap(), "京AF12345");
+        assert_eq!(normalize_plate("京A12345D").unwrap(), "京A12345D");
+        assert_eq!(normalize_plate("京a12345f").unwrap(), "京A12345F");
+    }
+
+    #[test]
+    fn rejects_invalid_plates() {
+        for input in [
+            "",
+            "京A1234",
+            "京A1234567",
+            "A12345",
+            "XA12345",
+            "京112345",
+            "京A1234I",
+            "京A12O45",
+            "京A12345挂",
+            "京A123挂5",
+            "京A1234学5",
+            "京AB12345",
+            "京A123456",
+            "京A12#45",
+        ] {
+            let err = normalize_plate(input).unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidPlate, "{}", input);
+        }
+    }
+
+    #[test]
+    fn normalizes_vins() {
+        assert_eq!(
+            normalize_vin(" lfv2a21k8a3012345 ").unwrap(),
+            "LFV2A21K8A3012345"
+        );
+        assert_eq!(
+            normalize_vin("LFV2A21K8A3012345").unwrap(),
+            "LFV2A21K8A3012345"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_vins() {
+        for input in [
+            "",
+            "LFV2A21K8A301234",
+            "LFV2A21K8A30123456",
+            "LFV2A21K8A30I2345",
+            "LFV2A21K8A30O2345",
+            "LFV2A21K8A30Q2345",
+            "LFV2A21K8A30-2345",
+            "LFV2A21K8A３012345",
+        ] {
+            let err = normalize_vin(input).unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidArgument, "{}", input);
+        }
+    }
+}
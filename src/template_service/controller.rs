@@ -0,0 +1,217 @@
+use poem::web::Data;
+use poem_openapi::{param::Path, payload::Json, ApiResponse, Object, OpenApi, Tags};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::auth_service::service::{TeamGuard, TeamPermission};
+use crate::error::{parse_uuid, ApiResult, AppError};
+use crate::session_service::service::SessionUser;
+use crate::state::AppState;
+
+use super::service::{BillingTemplate, TemplateItem, TemplateItemInput};
+
+#[derive(Tags)]
+enum ApiTags {
+    BillingTemplate,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct TemplateItemDTO {
+    #[oai(validator(max_length = 128))]
+    item_id: String,
+
+    default_cost: Option<Decimal>,
+
+    #[oai(default)]
+    required: bool,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct TemplateCreateDTO {
+    #[oai(validator(max_length = 128))]
+    name: String,
+
+    items: Vec<TemplateItemDTO>,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct TemplateUpdateDTO {
+    #[oai(validator(max_length = 128))]
+    name: Option<String>,
+
+    items: Option<Vec<TemplateItemDTO>>,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct TemplateItemEntityDTO {
+    item_id: String,
+    name: String,
+    item_type: String,
+    sort_order: i32,
+    default_cost: Option<Decimal>,
+    required: bool,
+}
+
+impl From<TemplateItem> for TemplateItemEntityDTO {
+    fn from(template_item: TemplateItem) -> Self {
+        TemplateItemEntityDTO {
+            item_id: template_item.item_id.to_string(),
+            name: template_item.name,
+            item_type: template_item.item_type,
+            sort_order: template_item.sort_order,
+            default_cost: template_item.default_cost,
+            required: template_item.required,
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+struct TemplateEntityDTO {
+    template_id: String,
+    name: String,
+    items: Vec<TemplateItemEntityDTO>,
+}
+
+impl From<BillingTemplate> for TemplateEntityDTO {
+    fn from(template: BillingTemplate) -> Self {
+        TemplateEntityDTO {
+            template_id: template.id.to_string(),
+            name: template.name,
+            items: template
+                .items
+                .into_iter()
+                .map(|template_item| template_item.into())
+                .collect(),
+        }
+    }
+}
+
+#[derive(ApiResponse)]
+enum TemplateResponse {
+    #[oai(status = 200)]
+    Ok(Json<TemplateEntityDTO>),
+
+    #[oai(status = 201)]
+    Created(Json<TemplateEntityDTO>),
+}
+
+#[derive(ApiResponse)]
+enum QueryTemplateResponse {
+    #[oai(status = 200)]
+    Ok(Json<Vec<TemplateEntityDTO>>),
+}
+
+#[derive(ApiResponse)]
+enum DeleteTemplateResponse {
+    #[oai(status = 204)]
+    Ok,
+}
+
+fn parse_template_items(items: Vec<TemplateItemDTO>) -> Result<Vec<TemplateItemInput>, AppError> {
+    let mut template_items = vec![];
+    for item in items {
+        template_items.push(TemplateItemInput {
+            item_id: parse_uuid("item_id", &item.item_id)?,
+            default_cost: item.default_cost,
+            required: item.required,
+        });
+    }
+    Ok(template_items)
+}
+
+fn parse_team_template_id(team_id: &str, template_id: &str) -> Result<(Uuid, Uuid), AppError> {
+    Ok((
+        parse_uuid("team_id", team_id)?,
+        parse_uuid("template_id", template_id)?,
+    ))
+}
+
+pub struct TemplateRouter;
+
+#[OpenApi]
+impl TemplateRouter {
+    #[oai(
+        path = "/team/:team_id/template",
+        method = "post",
+        tag = "ApiTags::BillingTemplate"
+    )]
+    async fn create_template(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        template: Json<TemplateCreateDTO>,
+    ) -> ApiResult<TemplateResponse> {
+        let template = template.0;
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        let items = parse_template_items(template.items)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let template = BillingTemplate::create(&state.db, team_uuid, template.name, items).await?;
+        Ok(TemplateResponse::Created(Json(template.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/template",
+        method = "get",
+        tag = "ApiTags::BillingTemplate"
+    )]
+    async fn query_template(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+    ) -> ApiResult<QueryTemplateResponse> {
+        let team_uuid = parse_uuid("team_id", &team_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Member).await?;
+        let templates = BillingTemplate::query(&state.db, team_uuid).await?;
+        Ok(QueryTemplateResponse::Ok(Json(
+            templates
+                .into_iter()
+                .map(|template| template.into())
+                .collect(),
+        )))
+    }
+
+    #[oai(
+        path = "/team/:team_id/template/:template_id",
+        method = "put",
+        tag = "ApiTags::BillingTemplate"
+    )]
+    async fn update_template(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        template_id: Path<String>,
+        template: Json<TemplateUpdateDTO>,
+    ) -> ApiResult<TemplateResponse> {
+        let (team_uuid, template_uuid) = parse_team_template_id(&team_id.0, &template_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let template = template.0;
+        let items = template.items.map(parse_template_items).transpose()?;
+        let team_template = BillingTemplate::from_id(&state.db, team_uuid, template_uuid).await?;
+        let template = team_template
+            .update(&state.db, template.name, items)
+            .await?;
+        Ok(TemplateResponse::Ok(Json(template.into())))
+    }
+
+    #[oai(
+        path = "/team/:team_id/template/:template_id",
+        method = "delete",
+        tag = "ApiTags::BillingTemplate"
+    )]
+    async fn delete_template(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        team_id: Path<String>,
+        template_id: Path<String>,
+    ) -> ApiResult<DeleteTemplateResponse> {
+        let (team_uuid, template_uuid) = parse_team_template_id(&team_id.0, &template_id.0)?;
+        TeamGuard::check(&state.db, &session_user, team_uuid, TeamPermission::Manage).await?;
+        let team_template = BillingTemplate::from_id(&state.db, team_uuid, template_uuid).await?;
+        team_template.delete(&state.db).await?;
+        Ok(DeleteTemplateResponse::Ok)
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashSet;
+
+use rust_decimal::Decimal;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseTransaction, EntityTrait,
+    IntoActiveModel, ModelTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
+};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    entities::{billing_template, billing_template_item, item},
+    error::{AppError, ErrorCode},
+};
+
+fn template_not_found(template_id: Uuid) -> AppError {
+    AppError::new(
+        ErrorCode::TemplateNotFound,
+        format!("can not find billing template {}", template_id),
+    )
+}
+
+#[derive(Debug)]
+pub struct TemplateItemInput {
+    pub item_id: Uuid,
+    pub default_cost: Option<Decimal>,
+    pub required: bool,
+}
+
+#[derive(Debug)]
+pub struct TemplateItem {
+    pub item_id: Uuid,
+    pub name: String,
+    pub item_type: String,
+    pub sort_order: i32,
+    pub default_cost: Option<Decimal>,
+    pub required: bool,
+}
+
+#[derive(Debug)]
+pub struct BillingTemplate {
+    pub id: Uuid,
+    pub team_id: Uuid,
+    pub name: String,
+    pub items: Vec<TemplateItem>,
+}
+
+impl BillingTemplate {
+    #[instrument(skip(db))]
+    pub async fn create<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        team_id: Uuid,
+        name: String,
+        items: Vec<TemplateItemInput>,
+    ) -> Result<BillingTemplate, AppError> {
+        let txn = db.begin().await?;
+        let template_model = billing_template::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            team_id: Set(team_id),
+            name: Set(name),
+        }
+        .insert(&txn)
+        .await?;
+        Self::insert_items(&txn, &template_model, items).await?;
+        txn.commit().await?;
+        Self::from_id(db, team_id, template_model.id).await
+    }
+
+    #[instrument(skip(db))]
+    pub async fn from_id<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+        template_id: Uuid,
+    ) -> Result<BillingTemplate, AppError> {
+        let template_model = billing_template::Entity::find_by_id(template_id)
+            .filter(billing_template::Column::TeamId.eq(team_id))
+            .one(db)
+            .await?
+            .ok_or_else(|| template_not_found(template_id))?;
+        let template_item_models = template_model
+            .find_related(billing_template_item::Entity)
+            .find_also_related(item::Entity)
+            .order_by_asc(billing_template_item::Column::SortOrder)
+            .all(db)
+            .await?;
+        Ok(BillingTemplate {
+            id: template_model.id,
+            team_id: template_model.team_id,
+            name: template_model.name,
+            items: template_item_models
+                .into_iter()
+                .filter_map(|(template_item_model, item_model)| {
+                    item_model.map(|item_model| TemplateItem {
+                        item_id: item_model.id,
+                        name: item_model.name,
+                        item_type: item_model.r#type.to_string(),
+                        sort_order: template_item_model.sort_order,
+                        default_cost: template_item_model.default_cost,
+                        required: template_item_model.required,
+                    })
+                })
+                .collect(),
+        })
+    }
+
+    #[instrument(skip(db))]
+    pub async fn query<C: ConnectionTrait>(
+        db: &C,
+        team_id: Uuid,
+    ) -> Result<Vec<BillingTemplate>, AppError> {
+        let template_models = billing_template::Entity::find()
+            .filter(billing_template::Column::TeamId.eq(team_id))
+            .order_by_asc(billing_template::Column::Name)
+            .all(db)
+            .await?;
+        let mut templates = vec![];
+        for template_model in template_models {
+            templates.push(Self::from_id(db, team_id, template_model.id).await?);
+        }
+        Ok(templates)
+    }
+
+    #[instrument(skip(db))]
+    pub async fn update<C: ConnectionTrait + TransactionTrait>(
+        self,
+        db: &C,
+        name: Option<String>,
+        items: Option<Vec<TemplateItemInput>>,
+    ) -> Result<BillingTemplate, AppError> {
+        let txn = db.begin().await?;
+        let template_model = billing_template::Entity::find_by_id(self.id)
+            .one(&txn)
+            .await?
+            .ok_or_else(|| template_not_found(self.id))?;
+        let template_model = match name {
+            Some(name) => {
+                let mut template_active_model = template_model.into_active_model();
+                template_active_model.name = Set(name);
+                template_active_model.update(&txn).await?
+            }
+            None => template_model,
+        };
+        if let Some(items) = items {
+            billing_template_item::Entity::delete_many()
+                .filter(billing_template_item::Column::TemplateId.eq(self.id))
+                .exec(&txn)
+                .await?;
+            Self::insert_items(&txn, &template_model, items).await?;
+        }
+        txn.commit().await?;
+        Self::from_id(db, self.team_id, self.id).await
+    }
+
+    #[instrument(skip(db))]
+    pub async fn delete<C: ConnectionTrait + TransactionTrait>(
+        self,
+        db: &C,
+    ) -> Result<(), AppError> {
+        let txn = db.begin().await?;
+        billing_template_item::Entity::delete_many()
+            .filter(billing_template_item::Column::TemplateId.eq(self.id))
+            .exec(&txn)
+            .await?;
+        billing_template::Entity::delete_by_id(self.id)
+            .exec(&txn)
+            .await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Items keep the order they are given in, and must be usable by the template team.
+    async fn insert_items(
+        txn: &DatabaseTransaction,
+        template_model: &billing_template::Model,
+        items: Vec<TemplateItemInput>,
+    ) -> Result<(), AppError> {
+        let mut item_ids = HashSet::new();
+        for (sort_order, template_item) in items.into_iter().enumerate() {
+            if !item_ids.insert(template_item.item_id) {
+                return Err(AppError::new(
+                    ErrorCode::DuplicateItem,
+                    format!(
+                        "item {} appears more than once in template",
+                        template_item.item_id
+                    ),
+                ));
+            }
+            if matches!(template_item.default_cost, Some(cost) if cost <= Decimal::ZERO) {
+                return Err(AppError::new(
+                    ErrorCode::InvalidCost,
+                    "default cost must be positive",
+                ));
+            }
+            let item_model = item::Entity::find_by_id(template_item.item_id)
+                .one(txn)
+                .await?
+                .filter(|item_model| {
+                    !item_model.archived
+                        && (item_model.team_id.is_none()
+                            || item_model.team_id == Some(template_model.team_id))
+                })
+                .ok_or_else(|| {
+                    AppError::new(
+                        ErrorCode::InvalidArgument,
+                        format!(
+                            "item {} can not be used by team template",
+                            template_item.item_id
+                        ),
+                    )
+                })?;
+            billing_template_item::ActiveModel {
+                id: Set(Uuid::new_v4()),
+                template_id: Set(template_model.id),
+                item_id: Set(item_model.id),
+                sort_order: Set(sort_order as i32),
+                default_cost: Set(template_item.default_cost),
+                required: Set(template_item.required),
+            }
+            .insert(txn)
+            .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use migration::{Migrator, MigratorTrait};
+use sea_orm::{ConnectionTrait, Database, DatabaseConnection, Statement};
+use uuid::Uuid;
+
+/// A throwaway Postgres database with every migration applied, for tests that need SQL.
+///
+/// The server is taken from `TEST_DATABASE_URL`, each test gets a database of its own so
+/// tests running in parallel never see each other's rows.
+pub struct TestDb {
+    pub db: DatabaseConnection,
+    admin: DatabaseConnection,
+    name: String,
+}
+
+impl TestDb {
+    /// `None` when `TEST_DATABASE_URL` is not set, the calling test then returns early.
+    pub async fn create() -> Option<TestDb> {
+        let url = match std::env::var("TEST_DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => {
+                eprintln!("TEST_DATABASE_URL is not set, skipping database test");
+                return None;
+            }
+        };
+        let admin = Database::connect(url.as_str()).await.unwrap();
+        let name = format!("truck_billing_test_{}", Uuid::new_v4().simple());
+        execute(&admin, &format!("CREATE DATABASE {}", name)).await;
+        let db = Database::connect(with_database(&url, &name).as_str())
+            .await
+            .unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        Some(TestDb { db, admin, name })
+    }
+
+    pub async fn drop(self) {
+        let TestDb { db, admin, name } = self;
+        drop(db);
+        execute(
+            &admin,
+            &format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", name),
+        )
+        .await;
+    }
+}
+
+async fn execute(db: &DatabaseConnection, sql: &str) {
+    db.execute(Statement::from_string(
+        db.get_database_backend(),
+        sql.to_owned(),
+    ))
+    .await
+    .unwrap();
+}
+
+/// The url with its database name replaced, query parameters are kept.
+fn with_database(url: &str, name: &str) -> String {
+    let (base, query) = match url.split_once('?') {
+        Some((base, query)) => (base, Some(query)),
+        None => (url, None),
+    };
+    let authority = base.find("://").map_or(0, |index| index + 3);
+    let server = match base[authority..].find('/') {
+        Some(index) => &base[..authority + index],
+        None => base,
+    };
+    match query {
+        Some(query) => format!("{}/{}?{}", server, name, query),
+        None => format!("{}/{}", server, name),
+    }
+}
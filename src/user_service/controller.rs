@@ -1,13 +1,15 @@
-use std::{env, vec};
+use std::vec;
 
 use crate::entities::{role, user};
-use crate::DATABASE;
-use poem::web::Path;
+use poem::web::Data;
 use poem_openapi::{payload::Json, ApiResponse, Object, OpenApi, Tags};
 use sea_orm::EntityTrait;
 use serde::{Deserialize, Serialize};
-use tracing::info;
-use tracing::{log::error, log::warn};
+
+use crate::auth_service::service::require_admin;
+use crate::error::ApiResult;
+use crate::session_service::{controller::SessionDTO, service::Session, service::SessionUser};
+use crate::state::AppState;
 
 use super::service::{UserAggregate, UserAggregateRole};
 
@@ -19,9 +21,6 @@ enum ApiTags {
 
 #[derive(Debug, Object, Clone, Eq, PartialEq)]
 pub struct UserCreateDTO {
-    /// Id
-    #[oai(validator(max_length = 128))]
-    pub id: String,
     /// Name
     #[oai(validator(max_length = 128))]
     pub name: String,
@@ -34,9 +33,6 @@ pub struct UserCreateDTO {
 enum CreateUserResponse {
     #[oai(status = 201)]
     Ok(Json<String>),
-
-    #[oai(status = 500)]
-    Error,
 }
 
 #[derive(Debug, Object, Clone, Eq, PartialEq)]
@@ -79,55 +75,42 @@ impl From<role::Model> for RoleDTO {
 enum GetUserResponse {
     #[oai(status = 200)]
     Ok(Json<UserQueryDTO>),
-
-    #[oai(status = 500)]
-    Error,
 }
 
 #[derive(ApiResponse)]
 enum GetAllUserResponse {
     #[oai(status = 200)]
     Ok(Json<Vec<UserQueryDTO>>),
-
-    #[oai(status = 401)]
-    Forbidden,
-
-    #[oai(status = 500)]
-    Error,
 }
 
 #[derive(Debug, Object, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct UserWxLoginDTO {
     pub code: String,
-}
 
-#[derive(Debug, Object, Clone, Eq, PartialEq, Deserialize, Serialize)]
-pub struct WxLoginDTO {
-    pub openid: Option<String>,
-    pub session_key: Option<String>,
-    pub unionid: Option<String>,
-    pub errcode: Option<i32>,
-    pub errmsg: Option<String>,
-}
+    /// Nickname from the mini program profile, kept on the account when sent.
+    #[oai(validator(max_length = 128))]
+    pub name: Option<String>,
 
-#[derive(Debug, Object, Clone, Eq, PartialEq)]
-pub struct UserWxLoginResponseDTO {
-    pub code: String,
+    pub avatar_url: Option<String>,
 }
 
 #[derive(Debug, Object, Clone, Eq, PartialEq)]
-pub struct UserWxLoginErrorResponse {
-    pub err_code: Option<String>,
-    pub err_msg: Option<String>,
+pub struct UserLoginDTO {
+    #[oai(flatten)]
+    pub session: SessionDTO,
+
+    pub user_name: String,
+
+    pub avatar_url: Option<String>,
+
+    /// True when this login created the account.
+    pub new_user: bool,
 }
 
 #[derive(ApiResponse)]
 enum UserLoginResponse {
     #[oai(status = 200)]
-    Ok(Json<UserWxLoginResponseDTO>),
-
-    #[oai(status = 500)]
-    Error(Json<UserWxLoginErrorResponse>),
+    Ok(Json<UserLoginDTO>),
 }
 
 pub struct UserRouter;
@@ -135,130 +118,46 @@ pub struct UserRouter;
 #[OpenApi]
 impl UserRouter {
     #[oai(path = "/user", method = "post", tag = "ApiTags::User")]
-    async fn create(&self, user: Json<UserCreateDTO>) -> CreateUserResponse {
+    async fn create(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+        user: Json<UserCreateDTO>,
+    ) -> ApiResult<CreateUserResponse> {
         let user_dto = user.0;
-        let user_aggregate: UserAggregate = user_dto.into();
-        let create_result = user_aggregate.create_user().await;
-        if let Err(err) = create_result {
-            warn!("create user met error {}", err);
-            return CreateUserResponse::Error;
-        }
-        CreateUserResponse::Ok(Json(create_result.unwrap()))
+        let user_aggregate: UserAggregate = (session_user.user_id, user_dto).into();
+        let user_id = user_aggregate.create_user(&state.db).await?;
+        Ok(CreateUserResponse::Ok(Json(user_id)))
     }
 
     #[oai(path = "/user/login", method = "post", tag = "ApiTags::User")]
-    async fn login(&self, user: Json<UserWxLoginDTO>) -> UserLoginResponse {
-        let (app_id, secret) = (env::var("APP_ID").unwrap(), env::var("APP_SECRET").unwrap());
-        let code = user.0.code;
-        let url = format!("https://api.weixin.qq.com/sns/jscode2session?appid={app_id}&secret={secret}&js_code={code}&grant_type=authorization_code", 
-                          app_id=app_id, secret = secret, code = code);
-        info!("query url is {}", url);
-        let resp = reqwest::get(url).await.unwrap();
-        if let reqwest::StatusCode::OK = resp.status() {
-            match resp.json::<WxLoginDTO>().await {
-                Ok(wx_resp) => match &wx_resp.errcode {
-                    Some(errcode) => match errcode {
-                        -1 => {
-                            error!(
-                                "wx system is busy, err is {}",
-                                wx_resp.errmsg.clone().unwrap_or("empty".to_owned())
-                            );
-                            UserLoginResponse::Error(Json(UserWxLoginErrorResponse {
-                                err_code: Some(errcode.to_string()),
-                                err_msg: wx_resp.errmsg.clone(),
-                            }))
-                        }
-                        0 => UserLoginResponse::Ok(Json(UserWxLoginResponseDTO {
-                            code: wx_resp.openid.unwrap(),
-                        })),
-                        40029 => {
-                            error!(
-                                "code is can not be used. err or msg is {}",
-                                wx_resp.errmsg.clone().unwrap_or("empty".to_owned())
-                            );
-                            UserLoginResponse::Error(Json(UserWxLoginErrorResponse {
-                                err_code: Some(errcode.to_string()),
-                                err_msg: wx_resp.errmsg,
-                            }))
-                        }
-                        45011 => {
-                            error!(
-                                "call api too frequently. err msg is {}",
-                                wx_resp.errmsg.clone().unwrap_or("empty".to_owned())
-                            );
-                            UserLoginResponse::Error(Json(UserWxLoginErrorResponse {
-                                err_code: Some(errcode.to_string()),
-                                err_msg: wx_resp.errmsg,
-                            }))
-                        }
-                        40226 => {
-                            error!(
-                                "high risk level user. err msg is {}",
-                                wx_resp.errmsg.clone().unwrap_or("empty".to_owned())
-                            );
-                            UserLoginResponse::Error(Json(UserWxLoginErrorResponse {
-                                err_code: Some(errcode.to_string()),
-                                err_msg: wx_resp.errmsg,
-                            }))
-                        }
-                        _ => {
-                            error!(
-                                "wx unused error code. err code is {}, err msg is {}",
-                                errcode,
-                                wx_resp.errmsg.clone().unwrap_or("empty".to_owned())
-                            );
-                            UserLoginResponse::Error(Json(UserWxLoginErrorResponse {
-                                err_code: Some(errcode.to_string()),
-                                err_msg: wx_resp.errmsg,
-                            }))
-                        }
-                    },
-                    None => match wx_resp.openid {
-                        Some(openid) => {
-                            UserLoginResponse::Ok(Json(UserWxLoginResponseDTO { code: openid }))
-                        }
-                        None => UserLoginResponse::Error(Json(UserWxLoginErrorResponse {
-                            err_code: None,
-                            err_msg: None,
-                        })),
-                    },
-                },
-                Err(err) => {
-                    error!("response is not correctly deserialize. error is {}", err);
-                    UserLoginResponse::Error(Json(UserWxLoginErrorResponse {
-                        err_code: None,
-                        err_msg: None,
-                    }))
-                }
-            }
-        } else {
-            error!("Wx login response failed.");
-            UserLoginResponse::Error(Json(UserWxLoginErrorResponse {
-                err_code: None,
-                err_msg: None,
-            }))
-        }
+    async fn login(
+        &self,
+        state: Data<&AppState>,
+        user: Json<UserWxLoginDTO>,
+    ) -> ApiResult<UserLoginResponse> {
+        let login = user.0;
+        let wx_session = state.wx_auth.code_to_session(&login.code).await?;
+        let (user_aggregate, new_user) =
+            UserAggregate::login(&state.db, wx_session.openid, login.name, login.avatar_url)
+                .await?;
+        let session = Session::issue(&state.db, user_aggregate.id).await?;
+        Ok(UserLoginResponse::Ok(Json(UserLoginDTO {
+            session: session.into(),
+            user_name: user_aggregate.name,
+            avatar_url: user_aggregate.avatar_url,
+            new_user,
+        })))
     }
 
-    #[oai(path = "/user/:user_id", method = "get", tag = "ApiTags::User")]
-    async fn get(&self, user_id: Path<String>) -> GetUserResponse {
-        let user_id = user_id.0;
-        let user_aggregate_result = UserAggregate::from_user_id(user_id.clone()).await;
-        if let Err(err) = user_aggregate_result {
-            error!(
-                "Get user form db error {}! user is is {}",
-                err,
-                user_id.clone()
-            );
-            return GetUserResponse::Error;
-        }
-        let user_aggregate = user_aggregate_result.unwrap();
-        let query_role_result = user_aggregate.get_user_role().await;
-        if let Err(err) = query_role_result {
-            warn!("create user met error {}", err);
-            return GetUserResponse::Error;
-        }
-        let query_roles = query_role_result.unwrap();
+    #[oai(path = "/user/me", method = "get", tag = "ApiTags::User")]
+    async fn get(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+    ) -> ApiResult<GetUserResponse> {
+        let user_aggregate = UserAggregate::from_user_id(&state.db, session_user.user_id).await?;
+        let query_roles = user_aggregate.get_user_role(&state.db).await?;
         let mut roles = vec![];
         for role in query_roles {
             roles.push(role.into());
@@ -269,45 +168,21 @@ impl UserRouter {
             avatar_url: user_aggregate.avatar_url,
             roles: Some(roles),
         };
-        GetUserResponse::Ok(Json(user_entity))
+        Ok(GetUserResponse::Ok(Json(user_entity)))
     }
 
-    #[oai(path = "/user/:user_id/get", method = "get", tag = "ApiTags::User")]
-    async fn get_users(&self, user_id: Path<String>) -> GetAllUserResponse {
-        let db = DATABASE.get().unwrap();
-        let user_id = user_id.0;
-        let user_aggregate_result = UserAggregate::from_user_id(user_id.clone()).await;
-        if let Err(err) = user_aggregate_result {
-            error!(
-                "Get user form db error {}! user is is {}",
-                err,
-                user_id.clone()
-            );
-            return GetAllUserResponse::Error;
-        }
-        let user_aggregate = user_aggregate_result.unwrap();
-        let is_user_admin_result = user_aggregate.is_admin().await;
-        if let Err(err) = is_user_admin_result {
-            error!(
-                "Get user form db error {}! user is is {}",
-                err,
-                user_id.clone()
-            );
-            return GetAllUserResponse::Error;
-        }
-        if !is_user_admin_result.unwrap() {
-            warn!("User ({}) is not ADMIN.", user_id.clone());
-            return GetAllUserResponse::Forbidden;
-        }
-        let user_roles_result = user::Entity::find()
+    #[oai(path = "/user", method = "get", tag = "ApiTags::User")]
+    async fn get_users(
+        &self,
+        state: Data<&AppState>,
+        session_user: SessionUser,
+    ) -> ApiResult<GetAllUserResponse> {
+        let db = &state.db;
+        require_admin(db, &session_user).await?;
+        let user_roles = user::Entity::find()
             .find_with_related(role::Entity)
             .all(db)
-            .await;
-        if user_roles_result.is_err() {
-            error!("query all user form db error!");
-            return GetAllUserResponse::Error;
-        }
-        let user_roles = user_roles_result.unwrap();
+            .await?;
         let mut response = vec![];
         for (user, roles) in user_roles {
             let mut role_array = vec![];
@@ -321,6 +196,99 @@ impl UserRouter {
                 roles: Some(role_array),
             })
         }
-        GetAllUserResponse::Ok(Json(response))
+        Ok(GetAllUserResponse::Ok(Json(response)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use poem::{http::StatusCode, Endpoint, EndpointExt, Request, Route};
+    use poem_openapi::OpenApiService;
+    use sea_orm::DatabaseConnection;
+
+    use super::UserRouter;
+    use crate::config::AppConfig;
+    use crate::error::ErrorCode;
+    use crate::reminder_service::notifier::MemoryNotifier;
+    use crate::state::AppState;
+    use crate::user_service::wx_client::{FakeWxAuthClient, WxAuthClient};
+
+    /// Posts a login with `code` to the user endpoints, WeChat answered by the fake client.
+    /// A rejected code never reaches the database, so none is connected.
+    async fn login(code: &str) -> (StatusCode, String) {
+        let state = AppState {
+            db: DatabaseConnection::Disconnected,
+            config: Arc::new(AppConfig::default()),
+            wx_auth: Arc::new(FakeWxAuthClient::new()),
+            notifier: Arc::new(MemoryNotifier::new()),
+        };
+        let app = Route::new()
+            .nest("/", OpenApiService::new(UserRouter, "test", "1.0"))
+            .data(state);
+        let req = Request::builder()
+            .method(poem::http::Method::POST)
+            .uri(poem::http::Uri::from_static("/user/login"))
+            .content_type("application/json")
+            .body(format!(r#"{{"code":"{}"}}"#, code));
+        let resp = app.get_response(req).await;
+        let status = resp.status();
+        let body = resp.into_body().into_string().await.unwrap();
+        (status, body)
+    }
+
+    fn problem_code(code: ErrorCode) -> String {
+        let code = match code {
+            ErrorCode::WxLoginError => "WX_LOGIN_ERROR",
+            ErrorCode::WxCodeInvalid => "WX_CODE_INVALID",
+            ErrorCode::WxUserBlocked => "WX_USER_BLOCKED",
+            code => panic!("{:?} is not a login error", code),
+        };
+        format!(r#""code":"{}""#, code)
+    }
+
+    #[tokio::test]
+    async fn scripted_errcodes_map_to_error_codes() {
+        for (wx_code, error_code) in [
+            ("-1", ErrorCode::WxLoginError),
+            ("40029", ErrorCode::WxCodeInvalid),
+            ("45011", ErrorCode::WxLoginError),
+            ("40226", ErrorCode::WxUserBlocked),
+        ] {
+            let (status, body) = login(wx_code).await;
+            assert_eq!(status.as_u16(), error_code.status(), "code {}", wx_code);
+            assert!(
+                body.contains(&problem_code(error_code)),
+                "code {} answered {}",
+                wx_code,
+                body
+            );
+            assert!(
+                body.contains(&format!("wx errcode {}", wx_code)),
+                "{}",
+                body
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn unscripted_code_logs_in_as_fake_openid() {
+        let session = FakeWxAuthClient::new()
+            .code_to_session("0a1b2c")
+            .await
+            .unwrap();
+        assert_eq!(session.openid, "fake-0a1b2c");
+    }
+
+    #[tokio::test]
+    async fn scripted_code_overrides_login() {
+        let err = FakeWxAuthClient::new()
+            .script("0a1b2c", 40029, "code been used")
+            .code_to_session("0a1b2c")
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::WxCodeInvalid);
+        assert_eq!(err.message, "wx errcode 40029, code been used");
     }
 }
@@ -1,41 +1,21 @@
-use sea_orm::{ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, QueryFilter, Set};
-use std::{error::Error, vec};
+use sea_orm::{
+    sea_query::OnConflict, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait,
+    IntoActiveModel, QueryFilter, QueryTrait, Set, TransactionTrait,
+};
+use std::vec;
 use tracing::instrument;
 
 use crate::{
     entities::{role, sea_orm_active_enums::RoleType, user},
+    error::{AppError, ErrorCode},
     role_service::service::UserRoleAggregate,
-    DATABASE,
 };
 
 use super::controller::UserCreateDTO;
 
-#[derive(Debug)]
-pub enum UserError {
-    EmptyUserError,
-    DbError,
-}
-
-impl Error for UserError {}
-
-impl std::fmt::Display for UserError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            UserError::EmptyUserError => {
-                write!(f, "There is nothing with this man.")
-            }
-            UserError::DbError => {
-                write!(f, "Connect with Db Error")
-            }
-        }
-    }
-}
-
-impl From<DbErr> for UserError {
-    fn from(_: DbErr) -> Self {
-        UserError::DbError
-    }
-}
+/// Name given to accounts whose first login did not send a profile, it is what WeChat
+/// itself shows for users without a nickname.
+const DEFAULT_USER_NAME: &str = "微信用户";
 
 impl ToString for RoleType {
     fn to_string(&self) -> String {
@@ -55,14 +35,20 @@ pub struct UserAggregate {
 }
 
 impl UserAggregate {
-    #[instrument]
-    pub async fn from_user_id(user_id: String) -> Result<UserAggregate, UserError> {
-        let db = DATABASE.get().unwrap();
-        let query_result = user::Entity::find_by_id(user_id).one(db).await?;
-        if query_result.is_none() {
-            return Err(UserError::EmptyUserError);
-        }
-        let query_model = query_result.unwrap();
+    #[instrument(skip(db))]
+    pub async fn from_user_id<C: ConnectionTrait>(
+        db: &C,
+        user_id: String,
+    ) -> Result<UserAggregate, AppError> {
+        let query_model = user::Entity::find_by_id(user_id.clone())
+            .one(db)
+            .await?
+            .ok_or_else(|| {
+                AppError::new(
+                    ErrorCode::UserNotFound,
+                    format!("user {} has not been created yet", user_id),
+                )
+            })?;
         let user = UserAggregate {
             id: query_model.id,
             name: query_model.user_name,
@@ -71,25 +57,88 @@ impl UserAggregate {
         return Ok(user);
     }
 
-    #[instrument]
-    pub async fn create_user(self) -> Result<String, UserError> {
-        let db = DATABASE.get().unwrap();
+    /// Inserts the user together with its default role, both or neither.
+    #[instrument(skip(db))]
+    pub async fn create_user<C: TransactionTrait>(self, db: &C) -> Result<String, AppError> {
+        let txn = db.begin().await?;
         user::ActiveModel {
             id: Set(self.id.to_owned()),
             user_name: Set(self.name.to_owned()),
             avatar_url: Set(self.avatar_url.to_owned()),
         }
-        .insert(db)
+        .insert(&txn)
         .await?;
 
         let role = UserRoleAggregate::default_from_user_id(self.id.clone());
-        role.save().await?;
+        role.save(&txn).await?;
+        txn.commit().await?;
         Ok(self.id.clone())
     }
 
-    #[instrument]
-    pub async fn get_user_role(&self) -> Result<Vec<UserAggregateRole>, UserError> {
-        let db = DATABASE.get().unwrap();
+    /// Upserts the user behind a WeChat openid. A first login inserts the row with the
+    /// default role in one transaction, a returning user only gets the profile fields that
+    /// were sent updated. The flag is true for a user created by this call.
+    #[instrument(skip(db))]
+    pub async fn login<C: TransactionTrait>(
+        db: &C,
+        openid: String,
+        name: Option<String>,
+        avatar_url: Option<String>,
+    ) -> Result<(UserAggregate, bool), AppError> {
+        let txn = db.begin().await?;
+        // `ON CONFLICT DO NOTHING` keeps two first logins racing each other from failing,
+        // the affected row count tells which one created the user.
+        let insert = user::Entity::insert(user::ActiveModel {
+            id: Set(openid.clone()),
+            user_name: Set(name.clone().unwrap_or_else(|| DEFAULT_USER_NAME.to_owned())),
+            avatar_url: Set(avatar_url.clone()),
+        })
+        .on_conflict(OnConflict::column(user::Column::Id).do_nothing().to_owned())
+        .build(txn.get_database_backend());
+        let created = txn.execute(insert).await?.rows_affected() == 1;
+
+        let user_model = if created {
+            UserRoleAggregate::default_from_user_id(openid.clone())
+                .save(&txn)
+                .await?;
+            user::Entity::find_by_id(openid.clone()).one(&txn).await?
+        } else {
+            let user_model = user::Entity::find_by_id(openid.clone()).one(&txn).await?;
+            match (user_model, name, avatar_url) {
+                (Some(user_model), None, None) => Some(user_model),
+                (Some(user_model), name, avatar_url) => {
+                    let mut user_active_model = user_model.into_active_model();
+                    if let Some(name) = name {
+                        user_active_model.user_name = Set(name);
+                    }
+                    if avatar_url.is_some() {
+                        user_active_model.avatar_url = Set(avatar_url);
+                    }
+                    Some(user_active_model.update(&txn).await?)
+                }
+                (None, _, _) => None,
+            }
+        }
+        .ok_or_else(|| {
+            AppError::new(
+                ErrorCode::UserNotFound,
+                format!("user {} has not been created yet", openid),
+            )
+        })?;
+        txn.commit().await?;
+        let user = UserAggregate {
+            id: user_model.id,
+            name: user_model.user_name,
+            avatar_url: user_model.avatar_url,
+        };
+        Ok((user, created))
+    }
+
+    #[instrument(skip(db))]
+    pub async fn get_user_role<C: ConnectionTrait>(
+        &self,
+        db: &C,
+    ) -> Result<Vec<UserAggregateRole>, AppError> {
         let query_result = role::Entity::find()
             .filter(role::Column::UserId.eq(self.id.clone()))
             .all(db)
@@ -103,23 +152,12 @@ impl UserAggregate {
         }
         Ok(result)
     }
-
-    #[instrument]
-    pub async fn is_admin(&self) -> Result<bool, UserError> {
-        let user_role_result = self.get_user_role().await?;
-        for user_aggregate_role in user_role_result {
-            if user_aggregate_role.role == RoleType::Admin.to_string() {
-                return Ok(true);
-            }
-        }
-        return Ok(false);
-    }
 }
 
-impl From<UserCreateDTO> for UserAggregate {
-    fn from(user: UserCreateDTO) -> Self {
+impl From<(String, UserCreateDTO)> for UserAggregate {
+    fn from((id, user): (String, UserCreateDTO)) -> Self {
         Self {
-            id: user.id,
+            id,
             name: user.name,
             avatar_url: user.avatar_url,
         }
@@ -0,0 +1,169 @@
+#[cfg(test)]
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tracing::{error, info};
+
+use crate::config::WechatConfig;
+use crate::error::{AppError, ErrorCode};
+
+/// What `jscode2session` hands back for a valid login code.
+#[derive(Debug, Clone)]
+pub struct WxSession {
+    pub openid: String,
+}
+
+/// Exchanges the `wx.login` code of the mini program for the user's openid.
+#[async_trait]
+pub trait WxAuthClient: Send + Sync {
+    async fn code_to_session(&self, code: &str) -> Result<WxSession, AppError>;
+}
+
+#[derive(Debug, Deserialize)]
+struct WxLoginDTO {
+    openid: Option<String>,
+    errcode: Option<i32>,
+    errmsg: Option<String>,
+}
+
+fn wx_login_error(message: impl Into<String>) -> AppError {
+    AppError::new(ErrorCode::WxLoginError, message)
+}
+
+/// Maps a `jscode2session` body to the session or to the error the client gets back.
+fn into_session(wx_resp: WxLoginDTO) -> Result<WxSession, AppError> {
+    let errcode = wx_resp.errcode.unwrap_or(0);
+    if errcode == 0 {
+        let openid = wx_resp.openid.ok_or_else(|| {
+            error!("wx login response has no openid.");
+            wx_login_error("wx login response has no openid")
+        })?;
+        return Ok(WxSession { openid });
+    }
+    let errmsg = wx_resp.errmsg.unwrap_or_else(|| "empty".to_owned());
+    let message = format!("wx errcode {}, {}", errcode, errmsg);
+    match errcode {
+        -1 => {
+            error!("wx system is busy, err is {}", errmsg);
+            Err(wx_login_error(message))
+        }
+        40029 => {
+            info!("code is can not be used. err msg is {}", errmsg);
+            Err(AppError::new(ErrorCode::WxCodeInvalid, message))
+        }
+        45011 => {
+            error!("call api too frequently. err msg is {}", errmsg);
+            Err(wx_login_error(message))
+        }
+        40226 => {
+            info!("high risk level user. err msg is {}", errmsg);
+            Err(AppError::new(ErrorCode::WxUserBlocked, message))
+        }
+        _ => {
+            error!(
+                "wx unused error code. err code is {}, err msg is {}",
+                errcode, errmsg
+            );
+            Err(wx_login_error(message))
+        }
+    }
+}
+
+/// Calls the real WeChat API under the configured base url.
+pub struct HttpWxAuthClient {
+    client: reqwest::Client,
+    config: WechatConfig,
+}
+
+impl HttpWxAuthClient {
+    pub fn new(config: WechatConfig) -> Self {
+        HttpWxAuthClient {
+            client: reqwest::Client::new(),
+            config,
+        }
+    }
+}
+
+#[async_trait]
+impl WxAuthClient for HttpWxAuthClient {
+    async fn code_to_session(&self, code: &str) -> Result<WxSession, AppError> {
+        // The query carries the app secret, neither the url nor the request is logged.
+        let resp = self
+            .client
+            .get(format!("{}/sns/jscode2session", self.config.base_url))
+            .query(&[
+                ("appid", self.config.app_id.as_str()),
+                ("secret", self.config.app_secret.as_str()),
+                ("js_code", code),
+                ("grant_type", "authorization_code"),
+            ])
+            .send()
+            .await
+            .map_err(|err| {
+                error!("wx login request failed. error is {}", err.without_url());
+                wx_login_error("wx login request failed")
+            })?;
+        if resp.status() != reqwest::StatusCode::OK {
+            error!("Wx login response failed. status is {}", resp.status());
+            return Err(wx_login_error("wx login response failed"));
+        }
+        let wx_resp = resp.json::<WxLoginDTO>().await.map_err(|err| {
+            error!(
+                "response is not correctly deserialize. error is {}",
+                err.without_url()
+            );
+            wx_login_error("wx login response is not correctly deserialize")
+        })?;
+        into_session(wx_resp)
+    }
+}
+
+/// In-process stand-in for WeChat, for testing the login flow offline.
+///
+/// A scripted code answers with its errcode, any other code logs in with the openid
+/// `fake-<code>`. `new` scripts the codes `-1`, `40029`, `45011` and `40226` to the
+/// errcodes of the same value.
+#[cfg(test)]
+pub struct FakeWxAuthClient {
+    errcodes: HashMap<String, (i32, String)>,
+}
+
+#[cfg(test)]
+impl FakeWxAuthClient {
+    pub fn new() -> Self {
+        FakeWxAuthClient {
+            errcodes: HashMap::new(),
+        }
+        .script("-1", -1, "system error")
+        .script("40029", 40029, "invalid code")
+        .script("45011", 45011, "api minute-quota reach limit")
+        .script("40226", 40226, "high risk user")
+    }
+
+    pub fn script(mut self, code: &str, errcode: i32, errmsg: &str) -> Self {
+        self.errcodes
+            .insert(code.to_owned(), (errcode, errmsg.to_owned()));
+        self
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl WxAuthClient for FakeWxAuthClient {
+    async fn code_to_session(&self, code: &str) -> Result<WxSession, AppError> {
+        let wx_resp = match self.errcodes.get(code) {
+            Some((errcode, errmsg)) => WxLoginDTO {
+                openid: None,
+                errcode: Some(*errcode),
+                errmsg: Some(errmsg.clone()),
+            },
+            None => WxLoginDTO {
+                openid: Some(format!("fake-{}", code)),
+                errcode: Some(0),
+                errmsg: None,
+            },
+        };
+        into_session(wx_resp)
+    }
+}